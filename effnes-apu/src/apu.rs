@@ -0,0 +1,235 @@
+use crate::{
+    dmc::Dmc,
+    mixer::ChannelLevels,
+    noise::Noise,
+    pulse::{Pulse, PulseKind},
+    triangle::Triangle,
+};
+
+/// Console region, which selects the APU timing tables.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Region {
+    #[default]
+    Ntsc,
+    Pal,
+}
+
+impl Region {
+    /// Frame sequencer steps (in CPU cycles since the sequencer start) of the
+    /// 4-step mode. The last one also asserts the frame interrupt.
+    fn four_step(&self) -> [u32; 4] {
+        match self {
+            Region::Ntsc => [7457, 14913, 22371, 29829],
+            Region::Pal => [8313, 16627, 24939, 33253],
+        }
+    }
+
+    /// Frame sequencer steps of the 5-step mode (the fourth step does
+    /// nothing).
+    fn five_step(&self) -> [u32; 4] {
+        match self {
+            Region::Ntsc => [7457, 14913, 22371, 37281],
+            Region::Pal => [8313, 16627, 24939, 41565],
+        }
+    }
+}
+
+/// 2A03 Audio Processing Unit.
+///
+/// ## Behaviour
+///
+/// - [Apu::cycle] must be called on every CPU cycle.
+/// - Registers $4000-$4013, $4015 and $4017 are handled by [Apu::write];
+///   $4015 reads are handled by [Apu::read_status].
+/// - The DMC sample fetches are left to the owner of the bus: see
+///   [Apu::dmc_request] and [Apu::dmc_fill].
+/// - $4017 writes restart the frame sequencer right away, instead of 3 or 4
+///   cycles later.
+///
+/// ```ignore
+/// let mut apu = Apu::new(Region::Ntsc);
+/// apu.write(0x4015, 0x01);
+/// apu.write(0x4000, 0xBF);
+/// apu.write(0x4002, 0xFD);
+/// apu.write(0x4003, 0x08);
+///
+/// for _ in 0..29_780 {
+///     apu.cycle();
+///     resampler.push(apu.levels().mix());
+/// }
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Apu {
+    region: Region,
+    pulses: [Pulse; 2],
+    triangle: Triangle,
+    noise: Noise,
+    dmc: Dmc,
+
+    /// Whether the next cycle is the second half of an APU cycle.
+    odd: bool,
+    /// CPU cycles since the frame sequencer start.
+    frame_cycle: u32,
+    five_step: bool,
+    irq_inhibit: bool,
+    frame_irq: bool,
+}
+
+impl Apu {
+    pub fn new(region: Region) -> Self {
+        Self {
+            region,
+            pulses: [Pulse::new(PulseKind::Pulse1), Pulse::new(PulseKind::Pulse2)],
+            triangle: Triangle::default(),
+            noise: Noise::new(region),
+            dmc: Dmc::new(region),
+            odd: false,
+            frame_cycle: 0,
+            five_step: false,
+            irq_inhibit: false,
+            frame_irq: false,
+        }
+    }
+
+    pub fn region(&self) -> Region {
+        self.region
+    }
+
+    /// Writes `data` into an APU register ($4000-$4017). Other addresses
+    /// are ignored.
+    pub fn write(&mut self, addr: u16, data: u8) {
+        let reg = addr as u8 & 0x03;
+        match addr {
+            0x4000..=0x4003 => self.pulses[0].write(reg, data),
+            0x4004..=0x4007 => self.pulses[1].write(reg, data),
+            0x4008..=0x400B => self.triangle.write(reg, data),
+            0x400C..=0x400F => self.noise.write(reg, data),
+            0x4010..=0x4013 => self.dmc.write(reg, data),
+            0x4015 => {
+                self.pulses[0].set_enabled(data & 0x01 != 0);
+                self.pulses[1].set_enabled(data & 0x02 != 0);
+                self.triangle.set_enabled(data & 0x04 != 0);
+                self.noise.set_enabled(data & 0x08 != 0);
+                self.dmc.set_enabled(data & 0x10 != 0);
+            }
+            0x4017 => {
+                self.five_step = data & 0x80 != 0;
+                self.irq_inhibit = data & 0x40 != 0;
+                if self.irq_inhibit {
+                    self.frame_irq = false;
+                }
+                self.frame_cycle = 0;
+                if self.five_step {
+                    self.clock_quarter();
+                    self.clock_half();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Reads the status register ($4015), acknowledging the frame
+    /// interrupt.
+    ///
+    /// Bit 5 isn't driven by the APU, and is returned cleared.
+    pub fn read_status(&mut self) -> u8 {
+        let status = self.peek_status();
+        self.frame_irq = false;
+        status
+    }
+
+    /// Reads the status register ($4015), without side effects.
+    pub fn peek_status(&self) -> u8 {
+        self.pulses[0].active() as u8
+            | (self.pulses[1].active() as u8) << 1
+            | (self.triangle.active() as u8) << 2
+            | (self.noise.active() as u8) << 3
+            | (self.dmc.active() as u8) << 4
+            | (self.frame_irq as u8) << 6
+            | (self.dmc.irq() as u8) << 7
+    }
+
+    /// IRQ output (frame counter or DMC).
+    pub fn irq(&self) -> bool {
+        self.frame_irq || self.dmc.irq()
+    }
+
+    /// Address the DMC wants to fetch, if any. See [Dmc::request].
+    pub fn dmc_request(&self) -> Option<u16> {
+        self.dmc.request()
+    }
+
+    /// Hands the byte read from [Apu::dmc_request] over to the DMC.
+    pub fn dmc_fill(&mut self, data: u8) {
+        self.dmc.fill(data);
+    }
+
+    /// CPU cycle clock.
+    pub fn cycle(&mut self) {
+        if self.odd {
+            for pulse in &mut self.pulses {
+                pulse.clock_timer();
+            }
+        }
+        self.odd = !self.odd;
+        self.triangle.clock_timer();
+        self.noise.clock_timer();
+        self.dmc.clock_timer();
+
+        self.frame_cycle += 1;
+        let steps = if self.five_step {
+            self.region.five_step()
+        } else {
+            self.region.four_step()
+        };
+        match steps.iter().position(|step| *step == self.frame_cycle) {
+            Some(0) | Some(2) => self.clock_quarter(),
+            Some(1) => {
+                self.clock_quarter();
+                self.clock_half();
+            }
+            Some(_) => {
+                self.clock_quarter();
+                self.clock_half();
+                if !self.five_step && !self.irq_inhibit {
+                    self.frame_irq = true;
+                }
+                self.frame_cycle = 0;
+            }
+            None => {}
+        }
+    }
+
+    fn clock_quarter(&mut self) {
+        for pulse in &mut self.pulses {
+            pulse.clock_quarter();
+        }
+        self.triangle.clock_quarter();
+        self.noise.clock_quarter();
+    }
+
+    fn clock_half(&mut self) {
+        for pulse in &mut self.pulses {
+            pulse.clock_half();
+        }
+        self.triangle.clock_half();
+        self.noise.clock_half();
+    }
+
+    /// Current output levels of every channel.
+    pub fn levels(&self) -> ChannelLevels {
+        ChannelLevels {
+            pulse1: self.pulses[0].output(),
+            pulse2: self.pulses[1].output(),
+            triangle: self.triangle.output(),
+            noise: self.noise.output(),
+            dmc: self.dmc.output(),
+        }
+    }
+
+    /// Power cycle (and reset button: the 2A03 silences every channel on
+    /// reset).
+    pub fn reset(&mut self) {
+        *self = Self::new(self.region);
+    }
+}
@@ -0,0 +1,169 @@
+use std::f64::consts::PI;
+
+/// Fractional bits used for the position of a clock inside the output stream.
+const FRAC_BITS: u32 = 32;
+/// Bits used for selecting one of the kernel phases.
+const PHASE_BITS: u32 = 6;
+/// Number of precomputed sub-sample kernel phases.
+const PHASES: usize = 1 << PHASE_BITS;
+/// Half of the kernel width, in output samples.
+const HALF_WIDTH: usize = 8;
+/// Kernel width, in output samples.
+const TAPS: usize = HALF_WIDTH * 2;
+/// Cutoff of the band-limiting kernel, relative to the output Nyquist rate.
+const CUTOFF: f64 = 0.9;
+
+/// Band-limited step synthesis buffer.
+///
+/// ## Prelude
+///
+/// The APU outputs a new amplitude on every CPU cycle (~1.79 MHz), but a
+/// frontend plays audio at 44.1/48 kHz. Naively picking one every ~40 input
+/// samples aliases every frequency above the output Nyquist rate back into
+/// the audible range.
+///
+/// ## Behaviour
+///
+/// Instead of storing samples, this buffer stores *changes* of amplitude
+/// (deltas) at the input clock they happened on. Every delta is spread over
+/// [TAPS] output samples using a windowed-sinc impulse (one of [PHASES]
+/// precomputed phases, selected by the sub-sample position of the delta), and
+/// the output is recovered by integrating the buffer while reading it.
+///
+/// As the impulse is band-limited, the resulting step is band-limited too,
+/// and its cost only depends on how often the amplitude changes (which, for
+/// the APU, is far less often than once per cycle).
+///
+/// The output is delayed by `HALF_WIDTH - 1` samples.
+pub struct BlipBuffer {
+    /// Output samples per input clock (`FRAC_BITS` fixed point).
+    factor: u64,
+    /// Position of the current frame start (`FRAC_BITS` fixed point).
+    offset: u64,
+    /// Running sum of the read deltas.
+    integrator: f32,
+    /// Pending deltas, starting at the first unread output sample.
+    buffer: Vec<f32>,
+    /// Band-limited impulses, one per sub-sample phase.
+    kernel: Box<[[f32; TAPS]; PHASES]>,
+}
+
+impl BlipBuffer {
+    /// Creates a buffer that converts a `clock_rate` Hz input into a
+    /// `sample_rate` Hz output.
+    ///
+    /// ## Panics
+    ///
+    /// If `sample_rate` is not lower than `clock_rate`.
+    pub fn new(clock_rate: f64, sample_rate: f64) -> Self {
+        assert!(
+            sample_rate > 0.0 && sample_rate < clock_rate,
+            "the sample rate must be lower than the clock rate"
+        );
+
+        Self {
+            factor: (sample_rate / clock_rate * (1u64 << FRAC_BITS) as f64).round() as u64,
+            offset: 0,
+            integrator: 0.0,
+            buffer: Vec::new(),
+            kernel: Self::kernel(),
+        }
+    }
+
+    fn kernel() -> Box<[[f32; TAPS]; PHASES]> {
+        let mut kernel = Box::new([[0.0; TAPS]; PHASES]);
+        for (phase, taps) in kernel.iter_mut().enumerate() {
+            let frac = phase as f64 / PHASES as f64;
+            let mut sum = 0.0;
+            let mut raw = [0.0f64; TAPS];
+
+            for (tap, value) in raw.iter_mut().enumerate() {
+                let x = tap as f64 - (HALF_WIDTH - 1) as f64 - frac;
+                let sinc = if x == 0.0 {
+                    CUTOFF
+                } else {
+                    (PI * CUTOFF * x).sin() / (PI * x)
+                };
+
+                // Blackman window
+                let w = x / HALF_WIDTH as f64;
+                let window = if w.abs() >= 1.0 {
+                    0.0
+                } else {
+                    0.42 + 0.5 * (PI * w).cos() + 0.08 * (2.0 * PI * w).cos()
+                };
+
+                *value = sinc * window;
+                sum += *value;
+            }
+
+            // Every phase must add up to one, otherwise steps would drift.
+            for (tap, value) in taps.iter_mut().zip(raw) {
+                *tap = (value / sum) as f32;
+            }
+        }
+        kernel
+    }
+
+    /// Adds an amplitude change of `delta` at `time` clocks from the start of
+    /// the current frame.
+    pub fn add_delta(&mut self, time: u32, delta: f32) {
+        let pos = self.offset + time as u64 * self.factor;
+        let index = (pos >> FRAC_BITS) as usize;
+        let phase = ((pos >> (FRAC_BITS - PHASE_BITS)) as usize) & (PHASES - 1);
+
+        if self.buffer.len() < index + TAPS {
+            self.buffer.resize(index + TAPS, 0.0);
+        }
+
+        for (sample, tap) in self.buffer[index..index + TAPS]
+            .iter_mut()
+            .zip(&self.kernel[phase])
+        {
+            *sample += delta * tap;
+        }
+    }
+
+    /// Ends the current frame after `clocks` clocks, making the output
+    /// samples that lie before that point available for reading.
+    ///
+    /// The following deltas are relative to the new frame start.
+    pub fn end_frame(&mut self, clocks: u32) {
+        self.offset += clocks as u64 * self.factor;
+    }
+
+    /// Returns the number of output samples that can be read.
+    pub fn samples_avail(&self) -> usize {
+        (self.offset >> FRAC_BITS) as usize
+    }
+
+    /// Returns the number of clocks needed to make `samples` more output
+    /// samples available.
+    pub fn clocks_needed(&self, samples: usize) -> u32 {
+        let needed = ((self.samples_avail() + samples) as u64) << FRAC_BITS;
+        needed.saturating_sub(self.offset).div_ceil(self.factor) as u32
+    }
+
+    /// Reads up to `out.len()` samples, returning how many were read.
+    pub fn read_samples(&mut self, out: &mut [f32]) -> usize {
+        let count = self.samples_avail().min(out.len());
+        if self.buffer.len() < count {
+            self.buffer.resize(count, 0.0);
+        }
+
+        for (sample, delta) in out.iter_mut().zip(self.buffer.drain(..count)) {
+            self.integrator += delta;
+            *sample = self.integrator;
+        }
+
+        self.offset -= (count as u64) << FRAC_BITS;
+        count
+    }
+
+    /// Drops every pending sample and delta.
+    pub fn clear(&mut self) {
+        self.offset = 0;
+        self.integrator = 0.0;
+        self.buffer.clear();
+    }
+}
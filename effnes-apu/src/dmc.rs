@@ -0,0 +1,166 @@
+use crate::apu::Region;
+
+/// DMC timer periods (in CPU cycles) of the NTSC 2A03.
+static RATES_NTSC: [u16; 16] = [
+    428, 380, 340, 320, 286, 254, 226, 214, 190, 160, 142, 128, 106, 84, 72, 54,
+];
+
+/// DMC timer periods (in CPU cycles) of the PAL 2A07.
+static RATES_PAL: [u16; 16] = [
+    398, 354, 316, 298, 276, 236, 210, 198, 176, 148, 132, 118, 98, 78, 66, 50,
+];
+
+/// Delta modulation channel.
+///
+/// ## Behaviour
+///
+/// - [Dmc::clock_timer] must be called on every CPU cycle.
+/// - Registers are numbered 0..=3, as in $4010-$4013.
+/// - The channel doesn't own a bus: while [Dmc::request] returns an
+///   address, the owner must read it and hand the byte over through
+///   [Dmc::fill] (the 2A03 does it through a DMA that stalls the CPU).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Dmc {
+    region: Region,
+    irq_enabled: bool,
+    looping: bool,
+    period: u16,
+    timer: u16,
+    level: u8,
+
+    sample_address: u16,
+    sample_length: u16,
+    address: u16,
+    remaining: u16,
+    buffer: Option<u8>,
+
+    shift: u8,
+    bits: u8,
+    silence: bool,
+    irq: bool,
+}
+
+impl Dmc {
+    pub fn new(region: Region) -> Self {
+        Self {
+            region,
+            irq_enabled: false,
+            looping: false,
+            period: RATES_NTSC[0],
+            timer: 0,
+            level: 0,
+            sample_address: 0xC000,
+            sample_length: 1,
+            address: 0xC000,
+            remaining: 0,
+            buffer: None,
+            shift: 0,
+            bits: 8,
+            silence: true,
+            irq: false,
+        }
+    }
+
+    /// Writes `data` into register `reg` (0..=3).
+    pub fn write(&mut self, reg: u8, data: u8) {
+        match reg & 0x03 {
+            0 => {
+                self.irq_enabled = data & 0x80 != 0;
+                self.looping = data & 0x40 != 0;
+                if !self.irq_enabled {
+                    self.irq = false;
+                }
+                let table = match self.region {
+                    Region::Ntsc => &RATES_NTSC,
+                    Region::Pal => &RATES_PAL,
+                };
+                self.period = table[(data & 0x0F) as usize];
+            }
+            1 => self.level = data & 0x7F,
+            2 => self.sample_address = 0xC000 | ((data as u16) << 6),
+            _ => self.sample_length = ((data as u16) << 4) | 1,
+        }
+    }
+
+    /// Enables ($4015 bit 4 set) or disables the channel. Either way, the
+    /// interrupt flag is acknowledged.
+    ///
+    /// Enabling it restarts the sample only if it had already finished.
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.irq = false;
+        if !enabled {
+            self.remaining = 0;
+        } else if self.remaining == 0 {
+            self.address = self.sample_address;
+            self.remaining = self.sample_length;
+        }
+    }
+
+    /// Whether there are sample bytes left to fetch.
+    pub fn active(&self) -> bool {
+        self.remaining > 0
+    }
+
+    /// IRQ output.
+    pub fn irq(&self) -> bool {
+        self.irq
+    }
+
+    /// Address of the next sample byte, while the sample buffer is empty
+    /// and there are bytes left.
+    pub fn request(&self) -> Option<u16> {
+        (self.buffer.is_none() && self.remaining > 0).then_some(self.address)
+    }
+
+    /// Fills the sample buffer with the byte read from [Dmc::request].
+    pub fn fill(&mut self, data: u8) {
+        self.buffer = Some(data);
+        self.address = self.address.checked_add(1).unwrap_or(0x8000);
+        self.remaining -= 1;
+        if self.remaining == 0 {
+            if self.looping {
+                self.address = self.sample_address;
+                self.remaining = self.sample_length;
+            } else if self.irq_enabled {
+                self.irq = true;
+            }
+        }
+    }
+
+    /// CPU cycle clock.
+    pub fn clock_timer(&mut self) {
+        if self.timer > 1 {
+            self.timer -= 1;
+            return;
+        }
+        self.timer = self.period;
+
+        if !self.silence {
+            if self.shift & 1 != 0 {
+                if self.level <= 125 {
+                    self.level += 2;
+                }
+            } else if self.level >= 2 {
+                self.level -= 2;
+            }
+        }
+        self.shift >>= 1;
+
+        self.bits -= 1;
+        if self.bits == 0 {
+            self.bits = 8;
+            match self.buffer.take() {
+                Some(data) => {
+                    self.shift = data;
+                    self.silence = false;
+                }
+                None => self.silence = true,
+            }
+        }
+    }
+
+    /// Current output level (0..=127).
+    pub fn output(&self) -> u8 {
+        self.level
+    }
+}
@@ -0,0 +1,108 @@
+use std::f32::consts::PI;
+
+/// First order high-pass filter.
+#[derive(Clone, Debug)]
+pub struct HighPass {
+    alpha: f32,
+    last_in: f32,
+    last_out: f32,
+}
+
+impl HighPass {
+    /// Creates a high-pass filter with a `cutoff` frequency (in Hz) for a
+    /// signal sampled at `sample_rate` Hz.
+    pub fn new(cutoff: f32, sample_rate: f32) -> Self {
+        let rc = 1.0 / (2.0 * PI * cutoff);
+        let dt = 1.0 / sample_rate;
+        Self {
+            alpha: rc / (rc + dt),
+            last_in: 0.0,
+            last_out: 0.0,
+        }
+    }
+
+    pub fn process(&mut self, input: f32) -> f32 {
+        self.last_out = self.alpha * (self.last_out + input - self.last_in);
+        self.last_in = input;
+        self.last_out
+    }
+
+    pub fn reset(&mut self) {
+        self.last_in = 0.0;
+        self.last_out = 0.0;
+    }
+}
+
+/// First order low-pass filter.
+#[derive(Clone, Debug)]
+pub struct LowPass {
+    alpha: f32,
+    last_out: f32,
+}
+
+impl LowPass {
+    /// Creates a low-pass filter with a `cutoff` frequency (in Hz) for a
+    /// signal sampled at `sample_rate` Hz.
+    pub fn new(cutoff: f32, sample_rate: f32) -> Self {
+        let rc = 1.0 / (2.0 * PI * cutoff);
+        let dt = 1.0 / sample_rate;
+        Self {
+            alpha: dt / (rc + dt),
+            last_out: 0.0,
+        }
+    }
+
+    pub fn process(&mut self, input: f32) -> f32 {
+        self.last_out += self.alpha * (input - self.last_out);
+        self.last_out
+    }
+
+    pub fn reset(&mut self) {
+        self.last_out = 0.0;
+    }
+}
+
+/// The analog output stage of the console.
+///
+/// The NES (front loader) has two high-pass filters (90 Hz and 440 Hz) and a
+/// 14 kHz low-pass filter between the APU and the RF/AV outputs. The Famicom
+/// only has a 37 Hz high-pass filter (and the same low-pass filter).
+#[derive(Clone, Debug)]
+pub struct OutputFilter {
+    high_pass: Vec<HighPass>,
+    low_pass: LowPass,
+}
+
+impl OutputFilter {
+    /// NES (front loader) output filter chain.
+    pub fn nes(sample_rate: f32) -> Self {
+        Self {
+            high_pass: vec![
+                HighPass::new(90.0, sample_rate),
+                HighPass::new(440.0, sample_rate),
+            ],
+            low_pass: LowPass::new(14_000.0, sample_rate),
+        }
+    }
+
+    /// Famicom output filter chain.
+    pub fn famicom(sample_rate: f32) -> Self {
+        Self {
+            high_pass: vec![HighPass::new(37.0, sample_rate)],
+            low_pass: LowPass::new(14_000.0, sample_rate),
+        }
+    }
+
+    pub fn process(&mut self, input: f32) -> f32 {
+        let mut out = input;
+        for filter in &mut self.high_pass {
+            out = filter.process(out);
+        }
+        self.low_pass.process(out)
+    }
+
+    pub fn reset(&mut self) {
+        self.high_pass.iter_mut().for_each(HighPass::reset);
+        self.low_pass.reset();
+    }
+}
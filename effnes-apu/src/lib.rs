@@ -0,0 +1,15 @@
+pub mod apu;
+pub mod blip;
+pub mod dmc;
+pub mod filter;
+pub mod mixer;
+pub mod noise;
+pub mod pulse;
+pub mod record;
+pub mod resampler;
+pub mod sink;
+pub mod triangle;
+pub mod wav;
+
+#[cfg(test)]
+mod tests;
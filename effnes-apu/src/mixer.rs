@@ -0,0 +1,111 @@
+/// Number of entries of [PULSE_TABLE] (`pulse1 + pulse2`, both 4-bit).
+pub const PULSE_TABLE_LEN: usize = 31;
+
+/// Number of entries of [TND_TABLE] (`3 * triangle + 2 * noise + dmc`).
+pub const TND_TABLE_LEN: usize = 203;
+
+/// Nonlinear pulse mixer lookup table.
+///
+/// `PULSE_TABLE[n] = 95.52 / (8128 / n + 100)`, with `PULSE_TABLE[0] = 0`.
+pub static PULSE_TABLE: [f32; PULSE_TABLE_LEN] = pulse_table();
+
+/// Nonlinear triangle/noise/DMC mixer lookup table.
+///
+/// `TND_TABLE[n] = 163.67 / (24329 / n + 100)`, with `TND_TABLE[0] = 0`.
+pub static TND_TABLE: [f32; TND_TABLE_LEN] = tnd_table();
+
+const fn pulse_table() -> [f32; PULSE_TABLE_LEN] {
+    let mut table = [0.0; PULSE_TABLE_LEN];
+    let mut n = 1;
+    while n < PULSE_TABLE_LEN {
+        table[n] = (95.52 / (8128.0 / n as f64 + 100.0)) as f32;
+        n += 1;
+    }
+    table
+}
+
+const fn tnd_table() -> [f32; TND_TABLE_LEN] {
+    let mut table = [0.0; TND_TABLE_LEN];
+    let mut n = 1;
+    while n < TND_TABLE_LEN {
+        table[n] = (163.67 / (24329.0 / n as f64 + 100.0)) as f32;
+        n += 1;
+    }
+    table
+}
+
+/// A 2A03 sound channel.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Channel {
+    Pulse1,
+    Pulse2,
+    Triangle,
+    Noise,
+    Dmc,
+}
+
+impl Channel {
+    pub const ALL: [Channel; 5] = [
+        Channel::Pulse1,
+        Channel::Pulse2,
+        Channel::Triangle,
+        Channel::Noise,
+        Channel::Dmc,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Channel::Pulse1 => "pulse1",
+            Channel::Pulse2 => "pulse2",
+            Channel::Triangle => "triangle",
+            Channel::Noise => "noise",
+            Channel::Dmc => "dmc",
+        }
+    }
+}
+
+/// Instantaneous output levels of the 2A03 sound channels.
+///
+/// Pulses, triangle and noise output 4-bit values (0..=15), while the DMC
+/// outputs a 7-bit value (0..=127). Out of range values are masked.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct ChannelLevels {
+    pub pulse1: u8,
+    pub pulse2: u8,
+    pub triangle: u8,
+    pub noise: u8,
+    pub dmc: u8,
+}
+
+impl ChannelLevels {
+    /// Mixes the channel levels through the nonlinear DAC of the 2A03.
+    ///
+    /// The output is in the `0.0 ..= 1.0` range (the maximum reachable value
+    /// is slightly below 1).
+    pub fn mix(&self) -> f32 {
+        let pulse = (self.pulse1 & 0x0F) as usize + (self.pulse2 & 0x0F) as usize;
+        let tnd = 3 * (self.triangle & 0x0F) as usize
+            + 2 * (self.noise & 0x0F) as usize
+            + (self.dmc & 0x7F) as usize;
+        PULSE_TABLE[pulse] + TND_TABLE[tnd]
+    }
+
+    /// Mixes the channel levels plus an `expansion` audio amplitude (from
+    /// the cartridge, already scaled to the [ChannelLevels::mix] range).
+    pub fn mix_with_expansion(&self, expansion: f32) -> f32 {
+        self.mix() + expansion
+    }
+
+    /// Mixes a single channel, as if every other channel was silent.
+    pub fn mix_channel(&self, channel: Channel) -> f32 {
+        let mut levels = ChannelLevels::default();
+        match channel {
+            Channel::Pulse1 => levels.pulse1 = self.pulse1,
+            Channel::Pulse2 => levels.pulse2 = self.pulse2,
+            Channel::Triangle => levels.triangle = self.triangle,
+            Channel::Noise => levels.noise = self.noise,
+            Channel::Dmc => levels.dmc = self.dmc,
+        }
+        levels.mix()
+    }
+}
@@ -0,0 +1,109 @@
+use crate::{
+    apu::Region,
+    pulse::{Envelope, LengthCounter},
+};
+
+/// Noise timer periods (in CPU cycles) of the NTSC 2A03.
+static PERIODS_NTSC: [u16; 16] = [
+    4, 8, 16, 32, 64, 96, 128, 160, 202, 254, 380, 508, 762, 1016, 2034, 4068,
+];
+
+/// Noise timer periods (in CPU cycles) of the PAL 2A07.
+static PERIODS_PAL: [u16; 16] = [
+    4, 8, 14, 30, 60, 88, 118, 148, 188, 236, 354, 472, 708, 944, 1890, 3778,
+];
+
+/// Pseudo-random noise channel.
+///
+/// ## Behaviour
+///
+/// - [Noise::clock_timer] must be called on every CPU cycle, with the
+///   periods of the console region (see [Region]).
+/// - Registers are numbered 0..=3, as in $400C-$400F ($400D is unused).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Noise {
+    region: Region,
+    short_mode: bool,
+    period: u16,
+    timer: u16,
+    shift: u16,
+    envelope: Envelope,
+    length: LengthCounter,
+}
+
+impl Noise {
+    pub fn new(region: Region) -> Self {
+        Self {
+            region,
+            short_mode: false,
+            period: PERIODS_NTSC[0],
+            timer: 0,
+            shift: 1,
+            envelope: Envelope::default(),
+            length: LengthCounter::default(),
+        }
+    }
+
+    /// Writes `data` into register `reg` (0..=3).
+    pub fn write(&mut self, reg: u8, data: u8) {
+        match reg & 0x03 {
+            0 => {
+                self.length.set_halt(data & 0x20 != 0);
+                self.envelope.write(data);
+            }
+            1 => {}
+            2 => {
+                self.short_mode = data & 0x80 != 0;
+                let table = match self.region {
+                    Region::Ntsc => &PERIODS_NTSC,
+                    Region::Pal => &PERIODS_PAL,
+                };
+                self.period = table[(data & 0x0F) as usize];
+            }
+            _ => {
+                self.length.load(data >> 3);
+                self.envelope.restart();
+            }
+        }
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.length.set_enabled(enabled);
+    }
+
+    /// Whether the length counter is non zero.
+    pub fn active(&self) -> bool {
+        self.length.active()
+    }
+
+    /// CPU cycle clock.
+    pub fn clock_timer(&mut self) {
+        if self.timer <= 1 {
+            self.timer = self.period;
+            let tap = if self.short_mode { 6 } else { 1 };
+            let feedback = (self.shift ^ (self.shift >> tap)) & 1;
+            self.shift = (self.shift >> 1) | (feedback << 14);
+        } else {
+            self.timer -= 1;
+        }
+    }
+
+    /// Quarter frame clock (envelope).
+    pub fn clock_quarter(&mut self) {
+        self.envelope.clock();
+    }
+
+    /// Half frame clock (length counter).
+    pub fn clock_half(&mut self) {
+        self.length.clock();
+    }
+
+    /// Current output level (0..=15).
+    pub fn output(&self) -> u8 {
+        if self.shift & 1 != 0 || !self.length.active() {
+            0
+        } else {
+            self.envelope.volume()
+        }
+    }
+}
@@ -0,0 +1,268 @@
+/// Length counter load values, indexed by the 5-bit value written into the
+/// channel length register.
+pub static LENGTH_TABLE: [u8; 32] = [
+    10, 254, 20, 2, 40, 4, 80, 6, 160, 8, 60, 10, 14, 12, 26, 14, 12, 16, 24, 18, 48, 20, 96, 22,
+    192, 24, 72, 26, 16, 28, 32, 30,
+];
+
+/// Pulse duty cycle sequences (12.5%, 25%, 50% and negated 25%).
+static DUTY_TABLE: [[u8; 8]; 4] = [
+    [0, 1, 0, 0, 0, 0, 0, 0],
+    [0, 1, 1, 0, 0, 0, 0, 0],
+    [0, 1, 1, 1, 1, 0, 0, 0],
+    [1, 0, 0, 1, 1, 1, 1, 1],
+];
+
+/// Volume envelope generator.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Envelope {
+    start: bool,
+    looping: bool,
+    constant: bool,
+    period: u8,
+    divider: u8,
+    decay: u8,
+}
+
+impl Envelope {
+    /// Writes the envelope bits (`--LC VVVV`) of a channel register.
+    pub fn write(&mut self, data: u8) {
+        self.looping = data & 0x20 != 0;
+        self.constant = data & 0x10 != 0;
+        self.period = data & 0x0F;
+    }
+
+    /// Restarts the envelope on the next quarter frame clock.
+    pub fn restart(&mut self) {
+        self.start = true;
+    }
+
+    /// Quarter frame clock.
+    pub fn clock(&mut self) {
+        if self.start {
+            self.start = false;
+            self.decay = 15;
+            self.divider = self.period;
+        } else if self.divider == 0 {
+            self.divider = self.period;
+            if self.decay > 0 {
+                self.decay -= 1;
+            } else if self.looping {
+                self.decay = 15;
+            }
+        } else {
+            self.divider -= 1;
+        }
+    }
+
+    /// Current volume (0..=15).
+    pub fn volume(&self) -> u8 {
+        if self.constant {
+            self.period
+        } else {
+            self.decay
+        }
+    }
+}
+
+/// Length counter.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LengthCounter {
+    enabled: bool,
+    halt: bool,
+    value: u8,
+}
+
+impl LengthCounter {
+    /// Enables or disables the counter. Disabling it also clears it.
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        if !enabled {
+            self.value = 0;
+        }
+    }
+
+    pub fn set_halt(&mut self, halt: bool) {
+        self.halt = halt;
+    }
+
+    /// Loads the counter from [LENGTH_TABLE], if it's enabled.
+    pub fn load(&mut self, index: u8) {
+        if self.enabled {
+            self.value = LENGTH_TABLE[(index & 0x1F) as usize];
+        }
+    }
+
+    /// Half frame clock.
+    pub fn clock(&mut self) {
+        if !self.halt && self.value > 0 {
+            self.value -= 1;
+        }
+    }
+
+    pub fn value(&self) -> u8 {
+        self.value
+    }
+
+    /// Whether the channel isn't silenced by the counter.
+    pub fn active(&self) -> bool {
+        self.value > 0
+    }
+}
+
+/// Pulse unit flavour.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum PulseKind {
+    /// 2A03 pulse 1: the sweep negates with ones' complement.
+    Pulse1,
+    /// 2A03 pulse 2: the sweep negates with two's complement.
+    Pulse2,
+    /// Expansion pulse (MMC5): no sweep unit, and no muting of short
+    /// periods.
+    Expansion,
+}
+
+/// Pulse wave channel.
+///
+/// ## Behaviour
+///
+/// - [Pulse::clock_timer] must be called on every APU cycle (every other
+///   CPU cycle), [Pulse::clock_quarter] and [Pulse::clock_half] by the
+///   frame sequencer.
+/// - Registers are numbered 0..=3, as in $4000-$4003.
+/// - 2A03 pulses are muted while the period is below 8, or while the sweep
+///   target period overflows $7FF.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Pulse {
+    kind: PulseKind,
+    duty: u8,
+    step: u8,
+    period: u16,
+    timer: u16,
+    envelope: Envelope,
+    length: LengthCounter,
+
+    sweep_enabled: bool,
+    sweep_period: u8,
+    sweep_negate: bool,
+    sweep_shift: u8,
+    sweep_reload: bool,
+    sweep_divider: u8,
+}
+
+impl Pulse {
+    pub fn new(kind: PulseKind) -> Self {
+        Self {
+            kind,
+            duty: 0,
+            step: 0,
+            period: 0,
+            timer: 0,
+            envelope: Envelope::default(),
+            length: LengthCounter::default(),
+            sweep_enabled: false,
+            sweep_period: 0,
+            sweep_negate: false,
+            sweep_shift: 0,
+            sweep_reload: false,
+            sweep_divider: 0,
+        }
+    }
+
+    /// Writes `data` into register `reg` (0..=3).
+    pub fn write(&mut self, reg: u8, data: u8) {
+        match reg & 0x03 {
+            0 => {
+                self.duty = data >> 6;
+                self.length.set_halt(data & 0x20 != 0);
+                self.envelope.write(data);
+            }
+            1 => {
+                self.sweep_enabled = data & 0x80 != 0;
+                self.sweep_period = (data >> 4) & 0x07;
+                self.sweep_negate = data & 0x08 != 0;
+                self.sweep_shift = data & 0x07;
+                self.sweep_reload = true;
+            }
+            2 => self.period = (self.period & 0x700) | data as u16,
+            _ => {
+                self.period = (self.period & 0x0FF) | (((data & 0x07) as u16) << 8);
+                self.length.load(data >> 3);
+                self.step = 0;
+                self.envelope.restart();
+            }
+        }
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.length.set_enabled(enabled);
+    }
+
+    /// Whether the length counter is non zero.
+    pub fn active(&self) -> bool {
+        self.length.active()
+    }
+
+    /// APU cycle clock.
+    pub fn clock_timer(&mut self) {
+        if self.timer == 0 {
+            self.timer = self.period;
+            self.step = (self.step + 1) & 0x07;
+        } else {
+            self.timer -= 1;
+        }
+    }
+
+    /// Quarter frame clock (envelope).
+    pub fn clock_quarter(&mut self) {
+        self.envelope.clock();
+    }
+
+    /// Half frame clock (length counter and sweep).
+    pub fn clock_half(&mut self) {
+        self.length.clock();
+        if self.kind == PulseKind::Expansion {
+            return;
+        }
+
+        if self.sweep_divider == 0
+            && self.sweep_enabled
+            && self.sweep_shift != 0
+            && !self.sweep_muted()
+        {
+            self.period = self.sweep_target();
+        }
+        if self.sweep_divider == 0 || self.sweep_reload {
+            self.sweep_divider = self.sweep_period;
+            self.sweep_reload = false;
+        } else {
+            self.sweep_divider -= 1;
+        }
+    }
+
+    fn sweep_target(&self) -> u16 {
+        let change = self.period >> self.sweep_shift;
+        if !self.sweep_negate {
+            self.period + change
+        } else if self.kind == PulseKind::Pulse1 {
+            self.period.saturating_sub(change + 1)
+        } else {
+            self.period.saturating_sub(change)
+        }
+    }
+
+    fn sweep_muted(&self) -> bool {
+        self.period < 8 || self.sweep_target() > 0x7FF
+    }
+
+    /// Current output level (0..=15).
+    pub fn output(&self) -> u8 {
+        let muted = self.kind != PulseKind::Expansion && self.sweep_muted();
+        if muted || !self.length.active() || DUTY_TABLE[self.duty as usize][self.step as usize] == 0
+        {
+            0
+        } else {
+            self.envelope.volume()
+        }
+    }
+}
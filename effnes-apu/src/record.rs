@@ -0,0 +1,140 @@
+use crate::{
+    mixer::{Channel, ChannelLevels},
+    resampler::Resampler,
+    wav::{WavSpec, WavWriter},
+};
+use std::{
+    fs::File,
+    io::{self, BufWriter, Seek, Write},
+    path::Path,
+};
+
+struct Track<W: Write + Seek> {
+    resampler: Resampler,
+    writer: WavWriter<W>,
+}
+
+impl<W: Write + Seek> Track<W> {
+    fn new(clock_rate: f64, sample_rate: u32, writer: W) -> io::Result<Self> {
+        let spec = WavSpec {
+            channels: 1,
+            sample_rate,
+        };
+        Ok(Self {
+            resampler: Resampler::new(clock_rate, sample_rate as f64),
+            writer: WavWriter::new(writer, spec)?,
+        })
+    }
+
+    fn end_frame(&mut self) {
+        self.resampler.end_frame::<i16>(&mut self.writer);
+    }
+
+    fn finish(mut self) -> io::Result<W> {
+        self.end_frame();
+        self.writer.finish()
+    }
+}
+
+/// Headless APU recorder.
+///
+/// Records the mixed APU output (and, optionally, every channel on its own)
+/// into mono 16-bit PCM WAV files, so runs can be compared against reference
+/// recordings.
+///
+/// ```ignore
+/// let mut recorder = Recorder::create("out.wav", CPU_CLOCK_NTSC, 44_100)?
+///     .with_channel_file(Channel::Triangle, "out.triangle.wav")?;
+///
+/// for _ in 0..frames {
+///     for _ in 0..29_780 {
+///         apu.cycle();
+///         recorder.cycle(&apu.levels());
+///     }
+///     recorder.end_frame();
+/// }
+/// recorder.finish()?;
+/// ```
+pub struct Recorder<W: Write + Seek> {
+    clock_rate: f64,
+    sample_rate: u32,
+    mixed: Track<W>,
+    channels: Vec<(Channel, Track<W>)>,
+}
+
+impl Recorder<BufWriter<File>> {
+    /// Records the mixed output into the file at `path`.
+    pub fn create(path: impl AsRef<Path>, clock_rate: f64, sample_rate: u32) -> io::Result<Self> {
+        Self::new(BufWriter::new(File::create(path)?), clock_rate, sample_rate)
+    }
+
+    /// Also records `channel` into the file at `path`.
+    pub fn with_channel_file(self, channel: Channel, path: impl AsRef<Path>) -> io::Result<Self> {
+        self.with_channel(channel, BufWriter::new(File::create(path)?))
+    }
+}
+
+impl<W: Write + Seek> Recorder<W> {
+    /// Records the mixed output into `writer`.
+    pub fn new(writer: W, clock_rate: f64, sample_rate: u32) -> io::Result<Self> {
+        Ok(Self {
+            clock_rate,
+            sample_rate,
+            mixed: Track::new(clock_rate, sample_rate, writer)?,
+            channels: Vec::new(),
+        })
+    }
+
+    /// Also records `channel` into `writer`.
+    pub fn with_channel(mut self, channel: Channel, writer: W) -> io::Result<Self> {
+        let track = Track::new(self.clock_rate, self.sample_rate, writer)?;
+        self.channels.push((channel, track));
+        Ok(self)
+    }
+
+    /// Adds the channel levels of the next CPU cycle.
+    pub fn cycle(&mut self, levels: &ChannelLevels) {
+        self.cycle_with_expansion(levels, 0.0);
+    }
+
+    /// Adds the channel levels of the next CPU cycle, plus an `expansion`
+    /// audio amplitude (already scaled to the APU output range) on the mixed
+    /// track.
+    pub fn cycle_with_expansion(&mut self, levels: &ChannelLevels, expansion: f32) {
+        self.mixed
+            .resampler
+            .push(levels.mix_with_expansion(expansion));
+        for (channel, track) in &mut self.channels {
+            track.resampler.push(levels.mix_channel(*channel));
+        }
+    }
+
+    /// Writes the samples of the current frame.
+    ///
+    /// Should be called regularly (e.g. once per video frame), as samples are
+    /// buffered until then.
+    pub fn end_frame(&mut self) {
+        self.mixed.end_frame();
+        for (_, track) in &mut self.channels {
+            track.end_frame();
+        }
+    }
+
+    /// Number of frames written into the mixed track.
+    pub fn frames(&self) -> u32 {
+        self.mixed.writer.frames()
+    }
+
+    /// Writes the pending samples and finishes every file, returning the
+    /// mixed track writer followed by the channel writers (in the order they
+    /// were added).
+    pub fn finish(self) -> io::Result<(W, Vec<(Channel, W)>)> {
+        let mixed = self.mixed.finish()?;
+        let channels = self
+            .channels
+            .into_iter()
+            .map(|(channel, track)| Ok((channel, track.finish()?)))
+            .collect::<io::Result<_>>()?;
+        Ok((mixed, channels))
+    }
+}
@@ -0,0 +1,142 @@
+use crate::{
+    blip::BlipBuffer,
+    filter::OutputFilter,
+    sink::{AudioSink, Sample},
+};
+
+/// NTSC (2A03) CPU clock rate, in Hz.
+pub const CPU_CLOCK_NTSC: f64 = 1_789_773.0;
+/// PAL (2A07) CPU clock rate, in Hz.
+pub const CPU_CLOCK_PAL: f64 = 1_662_607.0;
+/// Dendy CPU clock rate, in Hz.
+pub const CPU_CLOCK_DENDY: f64 = 1_773_448.0;
+
+/// Maximum number of output channels supported by [Resampler].
+pub const MAX_CHANNELS: usize = 8;
+
+/// Number of samples converted at once while draining the buffer.
+const CHUNK: usize = 512;
+
+/// APU output resampler.
+///
+/// Takes one (already mixed) amplitude per CPU cycle, band-limits it through
+/// a [BlipBuffer], applies the console [OutputFilter] and pushes the result
+/// into an [AudioSink] (the same sample is written on every output channel).
+///
+/// ```ignore
+/// let mut resampler = Resampler::new(CPU_CLOCK_NTSC, 48_000.0);
+/// let mut out: Vec<i16> = Vec::new();
+///
+/// for _ in 0..29_780 {
+///     apu.cycle();
+///     resampler.push(apu.levels().mix());
+/// }
+/// resampler.end_frame(&mut out);
+/// ```
+pub struct Resampler {
+    blip: BlipBuffer,
+    filter: OutputFilter,
+    channels: usize,
+
+    /// Last amplitude added to the buffer.
+    amplitude: f32,
+    /// Clocks elapsed since the start of the current frame.
+    time: u32,
+    scratch: [f32; CHUNK],
+}
+
+impl Resampler {
+    /// Creates a mono resampler, using the NES output filters.
+    pub fn new(clock_rate: f64, sample_rate: f64) -> Self {
+        Self {
+            blip: BlipBuffer::new(clock_rate, sample_rate),
+            filter: OutputFilter::nes(sample_rate as f32),
+            channels: 1,
+            amplitude: 0.0,
+            time: 0,
+            scratch: [0.0; CHUNK],
+        }
+    }
+
+    /// Sets the number of output channels (every frame contains the same
+    /// sample `channels` times).
+    ///
+    /// ## Panics
+    ///
+    /// If `channels` is zero or greater than [MAX_CHANNELS].
+    pub fn with_channels(mut self, channels: usize) -> Self {
+        assert!((1..=MAX_CHANNELS).contains(&channels));
+        self.channels = channels;
+        self
+    }
+
+    /// Replaces the output filter chain.
+    pub fn with_filter(mut self, filter: OutputFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    pub fn channels(&self) -> usize {
+        self.channels
+    }
+
+    /// Adds the amplitude of the next CPU cycle.
+    pub fn push(&mut self, amplitude: f32) {
+        self.set_amplitude(self.time, amplitude);
+        self.time += 1;
+    }
+
+    /// Sets the amplitude at `time` clocks from the start of the current
+    /// frame, without advancing the frame clock.
+    ///
+    /// Useful for sources that only report their changes. `time` must not be
+    /// lower than the time of the previous change.
+    pub fn set_amplitude(&mut self, time: u32, amplitude: f32) {
+        let delta = amplitude - self.amplitude;
+        if delta != 0.0 {
+            self.amplitude = amplitude;
+            self.blip.add_delta(time, delta);
+        }
+    }
+
+    /// Advances the frame clock by `clocks` cycles, keeping the current
+    /// amplitude.
+    pub fn skip(&mut self, clocks: u32) {
+        self.time += clocks;
+    }
+
+    /// Clocks elapsed since the start of the current frame.
+    pub fn time(&self) -> u32 {
+        self.time
+    }
+
+    /// Ends the current frame, pushing every available frame into `sink`.
+    ///
+    /// Returns the number of frames pushed.
+    pub fn end_frame<S: Sample>(&mut self, sink: &mut impl AudioSink<S>) -> usize {
+        self.blip.end_frame(self.time);
+        self.time = 0;
+
+        let mut total = 0;
+        loop {
+            let count = self.blip.read_samples(&mut self.scratch);
+            if count == 0 {
+                break total;
+            }
+
+            for sample in &self.scratch[..count] {
+                let frame = [S::from_f32(self.filter.process(*sample)); MAX_CHANNELS];
+                sink.push_frame(&frame[..self.channels]);
+            }
+            total += count;
+        }
+    }
+
+    /// Drops every pending sample, and resets the filters.
+    pub fn reset(&mut self) {
+        self.blip.clear();
+        self.filter.reset();
+        self.amplitude = 0.0;
+        self.time = 0;
+    }
+}
@@ -0,0 +1,44 @@
+/// Output sample format.
+pub trait Sample: Copy {
+    /// Converts a `-1.0 ..= 1.0` sample into this format, clipping it if
+    /// needed.
+    fn from_f32(value: f32) -> Self;
+
+    /// Converts this sample into a `-1.0 ..= 1.0` sample.
+    fn to_f32(self) -> f32;
+}
+
+impl Sample for f32 {
+    fn from_f32(value: f32) -> Self {
+        value.clamp(-1.0, 1.0)
+    }
+
+    fn to_f32(self) -> f32 {
+        self
+    }
+}
+
+impl Sample for i16 {
+    fn from_f32(value: f32) -> Self {
+        (value.clamp(-1.0, 1.0) * i16::MAX as f32).round() as i16
+    }
+
+    fn to_f32(self) -> f32 {
+        self as f32 / i16::MAX as f32
+    }
+}
+
+/// Audio Sink
+///
+/// Receives the resampled audio, one frame (a sample for every output
+/// channel) at a time.
+pub trait AudioSink<S: Sample> {
+    fn push_frame(&mut self, frame: &[S]);
+}
+
+/// Appends every frame, interleaved.
+impl<S: Sample> AudioSink<S> for Vec<S> {
+    fn push_frame(&mut self, frame: &[S]) {
+        self.extend_from_slice(frame);
+    }
+}
@@ -0,0 +1,292 @@
+use crate::{
+    apu::{Apu, Region},
+    blip::BlipBuffer,
+    filter::{HighPass, OutputFilter},
+    mixer::{Channel, ChannelLevels, PULSE_TABLE, TND_TABLE},
+    pulse::{Pulse, PulseKind},
+    record::Recorder,
+    resampler::{CPU_CLOCK_NTSC, Resampler},
+    sink::AudioSink,
+    wav::{WavSpec, WavWriter, read_wav},
+};
+use std::io::Cursor;
+
+#[test]
+fn mixer_tables_match_formula() {
+    assert_eq!(PULSE_TABLE[0], 0.0);
+    assert_eq!(TND_TABLE[0], 0.0);
+    assert!((PULSE_TABLE[30] - 0.257_512_6).abs() < 1e-6);
+    assert!((TND_TABLE[202] - 0.742_467_6).abs() < 1e-6);
+}
+
+#[test]
+fn mixer_is_monotonic() {
+    let mut last = -1.0;
+    for level in 0..16 {
+        let mixed = ChannelLevels {
+            pulse1: level,
+            ..Default::default()
+        }
+        .mix();
+        assert!(mixed > last);
+        last = mixed;
+    }
+
+    let full = ChannelLevels {
+        pulse1: 15,
+        pulse2: 15,
+        triangle: 15,
+        noise: 15,
+        dmc: 127,
+    };
+    assert!(full.mix() < 1.0);
+}
+
+#[test]
+fn high_pass_removes_dc() {
+    let mut filter = HighPass::new(90.0, 48_000.0);
+    let mut out = 1.0;
+    for _ in 0..48_000 {
+        out = filter.process(0.5);
+    }
+    assert!(out.abs() < 1e-4);
+}
+
+#[test]
+fn blip_sample_count() {
+    let mut blip = BlipBuffer::new(CPU_CLOCK_NTSC, 44_100.0);
+    let mut out = vec![0.0; 50_000];
+    let mut total = 0;
+    for _ in 0..60 {
+        blip.end_frame(29_830);
+        total += blip.read_samples(&mut out);
+    }
+
+    let expected = 60.0 * 29_830.0 * 44_100.0 / CPU_CLOCK_NTSC;
+    assert!((total as f64 - expected).abs() <= 1.0);
+}
+
+#[test]
+fn blip_step_settles() {
+    let mut blip = BlipBuffer::new(CPU_CLOCK_NTSC, 48_000.0);
+    blip.add_delta(1_000, 0.75);
+    blip.end_frame(10_000);
+
+    let mut out = vec![0.0; 1_000];
+    let count = blip.read_samples(&mut out);
+    assert!(out[0].abs() < 1e-6);
+    assert!((out[count - 1] - 0.75).abs() < 1e-5);
+}
+
+#[test]
+fn blip_clocks_needed() {
+    let mut blip = BlipBuffer::new(CPU_CLOCK_NTSC, 48_000.0);
+    let clocks = blip.clocks_needed(100);
+    blip.end_frame(clocks);
+    assert_eq!(blip.samples_avail(), 100);
+}
+
+#[test]
+fn resampler_pushes_frames() {
+    let mut resampler = Resampler::new(CPU_CLOCK_NTSC, 48_000.0)
+        .with_channels(2)
+        .with_filter(OutputFilter::famicom(48_000.0));
+    let mut out: Vec<i16> = Vec::new();
+
+    // 1 kHz square wave
+    for cycle in 0..29_830 {
+        let high = (cycle / 895) % 2 == 0;
+        resampler.push(if high { 0.25 } else { 0.0 });
+    }
+    let frames = resampler.end_frame(&mut out);
+
+    assert_eq!(out.len(), frames * 2);
+    assert!(out.chunks(2).all(|frame| frame[0] == frame[1]));
+    assert!(out.iter().any(|s| *s > 1_000));
+    assert!(out.iter().any(|s| *s < -1_000));
+
+    let mut float: Vec<f32> = Vec::new();
+    resampler.skip(1_000);
+    resampler.end_frame(&mut float);
+    assert!(float.iter().all(|s| (-1.0..=1.0).contains(s)));
+}
+
+#[test]
+fn wav_roundtrip() {
+    let spec = WavSpec {
+        channels: 2,
+        sample_rate: 44_100,
+    };
+    let mut writer = WavWriter::new(Cursor::new(Vec::new()), spec).unwrap();
+    writer.push_frame(&[0i16, -1]);
+    writer.push_frame(&[1.0f32, -1.0]);
+    assert_eq!(writer.frames(), 2);
+
+    let bytes = writer.finish().unwrap().into_inner();
+    assert_eq!(bytes.len(), 44 + 8);
+    assert_eq!(&bytes[4..8], &(36u32 + 8).to_le_bytes());
+
+    let (read_spec, samples) = read_wav(Cursor::new(bytes)).unwrap();
+    assert_eq!(read_spec, spec);
+    assert_eq!(samples, [0, -1, i16::MAX, -i16::MAX]);
+}
+
+#[test]
+fn recorder_splits_channels() {
+    let mut recorder = Recorder::new(Cursor::new(Vec::new()), CPU_CLOCK_NTSC, 44_100)
+        .unwrap()
+        .with_channel(Channel::Pulse1, Cursor::new(Vec::new()))
+        .unwrap()
+        .with_channel(Channel::Noise, Cursor::new(Vec::new()))
+        .unwrap();
+
+    for cycle in 0..60_000 {
+        let levels = ChannelLevels {
+            pulse1: if (cycle / 1_000) % 2 == 0 { 15 } else { 0 },
+            ..Default::default()
+        };
+        recorder.cycle(&levels);
+    }
+    recorder.end_frame();
+
+    let (mixed, channels) = recorder.finish().unwrap();
+    let (_, mixed) = read_wav(Cursor::new(mixed.into_inner())).unwrap();
+    assert!(!mixed.is_empty());
+
+    let [(pulse, pulse_wav), (noise, noise_wav)] = channels.try_into().unwrap();
+    assert_eq!((pulse, noise), (Channel::Pulse1, Channel::Noise));
+
+    let (_, pulse_samples) = read_wav(Cursor::new(pulse_wav.into_inner())).unwrap();
+    let (_, noise_samples) = read_wav(Cursor::new(noise_wav.into_inner())).unwrap();
+    assert_eq!(pulse_samples, mixed);
+    assert!(noise_samples.iter().all(|s| *s == 0));
+}
+
+#[test]
+fn pulse_sequence() {
+    let mut pulse = Pulse::new(PulseKind::Pulse1);
+    pulse.set_enabled(true);
+    // 50% duty, constant volume 9, period 8.
+    pulse.write(0, 0b1011_1001);
+    pulse.write(2, 8);
+    pulse.write(3, 0x08);
+    assert!(pulse.active());
+
+    let mut high = 0;
+    for _ in 0..8 * 9 {
+        pulse.clock_timer();
+        if pulse.output() == 9 {
+            high += 1;
+        }
+    }
+    assert_eq!(high, 4 * 9);
+
+    // Periods below 8 are muted, except on expansion pulses.
+    pulse.write(2, 7);
+    assert_eq!(
+        (0..16)
+            .map(|_| {
+                pulse.clock_timer();
+                pulse.output()
+            })
+            .max(),
+        Some(0)
+    );
+
+    let mut pulse = Pulse::new(PulseKind::Expansion);
+    pulse.set_enabled(true);
+    pulse.write(0, 0b1011_1001);
+    pulse.write(2, 7);
+    pulse.write(3, 0x08);
+    assert_eq!(
+        (0..16)
+            .map(|_| {
+                pulse.clock_timer();
+                pulse.output()
+            })
+            .max(),
+        Some(9)
+    );
+}
+
+#[test]
+fn pulse_length_and_envelope() {
+    let mut pulse = Pulse::new(PulseKind::Pulse2);
+    pulse.set_enabled(true);
+    // Decaying envelope with period 0, length index 1 (254).
+    pulse.write(0, 0b1000_0000);
+    pulse.write(2, 0x40);
+    pulse.write(3, 0b0000_1000);
+    pulse.clock_quarter();
+    pulse.clock_quarter();
+    assert_eq!(
+        (0..16)
+            .map(|_| {
+                pulse.clock_timer();
+                pulse.output()
+            })
+            .max(),
+        Some(14)
+    );
+
+    for _ in 0..254 {
+        pulse.clock_half();
+    }
+    assert!(!pulse.active());
+
+    pulse.write(3, 0b0000_1000);
+    pulse.set_enabled(false);
+    assert!(!pulse.active());
+}
+
+#[test]
+fn apu_frame_irq() {
+    let mut apu = Apu::new(Region::Ntsc);
+    apu.write(0x4015, 0x01);
+    // Length index 1 (254), halted envelope.
+    apu.write(0x4000, 0x3F);
+    apu.write(0x4003, 0x08);
+    assert_eq!(apu.peek_status(), 0x01);
+
+    for _ in 0..29_829 {
+        assert!(!apu.irq());
+        apu.cycle();
+    }
+    assert!(apu.irq());
+    assert_eq!(apu.read_status(), 0x41);
+    assert!(!apu.irq());
+
+    // Inhibited and 5-step sequences never interrupt.
+    apu.write(0x4017, 0x80);
+    for _ in 0..2 * 37_282 {
+        apu.cycle();
+    }
+    assert!(!apu.irq());
+}
+
+#[test]
+fn apu_dmc_fetches() {
+    let mut apu = Apu::new(Region::Pal);
+    // Fastest rate, IRQ enabled, sample at $C040 of 17 bytes.
+    apu.write(0x4010, 0x8F);
+    apu.write(0x4012, 0x01);
+    apu.write(0x4013, 0x01);
+    apu.write(0x4011, 0x40);
+    assert_eq!(apu.dmc_request(), None);
+
+    apu.write(0x4015, 0x10);
+    let mut fetched = Vec::new();
+    for _ in 0..17 * 8 * 50 + 8 * 50 {
+        if let Some(addr) = apu.dmc_request() {
+            fetched.push(addr);
+            apu.dmc_fill(0xFF);
+        }
+        apu.cycle();
+    }
+
+    assert_eq!(fetched, (0xC040..0xC051).collect::<Vec<_>>());
+    assert!(apu.irq());
+    assert_eq!(apu.levels().dmc, 126);
+    apu.write(0x4015, 0x00);
+    assert!(!apu.irq());
+}
@@ -0,0 +1,95 @@
+use crate::pulse::LengthCounter;
+
+/// Triangle output sequence (32 steps, 15 down to 0 and back up to 15).
+static SEQUENCE: [u8; 32] = [
+    15, 14, 13, 12, 11, 10, 9, 8, 7, 6, 5, 4, 3, 2, 1, 0, 0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12,
+    13, 14, 15,
+];
+
+/// Triangle wave channel.
+///
+/// ## Behaviour
+///
+/// - [Triangle::clock_timer] must be called on every CPU cycle (unlike the
+///   pulses, the triangle timer runs at the CPU rate).
+/// - Registers are numbered 0..=3, as in $4008-$400B ($4009 is unused).
+/// - Periods below 2 produce ultrasonic frequencies; the sequencer is kept
+///   still instead, which avoids popping (as most emulators do).
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Triangle {
+    step: u8,
+    period: u16,
+    timer: u16,
+    length: LengthCounter,
+
+    control: bool,
+    linear_reload_value: u8,
+    linear_reload: bool,
+    linear: u8,
+}
+
+impl Triangle {
+    /// Writes `data` into register `reg` (0..=3).
+    pub fn write(&mut self, reg: u8, data: u8) {
+        match reg & 0x03 {
+            0 => {
+                self.control = data & 0x80 != 0;
+                self.length.set_halt(self.control);
+                self.linear_reload_value = data & 0x7F;
+            }
+            1 => {}
+            2 => self.period = (self.period & 0x700) | data as u16,
+            _ => {
+                self.period = (self.period & 0x0FF) | (((data & 0x07) as u16) << 8);
+                self.length.load(data >> 3);
+                self.linear_reload = true;
+            }
+        }
+    }
+
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.length.set_enabled(enabled);
+    }
+
+    /// Whether the length counter is non zero.
+    pub fn active(&self) -> bool {
+        self.length.active()
+    }
+
+    /// CPU cycle clock.
+    pub fn clock_timer(&mut self) {
+        if self.timer == 0 {
+            self.timer = self.period;
+            if self.length.active() && self.linear > 0 && self.period >= 2 {
+                self.step = (self.step + 1) & 0x1F;
+            }
+        } else {
+            self.timer -= 1;
+        }
+    }
+
+    /// Quarter frame clock (linear counter).
+    pub fn clock_quarter(&mut self) {
+        if self.linear_reload {
+            self.linear = self.linear_reload_value;
+        } else if self.linear > 0 {
+            self.linear -= 1;
+        }
+        if !self.control {
+            self.linear_reload = false;
+        }
+    }
+
+    /// Half frame clock (length counter).
+    pub fn clock_half(&mut self) {
+        self.length.clock();
+    }
+
+    /// Current output level (0..=15).
+    ///
+    /// Silencing the channel stops the sequencer rather than muting it, so
+    /// the output keeps its last level.
+    pub fn output(&self) -> u8 {
+        SEQUENCE[self.step as usize]
+    }
+}
@@ -0,0 +1,171 @@
+use crate::sink::{AudioSink, Sample};
+use std::{
+    fs::File,
+    io::{self, BufWriter, Read, Seek, SeekFrom, Write},
+    path::Path,
+};
+
+/// Size of the canonical RIFF/WAVE header written by [WavWriter].
+const HEADER_LEN: u32 = 44;
+/// `WAVE_FORMAT_PCM`
+const FORMAT_PCM: u16 = 1;
+
+/// Format of a 16-bit PCM WAV stream.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct WavSpec {
+    pub channels: u16,
+    pub sample_rate: u32,
+}
+
+/// 16-bit PCM WAV writer.
+///
+/// The RIFF sizes are patched by [WavWriter::finish], so the output must be
+/// seekable. As [AudioSink::push_frame] can't fail, the first I/O error is
+/// stored (further frames are dropped) and returned by [WavWriter::finish].
+pub struct WavWriter<W: Write + Seek> {
+    inner: W,
+    spec: WavSpec,
+    data_len: u32,
+    error: Option<io::Error>,
+}
+
+impl WavWriter<BufWriter<File>> {
+    /// Creates (or truncates) the file at `path`.
+    pub fn create(path: impl AsRef<Path>, spec: WavSpec) -> io::Result<Self> {
+        Self::new(BufWriter::new(File::create(path)?), spec)
+    }
+}
+
+impl<W: Write + Seek> WavWriter<W> {
+    pub fn new(mut inner: W, spec: WavSpec) -> io::Result<Self> {
+        write_header(&mut inner, spec, 0)?;
+        Ok(Self {
+            inner,
+            spec,
+            data_len: 0,
+            error: None,
+        })
+    }
+
+    pub fn spec(&self) -> WavSpec {
+        self.spec
+    }
+
+    /// Number of frames written so far.
+    pub fn frames(&self) -> u32 {
+        self.data_len / (2 * self.spec.channels as u32)
+    }
+
+    pub fn write_samples(&mut self, samples: &[i16]) -> io::Result<()> {
+        let mut bytes = [0u8; 64];
+        for chunk in samples.chunks(bytes.len() / 2) {
+            for (dst, sample) in bytes.chunks_exact_mut(2).zip(chunk) {
+                dst.copy_from_slice(&sample.to_le_bytes());
+            }
+            self.inner.write_all(&bytes[..chunk.len() * 2])?;
+        }
+        self.data_len += samples.len() as u32 * 2;
+        Ok(())
+    }
+
+    /// Patches the header and flushes the output.
+    pub fn finish(mut self) -> io::Result<W> {
+        if let Some(error) = self.error.take() {
+            return Err(error);
+        }
+
+        self.inner.seek(SeekFrom::Start(0))?;
+        write_header(&mut self.inner, self.spec, self.data_len)?;
+        self.inner.seek(SeekFrom::End(0))?;
+        self.inner.flush()?;
+        Ok(self.inner)
+    }
+}
+
+impl<W: Write + Seek, S: Sample> AudioSink<S> for WavWriter<W> {
+    fn push_frame(&mut self, frame: &[S]) {
+        if self.error.is_some() {
+            return;
+        }
+
+        let mut samples = [0i16; 8];
+        for (dst, sample) in samples.iter_mut().zip(frame) {
+            *dst = i16::from_f32(sample.to_f32());
+        }
+
+        let len = frame.len().min(samples.len());
+        if let Err(error) = self.write_samples(&samples[..len]) {
+            self.error = Some(error);
+        }
+    }
+}
+
+fn write_header(out: &mut impl Write, spec: WavSpec, data_len: u32) -> io::Result<()> {
+    let block_align = spec.channels * 2;
+    out.write_all(b"RIFF")?;
+    out.write_all(&(HEADER_LEN - 8 + data_len).to_le_bytes())?;
+    out.write_all(b"WAVE")?;
+
+    out.write_all(b"fmt ")?;
+    out.write_all(&16u32.to_le_bytes())?;
+    out.write_all(&FORMAT_PCM.to_le_bytes())?;
+    out.write_all(&spec.channels.to_le_bytes())?;
+    out.write_all(&spec.sample_rate.to_le_bytes())?;
+    out.write_all(&(spec.sample_rate * block_align as u32).to_le_bytes())?;
+    out.write_all(&block_align.to_le_bytes())?;
+    out.write_all(&16u16.to_le_bytes())?;
+
+    out.write_all(b"data")?;
+    out.write_all(&data_len.to_le_bytes())
+}
+
+/// Reads a 16-bit PCM WAV stream, returning its format and interleaved
+/// samples.
+///
+/// Meant for comparing recordings against reference files, so only the
+/// format written by [WavWriter] (plus unknown chunks, which are skipped) is
+/// supported.
+pub fn read_wav(mut input: impl Read) -> io::Result<(WavSpec, Vec<i16>)> {
+    let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, msg.to_string());
+
+    let mut riff = [0u8; 12];
+    input.read_exact(&mut riff)?;
+    if &riff[0..4] != b"RIFF" || &riff[8..12] != b"WAVE" {
+        return Err(invalid("not a RIFF/WAVE stream"));
+    }
+
+    let mut spec = None;
+    loop {
+        let mut chunk = [0u8; 8];
+        input.read_exact(&mut chunk)?;
+        let len = u32::from_le_bytes([chunk[4], chunk[5], chunk[6], chunk[7]]) as usize;
+        let mut body = vec![0u8; len + (len & 1)];
+        input.read_exact(&mut body)?;
+
+        match &chunk[0..4] {
+            b"fmt " => {
+                if len < 16 {
+                    return Err(invalid("truncated fmt chunk"));
+                }
+                let format = u16::from_le_bytes([body[0], body[1]]);
+                let bits = u16::from_le_bytes([body[14], body[15]]);
+                if format != FORMAT_PCM || bits != 16 {
+                    return Err(invalid("only 16-bit PCM is supported"));
+                }
+                spec = Some(WavSpec {
+                    channels: u16::from_le_bytes([body[2], body[3]]),
+                    sample_rate: u32::from_le_bytes([body[4], body[5], body[6], body[7]]),
+                });
+            }
+            b"data" => {
+                let spec = spec.ok_or_else(|| invalid("data chunk before fmt chunk"))?;
+                let samples = body[..len]
+                    .chunks_exact(2)
+                    .map(|s| i16::from_le_bytes([s[0], s[1]]))
+                    .collect();
+                return Ok((spec, samples));
+            }
+            _ => {}
+        }
+    }
+}
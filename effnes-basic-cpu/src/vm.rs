@@ -1,5 +1,5 @@
 use crate::consts;
-use effnes_bus::{peripheral::Peripheral, MemoryBus};
+use effnes_bus::{peripheral::Peripheral, signal::Signals, MemoryBus};
 use effnes_cpu::{
     addr::{AddressingMode, IndexRegister},
     consts::{CpuVector, Flags},
@@ -99,9 +99,38 @@ impl VM {
         (self.stack_pop_byte(io) as u16) + ((self.stack_pop_byte(io) as u16) << 8)
     }
 
-    pub fn irq(&mut self) {}
+    /// Pushes the program counter and the status register (with the break
+    /// flag cleared), disables IRQs, and jumps through `vector`.
+    ///
+    /// It takes 7 cycles, like a `BRK`.
+    fn interrupt(&mut self, io: &mut impl MemoryBus, vector: CpuVector) {
+        self.stack_push_addr(io, self.r_pc);
+        self.stack_push_byte(
+            io,
+            (self.r_ps | Flags::Reserved)
+                .difference(Flags::Break)
+                .bits(),
+        );
+        self.r_ps |= Flags::IntDis;
+        self.r_pc = io.read_u16(vector as u16);
+        self.i_cc += 7;
+    }
+
+    /// Services an IRQ (through the `BRK` vector), unless they're disabled.
+    ///
+    /// Returns whether the IRQ was taken.
+    pub fn irq(&mut self, io: &mut impl MemoryBus) -> bool {
+        if self.r_ps.contains(Flags::IntDis) {
+            return false;
+        }
+        self.interrupt(io, CpuVector::Brk);
+        true
+    }
 
-    pub fn nmi(&mut self) {}
+    /// Services an NMI.
+    pub fn nmi(&mut self, io: &mut impl MemoryBus) {
+        self.interrupt(io, CpuVector::Nmi);
+    }
 }
 
 impl Default for VM {
@@ -219,7 +248,27 @@ impl Peripheral for VM {
     /// This is the most complex piece of the emulator's main code. It works by
     /// using a match statement that runs the desired opcode efficiently.
     ///
-    fn cycle(&mut self, io: &mut impl MemoryBus) -> () {
+    /// ## Signals
+    ///
+    /// The lines are sampled before every instruction: while RDY is held, the
+    /// CPU idles for one cycle (without repeating any read); otherwise, a
+    /// pending NMI (or an IRQ, if they are enabled) is serviced in place of
+    /// the next instruction.
+    ///
+    fn cycle(&mut self, io: &mut impl MemoryBus, signals: &mut Signals) -> () {
+        signals.set_stalled(signals.halted());
+        if signals.halted() {
+            self.i_cc += 1;
+            return;
+        }
+        if signals.take_nmi() {
+            self.nmi(io);
+            return;
+        }
+        if signals.irq() && self.irq(io) {
+            return;
+        }
+
         let opcode: OpCode = self.next_byte(io);
 
         let am: AddressingMode = opcode.into();
@@ -873,6 +922,16 @@ impl DebugCpu for VM {
         self.r_iy = iy;
     }
 
+    /// Every cycle runs a whole instruction (or interrupt sequence).
+    fn next_instruction(&self) -> Option<u16> {
+        Some(self.r_pc)
+    }
+
+    /// Reads aren't split into cycles, so none is reported.
+    fn dummy_read(&self, _signals: &Signals) -> bool {
+        false
+    }
+
     fn state(&self) -> CpuState {
         CpuState {
             pc: self.r_pc,
@@ -1,10 +1,17 @@
-use crate::{InspectBus, MemoryBus};
+use crate::{InspectBus, MemoryBus, power::RamInit};
 
 pub struct BasicMemory {
     pub memory: [u8; 65536],
 }
 
 impl BasicMemory {
+    /// 64 KiB of RAM, with the `init` power-on contents.
+    pub fn new(init: RamInit) -> Self {
+        let mut memory = [0; 65536];
+        init.fill(&mut memory);
+        Self { memory }
+    }
+
     pub fn default_with(value: u8) -> Self {
         let memory = [value; 65536];
         // TODO: Set up vectors
@@ -35,3 +42,107 @@ impl InspectBus for BasicMemory {
         (self.peek_u8(addr) as u16) + ((self.peek_u8(addr + 1) as u16) << 8)
     }
 }
+
+/// RAM chip of any size, mirrored over the addresses it receives.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Ram {
+    data: Box<[u8]>,
+}
+
+impl Ram {
+    /// ## Panics
+    ///
+    /// If `len` is zero.
+    pub fn new(len: usize) -> Self {
+        assert!(len > 0, "empty RAM");
+        Self {
+            data: vec![0; len].into_boxed_slice(),
+        }
+    }
+
+    /// ## Panics
+    ///
+    /// If `len` is zero.
+    pub fn with_init(len: usize, init: RamInit) -> Self {
+        let mut ram = Self::new(len);
+        init.fill(&mut ram.data);
+        ram
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    pub fn data_mut(&mut self) -> &mut [u8] {
+        &mut self.data
+    }
+}
+
+impl MemoryBus for Ram {
+    fn read_u8(&mut self, addr: u16) -> u8 {
+        self.peek_u8(addr)
+    }
+
+    fn read_u16(&mut self, addr: u16) -> u16 {
+        self.peek_u16(addr)
+    }
+
+    fn write_u8(&mut self, addr: u16, data: u8) {
+        let len = self.data.len();
+        self.data[addr as usize % len] = data;
+    }
+}
+
+impl InspectBus for Ram {
+    fn peek_u8(&self, addr: u16) -> u8 {
+        self.data[addr as usize % self.data.len()]
+    }
+
+    fn peek_u16(&self, addr: u16) -> u16 {
+        (self.peek_u8(addr) as u16) | ((self.peek_u8(addr.wrapping_add(1)) as u16) << 8)
+    }
+}
+
+/// ROM chip of any size, mirrored over the addresses it receives. Writes
+/// are ignored.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Rom {
+    data: Box<[u8]>,
+}
+
+impl Rom {
+    /// ## Panics
+    ///
+    /// If `data` is empty.
+    pub fn new(data: impl Into<Box<[u8]>>) -> Self {
+        let data = data.into();
+        assert!(!data.is_empty(), "empty ROM");
+        Self { data }
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+}
+
+impl MemoryBus for Rom {
+    fn read_u8(&mut self, addr: u16) -> u8 {
+        self.peek_u8(addr)
+    }
+
+    fn read_u16(&mut self, addr: u16) -> u16 {
+        self.peek_u16(addr)
+    }
+
+    fn write_u8(&mut self, _addr: u16, _data: u8) {}
+}
+
+impl InspectBus for Rom {
+    fn peek_u8(&self, addr: u16) -> u8 {
+        self.data[addr as usize % self.data.len()]
+    }
+
+    fn peek_u16(&self, addr: u16) -> u16 {
+        (self.peek_u8(addr) as u16) | ((self.peek_u8(addr.wrapping_add(1)) as u16) << 8)
+    }
+}
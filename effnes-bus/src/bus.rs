@@ -26,3 +26,27 @@ pub trait InspectBus {
     /// This must not mutate the bus or peripheral state.
     fn peek_u16(&self, addr: u16) -> u16;
 }
+
+impl<T: MemoryBus + ?Sized> MemoryBus for Box<T> {
+    fn read_u8(&mut self, addr: u16) -> u8 {
+        (**self).read_u8(addr)
+    }
+
+    fn read_u16(&mut self, addr: u16) -> u16 {
+        (**self).read_u16(addr)
+    }
+
+    fn write_u8(&mut self, addr: u16, data: u8) {
+        (**self).write_u8(addr, data)
+    }
+}
+
+impl<T: InspectBus + ?Sized> InspectBus for Box<T> {
+    fn peek_u8(&self, addr: u16) -> u8 {
+        (**self).peek_u8(addr)
+    }
+
+    fn peek_u16(&self, addr: u16) -> u16 {
+        (**self).peek_u16(addr)
+    }
+}
@@ -0,0 +1,291 @@
+use crate::{InspectBus, MemoryBus, error::Error};
+use std::ops::RangeInclusive;
+
+/// Any device that can be attached to a [Decoder], boxed or not.
+pub trait Device: MemoryBus + InspectBus {}
+
+impl<T: MemoryBus + InspectBus + ?Sized> Device for T {}
+
+/// Decoder of type-erased devices.
+pub type BoxedDecoder = Decoder<Box<dyn Device>>;
+
+/// Handle to a device added to a [DecoderBuilder].
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct DeviceId(usize);
+
+/// Handle to a mapping added to a [DecoderBuilder].
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct MappingId(usize);
+
+/// Address range mapped into a device.
+///
+/// The device receives the accessed address, made relative to the range
+/// start (if [Mapping::relative]), and masked with [Mapping::with_mask].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Mapping {
+    range: RangeInclusive<u16>,
+    device: DeviceId,
+    mask: u16,
+    relative: bool,
+    priority: u8,
+    read_only: bool,
+}
+
+impl Mapping {
+    /// Maps `range` into `device`, with absolute addresses and priority 0.
+    pub fn new(range: RangeInclusive<u16>, device: DeviceId) -> Self {
+        Self {
+            range,
+            device,
+            mask: 0xFFFF,
+            relative: false,
+            priority: 0,
+            read_only: false,
+        }
+    }
+
+    /// Only the address lines in `mask` reach the device, which mirrors it
+    /// over the whole range (e.g. `0x07FF` for 2 KiB of RAM).
+    pub fn with_mask(mut self, mask: u16) -> Self {
+        self.mask = mask;
+        self
+    }
+
+    /// The device receives addresses relative to the range start.
+    pub fn relative(mut self) -> Self {
+        self.relative = true;
+        self
+    }
+
+    /// Mappings with a higher priority shadow the ones below them.
+    pub fn with_priority(mut self, priority: u8) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// Writes fall through to the mapping below (e.g. ROM over RAM).
+    pub fn read_only(mut self) -> Self {
+        self.read_only = true;
+        self
+    }
+
+    pub fn range(&self) -> &RangeInclusive<u16> {
+        &self.range
+    }
+
+    pub fn device(&self) -> DeviceId {
+        self.device
+    }
+
+    pub fn priority(&self) -> u8 {
+        self.priority
+    }
+
+    /// Address seen by the device when `addr` is accessed.
+    pub fn translate(&self, addr: u16) -> u16 {
+        let addr = if self.relative {
+            addr - self.range.start()
+        } else {
+            addr
+        };
+        addr & self.mask
+    }
+
+    fn overlaps(&self, other: &Mapping) -> bool {
+        self.range.start() <= other.range.end() && other.range.start() <= self.range.end()
+    }
+}
+
+/// Builds a [Decoder] out of devices and the address ranges they answer.
+///
+/// ```ignore
+/// let mut builder = DecoderBuilder::<Box<dyn Device>>::new();
+/// let ram = builder.device(Box::new(Ram::new(0x800)));
+/// let basic = builder.device(Box::new(Rom::new(basic_rom)));
+///
+/// builder.map(Mapping::new(0x0000..=0xFFFF, ram).with_mask(0x07FF));
+/// let overlay = builder.map(
+///     Mapping::new(0xA000..=0xBFFF, basic)
+///         .relative()
+///         .read_only()
+///         .with_priority(1),
+/// );
+///
+/// let mut bus = builder.build()?;
+/// bus.set_enabled(overlay, false); // Banks out BASIC, exposing the RAM.
+/// ```
+pub struct DecoderBuilder<D> {
+    devices: Vec<D>,
+    mappings: Vec<Mapping>,
+}
+
+impl<D> Default for DecoderBuilder<D> {
+    fn default() -> Self {
+        Self {
+            devices: Vec::new(),
+            mappings: Vec::new(),
+        }
+    }
+}
+
+impl<D: Device> DecoderBuilder<D> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a device, which can then be mapped any number of times.
+    pub fn device(&mut self, device: D) -> DeviceId {
+        self.devices.push(device);
+        DeviceId(self.devices.len() - 1)
+    }
+
+    pub fn map(&mut self, mapping: Mapping) -> MappingId {
+        self.mappings.push(mapping);
+        MappingId(self.mappings.len() - 1)
+    }
+
+    /// ## Errors
+    ///
+    /// If a mapping covers no addresses, refers to an unknown device, or
+    /// overlaps another mapping with the same priority.
+    pub fn build(self) -> Result<Decoder<D>, Error> {
+        for (i, mapping) in self.mappings.iter().enumerate() {
+            if mapping.range.is_empty() {
+                return Err(Error::EmptyRange(mapping.range.clone()));
+            }
+            if mapping.device.0 >= self.devices.len() {
+                return Err(Error::UnknownDevice(mapping.device.0));
+            }
+            if let Some(other) = self.mappings[..i]
+                .iter()
+                .find(|other| other.priority == mapping.priority && other.overlaps(mapping))
+            {
+                return Err(Error::Overlap {
+                    first: other.range.clone(),
+                    second: mapping.range.clone(),
+                    priority: mapping.priority,
+                });
+            }
+        }
+
+        // Highest priority first: lookups stop at the first match.
+        let mut order: Vec<usize> = (0..self.mappings.len()).collect();
+        order.sort_by_key(|&i| std::cmp::Reverse(self.mappings[i].priority));
+
+        Ok(Decoder {
+            enabled: vec![true; self.mappings.len()],
+            devices: self.devices,
+            mappings: self.mappings,
+            order,
+            open_bus: 0,
+        })
+    }
+}
+
+/// Address Decoder
+///
+/// A [MemoryBus] dispatching every access to the devices mapped at its
+/// address (see [DecoderBuilder]).
+///
+/// ## Behaviour
+///
+/// - Where mappings overlap, the enabled one with the highest priority
+///   answers. Read only mappings let writes fall through to the next one.
+/// - Disabled mappings are transparent, which models bank switched overlays.
+/// - Reads of unmapped addresses return the last value seen on the bus, and
+///   writes to them are dropped.
+pub struct Decoder<D> {
+    devices: Vec<D>,
+    mappings: Vec<Mapping>,
+    enabled: Vec<bool>,
+    /// Mapping indices, by decreasing priority.
+    order: Vec<usize>,
+    open_bus: u8,
+}
+
+impl<D: Device> Decoder<D> {
+    pub fn builder() -> DecoderBuilder<D> {
+        DecoderBuilder::new()
+    }
+
+    pub fn device(&self, id: DeviceId) -> &D {
+        &self.devices[id.0]
+    }
+
+    pub fn device_mut(&mut self, id: DeviceId) -> &mut D {
+        &mut self.devices[id.0]
+    }
+
+    pub fn mapping(&self, id: MappingId) -> &Mapping {
+        &self.mappings[id.0]
+    }
+
+    pub fn is_enabled(&self, id: MappingId) -> bool {
+        self.enabled[id.0]
+    }
+
+    /// Enables or disables a mapping (e.g. on a bank switch).
+    pub fn set_enabled(&mut self, id: MappingId, enabled: bool) {
+        self.enabled[id.0] = enabled;
+    }
+
+    /// Last value driven on the data bus.
+    pub fn open_bus(&self) -> u8 {
+        self.open_bus
+    }
+
+    /// Mapping that answers accesses to `addr`, if any.
+    pub fn resolve(&self, addr: u16, write: bool) -> Option<MappingId> {
+        self.order
+            .iter()
+            .copied()
+            .find(|&i| {
+                let mapping = &self.mappings[i];
+                self.enabled[i] && mapping.range.contains(&addr) && !(write && mapping.read_only)
+            })
+            .map(MappingId)
+    }
+
+    fn peek_mapped(&self, addr: u16) -> Option<u8> {
+        self.resolve(addr, false).map(|MappingId(i)| {
+            let mapping = &self.mappings[i];
+            self.devices[mapping.device.0].peek_u8(mapping.translate(addr))
+        })
+    }
+}
+
+impl<D: Device> MemoryBus for Decoder<D> {
+    fn read_u8(&mut self, addr: u16) -> u8 {
+        if let Some(MappingId(i)) = self.resolve(addr, false) {
+            let mapping = &self.mappings[i];
+            self.open_bus = self.devices[mapping.device.0].read_u8(mapping.translate(addr));
+        }
+        self.open_bus
+    }
+
+    fn read_u16(&mut self, addr: u16) -> u16 {
+        (self.read_u8(addr) as u16) | ((self.read_u8(addr.wrapping_add(1)) as u16) << 8)
+    }
+
+    fn write_u8(&mut self, addr: u16, data: u8) {
+        self.open_bus = data;
+        if let Some(MappingId(i)) = self.resolve(addr, true) {
+            let mapping = &self.mappings[i];
+            self.devices[mapping.device.0].write_u8(mapping.translate(addr), data);
+        }
+    }
+}
+
+impl<D: Device> InspectBus for Decoder<D> {
+    fn peek_u8(&self, addr: u16) -> u8 {
+        self.peek_mapped(addr).unwrap_or(self.open_bus)
+    }
+
+    /// An unmapped high byte reads back the low byte, as it would on a real
+    /// read.
+    fn peek_u16(&self, addr: u16) -> u16 {
+        let low = self.peek_u8(addr);
+        let high = self.peek_mapped(addr.wrapping_add(1)).unwrap_or(low);
+        (low as u16) | ((high as u16) << 8)
+    }
+}
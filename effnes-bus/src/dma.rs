@@ -0,0 +1,184 @@
+use crate::{
+    MemoryBus,
+    signal::{Signals, Source},
+};
+
+/// Register that starts a sprite DMA, with the source page.
+pub const OAM_DMA: u16 = 0x4014;
+
+/// PPU register sprite DMA writes into.
+pub const OAM_DATA: u16 = 0x2004;
+
+/// Bytes copied by a sprite DMA.
+pub const OAM_LEN: u16 = 256;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+struct OamTransfer {
+    page: u8,
+    index: u16,
+    /// Byte read on the last get cycle, waiting for a put cycle.
+    latch: Option<u8>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+struct DmcTransfer {
+    addr: u16,
+    /// Halted cycles left before the fetch (the halt and dummy cycles).
+    delay: u8,
+}
+
+/// 2A03 DMA Unit
+///
+/// Runs the sprite (OAM) and DMC sample DMAs, stealing cycles from the CPU
+/// through RDY (see [Signals]).
+///
+/// ## Behaviour
+///
+/// Cycles alternate between get (read) and put (write) cycles, starting
+/// with a get cycle after reset, so the unit must be clocked on every CPU
+/// cycle. Once a transfer is requested, RDY is pulled, and the CPU halts on
+/// its next read cycle. Then:
+///
+/// - Sprite DMA copies 256 bytes from `page << 8` into [OAM_DATA], reading
+///   on get cycles and writing on put cycles, which takes 513 cycles (514
+///   if an alignment cycle is needed).
+/// - DMC DMA waits for a dummy cycle, and fetches its byte on the next get
+///   cycle, which takes 3 cycles (4 with the alignment cycle).
+/// - A DMC fetch during a sprite DMA takes over its next get cycle (its
+///   halt and dummy cycles overlap the sprite DMA), which usually delays
+///   the sprite DMA by 2 cycles.
+///
+/// The halted CPU repeats its pending read on every cycle where the unit
+/// leaves the bus alone (halt, dummy and alignment cycles), which is where
+/// the double reads of $2007 and $4016 come from.
+///
+/// ```ignore
+/// loop {
+///     bus.cycle_dma(&mut signals);
+///     if let Some(data) = bus.dma_mut().take_dmc() {
+///         apu.dmc_fill(data);
+///     }
+///     if let Some(addr) = apu.dmc_request() {
+///         bus.dma_mut().request_dmc(addr);
+///     }
+///     cpu.cycle(&mut bus, &mut signals);
+/// }
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Dma {
+    oam: Option<OamTransfer>,
+    dmc: Option<DmcTransfer>,
+    dmc_data: Option<u8>,
+    /// Whether the CPU acknowledged the halt.
+    halted: bool,
+    /// Whether the next cycle is a get cycle.
+    get: bool,
+}
+
+impl Default for Dma {
+    fn default() -> Self {
+        Self {
+            oam: None,
+            dmc: None,
+            dmc_data: None,
+            halted: false,
+            get: true,
+        }
+    }
+}
+
+impl Dma {
+    /// Starts a sprite DMA from `page` (as written into [OAM_DMA]).
+    pub fn request_oam(&mut self, page: u8) {
+        self.oam = Some(OamTransfer {
+            page,
+            index: 0,
+            latch: None,
+        });
+    }
+
+    /// Requests a DMC sample fetch from `addr`. Ignored while a fetch is
+    /// pending, or its byte wasn't taken yet.
+    pub fn request_dmc(&mut self, addr: u16) {
+        if self.dmc.is_none() && self.dmc_data.is_none() {
+            self.dmc = Some(DmcTransfer {
+                addr,
+                delay: if self.halted { 2 } else { 1 },
+            });
+        }
+    }
+
+    /// Byte fetched for the DMC, if any.
+    pub fn take_dmc(&mut self) -> Option<u8> {
+        self.dmc_data.take()
+    }
+
+    /// Whether a transfer is pending or running.
+    pub fn active(&self) -> bool {
+        self.oam.is_some() || self.dmc.is_some()
+    }
+
+    /// Whether the next cycle is a get cycle.
+    pub fn is_get_cycle(&self) -> bool {
+        self.get
+    }
+
+    /// Cancels every transfer, and releases RDY.
+    pub fn reset(&mut self, signals: &mut Signals) {
+        *self = Self::default();
+        signals.set_halt(Source::OamDma | Source::DmcDma, false);
+        signals.set_bus_driven(false);
+    }
+
+    /// Runs a CPU cycle, before the CPU does.
+    pub fn cycle(&mut self, bus: &mut impl MemoryBus, signals: &mut Signals) {
+        let get = self.get;
+        self.get = !self.get;
+        signals.set_bus_driven(false);
+
+        if !self.active() {
+            self.halted = false;
+            signals.set_halt(Source::OamDma | Source::DmcDma, false);
+            return;
+        }
+
+        signals.set_halt(Source::OamDma, self.oam.is_some());
+        signals.set_halt(Source::DmcDma, self.dmc.is_some());
+        if !self.halted {
+            // Waits for the CPU to reach a read cycle.
+            if !signals.stalled() {
+                return;
+            }
+            self.halted = true;
+        }
+
+        let dmc_ready = self.dmc.is_some_and(|dmc| dmc.delay == 0);
+        if let Some(dmc) = &mut self.dmc {
+            dmc.delay = dmc.delay.saturating_sub(1);
+        }
+
+        if get && dmc_ready {
+            let dmc = self.dmc.take().unwrap();
+            self.dmc_data = Some(bus.read_u8(dmc.addr));
+            signals.set_bus_driven(true);
+        } else if let Some(oam) = &mut self.oam {
+            match (get, oam.latch) {
+                (true, None) => {
+                    oam.latch = Some(bus.read_u8(((oam.page as u16) << 8) | oam.index));
+                    signals.set_bus_driven(true);
+                }
+                (false, Some(data)) => {
+                    bus.write_u8(OAM_DATA, data);
+                    oam.latch = None;
+                    oam.index += 1;
+                    if oam.index == OAM_LEN {
+                        self.oam = None;
+                    }
+                    signals.set_bus_driven(true);
+                }
+                // Alignment cycle.
+                _ => {}
+            }
+        }
+    }
+}
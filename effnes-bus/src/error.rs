@@ -0,0 +1,50 @@
+use std::{
+    fmt::{self, Display},
+    ops::RangeInclusive,
+};
+
+/// [crate::decoder::DecoderBuilder] error.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// Two mappings with the same priority share some addresses.
+    Overlap {
+        first: RangeInclusive<u16>,
+        second: RangeInclusive<u16>,
+        priority: u8,
+    },
+
+    /// A mapping covers no addresses.
+    EmptyRange(RangeInclusive<u16>),
+
+    /// A mapping refers to a device that wasn't added to the builder.
+    UnknownDevice(usize),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Overlap {
+                first,
+                second,
+                priority,
+            } => write!(
+                f,
+                "mappings ${:04x}-${:04x} and ${:04x}-${:04x} overlap at priority {}",
+                first.start(),
+                first.end(),
+                second.start(),
+                second.end(),
+                priority
+            ),
+            Error::EmptyRange(range) => write!(
+                f,
+                "empty mapping ${:04x}-${:04x}",
+                range.start(),
+                range.end()
+            ),
+            Error::UnknownDevice(id) => write!(f, "unknown device #{}", id),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
@@ -1,4 +1,14 @@
 mod bus;
 pub use bus::*;
 pub mod basic;
+pub mod decoder;
+pub mod dma;
+pub mod error;
+pub mod nes;
 pub mod peripheral;
+pub mod power;
+pub mod signal;
+pub mod uninit;
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,334 @@
+use crate::{
+    InspectBus, MemoryBus,
+    dma::{Dma, OAM_DMA},
+    power::RamInit,
+    signal::Signals,
+};
+
+/// Size of the console internal RAM.
+pub const RAM_LEN: usize = 0x800;
+
+/// Number of PPU registers ($2000-$2007).
+pub const PPU_REGISTERS: u16 = 8;
+
+/// A region of the CPU address space.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Region {
+    /// $0000-$1FFF: internal RAM, mirrored every 2 KiB.
+    Ram,
+    /// $2000-$3FFF: PPU registers, mirrored every 8 bytes.
+    Ppu,
+    /// $4000-$401F: APU and I/O registers.
+    Io,
+    /// $4020-$FFFF: cartridge space.
+    Cartridge,
+}
+
+impl Region {
+    /// Decodes `addr`, returning its region and the address seen by the
+    /// device (mirrors folded into their base address).
+    pub fn decode(addr: u16) -> (Self, u16) {
+        match addr {
+            0x0000..=0x1FFF => (Region::Ram, addr & (RAM_LEN as u16 - 1)),
+            0x2000..=0x3FFF => (Region::Ppu, 0x2000 | (addr & (PPU_REGISTERS - 1))),
+            0x4000..=0x401F => (Region::Io, addr),
+            _ => (Region::Cartridge, addr),
+        }
+    }
+}
+
+/// Value put on the data bus by a device.
+///
+/// Only the bits set in `mask` are driven; the rest keep the value last seen
+/// on the bus (open bus).
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct BusValue {
+    pub data: u8,
+    pub mask: u8,
+}
+
+impl BusValue {
+    /// Nothing is driven.
+    pub const FLOATING: Self = Self { data: 0, mask: 0 };
+
+    /// Every bit is driven.
+    pub fn full(data: u8) -> Self {
+        Self { data, mask: 0xFF }
+    }
+
+    /// Only the bits set in `mask` are driven.
+    pub fn partial(data: u8, mask: u8) -> Self {
+        Self { data, mask }
+    }
+
+    /// Byte seen on the bus, if it last held `open_bus`.
+    pub fn resolve(self, open_bus: u8) -> u8 {
+        (self.data & self.mask) | (open_bus & !self.mask)
+    }
+}
+
+impl From<Option<u8>> for BusValue {
+    fn from(data: Option<u8>) -> Self {
+        data.map_or(Self::FLOATING, Self::full)
+    }
+}
+
+/// Bus Device
+///
+/// A peripheral attached to a [NesBus], which may leave some (or all) data
+/// lines undriven.
+pub trait BusDevice {
+    /// Reads `addr`.
+    ///
+    /// Implementations may perform side effects (e.g., MMIO behavior).
+    fn read(&mut self, addr: u16) -> BusValue;
+
+    /// Reads `addr`.
+    ///
+    /// This must not mutate the device state.
+    fn peek(&self, addr: u16) -> BusValue;
+
+    /// Writes `data` into `addr`.
+    fn write(&mut self, addr: u16, data: u8);
+
+    /// Sees a CPU write of `data` into `addr` meant for another device.
+    ///
+    /// Only called on the cartridge, which may snoop the bus (e.g. MMC5
+    /// watching PPUCTRL). `addr` isn't mirrored down.
+    fn snoop(&mut self, _addr: u16, _data: u8) {}
+}
+
+/// A device that drives nothing: reads return open bus, writes are dropped.
+///
+/// Stands for the peripherals that aren't attached to a [NesBus].
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Unmapped;
+
+impl BusDevice for Unmapped {
+    fn read(&mut self, _addr: u16) -> BusValue {
+        BusValue::FLOATING
+    }
+
+    fn peek(&self, _addr: u16) -> BusValue {
+        BusValue::FLOATING
+    }
+
+    fn write(&mut self, _addr: u16, _data: u8) {}
+}
+
+/// Attaches a plain [MemoryBus] / [InspectBus] (e.g. [crate::basic::BasicMemory])
+/// to a [NesBus], as a device that drives every bit on every read.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Driven<T>(pub T);
+
+impl<T: MemoryBus + InspectBus> BusDevice for Driven<T> {
+    fn read(&mut self, addr: u16) -> BusValue {
+        BusValue::full(self.0.read_u8(addr))
+    }
+
+    fn peek(&self, addr: u16) -> BusValue {
+        BusValue::full(self.0.peek_u8(addr))
+    }
+
+    fn write(&mut self, addr: u16, data: u8) {
+        self.0.write_u8(addr, data);
+    }
+}
+
+/// NES CPU Bus
+///
+/// Decodes the CPU address space of the console (see [Region]), and
+/// dispatches every access to the attached peripherals:
+///
+/// - `ppu`: receives $2000-$2007 (mirrors folded).
+/// - `io`: receives $4000-$401F (APU, OAM DMA and controller ports).
+/// - `cartridge`: receives $4020-$FFFF.
+///
+/// The internal RAM is owned by the bus, and starts zeroed (see
+/// [NesBus::power_on]).
+///
+/// ## Behaviour
+///
+/// - The bus remembers the last value driven on it (by a read or a write).
+///   Reads of bits no device drives (see [BusDevice]) return that value,
+///   which covers unmapped addresses and partially driven registers, such
+///   as the upper bits of $4016/$4017.
+/// - The PPU keeps its own I/O latch, so the open bus bits of its
+///   registers are left to the `ppu` device.
+/// - [InspectBus] reads resolve open bus bits too, without updating it.
+/// - Writes to [OAM_DMA] start a sprite DMA (they also reach `io`). The
+///   [Dma] unit has to be clocked with [NesBus::cycle_dma].
+///
+/// ```ignore
+/// let cartridge = Cartridge::from_rom(rom)?;
+/// let mut bus = NesBus::new(Unmapped, Unmapped, cartridge);
+///
+/// bus.write_u8(0x0800, 0x42);
+/// assert_eq!(bus.read_u8(0x0000), 0x42);
+/// assert_eq!(bus.read_u8(0x5000), 0x42); // Open bus
+/// ```
+pub struct NesBus<P, A, C> {
+    ram: [u8; RAM_LEN],
+    open_bus: u8,
+    dma: Dma,
+    ppu: P,
+    io: A,
+    cartridge: C,
+}
+
+impl<P, A, C> NesBus<P, A, C> {
+    pub fn new(ppu: P, io: A, cartridge: C) -> Self {
+        Self {
+            ram: [0; RAM_LEN],
+            open_bus: 0,
+            dma: Dma::default(),
+            ppu,
+            io,
+            cartridge,
+        }
+    }
+
+    /// Power cycle: fills the internal RAM with `init`, clears the open bus
+    /// and cancels any DMA.
+    ///
+    /// The peripherals are left alone.
+    pub fn power_on(&mut self, init: RamInit) {
+        init.fill(&mut self.ram);
+        self.open_bus = 0;
+        self.dma = Dma::default();
+    }
+
+    /// Internal RAM.
+    pub fn ram(&self) -> &[u8; RAM_LEN] {
+        &self.ram
+    }
+
+    pub fn ram_mut(&mut self) -> &mut [u8; RAM_LEN] {
+        &mut self.ram
+    }
+
+    /// Last value driven on the data bus.
+    pub fn open_bus(&self) -> u8 {
+        self.open_bus
+    }
+
+    pub fn dma(&self) -> &Dma {
+        &self.dma
+    }
+
+    pub fn dma_mut(&mut self) -> &mut Dma {
+        &mut self.dma
+    }
+
+    pub fn ppu(&self) -> &P {
+        &self.ppu
+    }
+
+    pub fn ppu_mut(&mut self) -> &mut P {
+        &mut self.ppu
+    }
+
+    pub fn io(&self) -> &A {
+        &self.io
+    }
+
+    pub fn io_mut(&mut self) -> &mut A {
+        &mut self.io
+    }
+
+    pub fn cartridge(&self) -> &C {
+        &self.cartridge
+    }
+
+    pub fn cartridge_mut(&mut self) -> &mut C {
+        &mut self.cartridge
+    }
+
+    /// Detaches every peripheral.
+    pub fn into_parts(self) -> (P, A, C) {
+        (self.ppu, self.io, self.cartridge)
+    }
+}
+
+impl<P, A, C> NesBus<P, A, C>
+where
+    P: BusDevice,
+    A: BusDevice,
+    C: BusDevice,
+{
+    fn peek_value(&self, addr: u16) -> BusValue {
+        match Region::decode(addr) {
+            (Region::Ram, addr) => BusValue::full(self.ram[addr as usize]),
+            (Region::Ppu, addr) => self.ppu.peek(addr),
+            (Region::Io, addr) => self.io.peek(addr),
+            (Region::Cartridge, addr) => self.cartridge.peek(addr),
+        }
+    }
+
+    /// Runs a cycle of the [Dma] unit, before the CPU does.
+    pub fn cycle_dma(&mut self, signals: &mut Signals) {
+        let mut dma = std::mem::take(&mut self.dma);
+        dma.cycle(self, signals);
+        self.dma = dma;
+    }
+}
+
+impl<P, A, C> MemoryBus for NesBus<P, A, C>
+where
+    P: BusDevice,
+    A: BusDevice,
+    C: BusDevice,
+{
+    fn read_u8(&mut self, addr: u16) -> u8 {
+        let value = match Region::decode(addr) {
+            (Region::Ram, addr) => BusValue::full(self.ram[addr as usize]),
+            (Region::Ppu, addr) => self.ppu.read(addr),
+            (Region::Io, addr) => self.io.read(addr),
+            (Region::Cartridge, addr) => self.cartridge.read(addr),
+        };
+        self.open_bus = value.resolve(self.open_bus);
+        self.open_bus
+    }
+
+    fn read_u16(&mut self, addr: u16) -> u16 {
+        (self.read_u8(addr) as u16) | ((self.read_u8(addr.wrapping_add(1)) as u16) << 8)
+    }
+
+    fn write_u8(&mut self, addr: u16, data: u8) {
+        self.open_bus = data;
+        match Region::decode(addr) {
+            (Region::Ram, offset) => self.ram[offset as usize] = data,
+            (Region::Ppu, offset) => self.ppu.write(offset, data),
+            (Region::Io, offset) => {
+                if offset == OAM_DMA {
+                    self.dma.request_oam(data);
+                }
+                self.io.write(offset, data);
+            }
+            (Region::Cartridge, addr) => {
+                self.cartridge.write(addr, data);
+                return;
+            }
+        }
+        self.cartridge.snoop(addr, data);
+    }
+}
+
+impl<P, A, C> InspectBus for NesBus<P, A, C>
+where
+    P: BusDevice,
+    A: BusDevice,
+    C: BusDevice,
+{
+    fn peek_u8(&self, addr: u16) -> u8 {
+        self.peek_value(addr).resolve(self.open_bus)
+    }
+
+    /// The open bus bits of the high byte resolve to the low byte, as they
+    /// would on a real read.
+    fn peek_u16(&self, addr: u16) -> u16 {
+        let low = self.peek_u8(addr);
+        let high = self.peek_value(addr.wrapping_add(1)).resolve(low);
+        (low as u16) | ((high as u16) << 8)
+    }
+}
@@ -1,9 +1,12 @@
-use crate::MemoryBus;
+use crate::{MemoryBus, signal::Signals};
 
 pub trait Peripheral {
     fn cold_reset(&mut self);
     fn warm_reset(&mut self);
 
     fn recv(&mut self, addr: u16, value: u8);
-    fn cycle(&mut self, io: &mut impl MemoryBus);
+
+    /// Runs a cycle, driving (or, for the CPU, sampling) the shared
+    /// `signals`.
+    fn cycle(&mut self, io: &mut impl MemoryBus, signals: &mut Signals);
 }
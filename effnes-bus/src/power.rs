@@ -0,0 +1,60 @@
+/// Power-on RAM Contents
+///
+/// Real RAM chips power up with (mostly) unpredictable contents, which a
+/// few games end up depending on. Emulators usually pick one of these.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub enum RamInit {
+    /// Every byte is $00.
+    #[default]
+    Zero,
+    /// Every byte is $FF.
+    Ones,
+    /// 4 bytes of $00, then 4 bytes of $FF, and so on (as in FCEUX).
+    Alternating,
+    /// Pseudo-random bytes, the same for every `seed`.
+    Random(u64),
+}
+
+impl RamInit {
+    /// Byte at `offset` into a chip.
+    pub fn byte(self, offset: usize) -> u8 {
+        match self {
+            RamInit::Zero => 0x00,
+            RamInit::Ones => 0xFF,
+            RamInit::Alternating => {
+                if offset & 0x04 == 0 {
+                    0x00
+                } else {
+                    0xFF
+                }
+            }
+            RamInit::Random(seed) => {
+                splitmix64(seed.wrapping_add((offset as u64 / 8).wrapping_mul(GOLDEN_GAMMA)))
+                    .to_le_bytes()[offset % 8]
+            }
+        }
+    }
+
+    /// Fills a whole chip.
+    pub fn fill(self, ram: &mut [u8]) {
+        match self {
+            RamInit::Zero => ram.fill(0x00),
+            RamInit::Ones => ram.fill(0xFF),
+            _ => {
+                for (offset, byte) in ram.iter_mut().enumerate() {
+                    *byte = self.byte(offset);
+                }
+            }
+        }
+    }
+}
+
+const GOLDEN_GAMMA: u64 = 0x9E37_79B9_7F4A_7C15;
+
+/// SplitMix64 output function.
+fn splitmix64(state: u64) -> u64 {
+    let mut z = state.wrapping_add(GOLDEN_GAMMA);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
@@ -0,0 +1,135 @@
+use bitflags::bitflags;
+
+bitflags! {
+    /// Device driving an interrupt or RDY line.
+    ///
+    /// Bits without a name are free for custom devices.
+    #[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+    pub struct Source: u16 {
+        /// APU frame counter.
+        const ApuFrame  = 0b0000_0000_0001;
+        /// APU delta modulation channel.
+        const Dmc       = 0b0000_0000_0010;
+        /// Cartridge mapper.
+        const Mapper    = 0b0000_0000_0100;
+        /// Famicom Disk System adapter.
+        const Disk      = 0b0000_0000_1000;
+        /// Expansion port.
+        const Expansion = 0b0000_0001_0000;
+        /// PPU (vertical blank).
+        const Ppu       = 0b0000_0010_0000;
+        /// Sprite DMA ($4014).
+        const OamDma    = 0b0000_0100_0000;
+        /// DMC sample fetch.
+        const DmcDma    = 0b0000_1000_0000;
+
+        const _ = !0;
+    }
+}
+
+/// CPU Signals
+///
+/// The control lines shared by the CPU and its peripherals, which drive
+/// them on every cycle (see [crate::peripheral::Peripheral::cycle]):
+///
+/// - IRQ: level triggered, and wired-OR. It stays asserted as long as any
+///   source holds it, so each device acknowledges its own interrupt.
+/// - NMI: edge triggered. Asserting the line latches a pending NMI, which
+///   the CPU takes once (see [Signals::take_nmi]).
+/// - RDY: pulled low by DMA units to halt the CPU (on read cycles), for as
+///   long as any of them holds it. The CPU reports whether it actually
+///   stalled (see [Signals::stalled]), and the DMA units whether they drive
+///   the address bus (see [Signals::bus_driven]); otherwise, the halted CPU
+///   keeps repeating its read.
+///
+/// The CPU samples the lines between (or, on cycle accurate VMs, during)
+/// its instructions.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Signals {
+    irq: Source,
+    nmi: bool,
+    nmi_pending: bool,
+    halt: Source,
+    stalled: bool,
+    bus_driven: bool,
+}
+
+impl Signals {
+    /// Asserts (or releases) the IRQ line on behalf of `source`.
+    pub fn set_irq(&mut self, source: Source, asserted: bool) {
+        self.irq.set(source, asserted);
+    }
+
+    /// Whether any source asserts the IRQ line.
+    pub fn irq(&self) -> bool {
+        !self.irq.is_empty()
+    }
+
+    /// Sources asserting the IRQ line.
+    pub fn irq_sources(&self) -> Source {
+        self.irq
+    }
+
+    /// Drives the NMI line. A pending NMI is latched when it gets asserted.
+    pub fn set_nmi(&mut self, asserted: bool) {
+        if asserted && !self.nmi {
+            self.nmi_pending = true;
+        }
+        self.nmi = asserted;
+    }
+
+    /// Level of the NMI line.
+    pub fn nmi(&self) -> bool {
+        self.nmi
+    }
+
+    pub fn nmi_pending(&self) -> bool {
+        self.nmi_pending
+    }
+
+    /// Acknowledges the pending NMI, returning whether there was one.
+    pub fn take_nmi(&mut self) -> bool {
+        std::mem::take(&mut self.nmi_pending)
+    }
+
+    /// Pulls (or releases) RDY on behalf of `source`.
+    pub fn set_halt(&mut self, source: Source, halted: bool) {
+        self.halt.set(source, halted);
+    }
+
+    /// Whether any source halts the CPU.
+    pub fn halted(&self) -> bool {
+        !self.halt.is_empty()
+    }
+
+    /// Sources halting the CPU.
+    pub fn halt_sources(&self) -> Source {
+        self.halt
+    }
+
+    /// Reports whether the CPU stalled on the last cycle (set by the CPU).
+    pub fn set_stalled(&mut self, stalled: bool) {
+        self.stalled = stalled;
+    }
+
+    /// Whether the CPU stalled on the last cycle, which only happens on
+    /// read cycles while RDY is held.
+    pub fn stalled(&self) -> bool {
+        self.stalled
+    }
+
+    /// Reports whether a DMA unit drives the address bus this cycle.
+    pub fn set_bus_driven(&mut self, driven: bool) {
+        self.bus_driven = driven;
+    }
+
+    /// Whether a DMA unit drives the address bus this cycle.
+    pub fn bus_driven(&self) -> bool {
+        self.bus_driven
+    }
+
+    /// Releases every line, and drops the pending NMI.
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
@@ -0,0 +1,337 @@
+use crate::{
+    InspectBus, MemoryBus,
+    basic::{BasicMemory, Ram, Rom},
+    decoder::{BoxedDecoder, Decoder, Device, Mapping},
+    dma::{Dma, OAM_DATA, OAM_LEN},
+    error::Error,
+    nes::{BusDevice, BusValue, Driven, NesBus, Region, Unmapped},
+    power::RamInit,
+    signal::{Signals, Source},
+    uninit::{UninitDetector, UninitRead},
+};
+use std::ops::RangeInclusive;
+
+/// Device that records every access, and reads back the low byte of the
+/// address.
+#[derive(Default)]
+struct Recorder {
+    reads: Vec<u16>,
+    writes: Vec<(u16, u8)>,
+}
+
+impl BusDevice for Recorder {
+    fn read(&mut self, addr: u16) -> BusValue {
+        self.reads.push(addr);
+        BusValue::full(addr as u8)
+    }
+
+    fn peek(&self, addr: u16) -> BusValue {
+        BusValue::full(addr as u8)
+    }
+
+    fn write(&mut self, addr: u16, data: u8) {
+        self.writes.push((addr, data));
+    }
+}
+
+/// Controller port: only drives the low 5 bits of $4016/$4017.
+struct Port;
+
+impl BusDevice for Port {
+    fn read(&mut self, addr: u16) -> BusValue {
+        self.peek(addr)
+    }
+
+    fn peek(&self, addr: u16) -> BusValue {
+        match addr {
+            0x4016 | 0x4017 => BusValue::partial(0x01, 0x1F),
+            _ => BusValue::FLOATING,
+        }
+    }
+
+    fn write(&mut self, _addr: u16, _data: u8) {}
+}
+
+#[test]
+fn nes_bus_decoding() {
+    assert_eq!(Region::decode(0x1FFF), (Region::Ram, 0x07FF));
+    assert_eq!(Region::decode(0x3FFE), (Region::Ppu, 0x2006));
+    assert_eq!(Region::decode(0x4014), (Region::Io, 0x4014));
+    assert_eq!(Region::decode(0x401F), (Region::Io, 0x401F));
+    assert_eq!(Region::decode(0x4020), (Region::Cartridge, 0x4020));
+
+    let mut bus = NesBus::new(
+        Recorder::default(),
+        Recorder::default(),
+        Recorder::default(),
+    );
+    bus.write_u8(0x0801, 0x42);
+    assert_eq!(bus.read_u8(0x1801), 0x42);
+    assert_eq!(bus.peek_u8(0x0001), 0x42);
+    assert_eq!(bus.ram()[1], 0x42);
+
+    bus.write_u8(0x2008, 0x80);
+    bus.write_u8(0x3FF9, 0x1E);
+    assert_eq!(bus.read_u8(0x2402), 0x02);
+    assert_eq!(bus.ppu().writes, [(0x2000, 0x80), (0x2001, 0x1E)]);
+    assert_eq!(bus.ppu().reads, [0x2002]);
+
+    bus.write_u8(0x4014, 0x02);
+    assert_eq!(bus.read_u8(0x4016), 0x16);
+    assert_eq!(bus.io().writes, [(0x4014, 0x02)]);
+
+    assert_eq!(bus.read_u16(0xFFFC), 0xFDFC);
+    bus.write_u8(0x8000, 0x01);
+    assert_eq!(bus.cartridge().reads, [0xFFFC, 0xFFFD]);
+    assert_eq!(bus.cartridge().writes, [(0x8000, 0x01)]);
+    assert_eq!(bus.io().reads, [0x4016]);
+}
+
+#[test]
+fn nes_bus_open_bus() {
+    let mut rom = BasicMemory::default_with(0xEA);
+    rom.write_u8(0xFFFC, 0x00);
+    rom.write_u8(0xFFFD, 0x80);
+    rom.write_u8(0x8000, 0x40);
+
+    let mut bus = NesBus::new(Unmapped, Port, Driven(rom));
+    assert_eq!(bus.read_u8(0x2002), 0);
+    assert_eq!(bus.read_u16(0xFFFC), 0x8000);
+    assert_eq!(bus.open_bus(), 0x80);
+
+    // Unmapped reads return the last value on the bus.
+    bus.write_u8(0x2000, 0xFF);
+    assert_eq!(bus.read_u8(0x2002), 0xFF);
+    assert_eq!(bus.read_u8(0x8000), 0x40);
+    assert_eq!(bus.read_u8(0x4015), 0x40);
+
+    // Partially driven registers keep the upper bits.
+    assert_eq!(bus.read_u8(0x4016), 0x41);
+    bus.write_u8(0x0000, 0xE0);
+    assert_eq!(bus.peek_u8(0x4017), 0xE1);
+    assert_eq!(bus.open_bus(), 0xE0);
+    assert_eq!(bus.peek_u16(0x4017), 0xE1E1);
+    assert_eq!(bus.open_bus(), 0xE0);
+    assert_eq!(bus.read_u8(0x4017), 0xE1);
+    assert_eq!(bus.read_u8(0x4017), 0xE1);
+}
+
+#[test]
+fn decoder_mappings() {
+    let mut builder = BoxedDecoder::builder();
+    let ram = builder.device(Box::new(Ram::new(0x800)));
+    let rom = builder.device(Box::new(Rom::new(vec![0x11, 0x22, 0x33, 0x44])));
+    builder.map(Mapping::new(0x0000..=0xBFFF, ram).with_mask(0x07FF));
+    let overlay = builder.map(
+        Mapping::new(0xA000..=0xBFFF, rom)
+            .relative()
+            .read_only()
+            .with_priority(1),
+    );
+    builder.map(Mapping::new(0xE000..=0xFFFF, rom).relative());
+    let mut bus = builder.build().unwrap();
+
+    // RAM mirrors, with the ROM shadowing it at $A000-$BFFF.
+    bus.write_u8(0x0801, 0x55);
+    assert_eq!(bus.read_u8(0x0001), 0x55);
+    assert_eq!(bus.read_u8(0xA001), 0x22);
+    assert_eq!(bus.read_u16(0xFFFE), 0x4433);
+
+    // Writes to the ROM fall through into the RAM below.
+    bus.write_u8(0xA002, 0x66);
+    assert_eq!(bus.peek_u8(0xA002), 0x33);
+    assert_eq!(bus.peek_u8(0x0002), 0x66);
+    bus.set_enabled(overlay, false);
+    assert_eq!(bus.read_u8(0xA002), 0x66);
+
+    // Unmapped reads return the last value on the bus.
+    bus.write_u8(0xC000, 0x77);
+    assert_eq!(bus.read_u8(0xC000), 0x77);
+    assert_eq!(bus.peek_u16(0xDFFF), 0x1177);
+
+    let mut builder = Decoder::<Ram>::builder();
+    let low = builder.device(Ram::new(0x100));
+    let high = builder.device(Ram::new(0x100));
+    builder.map(Mapping::new(0x0000..=0x1FFF, low));
+    builder.map(Mapping::new(0x1000..=0x2FFF, high).with_priority(1));
+    builder.map(Mapping::new(0x1F00..=0x3FFF, low));
+    assert_eq!(
+        builder.build().err(),
+        Some(Error::Overlap {
+            first: 0x0000..=0x1FFF,
+            second: 0x1F00..=0x3FFF,
+            priority: 0,
+        })
+    );
+
+    let mut builder = Decoder::<Box<dyn Device>>::builder();
+    let ram = builder.device(Box::new(Ram::new(1)));
+    let empty = RangeInclusive::new(0x2000, 0x1000);
+    builder.map(Mapping::new(empty.clone(), ram));
+    assert_eq!(builder.build().err(), Some(Error::EmptyRange(empty)));
+}
+
+#[test]
+fn signal_lines() {
+    let mut signals = Signals::default();
+
+    // IRQ is wired-OR: it stays asserted until every source releases it.
+    signals.set_irq(Source::Mapper, true);
+    signals.set_irq(Source::ApuFrame, true);
+    signals.set_irq(Source::Mapper, false);
+    assert!(signals.irq());
+    assert_eq!(signals.irq_sources(), Source::ApuFrame);
+    signals.set_irq(Source::ApuFrame, false);
+    assert!(!signals.irq());
+
+    // NMI latches on the asserting edge only.
+    signals.set_nmi(true);
+    assert!(signals.take_nmi());
+    signals.set_nmi(true);
+    assert!(!signals.take_nmi());
+    signals.set_nmi(false);
+    signals.set_nmi(true);
+    assert!(signals.nmi_pending());
+
+    signals.set_halt(Source::OamDma, true);
+    signals.set_halt(Source::DmcDma, true);
+    signals.set_halt(Source::OamDma, false);
+    assert!(signals.halted());
+    signals.reset();
+    assert!(!signals.halted() && !signals.nmi() && !signals.nmi_pending());
+}
+
+/// Runs DMA cycles against a CPU that is always on a read cycle, until RDY
+/// is released. Returns the number of cycles the CPU stalled.
+fn run_dma(signals: &mut Signals, mut cycle: impl FnMut(&mut Signals)) -> usize {
+    let mut stalled = 0;
+    loop {
+        cycle(signals);
+        if !signals.halted() {
+            signals.set_stalled(false);
+            return stalled;
+        }
+        signals.set_stalled(true);
+        stalled += 1;
+    }
+}
+
+#[test]
+fn dma_timing() {
+    let mut bus = NesBus::new(Recorder::default(), Unmapped, Unmapped);
+    for i in 0..OAM_LEN {
+        bus.ram_mut()[0x0200 + i as usize] = i as u8;
+    }
+    let mut signals = Signals::default();
+
+    // Sprite DMA: halt, alignment (when needed), and 256 get/put pairs.
+    for (halt_on_get, expected) in [(true, 514), (false, 513)] {
+        while bus.dma().is_get_cycle() != halt_on_get {
+            bus.cycle_dma(&mut signals);
+        }
+        bus.write_u8(0x4014, 0x02);
+        let stalled = run_dma(&mut signals, |signals| bus.cycle_dma(signals));
+        assert_eq!(stalled, expected);
+    }
+    let writes = &bus.ppu().writes;
+    assert_eq!(writes.len(), 2 * OAM_LEN as usize);
+    assert!(writes[..256].iter().all(|&(addr, _)| addr == OAM_DATA));
+    assert_eq!(writes[255], (OAM_DATA, 0xFF));
+
+    // DMC DMA: halt, dummy, alignment (when needed), and fetch.
+    let mut memory = BasicMemory::default_with(0x5A);
+    for (halt_on_get, expected) in [(true, 3), (false, 4)] {
+        let mut dma = Dma::default();
+        while dma.is_get_cycle() != halt_on_get {
+            dma.cycle(&mut memory, &mut signals);
+        }
+        dma.request_dmc(0xC000);
+        let stalled = run_dma(&mut signals, |signals| dma.cycle(&mut memory, signals));
+        assert_eq!(stalled, expected);
+        assert_eq!(dma.take_dmc(), Some(0x5A));
+        assert_eq!(dma.take_dmc(), None);
+    }
+
+    // A DMC fetch in the middle of a sprite DMA delays it by 2 cycles.
+    let mut dma = Dma::default();
+    dma.request_oam(0x02);
+    let mut stalled = 0;
+    while dma.active() || signals.halted() {
+        if stalled == 100 {
+            dma.request_dmc(0xC000);
+        }
+        dma.cycle(&mut memory, &mut signals);
+        if signals.halted() {
+            stalled += 1;
+        }
+        signals.set_stalled(signals.halted());
+    }
+    assert_eq!(stalled, 514 + 2);
+    assert_eq!(dma.take_dmc(), Some(0x5A));
+}
+
+#[test]
+fn power_on_ram() {
+    let mut ram = [0x55; 12];
+    RamInit::Alternating.fill(&mut ram);
+    assert_eq!(ram, [0, 0, 0, 0, 0xFF, 0xFF, 0xFF, 0xFF, 0, 0, 0, 0]);
+    RamInit::Ones.fill(&mut ram);
+    assert!(ram.iter().all(|&byte| byte == 0xFF));
+
+    // Seeded patterns are reproducible, and differ between seeds.
+    let random = |seed| {
+        let mut ram = [0; 64];
+        RamInit::Random(seed).fill(&mut ram);
+        ram
+    };
+    assert_eq!(random(1), random(1));
+    assert_ne!(random(1), random(2));
+    assert_eq!(random(7)[9], RamInit::Random(7).byte(9));
+
+    let mut bus = NesBus::new(Unmapped, Unmapped, Unmapped);
+    bus.write_u8(0x4014, 0x00);
+    bus.power_on(RamInit::Alternating);
+    assert_eq!(bus.read_u16(0x1803), 0xFF00);
+    assert!(!bus.dma().active());
+    assert_eq!(BasicMemory::new(RamInit::Ones).peek_u8(0x1234), 0xFF);
+    assert_eq!(Ram::with_init(16, RamInit::Alternating).peek_u8(0x17), 0xFF);
+}
+
+#[test]
+fn uninit_reads() {
+    let mut bus = UninitDetector::new(NesBus::new(Unmapped, Unmapped, Unmapped));
+    bus.track(0x0000..=0x1FFF, 0x07FF);
+    bus.allow(0x00F0..=0x00FF);
+    bus.initialize(0x0100..=0x01FF);
+
+    bus.set_pc(0x8000);
+    bus.write_u8(0x0810, 0x42);
+    assert_eq!(bus.read_u8(0x0010), 0x42);
+    assert!(bus.is_initialized(0x1010));
+    bus.read_u8(0x01FF);
+    bus.read_u8(0x00F8);
+    // Untracked addresses never report.
+    bus.read_u8(0x2002);
+    assert!(bus.reports().is_empty());
+
+    bus.set_pc(0x8003);
+    bus.read_u16(0x0011);
+    bus.peek_u8(0x0020);
+    assert_eq!(
+        bus.take_reports(),
+        [
+            UninitRead {
+                pc: 0x8003,
+                addr: 0x0011
+            },
+            UninitRead {
+                pc: 0x8003,
+                addr: 0x0012
+            },
+        ]
+    );
+
+    bus.reset();
+    bus.read_u8(0x0810);
+    assert_eq!(bus.reports().len(), 1);
+}
@@ -0,0 +1,203 @@
+use crate::{InspectBus, MemoryBus};
+use std::ops::RangeInclusive;
+
+/// Read of a RAM byte that wasn't written since power-on.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct UninitRead {
+    /// Program counter of the instruction that read it.
+    pub pc: u16,
+    /// Address read (as seen by the CPU, mirrors included).
+    pub addr: u16,
+}
+
+/// RAM chip tracked by the detector.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Tracked {
+    range: RangeInclusive<u16>,
+    mask: u16,
+}
+
+impl Tracked {
+    /// Folds mirrors of `addr` into the base address of the chip.
+    fn fold(&self, addr: u16) -> Option<u16> {
+        self.range
+            .contains(&addr)
+            .then(|| self.range.start() + ((addr - self.range.start()) & self.mask))
+    }
+}
+
+/// Uninitialized Memory Read Detector
+///
+/// Wraps a bus, remembering which bytes of the tracked RAM chips were
+/// written since power-on, and reporting every CPU read of the rest.
+///
+/// ## Behaviour
+///
+/// - Only the tracked address ranges are checked (see
+///   [UninitDetector::track]), so ROM and registers never report.
+/// - Writes through any mirror initialize the byte for every mirror.
+/// - Reads of allowed addresses (see [UninitDetector::allow]), such as a
+///   RAM test or a seed for a random number generator, aren't reported.
+/// - The bus can't tell the program counter, which the frontend keeps up to
+///   date with [UninitDetector::set_pc] at every instruction boundary. The
+///   CPU `pc` register is only the instruction address there (cycle stepped
+///   CPUs move it mid-instruction), so it's taken from
+///   `DebugCpu::next_instruction` instead.
+/// - Dummy reads of cycle stepped CPUs, whose value is thrown away, aren't
+///   reported either, as long as the frontend tells them apart (see
+///   [UninitDetector::set_dummy_read]).
+/// - [InspectBus] reads are never reported.
+///
+/// ```ignore
+/// let mut bus = UninitDetector::new(NesBus::new(ppu, io, cartridge));
+/// bus.track(0x0000..=0x1FFF, 0x07FF);
+/// bus.track(0x6000..=0x7FFF, 0x1FFF);
+///
+/// loop {
+///     if let Some(pc) = cpu.next_instruction() {
+///         bus.set_pc(pc);
+///     }
+///     bus.set_dummy_read(cpu.dummy_read(&signals));
+///     cpu.cycle(&mut bus, &mut signals);
+///     for read in bus.take_reports() {
+///         eprintln!("${:04x}: read uninitialized ${:04x}", read.pc, read.addr);
+///     }
+/// }
+/// ```
+pub struct UninitDetector<B> {
+    inner: B,
+    tracked: Vec<Tracked>,
+    allowed: Vec<RangeInclusive<u16>>,
+    /// One bit per (folded) address.
+    written: Box<[u64; 0x10000 / 64]>,
+    pc: u16,
+    dummy_read: bool,
+    reports: Vec<UninitRead>,
+}
+
+impl<B> UninitDetector<B> {
+    /// Wraps `inner`, tracking nothing yet.
+    pub fn new(inner: B) -> Self {
+        Self {
+            inner,
+            tracked: Vec::new(),
+            allowed: Vec::new(),
+            written: Box::new([0; 0x10000 / 64]),
+            pc: 0,
+            dummy_read: false,
+            reports: Vec::new(),
+        }
+    }
+
+    /// Tracks the RAM chip at `range`, mirrored every `mask + 1` bytes (e.g.
+    /// `0x0000..=0x1FFF` and `0x07FF` for the console RAM).
+    pub fn track(&mut self, range: RangeInclusive<u16>, mask: u16) {
+        self.tracked.push(Tracked { range, mask });
+    }
+
+    /// Allows reading `range` before writing it (mirrors aren't allowed
+    /// along).
+    pub fn allow(&mut self, range: RangeInclusive<u16>) {
+        self.allowed.push(range);
+    }
+
+    /// Marks `range` as initialized, e.g. after loading a program into it.
+    pub fn initialize(&mut self, range: RangeInclusive<u16>) {
+        for addr in range {
+            self.mark(addr);
+        }
+    }
+
+    /// Power cycle: every tracked byte is uninitialized again, and the
+    /// reports are dropped.
+    pub fn reset(&mut self) {
+        self.written.fill(0);
+        self.reports.clear();
+    }
+
+    /// Sets the program counter attached to the next reports: the address of
+    /// the instruction about to run.
+    pub fn set_pc(&mut self, pc: u16) {
+        self.pc = pc;
+    }
+
+    /// Sets whether the reads until the next call are dummy ones, as told by
+    /// `DebugCpu::dummy_read` before every CPU cycle.
+    pub fn set_dummy_read(&mut self, dummy: bool) {
+        self.dummy_read = dummy;
+    }
+
+    pub fn is_initialized(&self, addr: u16) -> bool {
+        let addr = self.fold(addr).unwrap_or(addr);
+        self.written[addr as usize / 64] & (1 << (addr % 64)) != 0
+    }
+
+    /// Reads reported since the last [UninitDetector::take_reports].
+    pub fn reports(&self) -> &[UninitRead] {
+        &self.reports
+    }
+
+    pub fn take_reports(&mut self) -> Vec<UninitRead> {
+        std::mem::take(&mut self.reports)
+    }
+
+    pub fn inner(&self) -> &B {
+        &self.inner
+    }
+
+    pub fn inner_mut(&mut self) -> &mut B {
+        &mut self.inner
+    }
+
+    pub fn into_inner(self) -> B {
+        self.inner
+    }
+
+    fn fold(&self, addr: u16) -> Option<u16> {
+        self.tracked.iter().find_map(|tracked| tracked.fold(addr))
+    }
+
+    fn mark(&mut self, addr: u16) {
+        if let Some(addr) = self.fold(addr) {
+            self.written[addr as usize / 64] |= 1 << (addr % 64);
+        }
+    }
+
+    fn check(&mut self, addr: u16) {
+        if !self.dummy_read
+            && self.fold(addr).is_some()
+            && !self.is_initialized(addr)
+            && !self.allowed.iter().any(|range| range.contains(&addr))
+        {
+            self.reports.push(UninitRead { pc: self.pc, addr });
+        }
+    }
+}
+
+impl<B: MemoryBus> MemoryBus for UninitDetector<B> {
+    fn read_u8(&mut self, addr: u16) -> u8 {
+        self.check(addr);
+        self.inner.read_u8(addr)
+    }
+
+    fn read_u16(&mut self, addr: u16) -> u16 {
+        self.check(addr);
+        self.check(addr.wrapping_add(1));
+        self.inner.read_u16(addr)
+    }
+
+    fn write_u8(&mut self, addr: u16, data: u8) {
+        self.mark(addr);
+        self.inner.write_u8(addr, data);
+    }
+}
+
+impl<B: InspectBus> InspectBus for UninitDetector<B> {
+    fn peek_u8(&self, addr: u16) -> u8 {
+        self.inner.peek_u8(addr)
+    }
+
+    fn peek_u16(&self, addr: u16) -> u16 {
+        self.inner.peek_u16(addr)
+    }
+}
@@ -1,4 +1,4 @@
-use effnes_bus::{InspectBus, MemoryBus, peripheral::Peripheral};
+use effnes_bus::{InspectBus, MemoryBus, peripheral::Peripheral, signal::Signals};
 use effnes_cpu::{
     addr::{AddressingMode, IndexRegister},
     consts::{CpuVector, Flags},
@@ -7,7 +7,7 @@ use effnes_cpu::{
     opcode::Mnemonic,
 };
 
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 enum AddressResolverState {
     FetchOperand,
     FetchAddress {
@@ -25,15 +25,23 @@ enum AddressResolverState {
     ZeroPageAddIndexRegister,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 enum State {
     Fetch,
     ResolveAddress(AddressResolverState),
     Process,
-    Write { dummy: bool },
+    Write {
+        dummy: bool,
+    },
+    /// Interrupt sequence, on its `step`-th cycle after the opcode fetch.
+    Interrupt {
+        vector: u16,
+        step: u8,
+    },
     Halt,
 }
 
+#[derive(Clone)]
 pub struct VM {
     /// (Register) Program Counter
     r_pc: u16,
@@ -64,6 +72,12 @@ pub struct VM {
     /// [AddressingMode::ZeroPageI], [AddressingMode::IndirectI]).
     i_opr: u8,
 
+    /// (Internal) Data Read
+    /// Stores the value read in the [State::Process] state, which
+    /// read-modify-write instructions write back unmodified on their dummy
+    /// write cycle.
+    i_dr: u8,
+
     /// (Internal) EXecute
     /// Stores the opcode being executed.
     /// It is set on the [State::Fetch] state.
@@ -122,35 +136,26 @@ impl VM {
     }
 }
 
-impl Peripheral for VM {
-    fn recv(&mut self, _: u16, _: u8) {}
-
-    fn cold_reset(&mut self) {
-        self.r_ps = Flags::empty();
-        self.r_ac = 0;
-        self.r_ix = 0;
-        self.r_iy = 0;
-
-        self.r_sp = 0x00;
-        self.r_pc = CpuVector::Rst as u16;
-
-        self.warm_reset();
-    }
-
-    fn warm_reset(&mut self) {
-        self.r_ps |= Flags::IntDis;
-        self.r_sp = self.r_sp.wrapping_sub(0x03);
-
-        self.i_nst = State::Fetch;
-        self.i_adm = AddressingMode::Implied;
-        self.i_opr = 0;
-        self.i_ex = 0;
-        self.i_ab = 0;
-        self.i_tm = 0;
-        self.i_cc = 0;
+impl VM {
+    /// Whether the next cycle writes to the bus, which RDY can't halt.
+    fn is_write_cycle(&self) -> bool {
+        match self.i_nst {
+            State::Write { .. } => true,
+            State::Interrupt { step, .. } => (1..=3).contains(&step),
+            State::Process => matches!(
+                Mnemonic::from(self.i_ex),
+                Mnemonic::Sta | Mnemonic::Stx | Mnemonic::Sty
+            ),
+            _ => false,
+        }
     }
 
-    fn cycle(&mut self, io: &mut impl MemoryBus) {
+    /// Runs the next cycle of the state machine.
+    ///
+    /// Interrupts are polled on the opcode fetch, which turns into the first
+    /// cycle of the interrupt sequence (NMIs first, IRQs only when they are
+    /// enabled).
+    fn step(&mut self, io: &mut impl MemoryBus, signals: &mut Signals) {
         self.i_tm += 1;
         self.i_nst = 'new_state_match: {
             match &self.i_nst {
@@ -158,6 +163,21 @@ impl Peripheral for VM {
 
                 State::Fetch => {
                     self.i_tm = 0;
+                    let vector = if signals.take_nmi() {
+                        Some(CpuVector::Nmi)
+                    } else if signals.irq() && !self.r_ps.contains(Flags::IntDis) {
+                        Some(CpuVector::Brk)
+                    } else {
+                        None
+                    };
+                    if let Some(vector) = vector {
+                        io.read_u8(self.r_pc.wrapping_add(1));
+                        break 'new_state_match State::Interrupt {
+                            vector: vector as u16,
+                            step: 0,
+                        };
+                    }
+
                     self.r_pc = self.r_pc.wrapping_add(1);
                     self.i_ex = self.next_byte(io);
                     self.i_adm = self.i_ex.into();
@@ -299,7 +319,10 @@ impl Peripheral for VM {
                     // TODO: Don't read address on store operations
                     match self.i_adm {
                         AddressingMode::Implied => (),
-                        _ => self.i_opr = io.read_u8(self.i_ab),
+                        _ => {
+                            self.i_opr = io.read_u8(self.i_ab);
+                            self.i_dr = self.i_opr;
+                        }
                     };
 
                     let mnemonic: Mnemonic = self.i_ex.into();
@@ -647,17 +670,99 @@ impl Peripheral for VM {
                     State::Fetch
                 }
 
-                State::Write { dummy: true } => State::Write { dummy: false },
+                State::Write { dummy: true } => {
+                    io.write_u8(self.i_ab, self.i_dr);
+                    State::Write { dummy: false }
+                }
 
                 State::Write { dummy: false } => {
                     io.write_u8(self.i_ab, self.i_opr);
                     State::Fetch
                 }
+
+                State::Interrupt { vector, step } => {
+                    let (vector, step) = (*vector, *step);
+                    let ret = self.r_pc.wrapping_add(1);
+                    match step {
+                        0 => {
+                            io.read_u8(ret);
+                        }
+                        1 => self.stack_push_byte(io, (ret >> 8) as u8),
+                        2 => self.stack_push_byte(io, ret as u8),
+                        3 => {
+                            let ps = (self.r_ps | Flags::Reserved).difference(Flags::Break);
+                            self.stack_push_byte(io, ps.bits());
+                            self.r_ps |= Flags::IntDis;
+                        }
+                        4 => self.i_ab = io.read_u8(vector) as u16,
+                        _ => {
+                            self.i_ab |= (io.read_u8(vector.wrapping_add(1)) as u16) << 8;
+                            // The program counter points at the byte before the next opcode.
+                            self.r_pc = self.i_ab.wrapping_sub(1);
+                            break 'new_state_match State::Fetch;
+                        }
+                    }
+                    State::Interrupt {
+                        vector,
+                        step: step + 1,
+                    }
+                }
             }
         };
     }
 }
 
+impl Peripheral for VM {
+    fn recv(&mut self, _: u16, _: u8) {}
+
+    fn cold_reset(&mut self) {
+        self.r_ps = Flags::empty();
+        self.r_ac = 0;
+        self.r_ix = 0;
+        self.r_iy = 0;
+
+        self.r_sp = 0x00;
+        self.r_pc = CpuVector::Rst as u16;
+
+        self.warm_reset();
+    }
+
+    fn warm_reset(&mut self) {
+        self.r_ps |= Flags::IntDis;
+        self.r_sp = self.r_sp.wrapping_sub(0x03);
+
+        self.i_nst = State::Fetch;
+        self.i_adm = AddressingMode::Implied;
+        self.i_opr = 0;
+        self.i_dr = 0;
+        self.i_ex = 0;
+        self.i_ab = 0;
+        self.i_tm = 0;
+        self.i_cc = 0;
+    }
+
+    /// Runs a single cycle.
+    ///
+    /// RDY halts the CPU on any cycle but the write ones. While halted, the
+    /// CPU repeats the read of the cycle it's stuck on, unless a DMA unit
+    /// drives the bus.
+    fn cycle(&mut self, io: &mut impl MemoryBus, signals: &mut Signals) {
+        self.i_cc += 1;
+        if signals.halted() && !self.is_write_cycle() {
+            signals.set_stalled(true);
+            if !signals.bus_driven() {
+                let saved = self.clone();
+                self.step(io, &mut Signals::default());
+                *self = saved;
+            }
+            return;
+        }
+
+        signals.set_stalled(false);
+        self.step(io, signals);
+    }
+}
+
 impl Cpu for VM {
     fn is_cycle_accurate(&self) -> bool {
         true
@@ -693,6 +798,41 @@ impl DebugCpu for VM {
         self.r_iy = iy;
     }
 
+    /// The program counter points at the byte before the opcode while
+    /// fetching it.
+    fn next_instruction(&self) -> Option<u16> {
+        matches!(self.i_nst, State::Fetch).then(|| self.r_pc.wrapping_add(1))
+    }
+
+    /// Interrupts turn the opcode fetch and the next read into dummy reads,
+    /// and so does RDY with the reads it repeats.
+    fn dummy_read(&self, signals: &Signals) -> bool {
+        use AddressResolverState::*;
+
+        if signals.halted() {
+            return !self.is_write_cycle();
+        }
+        match &self.i_nst {
+            State::Fetch => {
+                signals.nmi_pending() || (signals.irq() && !self.r_ps.contains(Flags::IntDis))
+            }
+            State::ResolveAddress(IndXDummyRead | IndZPDummyRead) => true,
+            State::ResolveAddress(AddIndexRegister {
+                index_register,
+                bump_page: false,
+            }) => {
+                let index = if *index_register == IndexRegister::X {
+                    self.r_ix
+                } else {
+                    self.r_iy
+                };
+                (self.i_ab as u8).checked_add(index).is_none()
+            }
+            State::Interrupt { step: 0, .. } => true,
+            _ => false,
+        }
+    }
+
     fn state(&self) -> CpuState {
         CpuState {
             pc: self.r_pc,
@@ -703,8 +843,7 @@ impl DebugCpu for VM {
             am: self.i_adm,
             ps: self.r_ps,
 
-            // TODO: Count cycles
-            cc: 0,
+            cc: self.i_cc,
         }
     }
 }
@@ -722,6 +861,7 @@ impl Default for VM {
             i_nst: State::Fetch,
             i_adm: AddressingMode::Implied,
             i_opr: 0,
+            i_dr: 0,
             i_ex: 0,
             i_ab: 0,
             i_tm: 0,
@@ -1,6 +1,11 @@
 use super::*;
 use AddressResolverState::*;
-use effnes_bus::{MemoryBus, basic::BasicMemory};
+use effnes_bus::{
+    MemoryBus,
+    basic::BasicMemory,
+    signal::Source,
+    uninit::{UninitDetector, UninitRead},
+};
 
 const NOP_IMP: u8 = 0xEA;
 const LDA_IMM: u8 = 0xA9;
@@ -114,7 +119,7 @@ macro_rules! assert_execution_eq {
 
         ($io:expr, $vm:ident, $st:ident, cycle $bl:tt) => {
             modify_state!($st $bl);
-            $vm.cycle(&mut $io);
+            $vm.cycle(&mut $io, &mut Signals::default());
         };
 
         ($io:expr, $vm:ident, $st:ident, =) => {
@@ -483,3 +488,162 @@ fn test_abx_addressing() {
 fn test_aby_addressing() {
     test_abi_addressing(LDA_ABY, IndexRegister::Y);
 }
+
+#[test]
+fn test_interrupts() {
+    let (mut io, mut vm) = get_vm();
+    let mut signals = Signals::default();
+    setup_memory!(io + vm {
+        0xFFFA => 0x00,
+        0xFFFB => 0x90,
+        0xFFFE => 0x00,
+        0xFFFF => 0xA0
+    } [r_sp => 0xFF, r_ps => Flags::IntDis]);
+
+    // RDY freezes the CPU.
+    signals.set_nmi(true);
+    signals.set_halt(Source::OamDma, true);
+    vm.cycle(&mut io, &mut signals);
+    assert_eq!(vm.i_nst, State::Fetch);
+    assert!(signals.nmi_pending());
+    signals.set_halt(Source::OamDma, false);
+
+    // NMIs are taken even with IRQs disabled.
+    for _ in 0..7 {
+        vm.cycle(&mut io, &mut signals);
+    }
+    assert_eq!(vm.i_nst, State::Fetch);
+    assert_eq!(vm.r_pc.wrapping_add(1), 0x9000);
+    assert_eq!(vm.r_sp, 0xFC);
+    assert_eq!(io.peek_u16(0x1FE), 0x8001);
+    assert_eq!(io.peek_u8(0x1FD), (Flags::IntDis | Flags::Reserved).bits());
+    assert!(!signals.nmi_pending());
+
+    // The NMI line is edge triggered, and IRQs are masked.
+    signals.set_irq(Source::Mapper, true);
+    setup_memory!(io + vm { 0x9000 => LDA_IMM, 0x9001 => 0x42 });
+    vm.cycle(&mut io, &mut signals);
+    assert_eq!(vm.i_ex, LDA_IMM);
+    vm.cycle(&mut io, &mut signals);
+
+    vm.r_ps = Flags::empty();
+    for _ in 0..7 {
+        vm.cycle(&mut io, &mut signals);
+    }
+    assert_eq!(vm.r_pc.wrapping_add(1), 0xA000);
+    assert_eq!(io.peek_u16(0x1FB), 0x9002);
+    assert_eq!(io.peek_u8(0x1FA), Flags::Reserved.bits());
+    assert!(vm.r_ps.contains(Flags::IntDis));
+}
+
+/// Memory that counts the reads of a single address.
+struct Watched {
+    memory: BasicMemory,
+    addr: u16,
+    reads: usize,
+}
+
+impl MemoryBus for Watched {
+    fn read_u8(&mut self, addr: u16) -> u8 {
+        self.reads += (addr == self.addr) as usize;
+        self.memory.read_u8(addr)
+    }
+
+    fn read_u16(&mut self, addr: u16) -> u16 {
+        self.memory.read_u16(addr)
+    }
+
+    fn write_u8(&mut self, addr: u16, data: u8) {
+        self.memory.write_u8(addr, data);
+    }
+}
+
+#[test]
+fn test_rdy_repeats_reads() {
+    let (mut memory, mut vm) = get_vm();
+    setup_memory!(memory + vm {
+        0x8001 => LDA_ABS,
+        0x8002 => 0x16,
+        0x8003 => 0x40,
+        0x4016 => 0x41
+    });
+    let mut io = Watched {
+        memory,
+        addr: 0x4016,
+        reads: 0,
+    };
+    let mut signals = Signals::default();
+    for _ in 0..3 {
+        vm.cycle(&mut io, &mut signals);
+    }
+    assert_eq!(vm.i_nst, State::Process);
+
+    // Halt and dummy cycles of a DMC fetch: the CPU keeps reading $4016.
+    signals.set_halt(Source::DmcDma, true);
+    for _ in 0..2 {
+        vm.cycle(&mut io, &mut signals);
+        assert!(signals.stalled());
+    }
+    // Fetch cycle: the DMA drives the bus.
+    signals.set_bus_driven(true);
+    vm.cycle(&mut io, &mut signals);
+    assert_eq!(
+        (vm.i_nst.clone(), vm.r_ac, io.reads),
+        (State::Process, 0, 2)
+    );
+
+    signals.set_halt(Source::DmcDma, false);
+    signals.set_bus_driven(false);
+    vm.cycle(&mut io, &mut signals);
+    assert!(!signals.stalled());
+    assert_eq!((vm.r_ac, io.reads), (0x41, 3));
+}
+
+#[test]
+fn test_dummy_reads() {
+    let (mut memory, mut vm) = get_vm();
+    setup_memory!(memory + vm {
+        0x0600 => LDA_INX,
+        0x0601 => 0x10,
+        0x0602 => LDA_ABX,
+        0x0603 => 0xFF,
+        0x0604 => 0x02,
+        0x0014 => 0x00,
+        0x0015 => 0x03,
+        0x0300 => 0x11,
+        0x0303 => 0x33
+    } [ r_pc => 0x05FF, r_ix => 4, r_ps => Flags::empty() ]);
+    let mut io = UninitDetector::new(memory);
+    io.track(0x0000..=0x07FF, 0x07FF);
+    io.initialize(0x0600..=0x0604);
+    io.initialize(0x0014..=0x0015);
+    io.initialize(0x0300..=0x0300);
+
+    // `(zp,X)` reads the unindexed pointer at $10, `abs,X` reads $0203
+    // before fixing up the page, and the IRQ reads the opcode at $0605
+    // twice: only the read of $0303 counts.
+    let mut signals = Signals::default();
+    let cycle = |vm: &mut VM, io: &mut UninitDetector<BasicMemory>, signals: &mut Signals| {
+        if let Some(pc) = vm.next_instruction() {
+            io.set_pc(pc);
+        }
+        io.set_dummy_read(vm.dummy_read(signals));
+        vm.cycle(io, signals);
+    };
+    while vm.next_instruction() != Some(0x0605) {
+        cycle(&mut vm, &mut io, &mut signals);
+    }
+    assert_eq!(vm.r_ac, 0x33);
+    signals.set_irq(Source::Mapper, true);
+    for _ in 0..7 {
+        cycle(&mut vm, &mut io, &mut signals);
+    }
+    assert!(vm.r_ps.contains(Flags::IntDis));
+    assert_eq!(
+        io.take_reports(),
+        [UninitRead {
+            pc: 0x0602,
+            addr: 0x0303
+        }]
+    );
+}
@@ -0,0 +1,252 @@
+use effnes_apu::mixer::PULSE_TABLE;
+
+/// Amplitude of the loudest output (wave sample 63 at gain 32 and full
+/// master volume): about 2.4 times an APU pulse channel at full scale.
+const FULL_SCALE: f32 = 2.4 * PULSE_TABLE[15];
+
+/// Master volume multipliers (2/2, 2/3, 2/4 and 2/5).
+const MASTER_VOLUME: [f32; 4] = [1.0, 2.0 / 3.0, 2.0 / 4.0, 2.0 / 5.0];
+
+/// Modulation counter adjustment for every mod table entry (`None` resets
+/// the counter).
+const MOD_STEPS: [Option<i8>; 8] = [
+    Some(0),
+    Some(1),
+    Some(2),
+    Some(4),
+    None,
+    Some(-4),
+    Some(-2),
+    Some(-1),
+];
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+struct FdsEnvelope {
+    speed: u8,
+    gain: u8,
+    increase: bool,
+    disabled: bool,
+    timer: u32,
+}
+
+impl FdsEnvelope {
+    fn write(&mut self, data: u8) {
+        self.disabled = data & 0x80 != 0;
+        self.increase = data & 0x40 != 0;
+        self.speed = data & 0x3F;
+        self.timer = 0;
+        if self.disabled {
+            self.gain = self.speed;
+        }
+    }
+
+    fn clock(&mut self, master: u8) {
+        if self.disabled {
+            return;
+        }
+        if self.timer > 0 {
+            self.timer -= 1;
+            return;
+        }
+        self.timer = 8 * (self.speed as u32 + 1) * master as u32;
+        if self.increase && self.gain < 32 {
+            self.gain += 1;
+        } else if !self.increase && self.gain > 0 {
+            self.gain -= 1;
+        }
+    }
+}
+
+/// FDS expansion audio: a 64-step wavetable channel, frequency modulated by
+/// a 64-step modulation table, with volume and modulation envelopes.
+///
+/// ## Behaviour
+///
+/// - Wavetable RAM ($4040-$407F) is only writable while $4089 bit 7 is set,
+///   which also holds the output.
+/// - The mod table ($4088) is only writable while the modulator is halted
+///   ($4087 bit 7); every write fills two entries.
+/// - Envelopes are clocked every `8 * (speed + 1) * $408A` CPU cycles,
+///   unless halted by $4083 bit 6 (or bit 7, which also resets the wave).
+/// - The pitch modulation follows the documented RAM adapter arithmetic,
+///   and wave/modulator accumulators are clocked on every CPU cycle.
+/// - The output is linear (wave sample times gain, capped at 32), scaled by
+///   the master volume ($4089 bits 0-1).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FdsAudio {
+    wave: [u8; 64],
+    write_enabled: bool,
+    master_volume: u8,
+    volume: FdsEnvelope,
+    frequency: u16,
+    wave_halted: bool,
+    envelopes_halted: bool,
+    accumulator: u32,
+    position: u8,
+    output: u8,
+
+    modulation: FdsEnvelope,
+    mod_table: [u8; 64],
+    mod_frequency: u16,
+    mod_halted: bool,
+    mod_accumulator: u32,
+    mod_position: u8,
+    counter: i8,
+
+    envelope_speed: u8,
+}
+
+impl Default for FdsAudio {
+    fn default() -> Self {
+        Self {
+            wave: [0; 64],
+            write_enabled: false,
+            master_volume: 0,
+            volume: FdsEnvelope::default(),
+            frequency: 0,
+            wave_halted: true,
+            envelopes_halted: false,
+            accumulator: 0,
+            position: 0,
+            output: 0,
+            modulation: FdsEnvelope::default(),
+            mod_table: [0; 64],
+            mod_frequency: 0,
+            mod_halted: true,
+            mod_accumulator: 0,
+            mod_position: 0,
+            counter: 0,
+            envelope_speed: 0xE8,
+        }
+    }
+}
+
+impl FdsAudio {
+    /// Writes `data` into a sound register ($4040-$408A).
+    pub fn write(&mut self, addr: u16, data: u8) {
+        match addr {
+            0x4040..=0x407F if self.write_enabled => {
+                self.wave[addr as usize - 0x4040] = data & 0x3F;
+            }
+            0x4080 => self.volume.write(data),
+            0x4082 => self.frequency = (self.frequency & 0xF00) | data as u16,
+            0x4083 => {
+                self.frequency = (self.frequency & 0x0FF) | (((data & 0x0F) as u16) << 8);
+                self.wave_halted = data & 0x80 != 0;
+                self.envelopes_halted = data & 0x40 != 0;
+                if self.wave_halted {
+                    self.accumulator = 0;
+                    self.position = 0;
+                }
+            }
+            0x4084 => self.modulation.write(data),
+            0x4085 => {
+                // 7-bit signed.
+                self.counter = ((data << 1) as i8) >> 1;
+            }
+            0x4086 => self.mod_frequency = (self.mod_frequency & 0xF00) | data as u16,
+            0x4087 => {
+                self.mod_frequency = (self.mod_frequency & 0x0FF) | (((data & 0x0F) as u16) << 8);
+                self.mod_halted = data & 0x80 != 0;
+                if self.mod_halted {
+                    self.mod_accumulator = 0;
+                }
+            }
+            0x4088 if self.mod_halted => {
+                let position = self.mod_position as usize;
+                self.mod_table[position] = data & 0x07;
+                self.mod_table[(position + 1) & 0x3F] = data & 0x07;
+                self.mod_position = (self.mod_position + 2) & 0x3F;
+            }
+            0x4089 => {
+                self.write_enabled = data & 0x80 != 0;
+                self.master_volume = data & 0x03;
+            }
+            0x408A => self.envelope_speed = data,
+            _ => {}
+        }
+    }
+
+    /// Reads a sound register ($4040-$4092), if readable.
+    pub fn peek(&self, addr: u16) -> Option<u8> {
+        match addr {
+            0x4040..=0x407F => Some(self.wave[addr as usize - 0x4040]),
+            0x4090 => Some(self.volume.gain),
+            0x4092 => Some(self.modulation.gain),
+            _ => None,
+        }
+    }
+
+    /// CPU cycle clock.
+    pub fn cycle(&mut self) {
+        if !self.envelopes_halted && !self.wave_halted && self.envelope_speed != 0 {
+            self.volume.clock(self.envelope_speed);
+            self.modulation.clock(self.envelope_speed);
+        }
+
+        if !self.mod_halted && self.mod_frequency != 0 {
+            self.mod_accumulator += self.mod_frequency as u32;
+            if self.mod_accumulator > 0xFFFF {
+                self.mod_accumulator &= 0xFFFF;
+                self.step_modulator();
+            }
+        }
+
+        if !self.wave_halted {
+            self.accumulator += self.pitch();
+            if self.accumulator > 0xFFFF {
+                self.accumulator &= 0xFFFF;
+                self.position = (self.position + 1) & 0x3F;
+            }
+        }
+
+        if !self.write_enabled {
+            self.output = self.wave[self.position as usize];
+        }
+    }
+
+    fn step_modulator(&mut self) {
+        match MOD_STEPS[self.mod_table[self.mod_position as usize] as usize] {
+            Some(step) => {
+                // Wraps around the 7-bit signed range.
+                self.counter = (self.counter.wrapping_add(step) << 1) >> 1;
+            }
+            None => self.counter = 0,
+        }
+        self.mod_position = (self.mod_position + 1) & 0x3F;
+    }
+
+    /// Wave frequency, after pitch modulation.
+    fn pitch(&self) -> u32 {
+        let pitch = self.frequency as i32;
+        if self.mod_halted {
+            return pitch as u32;
+        }
+
+        let mut temp = self.counter as i32 * self.modulation.gain as i32;
+        let remainder = temp & 0x0F;
+        temp >>= 4;
+        if remainder > 0 && temp & 0x80 == 0 {
+            temp += if self.counter < 0 { -1 } else { 2 };
+        }
+        if temp >= 192 {
+            temp -= 256;
+        } else if temp < -64 {
+            temp += 256;
+        }
+
+        let mut temp = pitch * temp;
+        let remainder = temp & 0x3F;
+        temp >>= 6;
+        if remainder >= 32 {
+            temp += 1;
+        }
+        (pitch + temp).max(0) as u32
+    }
+
+    /// Output amplitude, in the APU mixer range.
+    pub fn output(&self) -> f32 {
+        let level = self.output as f32 * self.volume.gain.min(32) as f32;
+        level / (63.0 * 32.0) * MASTER_VOLUME[self.master_volume as usize] * FULL_SCALE
+    }
+}
@@ -0,0 +1,16 @@
+//! Expansion sound chips.
+//!
+//! Every chip is clocked by its board on [crate::Mapper::cpu_cycle], and
+//! mixed with the APU through [crate::Mapper::audio].
+
+pub mod fds;
+pub mod namco163;
+pub mod opll;
+pub mod sunsoft5b;
+pub mod vrc6;
+
+pub use fds::FdsAudio;
+pub use namco163::Namco163Audio;
+pub use opll::Opll;
+pub use sunsoft5b::Sunsoft5bAudio;
+pub use vrc6::Vrc6Audio;
@@ -0,0 +1,144 @@
+use crate::bank::RamData;
+use effnes_apu::mixer::PULSE_TABLE;
+
+/// Size of the N163 internal RAM (wavetables and channel registers).
+pub const SOUND_RAM_LEN: usize = 0x80;
+
+/// CPU cycles spent on every channel update.
+const CYCLES_PER_CHANNEL: u8 = 15;
+
+/// Amplitude of a single output step (a sample is `(nibble - 8) * volume`),
+/// so that a full scale channel matches the APU pulse volume at full
+/// scale.
+const STEP: f32 = PULSE_TABLE[15] / 120.0;
+
+/// Namco 163 expansion audio: up to 8 wavetable channels, sharing 128 bytes
+/// of internal RAM with the wavetables themselves.
+///
+/// ## Behaviour
+///
+/// - The RAM is accessed through an address port ($F800, bit 7 enables
+///   auto increment) and a data port ($4800).
+/// - Channel registers live at $40-$7F, 8 bytes per channel (channel 7
+///   first); $7F bits 4-6 hold the number of enabled channels minus one.
+/// - A single channel is updated every 15 CPU cycles, round robin from
+///   channel 7 downwards, and the output is time multiplexed: it only
+///   carries the last updated channel, as on the real chip. More enabled
+///   channels mean a lower update rate and an audible multiplexing tone.
+/// - The RAM can be battery-backed; it's then borrowed from the board
+///   PRG-RAM (see [crate::mappers::Namco163]).
+pub struct Namco163Audio<'a> {
+    ram: RamData<'a>,
+    address: u8,
+    auto_increment: bool,
+    divider: u8,
+    channel: u8,
+    output: i8,
+}
+
+impl<'a> Namco163Audio<'a> {
+    /// ## Panics
+    ///
+    /// If `ram` isn't [SOUND_RAM_LEN] bytes long.
+    pub fn new(ram: RamData<'a>) -> Self {
+        assert_eq!(ram.len(), SOUND_RAM_LEN, "invalid N163 RAM size");
+        Self {
+            ram,
+            address: 0,
+            auto_increment: false,
+            divider: 0,
+            channel: 7,
+            output: 0,
+        }
+    }
+
+    pub fn ram(&self) -> &RamData<'a> {
+        &self.ram
+    }
+
+    pub fn ram_mut(&mut self) -> &mut RamData<'a> {
+        &mut self.ram
+    }
+
+    /// Writes the address port ($F800).
+    pub fn write_address(&mut self, data: u8) {
+        self.address = data & 0x7F;
+        self.auto_increment = data & 0x80 != 0;
+    }
+
+    /// Reads the data port ($4800), without side effects.
+    pub fn peek_data(&self) -> u8 {
+        self.ram[self.address as usize]
+    }
+
+    /// Reads the data port ($4800).
+    pub fn read_data(&mut self) -> u8 {
+        let data = self.peek_data();
+        self.increment();
+        data
+    }
+
+    /// Writes the data port ($4800).
+    pub fn write_data(&mut self, data: u8) {
+        self.ram[self.address as usize] = data;
+        self.increment();
+    }
+
+    fn increment(&mut self) {
+        if self.auto_increment {
+            self.address = (self.address + 1) & 0x7F;
+        }
+    }
+
+    /// Number of enabled channels (1..=8).
+    pub fn channels(&self) -> u8 {
+        ((self.ram[0x7F] >> 4) & 0x07) + 1
+    }
+
+    /// Silences the output and restarts the channel round robin.
+    pub fn reset(&mut self) {
+        self.divider = 0;
+        self.channel = 7;
+        self.output = 0;
+    }
+
+    /// CPU cycle clock.
+    pub fn cycle(&mut self) {
+        self.divider += 1;
+        if self.divider < CYCLES_PER_CHANNEL {
+            return;
+        }
+        self.divider = 0;
+
+        self.output = self.update(self.channel);
+        self.channel = if self.channel <= 8 - self.channels() {
+            7
+        } else {
+            self.channel - 1
+        };
+    }
+
+    /// Advances the phase of `channel`, and returns its sample.
+    fn update(&mut self, channel: u8) -> i8 {
+        let base = 0x40 + channel as usize * 8;
+        let regs = &mut self.ram[base..base + 8];
+        let frequency = regs[0] as u32 | (regs[2] as u32) << 8 | ((regs[4] & 0x03) as u32) << 16;
+        let phase = regs[1] as u32 | (regs[3] as u32) << 8 | (regs[5] as u32) << 16;
+        let length = (256 - (regs[4] & 0xFC) as u32) << 16;
+
+        let phase = (phase + frequency) % length;
+        regs[1] = phase as u8;
+        regs[3] = (phase >> 8) as u8;
+        regs[5] = (phase >> 16) as u8;
+
+        let offset = (regs[6] as u32 + (phase >> 16)) & 0xFF;
+        let volume = (regs[7] & 0x0F) as i8;
+        let nibble = (self.ram[offset as usize >> 1] >> ((offset & 1) * 4)) & 0x0F;
+        (nibble as i8 - 8) * volume
+    }
+
+    /// Output amplitude, in the APU mixer range.
+    pub fn output(&self) -> f32 {
+        self.output as f32 * STEP
+    }
+}
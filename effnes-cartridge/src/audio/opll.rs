@@ -0,0 +1,391 @@
+use std::f32::consts::TAU;
+
+/// CPU cycles per OPLL sample (3.58 MHz / 72, from the 1.79 MHz M2 clock).
+pub const CYCLES_PER_SAMPLE: u8 = 36;
+
+/// VRC7 built-in instruments (1..=15), as dumped from the chip.
+pub static VRC7_PATCHES: [[u8; 8]; 15] = [
+    [0x03, 0x21, 0x05, 0x06, 0xE8, 0x81, 0x42, 0x27],
+    [0x13, 0x41, 0x14, 0x0D, 0xD8, 0xF6, 0x23, 0x12],
+    [0x11, 0x11, 0x08, 0x08, 0xFA, 0xB2, 0x20, 0x12],
+    [0x31, 0x61, 0x0C, 0x07, 0xA8, 0x64, 0x61, 0x27],
+    [0x32, 0x21, 0x1E, 0x06, 0xE1, 0x76, 0x01, 0x28],
+    [0x02, 0x01, 0x06, 0x00, 0xA3, 0xE2, 0xF4, 0xF4],
+    [0x21, 0x61, 0x1D, 0x07, 0x82, 0x81, 0x11, 0x07],
+    [0x23, 0x21, 0x22, 0x17, 0xA2, 0x72, 0x01, 0x17],
+    [0x35, 0x11, 0x25, 0x00, 0x40, 0x73, 0x72, 0x01],
+    [0xB5, 0x01, 0x0F, 0x0F, 0xA8, 0xA5, 0x51, 0x02],
+    [0x17, 0xC1, 0x24, 0x07, 0xF8, 0xF8, 0x22, 0x12],
+    [0x71, 0x23, 0x11, 0x06, 0x65, 0x74, 0x18, 0x16],
+    [0x01, 0x02, 0xD3, 0x05, 0xC9, 0x95, 0x03, 0x02],
+    [0x61, 0x63, 0x0C, 0x00, 0x94, 0xC0, 0x33, 0xF6],
+    [0x21, 0x72, 0x0D, 0x00, 0xC1, 0xD5, 0x56, 0x06],
+];
+
+/// Frequency multipliers, doubled (the first one is 1/2).
+const MULTIPLIERS: [u32; 16] = [1, 2, 4, 6, 8, 10, 12, 14, 16, 18, 20, 20, 24, 24, 30, 30];
+
+/// Key scale level attenuation on block 7, in dB, by the upper 4 bits of
+/// the F-Number.
+const KSL_DB: [f32; 16] = [
+    0.0, 9.0, 12.0, 13.875, 15.0, 16.125, 16.875, 17.625, 18.0, 18.75, 19.125, 19.5, 19.875, 20.25,
+    20.625, 21.0,
+];
+
+/// Envelope attenuation step, in dB.
+const ENVELOPE_STEP_DB: f32 = 0.375;
+
+/// Envelope attenuation past which an operator is silent.
+const ENVELOPE_MAX: u8 = 127;
+
+/// Phase modulation of a full scale modulator output, in sine table
+/// entries (1024 per period).
+const MODULATION_DEPTH: f32 = 4096.0;
+
+/// Tremolo depth (dB) and rate (Hz).
+const AM_DEPTH_DB: f32 = 4.8;
+const AM_RATE: f32 = 3.7;
+
+/// Vibrato depth (frequency ratio) and rate (Hz).
+const PM_DEPTH: f32 = 0.0046;
+const PM_RATE: f32 = 6.4;
+
+/// OPLL sample rate.
+const SAMPLE_RATE: f32 = 1_789_773.0 / CYCLES_PER_SAMPLE as f32;
+
+/// Output amplitude of a full scale channel, in the APU mixer range.
+const CHANNEL_SCALE: f32 = 0.04;
+
+/// Operator parameters, decoded from an instrument.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+struct OperatorPatch {
+    am: bool,
+    vibrato: bool,
+    sustained: bool,
+    ksr: bool,
+    multiplier: u32,
+    ksl: u8,
+    half_wave: bool,
+    attack: u8,
+    decay: u8,
+    sustain: u8,
+    release: u8,
+}
+
+/// Instrument, decoded from its 8 register bytes.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+struct Patch {
+    operators: [OperatorPatch; 2],
+    /// Modulator total level (0.75 dB steps).
+    total_level: u8,
+    feedback: u8,
+}
+
+impl Patch {
+    fn decode(bytes: &[u8; 8]) -> Self {
+        let operator = |i: usize| OperatorPatch {
+            am: bytes[i] & 0x80 != 0,
+            vibrato: bytes[i] & 0x40 != 0,
+            sustained: bytes[i] & 0x20 != 0,
+            ksr: bytes[i] & 0x10 != 0,
+            multiplier: MULTIPLIERS[(bytes[i] & 0x0F) as usize],
+            ksl: bytes[2 + i] >> 6,
+            half_wave: bytes[3] & (0x08 << i) != 0,
+            attack: bytes[4 + i] >> 4,
+            decay: bytes[4 + i] & 0x0F,
+            sustain: bytes[6 + i] >> 4,
+            release: bytes[6 + i] & 0x0F,
+        };
+        Self {
+            operators: [operator(0), operator(1)],
+            total_level: bytes[2] & 0x3F,
+            feedback: bytes[3] & 0x07,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+enum EnvelopeState {
+    Attack,
+    Decay,
+    Sustain,
+    Release,
+    #[default]
+    Off,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct Operator {
+    /// 18-bit phase; the upper 10 bits index the sine table.
+    phase: u32,
+    state: EnvelopeState,
+    envelope: u8,
+    output: f32,
+}
+
+impl Default for Operator {
+    fn default() -> Self {
+        Self {
+            phase: 0,
+            state: EnvelopeState::Off,
+            envelope: ENVELOPE_MAX,
+            output: 0.0,
+        }
+    }
+}
+
+impl Operator {
+    fn key_on(&mut self) {
+        self.phase = 0;
+        self.state = EnvelopeState::Attack;
+    }
+
+    fn key_off(&mut self) {
+        if self.state != EnvelopeState::Off {
+            self.state = EnvelopeState::Release;
+        }
+    }
+
+    /// Advances the envelope generator, for an effective `rate` (0..=63).
+    fn step_envelope(&mut self, rate: u8, counter: u32) {
+        if rate == 0 {
+            return;
+        }
+        let shift = 13u32.saturating_sub((rate >> 2) as u32);
+        if counter & ((1 << shift) - 1) != 0 {
+            return;
+        }
+        let increment = 1u8 << ((rate >> 2) as u32).saturating_sub(12);
+
+        match self.state {
+            EnvelopeState::Attack if rate >= 60 => self.envelope = 0,
+            EnvelopeState::Attack => {
+                let decrease = ((self.envelope as u16 * increment as u16) >> 3) as u8 + 1;
+                self.envelope = self.envelope.saturating_sub(decrease);
+            }
+            EnvelopeState::Off => {}
+            _ => self.envelope = (self.envelope + increment).min(ENVELOPE_MAX),
+        }
+    }
+
+    /// Computes the operator output for a phase offset (in sine table
+    /// entries) and a static attenuation (in dB).
+    fn compute(&mut self, offset: f32, attenuation: f32, half_wave: bool) -> f32 {
+        let db = self.envelope as f32 * ENVELOPE_STEP_DB + attenuation;
+        if self.state == EnvelopeState::Off || self.envelope >= ENVELOPE_MAX || db >= 96.0 {
+            self.output = 0.0;
+            return 0.0;
+        }
+
+        let index = (self.phase >> 8) as f32 + offset;
+        let wave = (index * TAU / 1024.0).sin();
+        let wave = if half_wave && wave < 0.0 { 0.0 } else { wave };
+        self.output = wave * 10f32.powf(-db / 20.0);
+        self.output
+    }
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+struct Channel {
+    fnum: u16,
+    block: u8,
+    key: bool,
+    sustain: bool,
+    instrument: u8,
+    volume: u8,
+    operators: [Operator; 2],
+    feedback: [f32; 2],
+}
+
+impl Channel {
+    /// Key scale rate offset.
+    fn rks(&self, ksr: bool) -> u8 {
+        let rks = (self.block << 1) | (self.fnum >> 8) as u8;
+        if ksr { rks } else { rks >> 2 }
+    }
+
+    /// Key scale level attenuation, in dB.
+    fn ksl(&self, ksl: u8) -> f32 {
+        if ksl == 0 {
+            return 0.0;
+        }
+        let db = (KSL_DB[(self.fnum >> 5) as usize] - 3.0 * (7 - self.block) as f32).max(0.0);
+        db * [0.0, 0.5, 1.0, 2.0][ksl as usize]
+    }
+}
+
+/// OPLL
+///
+/// FM synthesizer of the VRC7 (a YM2413 derivative): six two-operator
+/// channels, 15 built-in instruments plus a custom one, and no rhythm mode.
+///
+/// ## Behaviour
+///
+/// - [Opll::cycle] must be called on every CPU cycle; a new sample is
+///   computed every [CYCLES_PER_SAMPLE] cycles, and held in between.
+/// - Operators are modelled in floating point (sine waves attenuated in
+///   dB), with envelope rates, key scaling, feedback, tremolo and vibrato
+///   following the YM2413 register semantics; it's not bit exact.
+pub struct Opll {
+    address: u8,
+    custom: [u8; 8],
+    channels: [Channel; 6],
+    counter: u8,
+    envelope_counter: u32,
+    lfo_time: f32,
+    output: f32,
+}
+
+impl Default for Opll {
+    fn default() -> Self {
+        Self {
+            address: 0,
+            custom: [0; 8],
+            channels: [Channel::default(); 6],
+            counter: 0,
+            envelope_counter: 0,
+            lfo_time: 0.0,
+            output: 0.0,
+        }
+    }
+}
+
+impl Opll {
+    /// Selects the register written by [Opll::write_data].
+    pub fn write_address(&mut self, data: u8) {
+        self.address = data;
+    }
+
+    /// Writes `data` into the selected register.
+    pub fn write_data(&mut self, data: u8) {
+        let reg = self.address;
+        let channel = (reg & 0x0F) as usize;
+        match reg {
+            0x00..=0x07 => self.custom[reg as usize] = data,
+            0x10..=0x15 => {
+                let channel = &mut self.channels[channel];
+                channel.fnum = (channel.fnum & 0x100) | data as u16;
+            }
+            0x20..=0x25 => {
+                let channel = &mut self.channels[channel];
+                channel.fnum = (channel.fnum & 0x0FF) | (((data & 0x01) as u16) << 8);
+                channel.block = (data >> 1) & 0x07;
+                channel.sustain = data & 0x20 != 0;
+
+                let key = data & 0x10 != 0;
+                if key && !channel.key {
+                    channel.operators.iter_mut().for_each(Operator::key_on);
+                } else if !key && channel.key {
+                    channel.operators.iter_mut().for_each(Operator::key_off);
+                }
+                channel.key = key;
+            }
+            0x30..=0x35 => {
+                let channel = &mut self.channels[channel];
+                channel.instrument = data >> 4;
+                channel.volume = data & 0x0F;
+            }
+            _ => {}
+        }
+    }
+
+    /// Silences every channel.
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    /// CPU cycle clock.
+    pub fn cycle(&mut self) {
+        self.counter += 1;
+        if self.counter == CYCLES_PER_SAMPLE {
+            self.counter = 0;
+            self.output = self.sample();
+        }
+    }
+
+    /// Output amplitude, in the APU mixer range.
+    pub fn output(&self) -> f32 {
+        self.output
+    }
+
+    fn patch(&self, instrument: u8) -> Patch {
+        match instrument {
+            0 => Patch::decode(&self.custom),
+            n => Patch::decode(&VRC7_PATCHES[n as usize - 1]),
+        }
+    }
+
+    fn sample(&mut self) -> f32 {
+        self.envelope_counter = self.envelope_counter.wrapping_add(1);
+        self.lfo_time += 1.0 / SAMPLE_RATE;
+        let am = AM_DEPTH_DB * (1.0 + (TAU * AM_RATE * self.lfo_time).sin()) / 2.0;
+        let pm = 1.0 + PM_DEPTH * (TAU * PM_RATE * self.lfo_time).sin();
+
+        let mut sum = 0.0;
+        for i in 0..self.channels.len() {
+            let patch = self.patch(self.channels[i].instrument);
+            let counter = self.envelope_counter;
+            let channel = &mut self.channels[i];
+
+            let rks = patch.operators.map(|params| channel.rks(params.ksr));
+            let (fnum, block, sustain) = (channel.fnum as u32, channel.block, channel.sustain);
+
+            for ((op, params), rks) in channel.operators.iter_mut().zip(&patch.operators).zip(rks) {
+                let rate = |r: u8| if r == 0 { 0 } else { (4 * r + rks).min(63) };
+                let sustain_level = params.sustain * 8;
+                let rate = match op.state {
+                    EnvelopeState::Attack => {
+                        if op.envelope == 0 {
+                            op.state = EnvelopeState::Decay;
+                        }
+                        rate(params.attack)
+                    }
+                    EnvelopeState::Decay => {
+                        if op.envelope >= sustain_level {
+                            op.state = EnvelopeState::Sustain;
+                        }
+                        rate(params.decay)
+                    }
+                    EnvelopeState::Sustain if params.sustained => 0,
+                    EnvelopeState::Sustain => rate(params.release),
+                    EnvelopeState::Release if sustain => rate(5),
+                    EnvelopeState::Release if params.sustained => rate(params.release),
+                    EnvelopeState::Release => rate(7),
+                    EnvelopeState::Off => 0,
+                };
+                op.step_envelope(rate, counter);
+                if op.state == EnvelopeState::Release && op.envelope >= ENVELOPE_MAX {
+                    op.state = EnvelopeState::Off;
+                }
+
+                let increment = ((fnum * params.multiplier) << block) >> 2;
+                let increment = if params.vibrato {
+                    (increment as f32 * pm) as u32
+                } else {
+                    increment
+                };
+                op.phase = (op.phase + increment) & 0x3FFFF;
+            }
+
+            let [modulator, carrier] = &patch.operators;
+            let feedback = if patch.feedback == 0 {
+                0.0
+            } else {
+                (channel.feedback[0] + channel.feedback[1]) * (1 << (patch.feedback + 3)) as f32
+            };
+            let attenuation = patch.total_level as f32 * 0.75
+                + channel.ksl(modulator.ksl)
+                + if modulator.am { am } else { 0.0 };
+            let m = channel.operators[0].compute(feedback, attenuation, modulator.half_wave);
+            channel.feedback = [channel.feedback[1], m];
+
+            let attenuation = channel.volume as f32 * 3.0
+                + channel.ksl(carrier.ksl)
+                + if carrier.am { am } else { 0.0 };
+            sum +=
+                channel.operators[1].compute(m * MODULATION_DEPTH, attenuation, carrier.half_wave);
+        }
+
+        sum * CHANNEL_SCALE
+    }
+}
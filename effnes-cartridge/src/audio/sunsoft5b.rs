@@ -0,0 +1,223 @@
+use effnes_apu::mixer::PULSE_TABLE;
+
+/// CPU cycles per tone/envelope clock.
+const DIVIDER: u8 = 16;
+
+/// Amplitude of a channel at full volume, matching the APU pulse volume at
+/// full scale.
+const CHANNEL_SCALE: f32 = PULSE_TABLE[15];
+
+/// Channel amplitude for each volume step (3 dB apart, 0 is silent).
+fn amplitude(volume: u8) -> f32 {
+    match volume {
+        0 => 0.0,
+        v => 10f32.powf((v as f32 - 15.0) * 3.0 / 20.0),
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+struct Tone {
+    period: u16,
+    counter: u16,
+    high: bool,
+}
+
+impl Tone {
+    fn clock(&mut self) {
+        self.counter += 1;
+        if self.counter >= self.period.max(1) {
+            self.counter = 0;
+            self.high = !self.high;
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Envelope {
+    period: u16,
+    counter: u16,
+    shape: u8,
+    step: u8,
+    attack: bool,
+    holding: bool,
+}
+
+impl Default for Envelope {
+    fn default() -> Self {
+        Self {
+            period: 0,
+            counter: 0,
+            shape: 0,
+            step: 0,
+            attack: false,
+            holding: true,
+        }
+    }
+}
+
+impl Envelope {
+    /// Writes the shape register (`CONT ATT ALT HOLD`), restarting the
+    /// envelope.
+    fn write_shape(&mut self, data: u8) {
+        self.shape = data & 0x0F;
+        self.step = 0;
+        self.counter = 0;
+        self.attack = data & 0x04 != 0;
+        self.holding = false;
+    }
+
+    fn clock(&mut self) {
+        if self.holding {
+            return;
+        }
+        self.counter += 1;
+        if self.counter < self.period.max(1) {
+            return;
+        }
+        self.counter = 0;
+
+        if self.step < 15 {
+            self.step += 1;
+            return;
+        }
+
+        let (cont, alt, hold) = (
+            self.shape & 0x08 != 0,
+            self.shape & 0x02 != 0,
+            self.shape & 0x01 != 0,
+        );
+        if !cont {
+            // One-shot shapes hold at 0.
+            self.holding = true;
+            self.attack = false;
+            self.step = 15;
+        } else if hold {
+            self.holding = true;
+            self.attack ^= alt;
+        } else {
+            self.step = 0;
+            self.attack ^= alt;
+        }
+    }
+
+    fn volume(&self) -> u8 {
+        if self.attack {
+            self.step
+        } else {
+            15 - self.step
+        }
+    }
+}
+
+/// Sunsoft 5B expansion audio: the YM2149F (AY-3-8910 compatible) core
+/// of the FME-7, with three square wave channels, a noise generator and an
+/// envelope generator.
+///
+/// ## Behaviour
+///
+/// - Registers are written through an address latch ($C000) and a data
+///   port ($E000), and follow the AY-3-8910 layout: tone periods (R0-R5),
+///   noise period (R6), mixer (R7), volumes (R8-RA), envelope period and
+///   shape (RB-RD).
+/// - Tone and envelope counters are clocked every 16 CPU cycles, the noise
+///   LFSR every 32.
+/// - Volumes are logarithmic, 3 dB apart.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Sunsoft5bAudio {
+    address: u8,
+    tones: [Tone; 3],
+    noise_period: u8,
+    noise_counter: u8,
+    lfsr: u32,
+    mixer: u8,
+    volumes: [u8; 3],
+    envelope: Envelope,
+    divider: u8,
+    odd: bool,
+}
+
+impl Default for Sunsoft5bAudio {
+    fn default() -> Self {
+        Self {
+            address: 0,
+            tones: Default::default(),
+            noise_period: 0,
+            noise_counter: 0,
+            lfsr: 1,
+            mixer: 0xFF,
+            volumes: [0; 3],
+            envelope: Envelope::default(),
+            divider: 0,
+            odd: false,
+        }
+    }
+}
+
+impl Sunsoft5bAudio {
+    /// Selects the register written by [Sunsoft5bAudio::write_data]
+    /// ($C000).
+    pub fn write_address(&mut self, data: u8) {
+        self.address = data;
+    }
+
+    /// Writes `data` into the selected register ($E000).
+    pub fn write_data(&mut self, data: u8) {
+        match self.address {
+            reg @ 0x00..=0x05 => {
+                let tone = &mut self.tones[reg as usize >> 1];
+                tone.period = if reg & 1 == 0 {
+                    (tone.period & 0xF00) | data as u16
+                } else {
+                    (tone.period & 0x0FF) | (((data & 0x0F) as u16) << 8)
+                };
+            }
+            0x06 => self.noise_period = data & 0x1F,
+            0x07 => self.mixer = data,
+            reg @ 0x08..=0x0A => self.volumes[reg as usize - 8] = data & 0x1F,
+            0x0B => self.envelope.period = (self.envelope.period & 0xFF00) | data as u16,
+            0x0C => self.envelope.period = (self.envelope.period & 0x00FF) | ((data as u16) << 8),
+            0x0D => self.envelope.write_shape(data),
+            _ => {}
+        }
+    }
+
+    /// CPU cycle clock.
+    pub fn cycle(&mut self) {
+        self.divider += 1;
+        if self.divider < DIVIDER {
+            return;
+        }
+        self.divider = 0;
+
+        self.tones.iter_mut().for_each(Tone::clock);
+        self.envelope.clock();
+
+        self.odd = !self.odd;
+        if self.odd {
+            self.noise_counter += 1;
+            if self.noise_counter >= self.noise_period.max(1) {
+                self.noise_counter = 0;
+                let feedback = (self.lfsr ^ (self.lfsr >> 3)) & 1;
+                self.lfsr = (self.lfsr >> 1) | (feedback << 16);
+            }
+        }
+    }
+
+    /// Output amplitude, in the APU mixer range.
+    pub fn output(&self) -> f32 {
+        let noise = self.lfsr & 1 != 0;
+        let mut sum = 0.0;
+        for (i, tone) in self.tones.iter().enumerate() {
+            let tone_off = self.mixer & (0x01 << i) != 0;
+            let noise_off = self.mixer & (0x08 << i) != 0;
+            if (tone.high || tone_off) && (noise || noise_off) {
+                let volume = match self.volumes[i] {
+                    v if v & 0x10 != 0 => self.envelope.volume(),
+                    v => v & 0x0F,
+                };
+                sum += amplitude(volume);
+            }
+        }
+        sum * CHANNEL_SCALE
+    }
+}
@@ -0,0 +1,166 @@
+use effnes_apu::mixer::PULSE_TABLE;
+
+/// Amplitude of a single VRC6 output step, matching the APU pulse volume
+/// at full scale.
+const STEP: f32 = PULSE_TABLE[15] / 15.0;
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+struct Vrc6Pulse {
+    volume: u8,
+    duty: u8,
+    ignore_duty: bool,
+    period: u16,
+    enabled: bool,
+    timer: u16,
+    step: u8,
+}
+
+impl Vrc6Pulse {
+    fn write(&mut self, reg: u8, data: u8) {
+        match reg {
+            0 => {
+                self.ignore_duty = data & 0x80 != 0;
+                self.duty = (data >> 4) & 0x07;
+                self.volume = data & 0x0F;
+            }
+            1 => self.period = (self.period & 0xF00) | data as u16,
+            _ => {
+                self.period = (self.period & 0x0FF) | (((data & 0x0F) as u16) << 8);
+                self.enabled = data & 0x80 != 0;
+                if !self.enabled {
+                    self.step = 0;
+                }
+            }
+        }
+    }
+
+    fn clock(&mut self, shift: u8) {
+        if !self.enabled {
+            return;
+        }
+        if self.timer == 0 {
+            self.timer = self.period >> shift;
+            self.step = (self.step + 1) & 0x0F;
+        } else {
+            self.timer -= 1;
+        }
+    }
+
+    fn output(&self) -> u8 {
+        if self.enabled && (self.ignore_duty || self.step <= self.duty) {
+            self.volume
+        } else {
+            0
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+struct Vrc6Sawtooth {
+    rate: u8,
+    period: u16,
+    enabled: bool,
+    timer: u16,
+    step: u8,
+    accumulator: u8,
+}
+
+impl Vrc6Sawtooth {
+    fn write(&mut self, reg: u8, data: u8) {
+        match reg {
+            0 => self.rate = data & 0x3F,
+            1 => self.period = (self.period & 0xF00) | data as u16,
+            _ => {
+                self.period = (self.period & 0x0FF) | (((data & 0x0F) as u16) << 8);
+                self.enabled = data & 0x80 != 0;
+                if !self.enabled {
+                    self.step = 0;
+                    self.accumulator = 0;
+                }
+            }
+        }
+    }
+
+    fn clock(&mut self, shift: u8) {
+        if !self.enabled {
+            return;
+        }
+        if self.timer > 0 {
+            self.timer -= 1;
+            return;
+        }
+
+        self.timer = self.period >> shift;
+        self.step += 1;
+        if self.step == 14 {
+            self.step = 0;
+            self.accumulator = 0;
+        } else if self.step & 1 == 0 {
+            self.accumulator = self.accumulator.wrapping_add(self.rate);
+        }
+    }
+
+    fn output(&self) -> u8 {
+        self.accumulator >> 3
+    }
+}
+
+/// VRC6 expansion audio: two pulse channels with 8 duty cycles and a
+/// sawtooth channel.
+///
+/// ## Behaviour
+///
+/// - Registers are `(channel, reg)` pairs: channel 0 and 1 are the pulses
+///   ($9000-$9002, $A000-$A002), channel 2 is the sawtooth ($B000-$B002).
+/// - The frequency control register ($9003) halts every channel (bit 0), or
+///   shifts the periods right by 4 (bit 1) or 8 (bit 2) bits.
+/// - Timers are clocked on every CPU cycle.
+/// - The output is linear (0..=61 steps), scaled to the APU pulse volume.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Vrc6Audio {
+    pulses: [Vrc6Pulse; 2],
+    sawtooth: Vrc6Sawtooth,
+    halt: bool,
+    shift: u8,
+}
+
+impl Vrc6Audio {
+    /// Writes `data` into register `reg` (0..=2) of `channel` (0..=2).
+    pub fn write(&mut self, channel: u8, reg: u8, data: u8) {
+        match channel {
+            0 | 1 => self.pulses[channel as usize].write(reg, data),
+            _ => self.sawtooth.write(reg, data),
+        }
+    }
+
+    /// Writes the frequency control register ($9003).
+    pub fn write_control(&mut self, data: u8) {
+        self.halt = data & 0x01 != 0;
+        self.shift = match data & 0x06 {
+            0 => 0,
+            0x02 => 4,
+            _ => 8,
+        };
+    }
+
+    /// CPU cycle clock.
+    pub fn cycle(&mut self) {
+        if self.halt {
+            return;
+        }
+        for pulse in &mut self.pulses {
+            pulse.clock(self.shift);
+        }
+        self.sawtooth.clock(self.shift);
+    }
+
+    /// Output level (0..=61).
+    pub fn level(&self) -> u8 {
+        self.pulses[0].output() + self.pulses[1].output() + self.sawtooth.output()
+    }
+
+    /// Output amplitude, in the APU mixer range.
+    pub fn output(&self) -> f32 {
+        self.level() as f32 * STEP
+    }
+}
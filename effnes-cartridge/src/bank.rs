@@ -0,0 +1,174 @@
+use effnes_bus::power::RamInit;
+use std::{
+    borrow::Cow,
+    ops::{Deref, DerefMut},
+};
+
+/// Bank Window
+///
+/// Maps a CPU/PPU address window into offsets of a ROM/RAM chip, split into
+/// `N` equally sized slots. Bank switching only updates the slot offsets, so
+/// the chip contents can stay borrowed (e.g. from a memory-mapped file or
+/// `'static` data) and are never copied around.
+///
+/// ```ignore
+/// // 32 KiB window at $8000, 16 KiB slots, 128 KiB PRG-ROM.
+/// let mut prg = Banks::<2>::new(0x8000, 0x8000, rom.len());
+/// prg.set(0, 3);
+/// prg.set(1, prg.last());
+/// let data = rom[prg.offset(0x8000)];
+/// ```
+///
+/// ## Behaviour
+///
+/// - Bank numbers wrap around the chip size, as the upper bank lines are
+///   left unconnected on smaller chips.
+/// - Windows over empty chips always translate into offset 0; reading the
+///   chip is up to the caller.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Banks<const N: usize> {
+    base: u16,
+    slot_size: usize,
+    chip_len: usize,
+    offsets: [usize; N],
+}
+
+impl<const N: usize> Banks<N> {
+    /// Creates a window of `window` bytes at `base`, over a chip of
+    /// `chip_len` bytes. Every slot starts mapped into bank 0.
+    ///
+    /// ## Panics
+    ///
+    /// If `window` isn't a power of two multiple of `N`.
+    pub fn new(base: u16, window: usize, chip_len: usize) -> Self {
+        let slot_size = window / N;
+        assert!(
+            slot_size.is_power_of_two() && slot_size * N == window,
+            "invalid bank window"
+        );
+        Self {
+            base,
+            slot_size,
+            chip_len,
+            offsets: [0; N],
+        }
+    }
+
+    /// Size of each slot, in bytes.
+    pub fn slot_size(&self) -> usize {
+        self.slot_size
+    }
+
+    /// Number of slot sized banks on the chip.
+    pub fn count(&self) -> usize {
+        (self.chip_len / self.slot_size).max(1)
+    }
+
+    /// Last slot sized bank on the chip.
+    pub fn last(&self) -> usize {
+        self.count() - 1
+    }
+
+    /// Maps `slot` into `bank` (in slot sized units).
+    pub fn set(&mut self, slot: usize, bank: usize) {
+        self.offsets[slot] = (bank % self.count()) * self.slot_size;
+    }
+
+    /// Maps `count` consecutive slots, starting from `slot`, into the bank
+    /// `bank` of `count` slots size (e.g. a 32 KiB bank on a window with
+    /// 16 KiB slots).
+    pub fn set_wide(&mut self, slot: usize, count: usize, bank: usize) {
+        for i in 0..count {
+            self.set(slot + i, bank * count + i);
+        }
+    }
+
+    /// Bank mapped into `slot` (in slot sized units).
+    pub fn bank(&self, slot: usize) -> usize {
+        self.offsets[slot] / self.slot_size
+    }
+
+    /// Translates `addr` into an offset inside the chip.
+    pub fn offset(&self, addr: u16) -> usize {
+        let rel = addr.wrapping_sub(self.base) as usize;
+        let slot = (rel / self.slot_size) % N;
+        self.offsets[slot] + (rel & (self.slot_size - 1))
+    }
+
+    /// Translates `addr` into an offset inside `chip`, or `None` if the
+    /// chip is empty.
+    pub fn offset_in(&self, chip: &[u8], addr: u16) -> Option<usize> {
+        let offset = self.offset(addr);
+        (offset < chip.len()).then_some(offset)
+    }
+
+    /// Reads the byte at `addr` from `chip`, or `None` if the chip is empty.
+    pub fn read(&self, chip: &[u8], addr: u16) -> Option<u8> {
+        chip.get(self.offset(addr)).copied()
+    }
+
+    /// Writes `data` into `chip` at `addr`. Writes into empty chips are
+    /// ignored.
+    pub fn write(&self, chip: &mut [u8], addr: u16, data: u8) {
+        if let Some(byte) = chip.get_mut(self.offset(addr)) {
+            *byte = data;
+        }
+    }
+}
+
+/// ROM chip contents, borrowed whenever possible.
+pub type RomData<'a> = Cow<'a, [u8]>;
+
+/// RAM chip contents.
+///
+/// Can be either allocated by the mapper, or lent by the frontend (e.g. a
+/// `static` buffer on targets without an allocator-friendly heap).
+#[derive(Debug, PartialEq, Eq)]
+pub enum RamData<'a> {
+    Owned(Vec<u8>),
+    Borrowed(&'a mut [u8]),
+}
+
+impl RamData<'_> {
+    /// Allocates `len` bytes of RAM filled with `value`.
+    pub fn owned(len: usize, value: u8) -> Self {
+        Self::Owned(vec![value; len])
+    }
+
+    /// Allocates `len` bytes of RAM with the `init` power-on contents.
+    pub fn with_init(len: usize, init: RamInit) -> Self {
+        let mut data = vec![0; len];
+        init.fill(&mut data);
+        Self::Owned(data)
+    }
+
+    pub fn is_borrowed(&self) -> bool {
+        matches!(self, Self::Borrowed(_))
+    }
+}
+
+impl Default for RamData<'_> {
+    fn default() -> Self {
+        Self::Owned(Vec::new())
+    }
+}
+
+impl Deref for RamData<'_> {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            Self::Owned(data) => data,
+            Self::Borrowed(data) => data,
+        }
+    }
+}
+
+impl DerefMut for RamData<'_> {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        match self {
+            Self::Owned(data) => data,
+            Self::Borrowed(data) => data,
+        }
+    }
+}
@@ -0,0 +1,226 @@
+use crate::{
+    chips::Chips,
+    error::Error,
+    mapper::{CIRAM_LEN, FOUR_SCREEN_VRAM_LEN, Mapper, Mirroring},
+    mappers,
+    report::MemoryReport,
+};
+use effnes_bus::{
+    InspectBus, MemoryBus,
+    nes::{BusDevice, BusValue},
+    peripheral::Peripheral,
+    power::RamInit,
+    signal::{Signals, Source},
+};
+use effnes_ines::Rom;
+
+/// Cartridge
+///
+/// Wraps a [Mapper] together with the nametable RAM, and exposes both sides
+/// of the cartridge connector:
+///
+/// - CPU side: [MemoryBus] / [InspectBus] ($4020-$FFFF) and [Peripheral].
+/// - PPU side: [Cartridge::ppu_read], [Cartridge::ppu_write] and
+///   [Cartridge::ppu_peek] ($0000-$3FFF).
+///
+/// ## Behaviour
+///
+/// - Nametable RAM is 2 KiB (CIRAM), or 4 KiB if the mapper starts as
+///   [Mirroring::FourScreen].
+/// - Every PPU access updates the A12 line, and [Mapper::ppu_a12] is called
+///   on every change of it.
+/// - Reads the mapper doesn't drive return the last value seen on the data
+///   bus through the cartridge. Attached to a [effnes_bus::nes::NesBus]
+///   (through [BusDevice]), they're left floating instead, so the bus
+///   resolves them.
+pub struct Cartridge<M: Mapper> {
+    mapper: M,
+    vram: Vec<u8>,
+    ram_init: RamInit,
+    a12: bool,
+    open_bus: u8,
+}
+
+impl<M: Mapper> Cartridge<M> {
+    pub fn new(mapper: M) -> Self {
+        let len = match mapper.mirroring() {
+            Mirroring::FourScreen => FOUR_SCREEN_VRAM_LEN,
+            _ => CIRAM_LEN,
+        };
+        Self {
+            mapper,
+            vram: vec![0; len],
+            ram_init: RamInit::Zero,
+            a12: false,
+            open_bus: 0,
+        }
+    }
+
+    pub fn mapper(&self) -> &M {
+        &self.mapper
+    }
+
+    pub fn mapper_mut(&mut self) -> &mut M {
+        &mut self.mapper
+    }
+
+    pub fn into_mapper(self) -> M {
+        self.mapper
+    }
+
+    /// Nametable RAM.
+    pub fn vram(&self) -> &[u8] {
+        &self.vram
+    }
+
+    /// IRQ output (`true` while the cartridge asserts /IRQ).
+    pub fn irq(&self) -> bool {
+        self.mapper.irq()
+    }
+
+    /// Expansion audio output (see [Mapper::audio]).
+    pub fn audio(&self) -> f32 {
+        self.mapper.audio()
+    }
+
+    /// Lets the mapper see a CPU write outside the cartridge range.
+    pub fn snoop_write(&mut self, addr: u16, data: u8) {
+        self.mapper.snoop_write(addr, data);
+    }
+
+    /// Drives the PPU address bus with `addr`, updating A12.
+    pub fn ppu_address(&mut self, addr: u16) {
+        let a12 = addr & 0x1000 != 0;
+        if a12 != self.a12 {
+            self.a12 = a12;
+            self.mapper.ppu_a12(a12);
+        }
+    }
+
+    /// Reads a byte from the PPU side ($0000-$3FFF).
+    pub fn ppu_read(&mut self, addr: u16) -> u8 {
+        self.ppu_address(addr);
+        self.mapper.ppu_read(addr, &self.vram)
+    }
+
+    /// Reads a byte from the PPU side ($0000-$3FFF).
+    ///
+    /// This must not mutate the cartridge state.
+    pub fn ppu_peek(&self, addr: u16) -> u8 {
+        self.mapper.ppu_peek(addr, &self.vram)
+    }
+
+    /// Writes `data` into the PPU side ($0000-$3FFF).
+    pub fn ppu_write(&mut self, addr: u16, data: u8) {
+        self.ppu_address(addr);
+        self.mapper.ppu_write(addr, data, &mut self.vram);
+    }
+
+    /// Memory used by the cartridge: the nametable RAM plus everything
+    /// reported by the mapper.
+    pub fn memory_report(&self) -> MemoryReport {
+        let mut report = MemoryReport::default();
+        report.add("nametable RAM", self.vram.capacity());
+        self.mapper.memory_report(&mut report);
+        report
+    }
+
+    /// Battery-backed RAM, if any.
+    pub fn save_ram(&self) -> Option<&[u8]> {
+        self.mapper.save_ram()
+    }
+
+    pub fn save_ram_mut(&mut self) -> Option<&mut [u8]> {
+        self.mapper.save_ram_mut()
+    }
+}
+
+impl<'a> Cartridge<Box<dyn Mapper + 'a>> {
+    /// Creates a cartridge with the mapper described by the `rom` header
+    /// (see [mappers::from_rom]).
+    pub fn from_rom(rom: Rom<'a>) -> Result<Self, Error> {
+        mappers::from_rom(rom).map(Self::new)
+    }
+
+    /// Like [Cartridge::from_rom], but with the `init` power-on contents in
+    /// PRG-RAM, CHR-RAM and nametable RAM.
+    pub fn from_rom_with(rom: Rom<'a>, init: RamInit) -> Result<Self, Error> {
+        let mut cartridge = Self::new(mappers::from_chips(Chips::new(rom).with_ram_init(init))?);
+        init.fill(&mut cartridge.vram);
+        cartridge.ram_init = init;
+        Ok(cartridge)
+    }
+}
+
+impl<M: Mapper> MemoryBus for Cartridge<M> {
+    fn read_u8(&mut self, addr: u16) -> u8 {
+        if let Some(data) = self.mapper.cpu_read(addr) {
+            self.open_bus = data;
+        }
+        self.open_bus
+    }
+
+    fn read_u16(&mut self, addr: u16) -> u16 {
+        (self.read_u8(addr) as u16) | ((self.read_u8(addr.wrapping_add(1)) as u16) << 8)
+    }
+
+    fn write_u8(&mut self, addr: u16, data: u8) {
+        self.open_bus = data;
+        self.mapper.cpu_write(addr, data);
+    }
+}
+
+impl<M: Mapper> InspectBus for Cartridge<M> {
+    fn peek_u8(&self, addr: u16) -> u8 {
+        self.mapper.cpu_peek(addr).unwrap_or(self.open_bus)
+    }
+
+    fn peek_u16(&self, addr: u16) -> u16 {
+        (self.peek_u8(addr) as u16) | ((self.peek_u8(addr.wrapping_add(1)) as u16) << 8)
+    }
+}
+
+impl<M: Mapper> BusDevice for Cartridge<M> {
+    fn read(&mut self, addr: u16) -> BusValue {
+        let data = self.mapper.cpu_read(addr);
+        if let Some(data) = data {
+            self.open_bus = data;
+        }
+        data.into()
+    }
+
+    fn peek(&self, addr: u16) -> BusValue {
+        self.mapper.cpu_peek(addr).into()
+    }
+
+    fn write(&mut self, addr: u16, data: u8) {
+        self.write_u8(addr, data);
+    }
+
+    fn snoop(&mut self, addr: u16, data: u8) {
+        self.snoop_write(addr, data);
+    }
+}
+
+impl<M: Mapper> Peripheral for Cartridge<M> {
+    fn cold_reset(&mut self) {
+        self.ram_init.fill(&mut self.vram);
+        self.a12 = false;
+        self.open_bus = 0;
+        self.mapper.cold_reset();
+    }
+
+    fn warm_reset(&mut self) {
+        self.mapper.warm_reset();
+    }
+
+    fn recv(&mut self, addr: u16, value: u8) {
+        self.write_u8(addr, value);
+    }
+
+    /// Clocks the mapper, which drives the IRQ line.
+    fn cycle(&mut self, _io: &mut impl MemoryBus, signals: &mut Signals) {
+        self.mapper.cpu_cycle();
+        signals.set_irq(Source::Mapper, self.mapper.irq());
+    }
+}
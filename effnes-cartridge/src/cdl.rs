@@ -0,0 +1,397 @@
+use crate::{Cartridge, Error, Mapper};
+use bitflags::bitflags;
+use effnes_bus::{
+    InspectBus, MemoryBus,
+    nes::{NesBus, Region},
+};
+use effnes_cpu::{
+    addr::AddressingMode,
+    debug::DebugCpu,
+    disasm::{self, ByteKind, Line},
+};
+use effnes_ines::Rom;
+use std::{fs, io, ops::Range, path::Path};
+
+bitflags! {
+    /// PRG-ROM byte of a [Cdl] (FCEUX layout: `xPdcAADC`).
+    #[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+    pub struct PrgFlags: u8 {
+        /// Fetched as (part of) an instruction.
+        const Code         = 0x01;
+        /// Read as data.
+        const Data         = 0x02;
+        /// CPU window the byte was accessed through ($8000 + $2000 * n).
+        /// Left clear for ROM mapped into $6000-$7FFF, which FCEUX has no
+        /// window for.
+        const Bank         = 0x0C;
+        /// Jumped into through an indirect JMP.
+        const IndirectCode = 0x10;
+        /// Read through an indirect operand (`(zp,X)` or `(zp),Y`).
+        const IndirectData = 0x20;
+        /// Fetched by the DMC.
+        const Pcm          = 0x40;
+    }
+}
+
+bitflags! {
+    /// CHR-ROM byte of a [Cdl] (FCEUX layout: `xxxxxxRD`).
+    #[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+    pub struct ChrFlags: u8 {
+        /// Fetched by the PPU while rendering.
+        const Rendered = 0x01;
+        /// Read by the CPU through PPUDATA ($2007).
+        const Read     = 0x02;
+    }
+}
+
+impl PrgFlags {
+    /// Bank bits of a byte accessed through `addr` (none below $8000).
+    pub fn bank(addr: u16) -> Self {
+        match addr {
+            0x8000..=0xFFFF => Self::from_bits_retain((((addr >> 13) & 3) as u8) << 2),
+            _ => Self::empty(),
+        }
+    }
+}
+
+/// Translates CPU/PPU addresses into offsets of the cartridge ROM chips.
+pub trait RomMap {
+    /// See [Mapper::prg_rom_offset].
+    fn prg_rom_offset(&self, addr: u16) -> Option<usize>;
+
+    /// See [Mapper::chr_rom_offset].
+    fn chr_rom_offset(&self, addr: u16) -> Option<usize>;
+}
+
+impl<M: Mapper> RomMap for Cartridge<M> {
+    fn prg_rom_offset(&self, addr: u16) -> Option<usize> {
+        self.mapper().prg_rom_offset(addr)
+    }
+
+    fn chr_rom_offset(&self, addr: u16) -> Option<usize> {
+        self.mapper().chr_rom_offset(addr)
+    }
+}
+
+impl<P, A, M: Mapper> RomMap for NesBus<P, A, Cartridge<M>> {
+    fn prg_rom_offset(&self, addr: u16) -> Option<usize> {
+        match Region::decode(addr) {
+            (Region::Cartridge, addr) => self.cartridge().prg_rom_offset(addr),
+            _ => None,
+        }
+    }
+
+    fn chr_rom_offset(&self, addr: u16) -> Option<usize> {
+        self.cartridge().chr_rom_offset(addr)
+    }
+}
+
+/// Code/Data Log
+///
+/// One flag byte per PRG-ROM byte ([PrgFlags]), followed by one per CHR-ROM
+/// byte ([ChrFlags]), as in the `.cdl` files of FCEUX. CHR-RAM isn't
+/// logged.
+///
+/// Flags are only ever added; see [CodeDataLogger] for filling a log while
+/// playing.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Cdl {
+    prg: Vec<u8>,
+    chr: Vec<u8>,
+}
+
+impl Cdl {
+    /// Empty log for `prg_len` bytes of PRG-ROM and `chr_len` bytes of
+    /// CHR-ROM.
+    pub fn new(prg_len: usize, chr_len: usize) -> Self {
+        Self {
+            prg: vec![0; prg_len],
+            chr: vec![0; chr_len],
+        }
+    }
+
+    /// Empty log sized for the ROM chips of `rom`.
+    pub fn for_rom(rom: &Rom) -> Self {
+        Self::new(rom.prg_rom.len(), rom.chr_rom.len())
+    }
+
+    /// Parses the contents of a `.cdl` file.
+    ///
+    /// ## Errors
+    ///
+    /// [Error::CdlSizeMismatch] if `data` isn't `prg_len + chr_len` bytes
+    /// long.
+    pub fn from_bytes(data: &[u8], prg_len: usize, chr_len: usize) -> Result<Self, Error> {
+        if data.len() != prg_len + chr_len {
+            return Err(Error::CdlSizeMismatch {
+                expected: prg_len + chr_len,
+                actual: data.len(),
+            });
+        }
+        let (prg, chr) = data.split_at(prg_len);
+        Ok(Self {
+            prg: prg.to_vec(),
+            chr: chr.to_vec(),
+        })
+    }
+
+    /// Contents of the `.cdl` file.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        [&self.prg[..], &self.chr[..]].concat()
+    }
+
+    /// Loads a `.cdl` file.
+    ///
+    /// ## Errors
+    ///
+    /// I/O errors, and [io::ErrorKind::InvalidData] if the file size doesn't
+    /// match the ROM sizes.
+    pub fn load(path: impl AsRef<Path>, prg_len: usize, chr_len: usize) -> io::Result<Self> {
+        let data = fs::read(path)?;
+        Self::from_bytes(&data, prg_len, chr_len)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        fs::write(path, self.to_bytes())
+    }
+
+    /// Flags of the PRG-ROM byte at `offset`.
+    pub fn prg(&self, offset: usize) -> PrgFlags {
+        PrgFlags::from_bits_retain(self.prg.get(offset).copied().unwrap_or(0))
+    }
+
+    /// Flags of the CHR-ROM byte at `offset`.
+    pub fn chr(&self, offset: usize) -> ChrFlags {
+        ChrFlags::from_bits_retain(self.chr.get(offset).copied().unwrap_or(0))
+    }
+
+    pub fn prg_len(&self) -> usize {
+        self.prg.len()
+    }
+
+    pub fn chr_len(&self) -> usize {
+        self.chr.len()
+    }
+
+    /// Adds `flags` to the PRG-ROM byte mapped into `addr` (if any), along
+    /// with its bank bits.
+    pub fn log_prg(&mut self, map: &impl RomMap, addr: u16, flags: PrgFlags) {
+        if let Some(byte) = map
+            .prg_rom_offset(addr)
+            .and_then(|offset| self.prg.get_mut(offset))
+        {
+            *byte |= (flags | PrgFlags::bank(addr)).bits();
+        }
+    }
+
+    /// Adds `flags` to the CHR-ROM byte mapped into `addr` (if any).
+    pub fn log_chr(&mut self, map: &impl RomMap, addr: u16, flags: ChrFlags) {
+        if let Some(byte) = map
+            .chr_rom_offset(addr)
+            .and_then(|offset| self.chr.get_mut(offset))
+        {
+            *byte |= flags.bits();
+        }
+    }
+
+    /// Forgets every flag.
+    pub fn clear(&mut self) {
+        self.prg.fill(0);
+        self.chr.fill(0);
+    }
+
+    /// What the PRG-ROM byte at `offset` was used as. Bytes both executed
+    /// and read count as code.
+    pub fn kind(&self, offset: usize) -> ByteKind {
+        let flags = self.prg(offset);
+        if flags.contains(PrgFlags::Code) {
+            ByteKind::Code
+        } else if flags.intersects(PrgFlags::Data | PrgFlags::Pcm) {
+            ByteKind::Data
+        } else {
+            ByteKind::Unknown
+        }
+    }
+
+    /// CPU address the PRG-ROM byte at `offset` was accessed through, if it
+    /// was accessed at all.
+    ///
+    /// Bytes only accessed through $6000-$7FFF have no bank bits, so
+    /// they're reported in $8000-$9FFF.
+    pub fn cpu_addr(&self, offset: usize) -> Option<u16> {
+        let flags = self.prg(offset);
+        flags
+            .intersects(PrgFlags::Code | PrgFlags::Data | PrgFlags::Pcm)
+            .then(|| {
+                let bank = ((flags & PrgFlags::Bank).bits() >> 2) as u16;
+                0x8000 | (bank << 13) | (offset as u16 & 0x1FFF)
+            })
+    }
+
+    /// Disassembles `range` of `prg_rom`, placed at `base`, listing the
+    /// bytes logged as data (and never as code) as such.
+    pub fn disassemble(&self, prg_rom: &[u8], range: Range<usize>, base: u16) -> Vec<Line> {
+        let start = range.start;
+        disasm::disassemble(&prg_rom[range], base, |i| self.kind(start + i))
+    }
+}
+
+/// Code/Data Logger
+///
+/// Wraps a CPU bus, filling a [Cdl] with the PRG-ROM bytes the CPU executes
+/// and reads.
+///
+/// ## Behaviour
+///
+/// - The bus can't tell opcode fetches apart, so the frontend calls
+///   [CodeDataLogger::step] before every CPU cycle (or instruction), which
+///   starts a new instruction at every instruction boundary. The whole
+///   instruction is logged as code, and the target of an indirect JMP as
+///   indirect code too.
+/// - Every other PRG-ROM read is logged as data, and as indirect data if the
+///   instruction uses an indirect operand. The dummy read following 1-byte
+///   instructions isn't logged.
+/// - DMC fetches and PPU pattern fetches don't go through the CPU bus, so
+///   they're logged with [CodeDataLogger::log_pcm] and
+///   [CodeDataLogger::log_chr].
+/// - [InspectBus] reads are never logged.
+///
+/// ```ignore
+/// let mut bus = CodeDataLogger::new(NesBus::new(ppu, io, cartridge), Cdl::for_rom(&rom));
+///
+/// loop {
+///     bus.step(&cpu);
+///     cpu.cycle(&mut bus, &mut signals);
+/// }
+/// bus.cdl().save("game.cdl")?;
+/// ```
+pub struct CodeDataLogger<B> {
+    inner: B,
+    cdl: Cdl,
+    /// Opcode address of the current instruction.
+    pc: Option<u16>,
+    /// Bytes read by the current instruction fetch (dummy read included).
+    fetch_len: u16,
+    mode: AddressingMode,
+    jump_indirect: bool,
+}
+
+impl<B: InspectBus + RomMap> CodeDataLogger<B> {
+    pub fn new(inner: B, cdl: Cdl) -> Self {
+        Self {
+            inner,
+            cdl,
+            pc: None,
+            fetch_len: 0,
+            mode: AddressingMode::Implied,
+            jump_indirect: false,
+        }
+    }
+
+    /// Looks at the CPU before one of its cycles, starting the next
+    /// instruction if it's at an instruction boundary (see
+    /// [DebugCpu::next_instruction]).
+    pub fn step(&mut self, cpu: &impl DebugCpu) {
+        if let Some(pc) = cpu.next_instruction() {
+            self.set_pc(pc);
+        }
+    }
+
+    /// Starts the instruction at `pc`, logging it as code.
+    ///
+    /// Only meant for instruction boundaries; the CPU `pc` register moves
+    /// mid-instruction on cycle stepped CPUs. Calling it again with the same
+    /// address is harmless.
+    pub fn set_pc(&mut self, pc: u16) {
+        if self.pc == Some(pc) {
+            return;
+        }
+
+        let opcode = self.inner.peek_u8(pc);
+        let mode = AddressingMode::from(opcode);
+        let mut flags = PrgFlags::Code;
+        if self.jump_indirect {
+            flags |= PrgFlags::IndirectCode;
+        }
+        for i in 0..=mode.operand_len() {
+            self.cdl.log_prg(&self.inner, pc.wrapping_add(i), flags);
+        }
+
+        self.pc = Some(pc);
+        self.fetch_len = (mode.operand_len() + 1).max(2);
+        self.mode = mode;
+        self.jump_indirect = opcode == 0x6C;
+    }
+
+    /// Logs a DMC sample fetch from `addr`.
+    pub fn log_pcm(&mut self, addr: u16) {
+        self.cdl.log_prg(&self.inner, addr, PrgFlags::Pcm);
+    }
+
+    /// Logs a PPU access to the pattern tables ($0000-$1FFF).
+    pub fn log_chr(&mut self, addr: u16, flags: ChrFlags) {
+        self.cdl.log_chr(&self.inner, addr, flags);
+    }
+
+    pub fn cdl(&self) -> &Cdl {
+        &self.cdl
+    }
+
+    pub fn cdl_mut(&mut self) -> &mut Cdl {
+        &mut self.cdl
+    }
+
+    pub fn inner(&self) -> &B {
+        &self.inner
+    }
+
+    pub fn inner_mut(&mut self) -> &mut B {
+        &mut self.inner
+    }
+
+    pub fn into_parts(self) -> (B, Cdl) {
+        (self.inner, self.cdl)
+    }
+
+    fn log_read(&mut self, addr: u16) {
+        if let Some(pc) = self.pc
+            && addr.wrapping_sub(pc) < self.fetch_len
+        {
+            return;
+        }
+
+        let mut flags = PrgFlags::Data;
+        if let AddressingMode::IndirectI(_) = self.mode {
+            flags |= PrgFlags::IndirectData;
+        }
+        self.cdl.log_prg(&self.inner, addr, flags);
+    }
+}
+
+impl<B: MemoryBus + InspectBus + RomMap> MemoryBus for CodeDataLogger<B> {
+    fn read_u8(&mut self, addr: u16) -> u8 {
+        self.log_read(addr);
+        self.inner.read_u8(addr)
+    }
+
+    fn read_u16(&mut self, addr: u16) -> u16 {
+        self.log_read(addr);
+        self.log_read(addr.wrapping_add(1));
+        self.inner.read_u16(addr)
+    }
+
+    fn write_u8(&mut self, addr: u16, data: u8) {
+        self.inner.write_u8(addr, data);
+    }
+}
+
+impl<B: InspectBus> InspectBus for CodeDataLogger<B> {
+    fn peek_u8(&self, addr: u16) -> u8 {
+        self.inner.peek_u8(addr)
+    }
+
+    fn peek_u16(&self, addr: u16) -> u16 {
+        self.inner.peek_u16(addr)
+    }
+}
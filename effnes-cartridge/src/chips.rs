@@ -0,0 +1,152 @@
+use crate::{
+    bank::{Banks, RamData, RomData},
+    report::MemoryReport,
+};
+use effnes_bus::power::RamInit;
+use effnes_ines::{Header, Rom};
+
+/// ROM/RAM chips of a board.
+///
+/// ## Behaviour
+///
+/// - ROM contents are taken from the [Rom] as is, so borrowed data stays
+///   borrowed.
+/// - PRG-RAM covers both the volatile and the non volatile sizes declared by
+///   the header, and so does CHR-RAM. The non volatile part comes first.
+/// - RAM can be lent by the frontend instead (see [Chips::with_prg_ram] and
+///   [Chips::with_chr_ram]).
+/// - RAM starts zeroed, unless another power-on state is picked (see
+///   [Chips::with_ram_init]). Mappers allocating extra RAM (MMC5 ExRAM,
+///   Namco 163 sound RAM) use it too. Cold resets bring it back, except for
+///   battery-backed RAM (see [Chips::cold_reset]).
+pub struct Chips<'a> {
+    pub header: Header,
+    pub prg_rom: RomData<'a>,
+    pub prg_ram: RamData<'a>,
+    pub chr_rom: RomData<'a>,
+    pub chr_ram: RamData<'a>,
+    pub ram_init: RamInit,
+}
+
+impl<'a> Chips<'a> {
+    pub fn new(rom: Rom<'a>) -> Self {
+        let header = rom.header;
+        let prg_ram = header.prg_ram_size + header.prg_nvram_size;
+        let chr_ram = header.chr_ram_size + header.chr_nvram_size;
+        Self {
+            prg_rom: rom.prg_rom,
+            prg_ram: RamData::owned(prg_ram, 0),
+            chr_rom: rom.chr_rom,
+            chr_ram: RamData::owned(chr_ram, 0),
+            ram_init: RamInit::Zero,
+            header,
+        }
+    }
+
+    /// Uses `ram` as PRG-RAM.
+    pub fn with_prg_ram(mut self, ram: RamData<'a>) -> Self {
+        self.prg_ram = ram;
+        self
+    }
+
+    /// Uses `ram` as CHR-RAM.
+    pub fn with_chr_ram(mut self, ram: RamData<'a>) -> Self {
+        self.chr_ram = ram;
+        self
+    }
+
+    /// Fills PRG-RAM and CHR-RAM (lent or not) with the `init` power-on
+    /// contents.
+    pub fn with_ram_init(mut self, init: RamInit) -> Self {
+        init.fill(&mut self.prg_ram);
+        init.fill(&mut self.chr_ram);
+        self.ram_init = init;
+        self
+    }
+
+    /// Pattern table chip: CHR-ROM if there's any, CHR-RAM otherwise.
+    pub fn chr(&self) -> &[u8] {
+        if self.chr_rom.is_empty() {
+            &self.chr_ram
+        } else {
+            &self.chr_rom
+        }
+    }
+
+    /// Writable pattern table chip, if the board has CHR-RAM instead of
+    /// CHR-ROM.
+    pub fn chr_mut(&mut self) -> Option<&mut [u8]> {
+        self.chr_rom.is_empty().then_some(&mut *self.chr_ram)
+    }
+
+    /// PRG-ROM offset of the byte `prg` maps into `addr` ($8000-$FFFF), for
+    /// [crate::Mapper::prg_rom_offset].
+    pub fn prg_rom_offset<const N: usize>(&self, prg: &Banks<N>, addr: u16) -> Option<usize> {
+        match addr {
+            0x8000..=0xFFFF => prg.offset_in(&self.prg_rom, addr),
+            _ => None,
+        }
+    }
+
+    /// CHR-ROM offset of the byte `chr` maps into `addr`, for
+    /// [crate::Mapper::chr_rom_offset]. Boards with CHR-RAM have none.
+    pub fn chr_rom_offset<const N: usize>(&self, chr: &Banks<N>, addr: u16) -> Option<usize> {
+        chr.offset_in(&self.chr_rom, addr)
+    }
+
+    /// Reads the PRG-RAM byte mapped into `addr` ($6000-$7FFF), mirrored
+    /// over smaller chips.
+    pub fn wram_peek(&self, addr: u16) -> Option<u8> {
+        match self.prg_ram.len() {
+            0 => None,
+            len => Some(self.prg_ram[(addr as usize & 0x1FFF) % len]),
+        }
+    }
+
+    /// Writes `data` into the PRG-RAM byte mapped into `addr`
+    /// ($6000-$7FFF).
+    pub fn wram_write(&mut self, addr: u16, data: u8) {
+        let len = self.prg_ram.len();
+        if len != 0 {
+            self.prg_ram[(addr as usize & 0x1FFF) % len] = data;
+        }
+    }
+
+    /// Size of the battery-backed part of PRG-RAM.
+    fn prg_nvram_len(&self) -> usize {
+        match self.header.battery {
+            true => self.header.prg_nvram_size.min(self.prg_ram.len()),
+            false => 0,
+        }
+    }
+
+    /// Brings back the power-on contents of the volatile PRG-RAM and
+    /// CHR-RAM, keeping the battery-backed parts.
+    pub fn cold_reset(&mut self) {
+        let prg = self.prg_nvram_len();
+        let chr = self.header.chr_nvram_size.min(self.chr_ram.len());
+        for (ram, start) in [(&mut *self.prg_ram, prg), (&mut *self.chr_ram, chr)] {
+            for (offset, byte) in ram.iter_mut().enumerate().skip(start) {
+                *byte = self.ram_init.byte(offset);
+            }
+        }
+    }
+
+    /// The battery-backed part of PRG-RAM, if any.
+    pub fn save_ram(&self) -> Option<&[u8]> {
+        let len = self.prg_nvram_len();
+        (len != 0).then(|| &self.prg_ram[..len])
+    }
+
+    pub fn save_ram_mut(&mut self) -> Option<&mut [u8]> {
+        let len = self.prg_nvram_len();
+        (len != 0).then(|| &mut self.prg_ram[..len])
+    }
+
+    pub fn memory_report(&self, report: &mut MemoryReport) {
+        report.add_rom("PRG-ROM", &self.prg_rom);
+        report.add_ram("PRG-RAM", &self.prg_ram);
+        report.add_rom("CHR-ROM", &self.chr_rom);
+        report.add_ram("CHR-RAM", &self.chr_ram);
+    }
+}
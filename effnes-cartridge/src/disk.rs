@@ -0,0 +1,380 @@
+use effnes_ines::fds::{Blocks, DiskFormat, SIDE_LEN, block_len};
+
+/// CPU cycles per byte transferred by the drive (about 96.4 kbit/s).
+pub const BYTE_CYCLES: u32 = 149;
+
+/// CPU cycles between the motor start and the first byte.
+const SPIN_UP_CYCLES: u32 = 50_000;
+
+/// CPU cycles a disk stays ejected while switching sides, so the BIOS
+/// notices the change.
+pub const SWITCH_CYCLES: u32 = 1_789_773;
+
+/// Gap before the first block (28300 bits).
+const LEADING_GAP: usize = 28_300 / 8;
+
+/// Gap after every block (976 bits).
+const BLOCK_GAP: usize = 976 / 8;
+
+/// Marker ending every gap.
+const GAP_END: u8 = 0x80;
+
+fn update_crc(crc: u16, data: u8) -> u16 {
+    let mut crc = crc;
+    for bit in 0..8 {
+        let carry = crc & 1 != 0;
+        crc >>= 1;
+        if carry {
+            crc ^= 0x8408;
+        }
+        if data & (1 << bit) != 0 {
+            crc ^= 0x8000;
+        }
+    }
+    crc
+}
+
+/// CRC written after `block` (the gap end marker included).
+pub fn block_crc(block: &[u8]) -> u16 {
+    let crc = [GAP_END]
+        .iter()
+        .chain(block)
+        .fold(0, |crc, b| update_crc(crc, *b));
+    update_crc(update_crc(crc, 0), 0)
+}
+
+/// Builds the track seen by the drive head out of a disk side: gaps, gap
+/// end markers, blocks and their CRCs.
+pub fn build_track<'a>(blocks: impl Iterator<Item = &'a [u8]>) -> Vec<u8> {
+    let mut track = vec![0; LEADING_GAP];
+    for block in blocks {
+        track.push(GAP_END);
+        track.extend(block);
+        track.extend(block_crc(block).to_le_bytes());
+        track.resize(track.len() + BLOCK_GAP, 0);
+    }
+    let len = track.len().max(LEADING_GAP + SIDE_LEN);
+    track.resize(len, 0);
+    track
+}
+
+/// Turns a track back into a `.fds` disk side, keeping the blocks that
+/// could be decoded (up to the first malformed one).
+pub fn parse_track(track: &[u8]) -> Vec<u8> {
+    let mut side = Vec::with_capacity(SIDE_LEN);
+    let mut offset = 0;
+    let mut file_size = 0;
+
+    while let Some(start) = track[offset..].iter().position(|b| *b != 0) {
+        let start = offset + start;
+        if track[start] != GAP_END {
+            break;
+        }
+        let Some(&kind) = track.get(start + 1) else {
+            break;
+        };
+        let Some(len) = block_len(kind, file_size) else {
+            break;
+        };
+        let Some(block) = track.get(start + 1..start + 1 + len) else {
+            break;
+        };
+        if kind == 3 {
+            file_size = block[13] as usize | (block[14] as usize) << 8;
+        }
+        side.extend(block);
+        offset = start + 1 + len + 2;
+        if offset >= track.len() {
+            break;
+        }
+    }
+
+    side.resize(SIDE_LEN, 0);
+    side
+}
+
+/// FDS Disk Drive
+///
+/// Serial transfer between the RAM adapter and the disk sides, modelled at
+/// the byte level over tracks built by [build_track].
+///
+/// ## Behaviour
+///
+/// - With the motor on, the head waits [SPIN_UP_CYCLES] and then moves one
+///   byte every [BYTE_CYCLES] cycles; reaching the end of the track stops
+///   the motor, and the head goes back to the start.
+/// - Reads skip the gap up to the gap end marker, then every byte sets the
+///   transfer flag (and raises the IRQ if enabled).
+/// - Writes store the data register, or the accumulated CRC while CRC
+///   control is set.
+/// - [DiskDrive::switch_side] ejects the disk for [SWITCH_CYCLES] before
+///   inserting the new side.
+#[derive(Clone, Debug)]
+pub struct DiskDrive {
+    tracks: Vec<Vec<u8>>,
+    modified: Vec<bool>,
+    side: Option<usize>,
+    pending: Option<(usize, u32)>,
+
+    position: usize,
+    delay: u32,
+    motor: bool,
+    reset_transfer: bool,
+    read_mode: bool,
+    crc_control: bool,
+    previous_crc_control: bool,
+    ready: bool,
+    irq_enabled: bool,
+
+    scanning: bool,
+    end_of_head: bool,
+    gap_ended: bool,
+    crc: u16,
+    read_data: u8,
+    write_data: u8,
+    transfer: bool,
+    irq: bool,
+}
+
+impl DiskDrive {
+    /// Creates a drive with the first side inserted.
+    pub fn new(tracks: Vec<Vec<u8>>) -> Self {
+        Self {
+            side: (!tracks.is_empty()).then_some(0),
+            modified: vec![false; tracks.len()],
+            tracks,
+            pending: None,
+            position: 0,
+            delay: 0,
+            motor: false,
+            reset_transfer: false,
+            read_mode: true,
+            crc_control: false,
+            previous_crc_control: false,
+            ready: false,
+            irq_enabled: false,
+            scanning: false,
+            end_of_head: true,
+            gap_ended: false,
+            crc: 0,
+            read_data: 0,
+            write_data: 0,
+            transfer: false,
+            irq: false,
+        }
+    }
+
+    /// Builds the tracks of every side of a disk image.
+    pub fn from_sides<'a>(sides: impl Iterator<Item = &'a [u8]>, format: DiskFormat) -> Self {
+        Self::new(
+            sides
+                .map(|side| build_track(Blocks::new(side, format)))
+                .collect(),
+        )
+    }
+
+    pub fn sides(&self) -> usize {
+        self.tracks.len()
+    }
+
+    /// Inserted side, if any.
+    pub fn side(&self) -> Option<usize> {
+        self.side
+    }
+
+    /// Inserts `side` right away.
+    ///
+    /// ## Panics
+    ///
+    /// If `side` is out of range.
+    pub fn insert(&mut self, side: usize) {
+        assert!(side < self.tracks.len(), "invalid disk side");
+        self.side = Some(side);
+        self.pending = None;
+    }
+
+    pub fn eject(&mut self) {
+        self.side = None;
+        self.pending = None;
+    }
+
+    /// Ejects the disk, and inserts `side` after [SWITCH_CYCLES].
+    ///
+    /// ## Panics
+    ///
+    /// If `side` is out of range.
+    pub fn switch_side(&mut self, side: usize) {
+        assert!(side < self.tracks.len(), "invalid disk side");
+        self.side = None;
+        self.pending = Some((side, SWITCH_CYCLES));
+    }
+
+    /// Track of `side`.
+    pub fn track(&self, side: usize) -> &[u8] {
+        &self.tracks[side]
+    }
+
+    /// Whether `side` was written since it was built.
+    pub fn is_modified(&self, side: usize) -> bool {
+        self.modified[side]
+    }
+
+    /// `.fds` contents of `side`.
+    pub fn side_data(&self, side: usize) -> Vec<u8> {
+        parse_track(&self.tracks[side])
+    }
+
+    /// Writes the control register ($4025).
+    pub fn write_control(&mut self, data: u8) {
+        self.motor = data & 0x01 != 0;
+        self.reset_transfer = data & 0x02 != 0;
+        self.read_mode = data & 0x04 != 0;
+        self.crc_control = data & 0x10 != 0;
+        self.ready = data & 0x40 != 0;
+        self.irq_enabled = data & 0x80 != 0;
+        self.irq = false;
+    }
+
+    /// Writes the data register ($4024).
+    pub fn write_data(&mut self, data: u8) {
+        self.write_data = data;
+        self.transfer = false;
+        self.irq = false;
+    }
+
+    /// Status bits of $4030 (transfer flag, CRC error and end of head),
+    /// acknowledging the transfer.
+    pub fn read_status(&mut self) -> u8 {
+        let status = self.peek_status();
+        self.transfer = false;
+        self.irq = false;
+        status
+    }
+
+    pub fn peek_status(&self) -> u8 {
+        ((self.transfer as u8) << 1)
+            | (((self.crc != 0) as u8) << 4)
+            | ((self.end_of_head as u8) << 6)
+    }
+
+    /// Reads the data register ($4031), acknowledging the transfer.
+    pub fn read_data(&mut self) -> u8 {
+        self.transfer = false;
+        self.irq = false;
+        self.read_data
+    }
+
+    pub fn peek_data(&self) -> u8 {
+        self.read_data
+    }
+
+    /// Drive status ($4032): no disk (bit 0), not ready (bit 1) and write
+    /// protected (bit 2).
+    pub fn drive_status(&self) -> u8 {
+        let inserted = self.side.is_some();
+        (!inserted as u8) | (((!inserted || !self.scanning) as u8) << 1) | ((!inserted as u8) << 2)
+    }
+
+    pub fn irq(&self) -> bool {
+        self.irq
+    }
+
+    /// CPU cycle clock.
+    pub fn cycle(&mut self) {
+        if let Some((side, delay)) = &mut self.pending {
+            *delay -= 1;
+            if *delay == 0 {
+                self.side = Some(*side);
+                self.pending = None;
+            }
+        }
+
+        let Some(side) = self.side else {
+            self.end_of_head = true;
+            self.scanning = false;
+            return;
+        };
+        if !self.motor {
+            self.end_of_head = true;
+            self.scanning = false;
+            return;
+        }
+        if self.reset_transfer && !self.scanning {
+            return;
+        }
+        if self.end_of_head {
+            self.delay = SPIN_UP_CYCLES;
+            self.end_of_head = false;
+            self.position = 0;
+            self.gap_ended = false;
+            return;
+        }
+        if self.delay > 0 {
+            self.delay -= 1;
+            return;
+        }
+
+        self.scanning = true;
+        if self.read_mode {
+            self.read_byte(side);
+        } else {
+            self.write_byte(side);
+        }
+        self.previous_crc_control = self.crc_control;
+
+        self.position += 1;
+        if self.position >= self.tracks[side].len() {
+            self.motor = false;
+        } else {
+            self.delay = BYTE_CYCLES;
+        }
+    }
+
+    fn read_byte(&mut self, side: usize) {
+        let data = self.tracks[side][self.position];
+        if !self.previous_crc_control {
+            self.crc = update_crc(self.crc, data);
+        }
+
+        let mut irq = self.irq_enabled;
+        if !self.ready {
+            self.gap_ended = false;
+            self.crc = 0;
+        } else if data != 0 && !self.gap_ended {
+            self.gap_ended = true;
+            irq = false;
+        }
+
+        if self.gap_ended {
+            self.transfer = true;
+            self.read_data = data;
+            self.irq |= irq;
+        }
+    }
+
+    fn write_byte(&mut self, side: usize) {
+        let mut data = 0;
+        if !self.crc_control {
+            self.transfer = true;
+            data = self.write_data;
+            self.irq |= self.irq_enabled;
+        }
+        if !self.ready {
+            data = 0;
+        }
+
+        if !self.crc_control {
+            self.crc = update_crc(self.crc, data);
+        } else {
+            if !self.previous_crc_control {
+                self.crc = update_crc(update_crc(self.crc, 0), 0);
+            }
+            data = self.crc as u8;
+            self.crc >>= 8;
+        }
+
+        self.tracks[side][self.position] = data;
+        self.modified[side] = true;
+        self.gap_ended = false;
+    }
+}
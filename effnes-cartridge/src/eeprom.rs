@@ -0,0 +1,225 @@
+use crate::bank::RamData;
+
+/// Serial EEPROM chip.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum EepromKind {
+    /// Xicor X24C01: 128 bytes, LSB first, with the word address sent along
+    /// the start byte (no device address).
+    X24C01,
+    /// 24C02: 256 bytes, MSB first, standard I²C addressing.
+    C24C02,
+}
+
+impl EepromKind {
+    /// Size, in bytes.
+    pub fn size(self) -> usize {
+        match self {
+            EepromKind::X24C01 => 0x80,
+            EepromKind::C24C02 => 0x100,
+        }
+    }
+
+    /// Page size; writes past the end of a page wrap around it.
+    fn page(self) -> u8 {
+        match self {
+            EepromKind::X24C01 => 4,
+            EepromKind::C24C02 => 8,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Mode {
+    Idle,
+    Device,
+    Address,
+    Write,
+    Read,
+}
+
+/// Serial (I²C) EEPROM
+///
+/// Bit-banged through its clock (SCL) and data (SDA) lines, as found on
+/// Bandai boards.
+///
+/// ## Behaviour
+///
+/// - A start condition (SDA falling while SCL is high) begins a transfer,
+///   and a stop condition (SDA rising while SCL is high) ends it.
+/// - Bits are sampled on SCL rising edges, and driven on falling edges.
+///   Every byte is followed by an acknowledge clock: the EEPROM pulls SDA
+///   low after accepted bytes, and stops reading when the master doesn't.
+/// - Sequential writes wrap around the chip page, reads around the whole
+///   chip.
+pub struct Eeprom<'a> {
+    kind: EepromKind,
+    data: RamData<'a>,
+    scl: bool,
+    sda: bool,
+    mode: Mode,
+    next: Mode,
+    bit: u8,
+    shift: u8,
+    address: u8,
+    ack: bool,
+    output: bool,
+}
+
+impl<'a> Eeprom<'a> {
+    /// ## Panics
+    ///
+    /// If `data` doesn't match the chip size.
+    pub fn new(kind: EepromKind, data: RamData<'a>) -> Self {
+        assert_eq!(data.len(), kind.size(), "invalid EEPROM size");
+        Self {
+            kind,
+            data,
+            scl: false,
+            sda: false,
+            mode: Mode::Idle,
+            next: Mode::Idle,
+            bit: 0,
+            shift: 0,
+            address: 0,
+            ack: false,
+            output: true,
+        }
+    }
+
+    pub fn kind(&self) -> EepromKind {
+        self.kind
+    }
+
+    pub fn data(&self) -> &RamData<'a> {
+        &self.data
+    }
+
+    pub fn data_mut(&mut self) -> &mut RamData<'a> {
+        &mut self.data
+    }
+
+    /// SDA level driven by the EEPROM (`true` when released).
+    pub fn output(&self) -> bool {
+        self.output
+    }
+
+    /// Updates the SCL and SDA lines driven by the master.
+    pub fn write(&mut self, scl: bool, sda: bool) {
+        match (self.scl, scl) {
+            (true, true) if self.sda && !sda => self.start(),
+            (true, true) if !self.sda && sda => {
+                self.mode = Mode::Idle;
+                self.output = true;
+            }
+            (false, true) => self.rise(sda),
+            (true, false) => self.fall(),
+            _ => {}
+        }
+        self.scl = scl;
+        self.sda = sda;
+    }
+
+    /// Aborts any transfer in progress.
+    pub fn reset(&mut self) {
+        self.mode = Mode::Idle;
+        self.output = true;
+    }
+
+    fn start(&mut self) {
+        self.mode = Mode::Device;
+        self.bit = 0;
+        self.shift = 0;
+        self.output = true;
+    }
+
+    fn rise(&mut self, sda: bool) {
+        match self.mode {
+            Mode::Idle => return,
+            Mode::Read if self.bit == 8 => self.ack = !sda,
+            Mode::Read => {}
+            _ if self.bit < 8 => match self.kind {
+                EepromKind::X24C01 => self.shift |= (sda as u8) << self.bit,
+                EepromKind::C24C02 => self.shift = (self.shift << 1) | sda as u8,
+            },
+            _ => {}
+        }
+        self.bit += 1;
+    }
+
+    fn fall(&mut self) {
+        match (self.mode, self.bit) {
+            (Mode::Idle, _) => {}
+            (Mode::Read, 0..=7) => self.output = self.data_bit(self.bit),
+            (Mode::Read, 8) => self.output = true,
+            (Mode::Read, _) if self.ack => {
+                self.address = ((self.address as usize + 1) % self.kind.size()) as u8;
+                self.bit = 0;
+                self.output = self.data_bit(0);
+            }
+            (Mode::Read, _) => self.mode = Mode::Idle,
+            (_, 8) => {
+                let accepted = self.receive();
+                self.output = !accepted;
+                if !accepted {
+                    self.next = Mode::Idle;
+                }
+            }
+            (_, 9) => {
+                self.output = true;
+                self.mode = self.next;
+                self.bit = 0;
+                self.shift = 0;
+                if self.mode == Mode::Read {
+                    self.output = self.data_bit(0);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Bit `bit` of the current byte, in transfer order.
+    fn data_bit(&self, bit: u8) -> bool {
+        let data = self.data[self.address as usize];
+        match self.kind {
+            EepromKind::X24C01 => data >> bit & 1 != 0,
+            EepromKind::C24C02 => data >> (7 - bit) & 1 != 0,
+        }
+    }
+
+    /// Handles a received byte, and returns whether it's acknowledged.
+    fn receive(&mut self) -> bool {
+        let byte = self.shift;
+        match (self.mode, self.kind) {
+            (Mode::Device, EepromKind::X24C01) => {
+                self.address = byte & 0x7F;
+                self.next = if byte & 0x80 != 0 {
+                    Mode::Read
+                } else {
+                    Mode::Write
+                };
+            }
+            (Mode::Device, EepromKind::C24C02) => {
+                if byte & 0xF0 != 0xA0 {
+                    return false;
+                }
+                self.next = if byte & 0x01 != 0 {
+                    Mode::Read
+                } else {
+                    Mode::Address
+                };
+            }
+            (Mode::Address, _) => {
+                self.address = byte;
+                self.next = Mode::Write;
+            }
+            _ => {
+                self.data[self.address as usize] = byte;
+                let page = self.kind.page();
+                self.address =
+                    (self.address & !(page - 1)) | (self.address.wrapping_add(1) & (page - 1));
+                self.next = Mode::Write;
+            }
+        }
+        true
+    }
+}
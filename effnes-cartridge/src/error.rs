@@ -0,0 +1,50 @@
+use std::fmt::{self, Display};
+
+/// Cartridge error.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// There's no implementation for the board.
+    UnsupportedMapper { mapper: u16, submapper: u8 },
+
+    /// The FDS BIOS isn't 8 KiB long.
+    InvalidBios { len: usize },
+
+    /// Malformed IPS patch.
+    InvalidPatch,
+
+    /// A patch changes the size of the data it applies to.
+    PatchSizeMismatch { expected: usize, actual: usize },
+
+    /// A code/data log doesn't match the size of the ROM chips.
+    CdlSizeMismatch { expected: usize, actual: usize },
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::UnsupportedMapper { mapper, submapper } => {
+                write!(f, "unsupported mapper {}.{}", mapper, submapper)
+            }
+            Error::InvalidBios { len } => {
+                write!(
+                    f,
+                    "invalid FDS BIOS size (${:x} bytes, expected $2000)",
+                    len
+                )
+            }
+            Error::InvalidPatch => f.write_str("malformed IPS patch"),
+            Error::PatchSizeMismatch { expected, actual } => write!(
+                f,
+                "patched data is ${:x} bytes long, expected ${:x}",
+                actual, expected
+            ),
+            Error::CdlSizeMismatch { expected, actual } => write!(
+                f,
+                "code/data log is ${:x} bytes long, expected ${:x}",
+                actual, expected
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
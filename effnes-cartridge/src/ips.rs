@@ -0,0 +1,87 @@
+//! IPS patches, used as diff files for writable media (FDS disks).
+
+use crate::error::Error;
+
+pub const MAGIC: &[u8; 5] = b"PATCH";
+const EOF: &[u8; 3] = b"EOF";
+
+/// Largest record size.
+const MAX_RECORD: usize = 0xFFFF;
+
+/// Creates a patch turning `original` into `modified`.
+///
+/// ## Panics
+///
+/// If `modified` is larger than 16 MiB (the largest patchable offset).
+pub fn create(original: &[u8], modified: &[u8]) -> Vec<u8> {
+    assert!(modified.len() <= 0x100_0000, "data too large for IPS");
+    let mut patch = MAGIC.to_vec();
+    let differs = |i: usize| original.get(i) != Some(&modified[i]);
+
+    let mut offset = 0;
+    while offset < modified.len() {
+        if !differs(offset) {
+            offset += 1;
+            continue;
+        }
+
+        // An offset spelling `EOF` would end the patch early.
+        let start = if offset == 0x454F46 {
+            offset - 1
+        } else {
+            offset
+        };
+        let mut end = offset;
+        while end < modified.len() && end - start < MAX_RECORD && differs(end) {
+            end += 1;
+        }
+
+        patch.extend(&(start as u32).to_be_bytes()[1..]);
+        patch.extend(((end - start) as u16).to_be_bytes());
+        patch.extend(&modified[start..end]);
+        offset = end;
+    }
+
+    patch.extend(EOF);
+    patch
+}
+
+/// Applies `patch` into `data`, growing it if needed.
+///
+/// ## Errors
+///
+/// [Error::InvalidPatch] if the patch doesn't start with [MAGIC], or ends
+/// in the middle of a record.
+pub fn apply(data: &mut Vec<u8>, patch: &[u8]) -> Result<(), Error> {
+    let mut rest = patch.strip_prefix(MAGIC).ok_or(Error::InvalidPatch)?;
+    loop {
+        if rest.starts_with(EOF) {
+            return Ok(());
+        }
+        let [a, b, c, d, e, tail @ ..] = rest else {
+            return Err(Error::InvalidPatch);
+        };
+        let offset = u32::from_be_bytes([0, *a, *b, *c]) as usize;
+        let size = u16::from_be_bytes([*d, *e]) as usize;
+
+        let (bytes, tail) = if size == 0 {
+            // Run-length encoded record.
+            let [f, g, value, tail @ ..] = tail else {
+                return Err(Error::InvalidPatch);
+            };
+            let count = u16::from_be_bytes([*f, *g]) as usize;
+            (vec![*value; count], tail)
+        } else {
+            if tail.len() < size {
+                return Err(Error::InvalidPatch);
+            }
+            (tail[..size].to_vec(), &tail[size..])
+        };
+
+        if data.len() < offset + bytes.len() {
+            data.resize(offset + bytes.len(), 0);
+        }
+        data[offset..offset + bytes.len()].copy_from_slice(&bytes);
+        rest = tail;
+    }
+}
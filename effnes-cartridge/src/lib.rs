@@ -0,0 +1,25 @@
+pub mod audio;
+pub mod bank;
+pub mod cartridge;
+pub mod cdl;
+pub mod chips;
+pub mod disk;
+pub mod eeprom;
+pub mod error;
+pub mod ips;
+pub mod mapper;
+pub mod mappers;
+pub mod report;
+pub mod save;
+
+pub use bank::{Banks, RamData, RomData};
+pub use cartridge::Cartridge;
+pub use cdl::{Cdl, CodeDataLogger};
+pub use chips::Chips;
+pub use error::Error;
+pub use mapper::{Mapper, Mirroring};
+pub use report::MemoryReport;
+pub use save::{FlushPolicy, SaveFile};
+
+#[cfg(test)]
+mod tests;
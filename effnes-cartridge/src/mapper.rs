@@ -0,0 +1,259 @@
+use crate::report::MemoryReport;
+pub use effnes_ines::header::Mirroring;
+
+/// Size of the console internal nametable RAM (CIRAM).
+pub const CIRAM_LEN: usize = 0x800;
+
+/// Size of the nametable RAM seen by four-screen boards (CIRAM plus the
+/// cartridge extra 2 KiB).
+pub const FOUR_SCREEN_VRAM_LEN: usize = 0x1000;
+
+/// Returns the offset of the nametable byte at `addr` ($2000-$3EFF) inside
+/// the nametable RAM, for a given `mirroring`.
+///
+/// Offsets past [CIRAM_LEN] are only returned for [Mirroring::FourScreen].
+pub fn nametable_offset(mirroring: Mirroring, addr: u16) -> usize {
+    let page = match mirroring {
+        Mirroring::Horizontal => (addr >> 11) & 1,
+        Mirroring::Vertical => (addr >> 10) & 1,
+        Mirroring::SingleScreenLower => 0,
+        Mirroring::SingleScreenUpper => 1,
+        Mirroring::FourScreen => (addr >> 10) & 3,
+    };
+    ((page as usize) << 10) | (addr as usize & 0x3FF)
+}
+
+/// Cartridge Mapper
+///
+/// Models the board logic between the cartridge connector and the ROM/RAM
+/// chips. Both sides of the connector are covered:
+///
+/// - CPU side: $4020-$FFFF (registers, PRG-ROM, PRG-RAM).
+/// - PPU side: $0000-$3FFF (pattern tables and nametables; palette accesses
+///   never reach the cartridge).
+///
+/// Only the pattern tables and the mirroring are required for the PPU side:
+/// nametable accesses are routed into the nametable RAM (owned by
+/// [crate::Cartridge]) following [Mapper::mirroring], unless the mapper
+/// overrides [Mapper::ppu_read] / [Mapper::ppu_write].
+pub trait Mapper {
+    /// Reads a byte from `addr` ($4020-$FFFF).
+    ///
+    /// Returns `None` if the cartridge doesn't drive the data bus for `addr`
+    /// (open bus). Implementations may perform side effects.
+    fn cpu_read(&mut self, addr: u16) -> Option<u8> {
+        self.cpu_peek(addr)
+    }
+
+    /// Reads a byte from `addr` ($4020-$FFFF).
+    ///
+    /// This must not mutate the mapper state.
+    fn cpu_peek(&self, addr: u16) -> Option<u8>;
+
+    /// Writes `data` into `addr` ($4020-$FFFF).
+    fn cpu_write(&mut self, addr: u16, data: u8);
+
+    /// Reads a byte from the pattern tables ($0000-$1FFF).
+    fn chr_read(&mut self, addr: u16) -> u8 {
+        self.chr_peek(addr)
+    }
+
+    /// Reads a byte from the pattern tables ($0000-$1FFF).
+    ///
+    /// This must not mutate the mapper state.
+    fn chr_peek(&self, addr: u16) -> u8;
+
+    /// Writes `data` into the pattern tables ($0000-$1FFF).
+    fn chr_write(&mut self, addr: u16, data: u8);
+
+    /// Current nametable arrangement.
+    fn mirroring(&self) -> Mirroring;
+
+    /// Reads a byte from the PPU side ($0000-$3FFF).
+    fn ppu_read(&mut self, addr: u16, vram: &[u8]) -> u8 {
+        let addr = addr & 0x3FFF;
+        if addr < 0x2000 {
+            self.chr_read(addr)
+        } else {
+            vram[nametable_offset(self.mirroring(), addr) % vram.len()]
+        }
+    }
+
+    /// Reads a byte from the PPU side ($0000-$3FFF).
+    ///
+    /// This must not mutate the mapper state.
+    fn ppu_peek(&self, addr: u16, vram: &[u8]) -> u8 {
+        let addr = addr & 0x3FFF;
+        if addr < 0x2000 {
+            self.chr_peek(addr)
+        } else {
+            vram[nametable_offset(self.mirroring(), addr) % vram.len()]
+        }
+    }
+
+    /// Writes `data` into the PPU side ($0000-$3FFF).
+    fn ppu_write(&mut self, addr: u16, data: u8, vram: &mut [u8]) {
+        let addr = addr & 0x3FFF;
+        if addr < 0x2000 {
+            self.chr_write(addr, data);
+        } else {
+            let len = vram.len();
+            vram[nametable_offset(self.mirroring(), addr) % len] = data;
+        }
+    }
+
+    /// Offset inside the PRG-ROM of the byte mapped into `addr`
+    /// ($4020-$FFFF), or `None` if `addr` isn't mapped into the PRG-ROM.
+    ///
+    /// This must not mutate the mapper state.
+    fn prg_rom_offset(&self, _addr: u16) -> Option<usize> {
+        None
+    }
+
+    /// Offset inside the CHR-ROM of the byte mapped into `addr`
+    /// ($0000-$1FFF), or `None` if `addr` isn't mapped into the CHR-ROM
+    /// (e.g. on CHR-RAM boards).
+    ///
+    /// This must not mutate the mapper state.
+    fn chr_rom_offset(&self, _addr: u16) -> Option<usize> {
+        None
+    }
+
+    /// IRQ output (`true` while the mapper asserts /IRQ).
+    fn irq(&self) -> bool {
+        false
+    }
+
+    /// Called once per CPU (M2) cycle.
+    fn cpu_cycle(&mut self) {}
+
+    /// Called on CPU writes outside the cartridge range ($0000-$401F), for
+    /// boards that snoop the bus (e.g. MMC5 watching PPUCTRL/PPUMASK).
+    fn snoop_write(&mut self, _addr: u16, _data: u8) {}
+
+    /// Expansion audio output, already scaled to the APU mixer range (see
+    /// `effnes_apu::mixer::ChannelLevels::mix_with_expansion`).
+    fn audio(&self) -> f32 {
+        0.0
+    }
+
+    /// Called whenever the PPU address line A12 changes, with its new
+    /// level.
+    fn ppu_a12(&mut self, _high: bool) {}
+
+    /// Battery-backed (or otherwise persistent) RAM, if any.
+    fn save_ram(&self) -> Option<&[u8]> {
+        None
+    }
+
+    fn save_ram_mut(&mut self) -> Option<&mut [u8]> {
+        None
+    }
+
+    /// Adds the memory used by the mapper (ROM/RAM chips and registers)
+    /// into `report`.
+    ///
+    /// The default implementation only reports the size of the mapper
+    /// itself, as its chips are unknown.
+    fn memory_report(&self, report: &mut MemoryReport) {
+        report.add("mapper", size_of_val(self));
+    }
+
+    /// Power cycle.
+    fn cold_reset(&mut self) {
+        self.warm_reset();
+    }
+
+    /// Reset button.
+    fn warm_reset(&mut self) {}
+}
+
+impl<M: Mapper + ?Sized> Mapper for Box<M> {
+    fn cpu_read(&mut self, addr: u16) -> Option<u8> {
+        (**self).cpu_read(addr)
+    }
+
+    fn cpu_peek(&self, addr: u16) -> Option<u8> {
+        (**self).cpu_peek(addr)
+    }
+
+    fn cpu_write(&mut self, addr: u16, data: u8) {
+        (**self).cpu_write(addr, data)
+    }
+
+    fn chr_read(&mut self, addr: u16) -> u8 {
+        (**self).chr_read(addr)
+    }
+
+    fn chr_peek(&self, addr: u16) -> u8 {
+        (**self).chr_peek(addr)
+    }
+
+    fn chr_write(&mut self, addr: u16, data: u8) {
+        (**self).chr_write(addr, data)
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        (**self).mirroring()
+    }
+
+    fn ppu_read(&mut self, addr: u16, vram: &[u8]) -> u8 {
+        (**self).ppu_read(addr, vram)
+    }
+
+    fn ppu_peek(&self, addr: u16, vram: &[u8]) -> u8 {
+        (**self).ppu_peek(addr, vram)
+    }
+
+    fn ppu_write(&mut self, addr: u16, data: u8, vram: &mut [u8]) {
+        (**self).ppu_write(addr, data, vram)
+    }
+
+    fn prg_rom_offset(&self, addr: u16) -> Option<usize> {
+        (**self).prg_rom_offset(addr)
+    }
+
+    fn chr_rom_offset(&self, addr: u16) -> Option<usize> {
+        (**self).chr_rom_offset(addr)
+    }
+
+    fn irq(&self) -> bool {
+        (**self).irq()
+    }
+
+    fn cpu_cycle(&mut self) {
+        (**self).cpu_cycle()
+    }
+
+    fn snoop_write(&mut self, addr: u16, data: u8) {
+        (**self).snoop_write(addr, data)
+    }
+
+    fn audio(&self) -> f32 {
+        (**self).audio()
+    }
+
+    fn ppu_a12(&mut self, high: bool) {
+        (**self).ppu_a12(high)
+    }
+
+    fn save_ram(&self) -> Option<&[u8]> {
+        (**self).save_ram()
+    }
+
+    fn save_ram_mut(&mut self) -> Option<&mut [u8]> {
+        (**self).save_ram_mut()
+    }
+
+    fn memory_report(&self, report: &mut MemoryReport) {
+        (**self).memory_report(report)
+    }
+
+    fn cold_reset(&mut self) {
+        (**self).cold_reset()
+    }
+
+    fn warm_reset(&mut self) {
+        (**self).warm_reset()
+    }
+}
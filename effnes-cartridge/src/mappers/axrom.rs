@@ -0,0 +1,109 @@
+use crate::{
+    Banks, Mapper, MemoryReport, Mirroring,
+    chips::Chips,
+    mappers::{bus_conflict, submapper_bus_conflicts},
+};
+
+/// AxROM (mapper 7)
+///
+/// Switchable 32 KiB PRG-ROM bank, 8 KiB of CHR-RAM, and single-screen
+/// mirroring selected by software.
+///
+/// ## Behaviour
+///
+/// - Writes into $8000-$FFFF select the PRG bank (bits 0-2) and the CIRAM
+///   page used by every nametable (bit 4).
+/// - Submapper 2 boards (AMROM) have AND-type bus conflicts; submapper 1
+///   ones (ANROM, AN1ROM, AOROM) don't.
+pub struct AxRom<'a> {
+    chips: Chips<'a>,
+    prg: Banks<1>,
+    chr: Banks<1>,
+    upper_page: bool,
+    bus_conflicts: bool,
+}
+
+impl<'a> AxRom<'a> {
+    pub fn new(chips: Chips<'a>) -> Self {
+        let prg = Banks::new(0x8000, 0x8000, chips.prg_rom.len());
+        let chr = Banks::new(0x0000, 0x2000, chips.chr().len());
+        let bus_conflicts = submapper_bus_conflicts(chips.header.submapper);
+        Self {
+            chips,
+            prg,
+            chr,
+            upper_page: false,
+            bus_conflicts,
+        }
+    }
+}
+
+impl Mapper for AxRom<'_> {
+    fn cpu_peek(&self, addr: u16) -> Option<u8> {
+        match addr {
+            0x6000..=0x7FFF => self.chips.wram_peek(addr),
+            0x8000..=0xFFFF => self.prg.read(&self.chips.prg_rom, addr),
+            _ => None,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, data: u8) {
+        match addr {
+            0x6000..=0x7FFF => self.chips.wram_write(addr, data),
+            0x8000..=0xFFFF => {
+                let data = bus_conflict(self.bus_conflicts, data, self.cpu_peek(addr));
+                self.prg.set(0, (data & 0x07) as usize);
+                self.upper_page = data & 0x10 != 0;
+            }
+            _ => {}
+        }
+    }
+
+    fn chr_peek(&self, addr: u16) -> u8 {
+        self.chr.read(self.chips.chr(), addr).unwrap_or(0)
+    }
+
+    fn chr_write(&mut self, addr: u16, data: u8) {
+        if let Some(chr) = self.chips.chr_mut() {
+            self.chr.write(chr, addr, data);
+        }
+    }
+
+    fn prg_rom_offset(&self, addr: u16) -> Option<usize> {
+        self.chips.prg_rom_offset(&self.prg, addr)
+    }
+
+    fn chr_rom_offset(&self, addr: u16) -> Option<usize> {
+        self.chips.chr_rom_offset(&self.chr, addr)
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        if self.upper_page {
+            Mirroring::SingleScreenUpper
+        } else {
+            Mirroring::SingleScreenLower
+        }
+    }
+
+    fn save_ram(&self) -> Option<&[u8]> {
+        self.chips.save_ram()
+    }
+
+    fn save_ram_mut(&mut self) -> Option<&mut [u8]> {
+        self.chips.save_ram_mut()
+    }
+
+    fn memory_report(&self, report: &mut MemoryReport) {
+        report.add("AxROM", size_of_val(self));
+        self.chips.memory_report(report);
+    }
+
+    fn cold_reset(&mut self) {
+        self.chips.cold_reset();
+    }
+
+    fn warm_reset(&mut self) {
+        self.prg.set(0, 0);
+        self.upper_page = false;
+    }
+}
@@ -0,0 +1,233 @@
+use crate::{
+    Banks, Mapper, MemoryReport, Mirroring,
+    bank::RamData,
+    chips::Chips,
+    eeprom::{Eeprom, EepromKind},
+};
+
+/// Bandai FCG and LZ93D50 (mappers 16 and 159)
+///
+/// A switchable 16 KiB PRG-ROM bank, eight 1 KiB CHR banks, a 16-bit CPU
+/// cycle IRQ counter and, on LZ93D50 boards, a serial [Eeprom] for saves.
+///
+/// ## Behaviour
+///
+/// - Registers are selected by the lowest 4 address bits, at $6000-$7FFF
+///   on FCG boards (submapper 4), at $8000-$FFFF on LZ93D50 boards
+///   (submapper 5 and mapper 159), and at both on submapper 0.
+/// - The IRQ counter decrements on every CPU cycle while enabled, and
+///   raises the IRQ when reaching 0. LZ93D50 boards write a latch instead,
+///   copied into the counter by the control register ($xxxA).
+/// - Mapper 159 boards have an X24C01 (128 bytes); mapper 16 boards with a
+///   battery have a 24C02 (256 bytes). Register $xxxD drives the EEPROM
+///   lines (bit 5 SCL, bit 6 SDA, bit 7 enables reads), and the EEPROM
+///   output is read back into bit 4 of $6000-$7FFF.
+/// - When the header declares PRG-RAM of the EEPROM size, it becomes the
+///   EEPROM contents. The EEPROM is reported by [Mapper::save_ram] whether
+///   or not the header declares a battery, as it's non-volatile.
+pub struct Bandai<'a> {
+    chips: Chips<'a>,
+    prg: Banks<2>,
+    chr: Banks<8>,
+    low_registers: bool,
+    high_registers: bool,
+    latched: bool,
+
+    prg_bank: u8,
+    chr_banks: [u8; 8],
+    mirroring: u8,
+    irq_enabled: bool,
+    latch: u16,
+    counter: u16,
+    pending: bool,
+    eeprom_read: bool,
+    eeprom: Option<Eeprom<'a>>,
+}
+
+impl<'a> Bandai<'a> {
+    pub fn new(mut chips: Chips<'a>) -> Self {
+        let (mapper, submapper) = (chips.header.mapper, chips.header.submapper);
+        let kind = match (mapper, submapper) {
+            (159, _) => Some(EepromKind::X24C01),
+            (_, 4) => None,
+            _ if chips.header.battery => Some(EepromKind::C24C02),
+            _ => None,
+        };
+        let eeprom = kind.map(|kind| {
+            let data = if chips.prg_ram.len() == kind.size() {
+                std::mem::take(&mut chips.prg_ram)
+            } else {
+                RamData::owned(kind.size(), 0xFF)
+            };
+            Eeprom::new(kind, data)
+        });
+
+        let prg = Banks::new(0x8000, 0x8000, chips.prg_rom.len());
+        let chr = Banks::new(0x0000, 0x2000, chips.chr().len());
+        let mut mapper = Self {
+            chips,
+            prg,
+            chr,
+            low_registers: submapper != 5 && mapper != 159,
+            high_registers: submapper != 4,
+            latched: submapper == 5 || mapper == 159,
+            prg_bank: 0,
+            chr_banks: [0; 8],
+            mirroring: 0,
+            irq_enabled: false,
+            latch: 0,
+            counter: 0,
+            pending: false,
+            eeprom_read: false,
+            eeprom,
+        };
+        mapper.update();
+        mapper
+    }
+
+    fn update(&mut self) {
+        self.prg.set(0, self.prg_bank as usize);
+        self.prg.set(1, self.prg.last());
+        for (slot, bank) in self.chr_banks.iter().enumerate() {
+            self.chr.set(slot, *bank as usize);
+        }
+    }
+
+    fn write_register(&mut self, addr: u16, data: u8) {
+        match addr & 0x0F {
+            reg @ 0x0..=0x7 => self.chr_banks[reg as usize] = data,
+            0x8 => self.prg_bank = data & 0x0F,
+            0x9 => self.mirroring = data & 0x03,
+            0xA => {
+                self.irq_enabled = data & 0x01 != 0;
+                self.pending = false;
+                if self.latched {
+                    self.counter = self.latch;
+                }
+            }
+            0xB => self.write_counter(0xFF00, data as u16),
+            0xC => self.write_counter(0x00FF, (data as u16) << 8),
+            0xD => {
+                self.eeprom_read = data & 0x80 != 0;
+                if let Some(eeprom) = &mut self.eeprom {
+                    eeprom.write(data & 0x20 != 0, data & 0x40 != 0);
+                }
+            }
+            _ => {}
+        }
+        self.update();
+    }
+
+    fn write_counter(&mut self, keep: u16, value: u16) {
+        if self.latched {
+            self.latch = (self.latch & keep) | value;
+        } else {
+            self.counter = (self.counter & keep) | value;
+        }
+    }
+}
+
+impl Mapper for Bandai<'_> {
+    fn cpu_peek(&self, addr: u16) -> Option<u8> {
+        match addr {
+            0x6000..=0x7FFF => match &self.eeprom {
+                Some(eeprom) if self.eeprom_read => Some((eeprom.output() as u8) << 4),
+                Some(_) => None,
+                None => self.chips.wram_peek(addr),
+            },
+            0x8000..=0xFFFF => self.prg.read(&self.chips.prg_rom, addr),
+            _ => None,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, data: u8) {
+        match addr {
+            0x6000..=0x7FFF if self.low_registers => self.write_register(addr, data),
+            0x6000..=0x7FFF if self.eeprom.is_none() => self.chips.wram_write(addr, data),
+            0x8000..=0xFFFF if self.high_registers => self.write_register(addr, data),
+            _ => {}
+        }
+    }
+
+    fn chr_peek(&self, addr: u16) -> u8 {
+        self.chr.read(self.chips.chr(), addr).unwrap_or(0)
+    }
+
+    fn chr_write(&mut self, addr: u16, data: u8) {
+        if let Some(chr) = self.chips.chr_mut() {
+            self.chr.write(chr, addr, data);
+        }
+    }
+
+    fn prg_rom_offset(&self, addr: u16) -> Option<usize> {
+        self.chips.prg_rom_offset(&self.prg, addr)
+    }
+
+    fn chr_rom_offset(&self, addr: u16) -> Option<usize> {
+        self.chips.chr_rom_offset(&self.chr, addr)
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        match self.mirroring {
+            0 => Mirroring::Vertical,
+            1 => Mirroring::Horizontal,
+            2 => Mirroring::SingleScreenLower,
+            _ => Mirroring::SingleScreenUpper,
+        }
+    }
+
+    fn irq(&self) -> bool {
+        self.pending
+    }
+
+    fn cpu_cycle(&mut self) {
+        if self.irq_enabled {
+            self.counter = self.counter.wrapping_sub(1);
+            if self.counter == 0 {
+                self.pending = true;
+            }
+        }
+    }
+
+    fn save_ram(&self) -> Option<&[u8]> {
+        match &self.eeprom {
+            Some(eeprom) => Some(eeprom.data()),
+            None => self.chips.save_ram(),
+        }
+    }
+
+    fn save_ram_mut(&mut self) -> Option<&mut [u8]> {
+        match &mut self.eeprom {
+            Some(eeprom) => Some(eeprom.data_mut()),
+            None => self.chips.save_ram_mut(),
+        }
+    }
+
+    fn memory_report(&self, report: &mut MemoryReport) {
+        report.add("Bandai FCG", size_of_val(self));
+        if let Some(eeprom) = &self.eeprom {
+            report.add_ram("EEPROM", eeprom.data());
+        }
+        self.chips.memory_report(report);
+    }
+
+    fn cold_reset(&mut self) {
+        self.chips.cold_reset();
+        self.prg_bank = 0;
+        self.chr_banks = [0; 8];
+        self.mirroring = 0;
+        self.warm_reset();
+    }
+
+    fn warm_reset(&mut self) {
+        self.irq_enabled = false;
+        self.latch = 0;
+        self.counter = 0;
+        self.pending = false;
+        self.eeprom_read = false;
+        if let Some(eeprom) = &mut self.eeprom {
+            eeprom.reset();
+        }
+        self.update();
+    }
+}
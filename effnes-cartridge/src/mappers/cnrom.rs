@@ -0,0 +1,100 @@
+use crate::{
+    Banks, Mapper, MemoryReport, Mirroring,
+    chips::Chips,
+    mappers::{bus_conflict, submapper_bus_conflicts},
+};
+
+/// CNROM (mapper 3)
+///
+/// NROM-like PRG-ROM layout plus a switchable 8 KiB CHR-ROM bank. Mirroring
+/// is hardwired.
+///
+/// ## Behaviour
+///
+/// - Writes into $8000-$FFFF select the CHR bank.
+/// - Submapper 2 boards have AND-type bus conflicts.
+pub struct CnRom<'a> {
+    chips: Chips<'a>,
+    prg: Banks<2>,
+    chr: Banks<1>,
+    bus_conflicts: bool,
+}
+
+impl<'a> CnRom<'a> {
+    pub fn new(chips: Chips<'a>) -> Self {
+        let mut prg = Banks::new(0x8000, 0x8000, chips.prg_rom.len());
+        prg.set(1, 1);
+        let chr = Banks::new(0x0000, 0x2000, chips.chr().len());
+        let bus_conflicts = submapper_bus_conflicts(chips.header.submapper);
+        Self {
+            chips,
+            prg,
+            chr,
+            bus_conflicts,
+        }
+    }
+}
+
+impl Mapper for CnRom<'_> {
+    fn cpu_peek(&self, addr: u16) -> Option<u8> {
+        match addr {
+            0x6000..=0x7FFF => self.chips.wram_peek(addr),
+            0x8000..=0xFFFF => self.prg.read(&self.chips.prg_rom, addr),
+            _ => None,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, data: u8) {
+        match addr {
+            0x6000..=0x7FFF => self.chips.wram_write(addr, data),
+            0x8000..=0xFFFF => {
+                let data = bus_conflict(self.bus_conflicts, data, self.cpu_peek(addr));
+                self.chr.set(0, data as usize);
+            }
+            _ => {}
+        }
+    }
+
+    fn chr_peek(&self, addr: u16) -> u8 {
+        self.chr.read(self.chips.chr(), addr).unwrap_or(0)
+    }
+
+    fn chr_write(&mut self, addr: u16, data: u8) {
+        if let Some(chr) = self.chips.chr_mut() {
+            self.chr.write(chr, addr, data);
+        }
+    }
+
+    fn prg_rom_offset(&self, addr: u16) -> Option<usize> {
+        self.chips.prg_rom_offset(&self.prg, addr)
+    }
+
+    fn chr_rom_offset(&self, addr: u16) -> Option<usize> {
+        self.chips.chr_rom_offset(&self.chr, addr)
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.chips.header.mirroring
+    }
+
+    fn save_ram(&self) -> Option<&[u8]> {
+        self.chips.save_ram()
+    }
+
+    fn save_ram_mut(&mut self) -> Option<&mut [u8]> {
+        self.chips.save_ram_mut()
+    }
+
+    fn memory_report(&self, report: &mut MemoryReport) {
+        report.add("CNROM", size_of_val(self));
+        self.chips.memory_report(report);
+    }
+
+    fn cold_reset(&mut self) {
+        self.chips.cold_reset();
+    }
+
+    fn warm_reset(&mut self) {
+        self.chr.set(0, 0);
+    }
+}
@@ -0,0 +1,92 @@
+use crate::{Banks, Mapper, MemoryReport, Mirroring, chips::Chips, mappers::bus_conflict};
+
+/// Color Dreams (mapper 11)
+///
+/// Switchable 32 KiB PRG-ROM and 8 KiB CHR-ROM banks. Mirroring is
+/// hardwired.
+///
+/// ## Behaviour
+///
+/// - Writes into $8000-$FFFF select the PRG bank (bits 0-1) and the CHR
+///   bank (bits 4-7).
+/// - The register is a discrete latch, so there are always AND-type bus
+///   conflicts.
+pub struct ColorDreams<'a> {
+    chips: Chips<'a>,
+    prg: Banks<1>,
+    chr: Banks<1>,
+}
+
+impl<'a> ColorDreams<'a> {
+    pub fn new(chips: Chips<'a>) -> Self {
+        let prg = Banks::new(0x8000, 0x8000, chips.prg_rom.len());
+        let chr = Banks::new(0x0000, 0x2000, chips.chr().len());
+        Self { chips, prg, chr }
+    }
+}
+
+impl Mapper for ColorDreams<'_> {
+    fn cpu_peek(&self, addr: u16) -> Option<u8> {
+        match addr {
+            0x6000..=0x7FFF => self.chips.wram_peek(addr),
+            0x8000..=0xFFFF => self.prg.read(&self.chips.prg_rom, addr),
+            _ => None,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, data: u8) {
+        match addr {
+            0x6000..=0x7FFF => self.chips.wram_write(addr, data),
+            0x8000..=0xFFFF => {
+                let data = bus_conflict(true, data, self.cpu_peek(addr));
+                self.prg.set(0, (data & 0x03) as usize);
+                self.chr.set(0, (data >> 4) as usize);
+            }
+            _ => {}
+        }
+    }
+
+    fn chr_peek(&self, addr: u16) -> u8 {
+        self.chr.read(self.chips.chr(), addr).unwrap_or(0)
+    }
+
+    fn chr_write(&mut self, addr: u16, data: u8) {
+        if let Some(chr) = self.chips.chr_mut() {
+            self.chr.write(chr, addr, data);
+        }
+    }
+
+    fn prg_rom_offset(&self, addr: u16) -> Option<usize> {
+        self.chips.prg_rom_offset(&self.prg, addr)
+    }
+
+    fn chr_rom_offset(&self, addr: u16) -> Option<usize> {
+        self.chips.chr_rom_offset(&self.chr, addr)
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.chips.header.mirroring
+    }
+
+    fn save_ram(&self) -> Option<&[u8]> {
+        self.chips.save_ram()
+    }
+
+    fn save_ram_mut(&mut self) -> Option<&mut [u8]> {
+        self.chips.save_ram_mut()
+    }
+
+    fn memory_report(&self, report: &mut MemoryReport) {
+        report.add("Color Dreams", size_of_val(self));
+        self.chips.memory_report(report);
+    }
+
+    fn cold_reset(&mut self) {
+        self.chips.cold_reset();
+    }
+
+    fn warm_reset(&mut self) {
+        self.prg.set(0, 0);
+        self.chr.set(0, 0);
+    }
+}
@@ -0,0 +1,343 @@
+use crate::{
+    Mapper, MemoryReport, Mirroring, RamData, RomData, audio::FdsAudio, disk::DiskDrive,
+    error::Error, ips,
+};
+use effnes_bus::power::RamInit;
+use effnes_ines::fds::{Blocks, DiskFormat, DiskImage, SIDE_LEN};
+use std::{fs, io, path::Path};
+
+/// Size of the FDS BIOS ROM.
+pub const BIOS_LEN: usize = 0x2000;
+
+/// Size of the RAM adapter PRG-RAM ($6000-$DFFF).
+pub const PRG_RAM_LEN: usize = 0x8000;
+
+/// Size of the RAM adapter CHR-RAM.
+pub const CHR_RAM_LEN: usize = 0x2000;
+
+/// Reads an FDS BIOS (`disksys.rom`) from a user supplied file.
+///
+/// ## Errors
+///
+/// I/O errors, and [io::ErrorKind::InvalidData] if the file isn't 8 KiB
+/// long.
+pub fn read_bios(path: impl AsRef<Path>) -> io::Result<Vec<u8>> {
+    let bios = fs::read(path)?;
+    if bios.len() != BIOS_LEN {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            Error::InvalidBios { len: bios.len() },
+        ));
+    }
+    Ok(bios)
+}
+
+/// Famicom Disk System
+///
+/// The RAM adapter (32 KiB of PRG-RAM, 8 KiB of CHR-RAM, the BIOS, a timer
+/// IRQ and the [FdsAudio] channel) together with the [DiskDrive].
+///
+/// ```ignore
+/// let bios = fds::read_bios("disksys.rom")?;
+/// let image = DiskImage::parse(&data)?;
+/// let mut fds = Fds::new(bios.into(), &image)?;
+/// fds.load_diff("game.ips")?;
+/// // ...
+/// fds.drive_mut().switch_side(1);
+/// // ...
+/// fds.save_diff("game.ips")?;
+/// ```
+///
+/// ## Behaviour
+///
+/// - $4020-$4026 are write-only and $4030-$4033 read-only; disk registers
+///   (and the timer IRQ) are disabled unless $4023 bit 0 is set, and sound
+///   registers ($4040-$4092) unless bit 1 is.
+/// - The timer IRQ counter decrements on every CPU cycle, and raises the
+///   IRQ when clocked at 0, reloading (or disabling itself, unless $4022
+///   bit 0 is set). Reading $4030 acknowledges both IRQs.
+/// - Disk writes are never written into the image: they're kept in the
+///   drive tracks, and persisted as an IPS diff against the image sides
+///   (in `.fds` layout, without header) through [Fds::save_diff].
+pub struct Fds<'a> {
+    bios: RomData<'a>,
+    prg_ram: RamData<'a>,
+    chr_ram: RamData<'a>,
+    /// Power-on contents of both RAM chips.
+    ram_init: RamInit,
+    /// Image sides in `.fds` layout, for diffs.
+    original: Vec<u8>,
+    drive: DiskDrive,
+
+    disk_enabled: bool,
+    sound_enabled: bool,
+    timer_reload: u16,
+    timer: u16,
+    timer_enabled: bool,
+    timer_repeat: bool,
+    timer_irq: bool,
+    mirroring: Mirroring,
+    external: u8,
+    audio: FdsAudio,
+}
+
+impl<'a> Fds<'a> {
+    /// ## Errors
+    ///
+    /// [Error::InvalidBios] if `bios` isn't [BIOS_LEN] bytes long.
+    pub fn new(bios: RomData<'a>, image: &DiskImage<'_>) -> Result<Self, Error> {
+        if bios.len() != BIOS_LEN {
+            return Err(Error::InvalidBios { len: bios.len() });
+        }
+
+        let drive = DiskDrive::from_sides(image.sides.iter().map(|side| &side[..]), image.format);
+        let original = (0..drive.sides())
+            .flat_map(|side| drive.side_data(side))
+            .collect();
+        Ok(Self {
+            bios,
+            prg_ram: RamData::owned(PRG_RAM_LEN, 0),
+            chr_ram: RamData::owned(CHR_RAM_LEN, 0),
+            ram_init: RamInit::Zero,
+            original,
+            drive,
+            disk_enabled: false,
+            sound_enabled: false,
+            timer_reload: 0,
+            timer: 0,
+            timer_enabled: false,
+            timer_repeat: false,
+            timer_irq: false,
+            mirroring: Mirroring::Horizontal,
+            external: 0,
+            audio: FdsAudio::default(),
+        })
+    }
+
+    /// Fills PRG-RAM and CHR-RAM with the `init` power-on contents (also
+    /// used on every power cycle).
+    pub fn with_ram_init(mut self, init: RamInit) -> Self {
+        self.ram_init = init;
+        init.fill(&mut self.prg_ram);
+        init.fill(&mut self.chr_ram);
+        self
+    }
+
+    pub fn drive(&self) -> &DiskDrive {
+        &self.drive
+    }
+
+    /// Disk drive, for inserting, ejecting and switching disk sides.
+    pub fn drive_mut(&mut self) -> &mut DiskDrive {
+        &mut self.drive
+    }
+
+    /// Whether any side was written since the image (or diff) was loaded.
+    pub fn is_modified(&self) -> bool {
+        (0..self.drive.sides()).any(|side| self.drive.is_modified(side))
+    }
+
+    /// Current disk contents, in `.fds` layout (without header).
+    pub fn disk_data(&self) -> Vec<u8> {
+        (0..self.drive.sides())
+            .flat_map(|side| self.drive.side_data(side))
+            .collect()
+    }
+
+    /// IPS patch from the image into the current disk contents.
+    pub fn diff(&self) -> Vec<u8> {
+        ips::create(&self.original, &self.disk_data())
+    }
+
+    /// Applies an IPS patch created by [Fds::diff] on the image, replacing
+    /// the current disk contents.
+    ///
+    /// ## Errors
+    ///
+    /// [Error::InvalidPatch] for malformed patches, and
+    /// [Error::PatchSizeMismatch] for patches meant for another image.
+    pub fn apply_diff(&mut self, patch: &[u8]) -> Result<(), Error> {
+        let mut data = self.original.clone();
+        ips::apply(&mut data, patch)?;
+        if data.len() != self.original.len() {
+            return Err(Error::PatchSizeMismatch {
+                expected: self.original.len(),
+                actual: data.len(),
+            });
+        }
+
+        let side = self.drive.side();
+        let sides = data.chunks(SIDE_LEN);
+        let mut drive = DiskDrive::from_sides(sides, DiskFormat::Fds);
+        match side {
+            Some(side) => drive.insert(side),
+            None => drive.eject(),
+        }
+        self.drive = drive;
+        Ok(())
+    }
+
+    /// Loads the diff file at `path`, if it exists.
+    ///
+    /// Returns whether a diff was applied.
+    pub fn load_diff(&mut self, path: impl AsRef<Path>) -> io::Result<bool> {
+        let patch = match fs::read(path) {
+            Ok(patch) => patch,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(false),
+            Err(err) => return Err(err),
+        };
+        self.apply_diff(&patch)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        Ok(true)
+    }
+
+    /// Writes the diff file at `path`, if the disk was written.
+    ///
+    /// Returns whether the file was written.
+    pub fn save_diff(&self, path: impl AsRef<Path>) -> io::Result<bool> {
+        if !self.is_modified() {
+            return Ok(false);
+        }
+        fs::write(path, self.diff())?;
+        Ok(true)
+    }
+
+    /// Blocks of the current contents of `side`.
+    pub fn blocks(&self, side: usize) -> Vec<Vec<u8>> {
+        let data = self.drive.side_data(side);
+        Blocks::new(&data, DiskFormat::Fds)
+            .map(<[u8]>::to_vec)
+            .collect()
+    }
+
+    fn read_register(&mut self, addr: u16) -> Option<u8> {
+        match addr {
+            0x4030 if self.disk_enabled => {
+                let status = self.timer_irq as u8 | self.drive.read_status();
+                self.timer_irq = false;
+                Some(status)
+            }
+            0x4031 if self.disk_enabled => Some(self.drive.read_data()),
+            _ => self.cpu_peek(addr),
+        }
+    }
+}
+
+impl Mapper for Fds<'_> {
+    fn cpu_read(&mut self, addr: u16) -> Option<u8> {
+        self.read_register(addr)
+    }
+
+    fn cpu_peek(&self, addr: u16) -> Option<u8> {
+        match addr {
+            0x4030 if self.disk_enabled => Some(self.timer_irq as u8 | self.drive.peek_status()),
+            0x4031 if self.disk_enabled => Some(self.drive.peek_data()),
+            0x4032 if self.disk_enabled => Some(0x40 | self.drive.drive_status()),
+            // Bit 7: battery good.
+            0x4033 if self.disk_enabled => Some(0x80 | (self.external & 0x7F)),
+            0x4040..=0x4092 if self.sound_enabled => self.audio.peek(addr).map(|data| data | 0x40),
+            0x6000..=0xDFFF => Some(self.prg_ram[addr as usize - 0x6000]),
+            0xE000..=0xFFFF => Some(self.bios[addr as usize - 0xE000]),
+            _ => None,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, data: u8) {
+        match addr {
+            0x4020 => self.timer_reload = (self.timer_reload & 0xFF00) | data as u16,
+            0x4021 => self.timer_reload = (self.timer_reload & 0x00FF) | ((data as u16) << 8),
+            0x4022 => {
+                self.timer_repeat = data & 0x01 != 0;
+                self.timer_enabled = data & 0x02 != 0 && self.disk_enabled;
+                self.timer_irq = false;
+                if self.timer_enabled {
+                    self.timer = self.timer_reload;
+                }
+            }
+            0x4023 => {
+                self.disk_enabled = data & 0x01 != 0;
+                self.sound_enabled = data & 0x02 != 0;
+                if !self.disk_enabled {
+                    self.timer_enabled = false;
+                    self.timer_irq = false;
+                }
+            }
+            0x4024 if self.disk_enabled => self.drive.write_data(data),
+            0x4025 if self.disk_enabled => {
+                self.drive.write_control(data);
+                self.mirroring = if data & 0x08 != 0 {
+                    Mirroring::Horizontal
+                } else {
+                    Mirroring::Vertical
+                };
+            }
+            0x4026 if self.disk_enabled => self.external = data,
+            0x4040..=0x408A if self.sound_enabled => self.audio.write(addr, data),
+            0x6000..=0xDFFF => self.prg_ram[addr as usize - 0x6000] = data,
+            _ => {}
+        }
+    }
+
+    fn chr_peek(&self, addr: u16) -> u8 {
+        self.chr_ram[addr as usize & 0x1FFF]
+    }
+
+    fn chr_write(&mut self, addr: u16, data: u8) {
+        self.chr_ram[addr as usize & 0x1FFF] = data;
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn irq(&self) -> bool {
+        self.timer_irq || self.drive.irq()
+    }
+
+    fn cpu_cycle(&mut self) {
+        if self.timer_enabled {
+            if self.timer == 0 {
+                self.timer_irq = true;
+                self.timer = self.timer_reload;
+                self.timer_enabled = self.timer_repeat;
+            } else {
+                self.timer -= 1;
+            }
+        }
+        self.drive.cycle();
+        self.audio.cycle();
+    }
+
+    fn audio(&self) -> f32 {
+        self.audio.output()
+    }
+
+    fn memory_report(&self, report: &mut MemoryReport) {
+        report.add("FDS", size_of_val(self));
+        report.add_rom("BIOS", &self.bios);
+        report.add_ram("PRG-RAM", &self.prg_ram);
+        report.add_ram("CHR-RAM", &self.chr_ram);
+        report.add("disk image", self.original.capacity());
+        report.add(
+            "disk tracks",
+            (0..self.drive.sides())
+                .map(|side| self.drive.track(side).len())
+                .sum(),
+        );
+    }
+
+    fn cold_reset(&mut self) {
+        self.ram_init.fill(&mut self.prg_ram);
+        self.ram_init.fill(&mut self.chr_ram);
+        self.warm_reset();
+    }
+
+    fn warm_reset(&mut self) {
+        self.disk_enabled = false;
+        self.sound_enabled = false;
+        self.timer_enabled = false;
+        self.timer_irq = false;
+        self.drive.write_control(0);
+        self.audio = FdsAudio::default();
+    }
+}
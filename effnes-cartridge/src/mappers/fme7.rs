@@ -0,0 +1,196 @@
+use crate::{Banks, Mapper, MemoryReport, Mirroring, audio::Sunsoft5bAudio, chips::Chips};
+
+/// Sunsoft FME-7 and 5B (mapper 69)
+///
+/// Four switchable 8 KiB PRG banks (the one at $6000 can select PRG-RAM),
+/// eight 1 KiB CHR banks, a 16-bit CPU cycle IRQ counter and, on the 5B,
+/// the [Sunsoft5bAudio] expansion sound.
+///
+/// ## Behaviour
+///
+/// - Registers are written through a command latch ($8000-$9FFF) and a
+///   parameter port ($A000-$BFFF).
+/// - Command $8 maps PRG-ROM or PRG-RAM (bit 6) into $6000; PRG-RAM is
+///   open bus unless enabled (bit 7).
+/// - The IRQ counter decrements on every CPU cycle while enabled (command
+///   $D bit 7), and raises the IRQ when wrapping from $0000 to $FFFF if
+///   IRQs are enabled (bit 0). Writing command $D acknowledges it.
+/// - The audio registers ($C000-$FFFF) are decoded on every board, as the
+///   FME-7 simply ignores them.
+pub struct Fme7<'a> {
+    chips: Chips<'a>,
+    prg: Banks<4>,
+    chr: Banks<8>,
+
+    command: u8,
+    wram: u8,
+    prg_banks: [u8; 3],
+    chr_banks: [u8; 8],
+    mirroring: u8,
+    irq_control: u8,
+    counter: u16,
+    pending: bool,
+    audio: Sunsoft5bAudio,
+}
+
+impl<'a> Fme7<'a> {
+    pub fn new(chips: Chips<'a>) -> Self {
+        let prg = Banks::new(0x8000, 0x8000, chips.prg_rom.len());
+        let chr = Banks::new(0x0000, 0x2000, chips.chr().len());
+        let mut mapper = Self {
+            chips,
+            prg,
+            chr,
+            command: 0,
+            wram: 0,
+            prg_banks: [0; 3],
+            chr_banks: [0; 8],
+            mirroring: 0,
+            irq_control: 0,
+            counter: 0,
+            pending: false,
+            audio: Sunsoft5bAudio::default(),
+        };
+        mapper.update();
+        mapper
+    }
+
+    fn update(&mut self) {
+        for (slot, bank) in self.prg_banks.iter().enumerate() {
+            self.prg.set(slot, *bank as usize);
+        }
+        self.prg.set(3, self.prg.last());
+        for (slot, bank) in self.chr_banks.iter().enumerate() {
+            self.chr.set(slot, *bank as usize);
+        }
+    }
+
+    fn write_parameter(&mut self, data: u8) {
+        match self.command {
+            reg @ 0x0..=0x7 => self.chr_banks[reg as usize] = data,
+            0x8 => self.wram = data,
+            reg @ 0x9..=0xB => self.prg_banks[reg as usize - 9] = data & 0x3F,
+            0xC => self.mirroring = data & 0x03,
+            0xD => {
+                self.irq_control = data;
+                self.pending = false;
+            }
+            0xE => self.counter = (self.counter & 0xFF00) | data as u16,
+            _ => self.counter = (self.counter & 0x00FF) | ((data as u16) << 8),
+        }
+        self.update();
+    }
+
+    /// Offset of `addr` ($6000-$7FFF) inside the 8 KiB bank selected by
+    /// command $8.
+    fn wram_offset(&self, addr: u16) -> usize {
+        (self.wram & 0x3F) as usize * 0x2000 + (addr as usize & 0x1FFF)
+    }
+}
+
+impl Mapper for Fme7<'_> {
+    fn cpu_peek(&self, addr: u16) -> Option<u8> {
+        match addr {
+            0x6000..=0x7FFF if self.wram & 0x40 == 0 => {
+                let rom = &self.chips.prg_rom;
+                (!rom.is_empty()).then(|| rom[self.wram_offset(addr) % rom.len()])
+            }
+            0x6000..=0x7FFF if self.wram & 0x80 != 0 => self.chips.wram_peek(addr),
+            0x8000..=0xFFFF => self.prg.read(&self.chips.prg_rom, addr),
+            _ => None,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, data: u8) {
+        match addr {
+            0x6000..=0x7FFF if self.wram & 0xC0 == 0xC0 => self.chips.wram_write(addr, data),
+            0x8000..=0x9FFF => self.command = data & 0x0F,
+            0xA000..=0xBFFF => self.write_parameter(data),
+            0xC000..=0xDFFF => self.audio.write_address(data),
+            0xE000..=0xFFFF => self.audio.write_data(data),
+            _ => {}
+        }
+    }
+
+    fn chr_peek(&self, addr: u16) -> u8 {
+        self.chr.read(self.chips.chr(), addr).unwrap_or(0)
+    }
+
+    fn chr_write(&mut self, addr: u16, data: u8) {
+        if let Some(chr) = self.chips.chr_mut() {
+            self.chr.write(chr, addr, data);
+        }
+    }
+
+    fn prg_rom_offset(&self, addr: u16) -> Option<usize> {
+        let rom = &self.chips.prg_rom;
+        match addr {
+            0x6000..=0x7FFF if self.wram & 0x40 == 0 && !rom.is_empty() => {
+                Some(self.wram_offset(addr) % rom.len())
+            }
+            _ => self.chips.prg_rom_offset(&self.prg, addr),
+        }
+    }
+
+    fn chr_rom_offset(&self, addr: u16) -> Option<usize> {
+        self.chips.chr_rom_offset(&self.chr, addr)
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        match self.mirroring {
+            0 => Mirroring::Vertical,
+            1 => Mirroring::Horizontal,
+            2 => Mirroring::SingleScreenLower,
+            _ => Mirroring::SingleScreenUpper,
+        }
+    }
+
+    fn irq(&self) -> bool {
+        self.pending
+    }
+
+    fn cpu_cycle(&mut self) {
+        if self.irq_control & 0x80 != 0 {
+            self.counter = self.counter.wrapping_sub(1);
+            if self.counter == 0xFFFF && self.irq_control & 0x01 != 0 {
+                self.pending = true;
+            }
+        }
+        self.audio.cycle();
+    }
+
+    fn audio(&self) -> f32 {
+        self.audio.output()
+    }
+
+    fn save_ram(&self) -> Option<&[u8]> {
+        self.chips.save_ram()
+    }
+
+    fn save_ram_mut(&mut self) -> Option<&mut [u8]> {
+        self.chips.save_ram_mut()
+    }
+
+    fn memory_report(&self, report: &mut MemoryReport) {
+        report.add("FME-7", size_of_val(self));
+        self.chips.memory_report(report);
+    }
+
+    fn cold_reset(&mut self) {
+        self.chips.cold_reset();
+        self.prg_banks = [0; 3];
+        self.chr_banks = [0; 8];
+        self.wram = 0;
+        self.mirroring = 0;
+        self.warm_reset();
+    }
+
+    fn warm_reset(&mut self) {
+        self.command = 0;
+        self.irq_control = 0;
+        self.counter = 0;
+        self.pending = false;
+        self.audio = Sunsoft5bAudio::default();
+        self.update();
+    }
+}
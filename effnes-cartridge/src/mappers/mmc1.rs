@@ -0,0 +1,241 @@
+use crate::{Banks, Mapper, MemoryReport, Mirroring, chips::Chips};
+
+/// MMC1 (mapper 1, SxROM boards)
+///
+/// Switchable PRG-ROM (16 KiB or 32 KiB banks), CHR (4 KiB or 8 KiB banks),
+/// software controlled mirroring and optional (battery-backed) PRG-RAM,
+/// configured through a 5-bit serial port at $8000-$FFFF.
+///
+/// ## Behaviour
+///
+/// - Every write shifts bit 0 into the shift register, and the 5th one
+///   copies it into the register selected by address bits 13-14.
+/// - Writes with bit 7 set clear the shift register and set PRG mode 3.
+/// - Writes on consecutive CPU cycles are ignored, so only the first write
+///   of a read-modify-write instruction reaches the serial port. This needs
+///   [Mapper::cpu_cycle] to be clocked.
+/// - Boards with 8 KiB of CHR reuse the upper CHR bank bits (from the bank
+///   register selected by PPU A12 in 4 KiB mode): bit 4 selects the
+///   256 KiB PRG-ROM half on 512 KiB boards (SUROM, SXROM), and bits 2-3
+///   select the 8 KiB PRG-RAM bank on 16 KiB (SOROM, bit 3 only) and 32 KiB
+///   (SXROM) boards.
+/// - Bit 4 of the PRG bank register disables PRG-RAM (MMC1B).
+/// - Submapper 5 (SEROM, SHROM, SH1ROM) has a fixed 32 KiB PRG-ROM.
+pub struct Mmc1<'a> {
+    chips: Chips<'a>,
+    prg: Banks<2>,
+    chr: Banks<2>,
+    wram: Banks<1>,
+
+    shift: u8,
+    shift_count: u8,
+    control: u8,
+    chr_bank: [u8; 2],
+    prg_bank: u8,
+
+    a12: bool,
+    cycle: u64,
+    last_write: Option<u64>,
+}
+
+impl<'a> Mmc1<'a> {
+    pub fn new(chips: Chips<'a>) -> Self {
+        let prg = Banks::new(0x8000, 0x8000, chips.prg_rom.len());
+        let chr = Banks::new(0x0000, 0x2000, chips.chr().len());
+        let wram = Banks::new(0x6000, 0x2000, chips.prg_ram.len());
+        let mut mapper = Self {
+            chips,
+            prg,
+            chr,
+            wram,
+            shift: 0,
+            shift_count: 0,
+            control: 0x0C,
+            chr_bank: [0; 2],
+            prg_bank: 0,
+            a12: false,
+            cycle: 0,
+            last_write: None,
+        };
+        mapper.update();
+        mapper
+    }
+
+    /// CHR bank register driving the repurposed PRG lines.
+    fn outer_bank(&self) -> u8 {
+        if self.control & 0x10 != 0 && self.a12 {
+            self.chr_bank[1]
+        } else {
+            self.chr_bank[0]
+        }
+    }
+
+    fn wram_enabled(&self) -> bool {
+        self.prg_bank & 0x10 == 0
+    }
+
+    fn update(&mut self) {
+        let outer = self.outer_bank();
+        let large_prg = self.chips.prg_rom.len() > 0x40000;
+        let prg_outer = if large_prg {
+            (outer & 0x10) as usize
+        } else {
+            0
+        };
+
+        let bank = (self.prg_bank & 0x0F) as usize;
+        if self.chips.header.submapper == 5 {
+            self.prg.set_wide(0, 2, 0);
+        } else {
+            match (self.control >> 2) & 0x03 {
+                0 | 1 => {
+                    self.prg.set(0, prg_outer | (bank & !1));
+                    self.prg.set(1, prg_outer | (bank & !1) | 1);
+                }
+                2 => {
+                    self.prg.set(0, prg_outer);
+                    self.prg.set(1, prg_outer | bank);
+                }
+                _ => {
+                    self.prg.set(0, prg_outer | bank);
+                    self.prg.set(1, prg_outer | 0x0F);
+                }
+            }
+        }
+
+        if self.control & 0x10 == 0 {
+            self.chr.set_wide(0, 2, (self.chr_bank[0] >> 1) as usize);
+        } else {
+            self.chr.set(0, self.chr_bank[0] as usize);
+            self.chr.set(1, self.chr_bank[1] as usize);
+        }
+
+        let wram_bank = match self.chips.prg_ram.len() {
+            0x8000 => (outer >> 2) & 0x03,
+            0x4000 => (outer >> 3) & 0x01,
+            _ => 0,
+        };
+        self.wram.set(0, wram_bank as usize);
+    }
+
+    fn write_register(&mut self, addr: u16, data: u8) {
+        match addr & 0x6000 {
+            0x0000 => self.control = data,
+            0x2000 => self.chr_bank[0] = data,
+            0x4000 => self.chr_bank[1] = data,
+            _ => self.prg_bank = data,
+        }
+        self.update();
+    }
+
+    fn write_serial(&mut self, addr: u16, data: u8) {
+        let consecutive = self.last_write == Some(self.cycle.wrapping_sub(1));
+        self.last_write = Some(self.cycle);
+        if consecutive {
+            return;
+        }
+
+        if data & 0x80 != 0 {
+            self.shift = 0;
+            self.shift_count = 0;
+            self.control |= 0x0C;
+            self.update();
+            return;
+        }
+
+        self.shift |= (data & 1) << self.shift_count;
+        self.shift_count += 1;
+        if self.shift_count == 5 {
+            let value = self.shift;
+            self.shift = 0;
+            self.shift_count = 0;
+            self.write_register(addr, value);
+        }
+    }
+}
+
+impl Mapper for Mmc1<'_> {
+    fn cpu_peek(&self, addr: u16) -> Option<u8> {
+        match addr {
+            0x6000..=0x7FFF if self.wram_enabled() => self.wram.read(&self.chips.prg_ram, addr),
+            0x8000..=0xFFFF => self.prg.read(&self.chips.prg_rom, addr),
+            _ => None,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, data: u8) {
+        match addr {
+            0x6000..=0x7FFF if self.wram_enabled() => {
+                self.wram.write(&mut self.chips.prg_ram, addr, data)
+            }
+            0x8000..=0xFFFF => self.write_serial(addr, data),
+            _ => {}
+        }
+    }
+
+    fn chr_peek(&self, addr: u16) -> u8 {
+        self.chr.read(self.chips.chr(), addr).unwrap_or(0)
+    }
+
+    fn chr_write(&mut self, addr: u16, data: u8) {
+        if let Some(chr) = self.chips.chr_mut() {
+            self.chr.write(chr, addr, data);
+        }
+    }
+
+    fn prg_rom_offset(&self, addr: u16) -> Option<usize> {
+        self.chips.prg_rom_offset(&self.prg, addr)
+    }
+
+    fn chr_rom_offset(&self, addr: u16) -> Option<usize> {
+        self.chips.chr_rom_offset(&self.chr, addr)
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        match self.control & 0x03 {
+            0 => Mirroring::SingleScreenLower,
+            1 => Mirroring::SingleScreenUpper,
+            2 => Mirroring::Vertical,
+            _ => Mirroring::Horizontal,
+        }
+    }
+
+    fn cpu_cycle(&mut self) {
+        self.cycle = self.cycle.wrapping_add(1);
+    }
+
+    fn ppu_a12(&mut self, high: bool) {
+        self.a12 = high;
+        if self.control & 0x10 != 0 && self.chr_bank[0] != self.chr_bank[1] {
+            self.update();
+        }
+    }
+
+    fn save_ram(&self) -> Option<&[u8]> {
+        self.chips.save_ram()
+    }
+
+    fn save_ram_mut(&mut self) -> Option<&mut [u8]> {
+        self.chips.save_ram_mut()
+    }
+
+    fn memory_report(&self, report: &mut MemoryReport) {
+        report.add("MMC1", size_of_val(self));
+        self.chips.memory_report(report);
+    }
+
+    fn cold_reset(&mut self) {
+        self.chips.cold_reset();
+        self.chr_bank = [0; 2];
+        self.prg_bank = 0;
+        self.warm_reset();
+    }
+
+    fn warm_reset(&mut self) {
+        self.shift = 0;
+        self.shift_count = 0;
+        self.control |= 0x0C;
+        self.last_write = None;
+        self.update();
+    }
+}
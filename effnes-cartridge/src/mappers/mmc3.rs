@@ -0,0 +1,344 @@
+use crate::{Banks, Mapper, MemoryReport, Mirroring, RamData, chips::Chips};
+
+/// Minimum number of CPU cycles A12 must stay low for the next rising edge
+/// to clock the scanline counter.
+pub const A12_FILTER_CYCLES: u64 = 3;
+
+/// MMC3 board variant.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Mmc3Variant {
+    /// MMC3B/C (Sharp): the IRQ fires whenever the counter is zero after
+    /// being clocked.
+    Sharp,
+    /// MMC3A (NEC): the IRQ only fires when the counter decrements into
+    /// zero, or when it's reloaded through $C001.
+    Nec,
+    /// MMC6 (HKROM): 1 KiB of internal PRG-RAM with per-half protection.
+    Mmc6,
+    /// TQROM (mapper 119): bit 6 of the CHR bank numbers selects 8 KiB of
+    /// CHR-RAM instead of CHR-ROM.
+    TqRom,
+}
+
+impl Mmc3Variant {
+    /// Variant described by a mapper and submapper number.
+    pub fn from_header(mapper: u16, submapper: u8) -> Self {
+        match (mapper, submapper) {
+            (119, _) => Self::TqRom,
+            (_, 1) => Self::Mmc6,
+            (_, 4) => Self::Nec,
+            _ => Self::Sharp,
+        }
+    }
+}
+
+/// MMC3 (mapper 4, TxROM boards), MMC6 and TQROM (mapper 119)
+///
+/// Eight bank registers (two 8 KiB PRG banks, two 2 KiB plus four 1 KiB CHR
+/// banks) set through $8000/$8001, software controlled mirroring, PRG-RAM
+/// protection, and a scanline counter clocked by PPU A12.
+///
+/// ## Behaviour
+///
+/// - The counter is clocked on A12 rising edges after A12 stayed low for
+///   at least [A12_FILTER_CYCLES] CPU cycles, so sprite fetches don't clock
+///   it more than once per scanline. This needs [Mapper::cpu_cycle] to be
+///   clocked.
+/// - When clocked, the counter is reloaded from the latch if it's zero (or
+///   a reload was requested), and decremented otherwise; the IRQ conditions
+///   depend on the [Mmc3Variant].
+/// - Four-screen boards ignore the mirroring register.
+pub struct Mmc3<'a> {
+    chips: Chips<'a>,
+    variant: Mmc3Variant,
+    prg: Banks<4>,
+    chr_rom: Banks<8>,
+    chr_ram: Banks<8>,
+    /// CHR slots mapped into CHR-RAM (TQROM), as a bitmask.
+    chr_ram_slots: u8,
+
+    bank_select: u8,
+    registers: [u8; 8],
+    horizontal: bool,
+    wram_protect: u8,
+
+    irq_latch: u8,
+    irq_counter: u8,
+    irq_reload: bool,
+    irq_enabled: bool,
+    irq_pending: bool,
+
+    a12: bool,
+    a12_fall: u64,
+    cycle: u64,
+}
+
+impl<'a> Mmc3<'a> {
+    pub fn new(mut chips: Chips<'a>) -> Self {
+        let variant = Mmc3Variant::from_header(chips.header.mapper, chips.header.submapper);
+        if variant == Mmc3Variant::TqRom && chips.chr_ram.is_empty() {
+            chips.chr_ram = RamData::with_init(0x2000, chips.ram_init);
+        }
+        let prg = Banks::new(0x8000, 0x8000, chips.prg_rom.len());
+        let chr_rom = Banks::new(0x0000, 0x2000, chips.chr().len());
+        let chr_ram = Banks::new(0x0000, 0x2000, chips.chr_ram.len());
+        let mut mapper = Self {
+            chips,
+            variant,
+            prg,
+            chr_rom,
+            chr_ram,
+            chr_ram_slots: 0,
+            bank_select: 0,
+            registers: [0, 2, 4, 5, 6, 7, 0, 1],
+            horizontal: false,
+            wram_protect: 0,
+            irq_latch: 0,
+            irq_counter: 0,
+            irq_reload: false,
+            irq_enabled: false,
+            irq_pending: false,
+            a12: false,
+            a12_fall: 0,
+            cycle: 0,
+        };
+        mapper.update();
+        mapper
+    }
+
+    pub fn variant(&self) -> Mmc3Variant {
+        self.variant
+    }
+
+    fn update(&mut self) {
+        let r = self.registers.map(|r| r as usize);
+        let second_last = self.prg.count().saturating_sub(2);
+        if self.bank_select & 0x40 == 0 {
+            self.prg.set(0, r[6]);
+            self.prg.set(2, second_last);
+        } else {
+            self.prg.set(0, second_last);
+            self.prg.set(2, r[6]);
+        }
+        self.prg.set(1, r[7]);
+        self.prg.set(3, self.prg.last());
+
+        let banks = [
+            r[0] & !1,
+            r[0] | 1,
+            r[1] & !1,
+            r[1] | 1,
+            r[2],
+            r[3],
+            r[4],
+            r[5],
+        ];
+        let invert = if self.bank_select & 0x80 != 0 { 4 } else { 0 };
+        self.chr_ram_slots = 0;
+        for (i, bank) in banks.into_iter().enumerate() {
+            let slot = i ^ invert;
+            if self.variant == Mmc3Variant::TqRom && bank & 0x40 != 0 {
+                self.chr_ram_slots |= 1 << slot;
+                self.chr_ram.set(slot, bank & 0x07);
+            } else {
+                self.chr_rom.set(slot, bank);
+            }
+        }
+    }
+
+    fn clock_counter(&mut self) {
+        let before = self.irq_counter;
+        let reload = self.irq_reload;
+        if self.irq_counter == 0 || self.irq_reload {
+            self.irq_counter = self.irq_latch;
+            self.irq_reload = false;
+        } else {
+            self.irq_counter -= 1;
+        }
+
+        let fire = match self.variant {
+            Mmc3Variant::Nec => self.irq_counter == 0 && (before != 0 || reload),
+            _ => self.irq_counter == 0,
+        };
+        if fire && self.irq_enabled {
+            self.irq_pending = true;
+        }
+    }
+
+    /// MMC6 internal PRG-RAM ($7000-$7FFF, 1 KiB mirrored).
+    fn mmc6_wram_peek(&self, addr: u16) -> Option<u8> {
+        if self.bank_select & 0x20 == 0 || self.chips.prg_ram.is_empty() {
+            return None;
+        }
+        let readable = |high: bool| {
+            let bit = if high { 0x80 } else { 0x20 };
+            self.wram_protect & bit != 0
+        };
+        if !readable(false) && !readable(true) {
+            return None;
+        }
+
+        let high = addr & 0x200 != 0;
+        if readable(high) {
+            self.chips.wram_peek(addr & 0x3FF)
+        } else {
+            Some(0)
+        }
+    }
+
+    /// Halves are only writable if they're readable too.
+    fn mmc6_wram_write(&mut self, addr: u16, data: u8) {
+        let bit = if addr & 0x200 != 0 { 0x40 } else { 0x10 };
+        let enabled = bit | (bit << 1);
+        if self.bank_select & 0x20 != 0 && self.wram_protect & enabled == enabled {
+            self.chips.wram_write(addr & 0x3FF, data);
+        }
+    }
+
+    fn write_register(&mut self, addr: u16, data: u8) {
+        match (addr & 0xE000, addr & 1) {
+            (0x8000, 0) => {
+                self.bank_select = data;
+                self.update();
+            }
+            (0x8000, _) => {
+                self.registers[(self.bank_select & 0x07) as usize] = data;
+                self.update();
+            }
+            (0xA000, 0) => self.horizontal = data & 1 != 0,
+            (0xA000, _) => {
+                if self.variant != Mmc3Variant::Mmc6 || self.bank_select & 0x20 != 0 {
+                    self.wram_protect = data;
+                }
+            }
+            (0xC000, 0) => self.irq_latch = data,
+            (0xC000, _) => {
+                self.irq_counter = 0;
+                self.irq_reload = true;
+            }
+            (_, 0) => {
+                self.irq_enabled = false;
+                self.irq_pending = false;
+            }
+            _ => self.irq_enabled = true,
+        }
+    }
+}
+
+impl Mapper for Mmc3<'_> {
+    fn cpu_peek(&self, addr: u16) -> Option<u8> {
+        match addr {
+            0x7000..=0x7FFF if self.variant == Mmc3Variant::Mmc6 => self.mmc6_wram_peek(addr),
+            0x6000..=0x7FFF
+                if self.variant != Mmc3Variant::Mmc6 && self.wram_protect & 0x80 != 0 =>
+            {
+                self.chips.wram_peek(addr)
+            }
+            0x8000..=0xFFFF => self.prg.read(&self.chips.prg_rom, addr),
+            _ => None,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, data: u8) {
+        match addr {
+            0x7000..=0x7FFF if self.variant == Mmc3Variant::Mmc6 => {
+                self.mmc6_wram_write(addr, data)
+            }
+            0x6000..=0x7FFF
+                if self.variant != Mmc3Variant::Mmc6 && self.wram_protect & 0xC0 == 0x80 =>
+            {
+                self.chips.wram_write(addr, data)
+            }
+            0x8000..=0xFFFF => self.write_register(addr, data),
+            _ => {}
+        }
+    }
+
+    fn chr_peek(&self, addr: u16) -> u8 {
+        let slot = (addr >> 10) & 0x07;
+        if self.chr_ram_slots & (1 << slot) != 0 {
+            self.chr_ram.read(&self.chips.chr_ram, addr)
+        } else {
+            self.chr_rom.read(self.chips.chr(), addr)
+        }
+        .unwrap_or(0)
+    }
+
+    fn chr_write(&mut self, addr: u16, data: u8) {
+        let slot = (addr >> 10) & 0x07;
+        if self.chr_ram_slots & (1 << slot) != 0 {
+            self.chr_ram.write(&mut self.chips.chr_ram, addr, data);
+        } else if let Some(chr) = self.chips.chr_mut() {
+            self.chr_rom.write(chr, addr, data);
+        }
+    }
+
+    fn prg_rom_offset(&self, addr: u16) -> Option<usize> {
+        self.chips.prg_rom_offset(&self.prg, addr)
+    }
+
+    fn chr_rom_offset(&self, addr: u16) -> Option<usize> {
+        let slot = (addr >> 10) & 0x07;
+        if self.chr_ram_slots & (1 << slot) != 0 {
+            None
+        } else {
+            self.chips.chr_rom_offset(&self.chr_rom, addr)
+        }
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        match self.chips.header.mirroring {
+            Mirroring::FourScreen => Mirroring::FourScreen,
+            _ if self.horizontal => Mirroring::Horizontal,
+            _ => Mirroring::Vertical,
+        }
+    }
+
+    fn irq(&self) -> bool {
+        self.irq_pending
+    }
+
+    fn cpu_cycle(&mut self) {
+        self.cycle = self.cycle.wrapping_add(1);
+    }
+
+    fn ppu_a12(&mut self, high: bool) {
+        if high && !self.a12 && self.cycle.wrapping_sub(self.a12_fall) >= A12_FILTER_CYCLES {
+            self.clock_counter();
+        } else if !high && self.a12 {
+            self.a12_fall = self.cycle;
+        }
+        self.a12 = high;
+    }
+
+    fn save_ram(&self) -> Option<&[u8]> {
+        self.chips.save_ram()
+    }
+
+    fn save_ram_mut(&mut self) -> Option<&mut [u8]> {
+        self.chips.save_ram_mut()
+    }
+
+    fn memory_report(&self, report: &mut MemoryReport) {
+        report.add("MMC3", size_of_val(self));
+        self.chips.memory_report(report);
+    }
+
+    fn cold_reset(&mut self) {
+        self.chips.cold_reset();
+        self.registers = [0, 2, 4, 5, 6, 7, 0, 1];
+        self.horizontal = false;
+        self.wram_protect = 0;
+        self.irq_latch = 0;
+        self.irq_counter = 0;
+        self.warm_reset();
+    }
+
+    fn warm_reset(&mut self) {
+        self.bank_select = 0;
+        self.irq_reload = false;
+        self.irq_enabled = false;
+        self.irq_pending = false;
+        self.update();
+    }
+}
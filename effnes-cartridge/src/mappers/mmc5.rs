@@ -0,0 +1,737 @@
+use crate::{Banks, Mapper, MemoryReport, Mirroring, chips::Chips};
+use effnes_apu::{
+    mixer::{PULSE_TABLE, TND_TABLE},
+    pulse::{Pulse, PulseKind},
+};
+
+/// Size of the MMC5 internal expansion RAM.
+pub const EXRAM_LEN: usize = 0x400;
+
+/// CPU cycles between envelope/length counter clocks of the MMC5 pulses
+/// (about 240 Hz, as they don't use the APU frame sequencer).
+const AUDIO_FRAME_PERIOD: u16 = 7457;
+
+/// Number of CPU cycles without PPU reads after which rendering is
+/// considered stopped.
+const IDLE_CYCLES: u8 = 3;
+
+/// MMC5 expansion audio: two pulse channels (without sweep units) and an
+/// 8-bit PCM channel.
+pub struct Mmc5Audio {
+    pulses: [Pulse; 2],
+    pcm: u8,
+    pcm_read_mode: bool,
+    pcm_irq_enabled: bool,
+    pcm_irq: bool,
+    frame: u16,
+    odd: bool,
+}
+
+impl Default for Mmc5Audio {
+    fn default() -> Self {
+        Self {
+            pulses: [
+                Pulse::new(PulseKind::Expansion),
+                Pulse::new(PulseKind::Expansion),
+            ],
+            pcm: 0,
+            pcm_read_mode: false,
+            pcm_irq_enabled: false,
+            pcm_irq: false,
+            frame: AUDIO_FRAME_PERIOD,
+            odd: false,
+        }
+    }
+}
+
+impl Mmc5Audio {
+    /// Writes `data` into an audio register ($5000-$5015).
+    pub fn write(&mut self, addr: u16, data: u8) {
+        match addr {
+            0x5000..=0x5003 => self.pulses[0].write(addr as u8, data),
+            0x5004..=0x5007 => self.pulses[1].write(addr as u8, data),
+            0x5010 => {
+                self.pcm_read_mode = data & 0x01 != 0;
+                self.pcm_irq_enabled = data & 0x80 != 0;
+            }
+            0x5011 if !self.pcm_read_mode && data != 0 => self.pcm = data,
+            0x5015 => {
+                self.pulses[0].set_enabled(data & 0x01 != 0);
+                self.pulses[1].set_enabled(data & 0x02 != 0);
+            }
+            _ => {}
+        }
+    }
+
+    /// Pulse length counter status ($5015).
+    pub fn status(&self) -> u8 {
+        self.pulses[0].active() as u8 | ((self.pulses[1].active() as u8) << 1)
+    }
+
+    /// PCM status ($5010): bit 7 is set if a PCM IRQ occurred.
+    pub fn pcm_status(&self) -> u8 {
+        ((self.pcm_irq as u8) << 7) | self.pcm_read_mode as u8
+    }
+
+    /// Acknowledges the PCM IRQ.
+    pub fn acknowledge(&mut self) {
+        self.pcm_irq = false;
+    }
+
+    /// Feeds a CPU read from $8000-$BFFF into the PCM channel. While in read
+    /// mode, zero bytes raise the PCM IRQ instead of being output.
+    pub fn pcm_read(&mut self, data: u8) {
+        if self.pcm_read_mode {
+            if data == 0 {
+                self.pcm_irq = true;
+            } else {
+                self.pcm = data;
+            }
+        }
+    }
+
+    pub fn irq(&self) -> bool {
+        self.pcm_irq && self.pcm_irq_enabled
+    }
+
+    /// CPU cycle clock.
+    pub fn cycle(&mut self) {
+        self.odd = !self.odd;
+        if self.odd {
+            for pulse in &mut self.pulses {
+                pulse.clock_timer();
+            }
+        }
+
+        self.frame -= 1;
+        if self.frame == 0 {
+            self.frame = AUDIO_FRAME_PERIOD;
+            for pulse in &mut self.pulses {
+                pulse.clock_quarter();
+                pulse.clock_half();
+            }
+        }
+    }
+
+    /// Output amplitude, in the APU mixer range.
+    pub fn output(&self) -> f32 {
+        let pulses = (self.pulses[0].output() + self.pulses[1].output()) as usize;
+        PULSE_TABLE[pulses] + TND_TABLE[(self.pcm >> 1) as usize]
+    }
+}
+
+/// PPU fetch, as identified by its position on the scanline.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Fetch {
+    /// Background tile fetch: tile column and step (0: nametable,
+    /// 1: attribute, 2-3: pattern), and whether the tile belongs to the next
+    /// scanline (prefetch).
+    Background {
+        x: u8,
+        step: u8,
+        next_line: bool,
+    },
+    Sprite,
+    Other,
+}
+
+impl Fetch {
+    fn classify(index: u16) -> Self {
+        let step = (index % 4) as u8;
+        match index {
+            0..=127 => Fetch::Background {
+                x: 2 + (index / 4) as u8,
+                step,
+                next_line: false,
+            },
+            128..=159 => Fetch::Sprite,
+            160..=167 => Fetch::Background {
+                x: ((index - 160) / 4) as u8,
+                step,
+                next_line: true,
+            },
+            _ => Fetch::Other,
+        }
+    }
+}
+
+/// Background tile being fetched.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+struct Tile {
+    /// Vertical position inside the split region, if the tile is in it.
+    split_y: Option<u16>,
+    /// ExRAM byte of the tile, on extended attribute mode.
+    ext: Option<u8>,
+}
+
+/// MMC5 (mapper 5, ExROM boards)
+///
+/// ## Behaviour
+///
+/// - PRG: four modes ($5100) of 32/16/8 KiB banks ($5114-$5117, bit 7
+///   selects ROM over RAM), plus an 8 KiB PRG-RAM bank at $6000 ($5113).
+///   PRG-RAM is writable only with $5102 = 2 and $5103 = 1.
+/// - CHR: four modes ($5101) of 8/4/2/1 KiB banks, with two register sets:
+///   A ($5120-$5127) and B ($5128-$512B). With 8x16 sprites, sprite fetches
+///   use set A and background fetches set B; otherwise the last written set
+///   is used for everything. $5130 provides the upper bank bits.
+/// - Nametables: each one ($5105) maps into a CIRAM page, ExRAM or the fill
+///   tile/attribute ($5106/$5107). [Mapper::mirroring] only approximates
+///   the mapping.
+/// - ExRAM ($5104): nametable (modes 0/1, only writable while rendering),
+///   extended attributes (mode 1: per tile 4 KiB CHR bank and palette),
+///   CPU RAM (mode 2) and read-only CPU RAM (mode 3).
+/// - Split screen ($5200-$5202): background tiles left or right of a tile
+///   column are fetched from ExRAM, with their own vertical scroll and 4 KiB
+///   CHR page.
+/// - Scanlines are detected from PPU reads (three consecutive reads of the
+///   same nametable address), and compared against $5203 to raise the IRQ.
+///   Rendering is considered stopped after [IDLE_CYCLES] CPU cycles without
+///   PPU reads, on NMI vector reads, and on PPUMASK writes disabling it, so
+///   PPU and CPU clocking must be interleaved.
+/// - $5205/$5206: unsigned 8x8 multiplier.
+/// - Audio: see [Mmc5Audio], routed through [Mapper::audio].
+///
+/// PPUCTRL and PPUMASK writes must be forwarded through
+/// [Mapper::snoop_write].
+pub struct Mmc5<'a> {
+    chips: Chips<'a>,
+    prg_rom: Banks<4>,
+    prg_ram: Banks<4>,
+    /// PRG slots ($8000-$FFFF) mapped into PRG-RAM, as a bitmask.
+    prg_ram_slots: u8,
+    wram: Banks<1>,
+    chr_a: Banks<8>,
+    chr_b: Banks<8>,
+
+    prg_mode: u8,
+    chr_mode: u8,
+    wram_protect: [u8; 2],
+    exram_mode: u8,
+    nametables: u8,
+    fill_tile: u8,
+    fill_attribute: u8,
+    prg_banks: [u8; 4],
+    chr_banks: [u16; 12],
+    chr_upper: u8,
+    last_set_b: bool,
+
+    exram: [u8; EXRAM_LEN],
+
+    split_control: u8,
+    split_scroll: u8,
+    split_page: u8,
+
+    irq_compare: u8,
+    irq_enabled: bool,
+    irq_pending: bool,
+    in_frame: bool,
+    scanline: u8,
+
+    multiplicands: [u8; 2],
+
+    sprites_8x16: bool,
+    last_read: Option<u16>,
+    repeats: u8,
+    fetch: u16,
+    idle: u8,
+    tile: Tile,
+
+    audio: Mmc5Audio,
+}
+
+impl<'a> Mmc5<'a> {
+    pub fn new(chips: Chips<'a>) -> Self {
+        let prg_rom = Banks::new(0x8000, 0x8000, chips.prg_rom.len());
+        let prg_ram = Banks::new(0x8000, 0x8000, chips.prg_ram.len());
+        let wram = Banks::new(0x6000, 0x2000, chips.prg_ram.len());
+        let chr_a = Banks::new(0x0000, 0x2000, chips.chr().len());
+        let chr_b = Banks::new(0x0000, 0x2000, chips.chr().len());
+        let mut mapper = Self {
+            chips,
+            prg_rom,
+            prg_ram,
+            prg_ram_slots: 0,
+            wram,
+            chr_a,
+            chr_b,
+            prg_mode: 3,
+            chr_mode: 0,
+            wram_protect: [0; 2],
+            exram_mode: 0,
+            nametables: 0,
+            fill_tile: 0,
+            fill_attribute: 0,
+            prg_banks: [0xFF; 4],
+            chr_banks: [0; 12],
+            chr_upper: 0,
+            last_set_b: false,
+            exram: [0; EXRAM_LEN],
+            split_control: 0,
+            split_scroll: 0,
+            split_page: 0,
+            irq_compare: 0,
+            irq_enabled: false,
+            irq_pending: false,
+            in_frame: false,
+            scanline: 0,
+            multiplicands: [0xFF; 2],
+            sprites_8x16: false,
+            last_read: None,
+            repeats: 0,
+            fetch: 0,
+            idle: 0,
+            tile: Tile::default(),
+            audio: Mmc5Audio::default(),
+        };
+        mapper.chips.ram_init.fill(&mut mapper.exram);
+        mapper.update_prg();
+        mapper.update_chr();
+        mapper
+    }
+
+    /// Expansion RAM.
+    pub fn exram(&self) -> &[u8; EXRAM_LEN] {
+        &self.exram
+    }
+
+    pub fn in_frame(&self) -> bool {
+        self.in_frame
+    }
+
+    fn update_prg(&mut self) {
+        // (first slot, slots, bank register)
+        let layout: &[(usize, usize, u8)] = match self.prg_mode {
+            0 => &[(0, 4, self.prg_banks[3])],
+            1 => &[(0, 2, self.prg_banks[1]), (2, 2, self.prg_banks[3])],
+            2 => &[
+                (0, 2, self.prg_banks[1]),
+                (2, 1, self.prg_banks[2]),
+                (3, 1, self.prg_banks[3]),
+            ],
+            _ => &[
+                (0, 1, self.prg_banks[0]),
+                (1, 1, self.prg_banks[1]),
+                (2, 1, self.prg_banks[2]),
+                (3, 1, self.prg_banks[3]),
+            ],
+        };
+
+        self.prg_ram_slots = 0;
+        for &(first, slots, register) in layout {
+            // $E000-$FFFF is always mapped into PRG-ROM.
+            let rom = register & 0x80 != 0 || first + slots == 4;
+            let bank = (register & 0x7F) as usize & !(slots - 1);
+            for i in 0..slots {
+                if rom {
+                    self.prg_rom.set(first + i, bank + i);
+                } else {
+                    self.prg_ram_slots |= 1 << (first + i);
+                    self.prg_ram.set(first + i, bank + i);
+                }
+            }
+        }
+    }
+
+    fn update_chr(&mut self) {
+        let r = self.chr_banks.map(|bank| bank as usize);
+        match self.chr_mode {
+            0 => {
+                self.chr_a.set_wide(0, 8, r[7]);
+                self.chr_b.set_wide(0, 8, r[11]);
+            }
+            1 => {
+                self.chr_a.set_wide(0, 4, r[3]);
+                self.chr_a.set_wide(4, 4, r[7]);
+                self.chr_b.set_wide(0, 4, r[11]);
+                self.chr_b.set_wide(4, 4, r[11]);
+            }
+            2 => {
+                for i in 0..4 {
+                    self.chr_a.set_wide(i * 2, 2, r[i * 2 + 1]);
+                    self.chr_b.set_wide(i * 2, 2, r[9 + (i & 1) * 2]);
+                }
+            }
+            _ => {
+                for i in 0..8 {
+                    self.chr_a.set(i, r[i]);
+                    self.chr_b.set(i, r[8 + (i & 3)]);
+                }
+            }
+        }
+    }
+
+    fn wram_writable(&self) -> bool {
+        self.wram_protect == [0x02, 0x01]
+    }
+
+    fn product(&self) -> u16 {
+        self.multiplicands[0] as u16 * self.multiplicands[1] as u16
+    }
+
+    fn stop_frame(&mut self) {
+        self.in_frame = false;
+        self.last_read = None;
+        self.repeats = 0;
+    }
+
+    /// Tracks PPU reads, detecting scanline starts. Returns the fetch
+    /// position of the read inside the scanline.
+    fn detect(&mut self, addr: u16) -> Fetch {
+        self.idle = 0;
+        if (0x2000..0x3000).contains(&addr) && self.last_read == Some(addr) {
+            self.repeats += 1;
+        } else {
+            self.repeats = 0;
+        }
+        self.last_read = Some(addr);
+
+        if self.repeats == 2 {
+            if self.in_frame {
+                self.scanline = self.scanline.wrapping_add(1);
+                if self.scanline == self.irq_compare {
+                    self.irq_pending = true;
+                }
+            } else {
+                self.in_frame = true;
+                self.scanline = 0;
+                self.irq_pending = false;
+            }
+            self.fetch = 0;
+        }
+
+        if !self.in_frame {
+            return Fetch::Other;
+        }
+        let fetch = Fetch::classify(self.fetch);
+        self.fetch = self.fetch.saturating_add(1);
+        fetch
+    }
+
+    fn in_split(&self, x: u8) -> bool {
+        if self.split_control & 0x80 == 0 || self.exram_mode > 1 {
+            return false;
+        }
+        let column = self.split_control & 0x1F;
+        if self.split_control & 0x40 != 0 {
+            x >= column
+        } else {
+            x < column
+        }
+    }
+
+    fn nametable(&self, addr: u16, vram: &[u8]) -> u8 {
+        let offset = (addr & 0x3FF) as usize;
+        match (self.nametables >> (((addr >> 10) & 0x03) * 2)) & 0x03 {
+            page @ (0 | 1) => vram[((page as usize) << 10 | offset) % vram.len()],
+            2 if self.exram_mode <= 1 => self.exram[offset],
+            2 => 0,
+            _ if offset < 0x3C0 => self.fill_tile,
+            _ => self.fill_attribute * 0x55,
+        }
+    }
+
+    fn chr(&self, set_b: bool, addr: u16) -> u8 {
+        let banks = if set_b { &self.chr_b } else { &self.chr_a };
+        banks.read(self.chips.chr(), addr).unwrap_or(0)
+    }
+
+    fn chr_at(&self, offset: usize) -> u8 {
+        let chr = self.chips.chr();
+        match chr.len() {
+            0 => 0,
+            len => chr[offset % len],
+        }
+    }
+
+    fn background_read(&mut self, addr: u16, x: u8, step: u8, line: u8, vram: &[u8]) -> u8 {
+        match step {
+            0 => {
+                self.tile = Tile::default();
+                if self.in_split(x) {
+                    let y = (line as u16 + self.split_scroll as u16) % 240;
+                    self.tile.split_y = Some(y);
+                    return self.exram[((y / 8) * 32 + (x & 0x1F) as u16) as usize];
+                }
+                if self.exram_mode == 1 {
+                    self.tile.ext = Some(self.exram[(addr & 0x3FF) as usize]);
+                }
+                self.nametable(addr, vram)
+            }
+            1 => {
+                if let Some(y) = self.tile.split_y {
+                    let x = (x & 0x1F) as u16;
+                    let byte = self.exram[(0x3C0 + (y / 32) * 8 + x / 4) as usize];
+                    let shift = ((y >> 4) & 1) * 4 + ((x >> 1) & 1) * 2;
+                    ((byte >> shift) & 0x03) * 0x55
+                } else if let Some(ext) = self.tile.ext {
+                    (ext >> 6) * 0x55
+                } else {
+                    self.nametable(addr, vram)
+                }
+            }
+            _ => self.nametable(addr, vram),
+        }
+    }
+
+    fn background_pattern(&self, addr: u16) -> u8 {
+        if let Some(y) = self.tile.split_y {
+            let offset = (self.split_page as usize) * 0x1000
+                + ((addr & 0xFF8) as usize | (y & 0x07) as usize);
+            self.chr_at(offset)
+        } else if let Some(ext) = self.tile.ext {
+            let bank = (ext & 0x3F) as usize | ((self.chr_upper & 0x03) as usize) << 6;
+            self.chr_at(bank * 0x1000 + (addr & 0xFFF) as usize)
+        } else {
+            self.chr(self.sprites_8x16 || self.last_set_b, addr)
+        }
+    }
+
+    fn write_register(&mut self, addr: u16, data: u8) {
+        match addr {
+            0x5000..=0x5015 => self.audio.write(addr, data),
+            0x5100 => {
+                self.prg_mode = data & 0x03;
+                self.update_prg();
+            }
+            0x5101 => {
+                self.chr_mode = data & 0x03;
+                self.update_chr();
+            }
+            0x5102 => self.wram_protect[0] = data & 0x03,
+            0x5103 => self.wram_protect[1] = data & 0x03,
+            0x5104 => self.exram_mode = data & 0x03,
+            0x5105 => self.nametables = data,
+            0x5106 => self.fill_tile = data,
+            0x5107 => self.fill_attribute = data & 0x03,
+            0x5113 => self.wram.set(0, (data & 0x7F) as usize),
+            0x5114..=0x5117 => {
+                self.prg_banks[(addr - 0x5114) as usize] = data;
+                self.update_prg();
+            }
+            0x5120..=0x512B => {
+                let index = (addr - 0x5120) as usize;
+                self.chr_banks[index] = data as u16 | ((self.chr_upper as u16) << 8);
+                self.last_set_b = index >= 8;
+                self.update_chr();
+            }
+            0x5130 => self.chr_upper = data & 0x03,
+            0x5200 => self.split_control = data,
+            0x5201 => self.split_scroll = data,
+            0x5202 => self.split_page = data,
+            0x5203 => self.irq_compare = data,
+            0x5204 => self.irq_enabled = data & 0x80 != 0,
+            0x5205 => self.multiplicands[0] = data,
+            0x5206 => self.multiplicands[1] = data,
+            0x5C00..=0x5FFF => {
+                let offset = (addr & 0x3FF) as usize;
+                match self.exram_mode {
+                    0 | 1 => self.exram[offset] = if self.in_frame { data } else { 0 },
+                    2 => self.exram[offset] = data,
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Mapper for Mmc5<'_> {
+    fn cpu_read(&mut self, addr: u16) -> Option<u8> {
+        let data = self.cpu_peek(addr);
+        match addr {
+            0x5010 => self.audio.acknowledge(),
+            0x5204 => self.irq_pending = false,
+            0x8000..=0xBFFF => self.audio.pcm_read(data.unwrap_or(0)),
+            0xFFFA | 0xFFFB => self.stop_frame(),
+            _ => {}
+        }
+        data
+    }
+
+    fn cpu_peek(&self, addr: u16) -> Option<u8> {
+        match addr {
+            0x5010 => Some(self.audio.pcm_status()),
+            0x5015 => Some(self.audio.status()),
+            0x5204 => Some(((self.irq_pending as u8) << 7) | ((self.in_frame as u8) << 6)),
+            0x5205 => Some(self.product() as u8),
+            0x5206 => Some((self.product() >> 8) as u8),
+            0x5C00..=0x5FFF if self.exram_mode >= 2 => Some(self.exram[(addr & 0x3FF) as usize]),
+            0x6000..=0x7FFF => self.wram.read(&self.chips.prg_ram, addr),
+            0x8000..=0xFFFF => {
+                let slot = (addr - 0x8000) >> 13;
+                if self.prg_ram_slots & (1 << slot) != 0 {
+                    self.prg_ram.read(&self.chips.prg_ram, addr)
+                } else {
+                    self.prg_rom.read(&self.chips.prg_rom, addr)
+                }
+            }
+            _ => None,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, data: u8) {
+        match addr {
+            0x5000..=0x5FFF => self.write_register(addr, data),
+            0x6000..=0x7FFF if self.wram_writable() => {
+                self.wram.write(&mut self.chips.prg_ram, addr, data)
+            }
+            0x8000..=0xDFFF if self.wram_writable() => {
+                let slot = (addr - 0x8000) >> 13;
+                if self.prg_ram_slots & (1 << slot) != 0 {
+                    self.prg_ram.write(&mut self.chips.prg_ram, addr, data);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn chr_peek(&self, addr: u16) -> u8 {
+        self.chr(self.last_set_b, addr)
+    }
+
+    fn chr_write(&mut self, addr: u16, data: u8) {
+        let banks = if self.last_set_b {
+            &self.chr_b
+        } else {
+            &self.chr_a
+        };
+        if let Some(chr) = self.chips.chr_mut() {
+            banks.write(chr, addr, data);
+        }
+    }
+
+    fn prg_rom_offset(&self, addr: u16) -> Option<usize> {
+        match addr {
+            0x8000..=0xFFFF if self.prg_ram_slots & (1 << ((addr - 0x8000) >> 13)) != 0 => None,
+            _ => self.chips.prg_rom_offset(&self.prg_rom, addr),
+        }
+    }
+
+    fn chr_rom_offset(&self, addr: u16) -> Option<usize> {
+        let banks = if self.last_set_b {
+            &self.chr_b
+        } else {
+            &self.chr_a
+        };
+        self.chips.chr_rom_offset(banks, addr)
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        match self.nametables {
+            0x00 => Mirroring::SingleScreenLower,
+            0x55 => Mirroring::SingleScreenUpper,
+            0x44 => Mirroring::Vertical,
+            _ => Mirroring::Horizontal,
+        }
+    }
+
+    fn ppu_read(&mut self, addr: u16, vram: &[u8]) -> u8 {
+        let addr = addr & 0x3FFF;
+        let fetch = self.detect(addr);
+        match (fetch, addr < 0x2000) {
+            (
+                Fetch::Background {
+                    x, step, next_line, ..
+                },
+                false,
+            ) => {
+                let line = self.scanline.wrapping_add(next_line as u8);
+                self.background_read(addr, x, step, line, vram)
+            }
+            (Fetch::Background { .. }, true) => self.background_pattern(addr),
+            (Fetch::Sprite, true) => self.chr(!self.sprites_8x16 && self.last_set_b, addr),
+            (_, true) => self.chr_peek(addr),
+            (_, false) => self.nametable(addr, vram),
+        }
+    }
+
+    fn ppu_peek(&self, addr: u16, vram: &[u8]) -> u8 {
+        let addr = addr & 0x3FFF;
+        if addr < 0x2000 {
+            self.chr_peek(addr)
+        } else {
+            self.nametable(addr, vram)
+        }
+    }
+
+    fn ppu_write(&mut self, addr: u16, data: u8, vram: &mut [u8]) {
+        let addr = addr & 0x3FFF;
+        if addr < 0x2000 {
+            self.chr_write(addr, data);
+            return;
+        }
+
+        let offset = (addr & 0x3FF) as usize;
+        match (self.nametables >> (((addr >> 10) & 0x03) * 2)) & 0x03 {
+            page @ (0 | 1) => {
+                let len = vram.len();
+                vram[((page as usize) << 10 | offset) % len] = data;
+            }
+            2 if self.exram_mode <= 1 => self.exram[offset] = data,
+            _ => {}
+        }
+    }
+
+    fn irq(&self) -> bool {
+        (self.irq_pending && self.irq_enabled) || self.audio.irq()
+    }
+
+    fn cpu_cycle(&mut self) {
+        self.audio.cycle();
+        if self.idle < IDLE_CYCLES {
+            self.idle += 1;
+            if self.idle == IDLE_CYCLES {
+                self.stop_frame();
+            }
+        }
+    }
+
+    fn snoop_write(&mut self, addr: u16, data: u8) {
+        match addr & 0x2007 {
+            0x2000 => self.sprites_8x16 = data & 0x20 != 0,
+            0x2001 if data & 0x18 == 0 => self.stop_frame(),
+            _ => {}
+        }
+    }
+
+    fn audio(&self) -> f32 {
+        self.audio.output()
+    }
+
+    fn save_ram(&self) -> Option<&[u8]> {
+        self.chips.save_ram()
+    }
+
+    fn save_ram_mut(&mut self) -> Option<&mut [u8]> {
+        self.chips.save_ram_mut()
+    }
+
+    fn memory_report(&self, report: &mut MemoryReport) {
+        report.add("MMC5", size_of_val(self));
+        self.chips.memory_report(report);
+    }
+
+    fn cold_reset(&mut self) {
+        self.chips.cold_reset();
+        self.chips.ram_init.fill(&mut self.exram);
+        self.prg_banks = [0xFF; 4];
+        self.chr_banks = [0; 12];
+        self.audio = Mmc5Audio::default();
+        self.warm_reset();
+    }
+
+    fn warm_reset(&mut self) {
+        self.prg_mode = 3;
+        self.chr_mode = 0;
+        self.wram_protect = [0; 2];
+        self.exram_mode = 0;
+        self.nametables = 0;
+        self.split_control = 0;
+        self.irq_enabled = false;
+        self.irq_pending = false;
+        self.stop_frame();
+        self.update_prg();
+        self.update_chr();
+    }
+}
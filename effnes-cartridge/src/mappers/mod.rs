@@ -0,0 +1,92 @@
+//! Board implementations.
+//!
+//! Every mapper is built from the [Chips] of a [Rom]; [from_rom] picks the
+//! implementation from the header mapper number.
+
+pub mod axrom;
+pub mod bandai;
+pub mod cnrom;
+pub mod color_dreams;
+pub mod fds;
+pub mod fme7;
+pub mod gxrom;
+pub mod mmc1;
+pub mod mmc3;
+pub mod mmc5;
+pub mod namco163;
+pub mod nrom;
+pub mod uxrom;
+pub mod vrc4;
+pub mod vrc6;
+pub mod vrc7;
+pub mod vrc_irq;
+
+pub use axrom::AxRom;
+pub use bandai::Bandai;
+pub use cnrom::CnRom;
+pub use color_dreams::ColorDreams;
+pub use fds::Fds;
+pub use fme7::Fme7;
+pub use gxrom::GxRom;
+pub use mmc1::Mmc1;
+pub use mmc3::{Mmc3, Mmc3Variant};
+pub use mmc5::Mmc5;
+pub use namco163::Namco163;
+pub use nrom::NRom;
+pub use uxrom::UxRom;
+pub use vrc_irq::VrcIrq;
+pub use vrc4::Vrc4;
+pub use vrc6::Vrc6;
+pub use vrc7::Vrc7;
+
+use crate::{Mapper, chips::Chips, error::Error};
+use effnes_ines::Rom;
+
+/// Value latched by a board with AND-type bus conflicts: both the CPU and
+/// the PRG-ROM drive the data bus during the write, and zeros win.
+pub(crate) fn bus_conflict(conflicts: bool, data: u8, rom: Option<u8>) -> u8 {
+    match (conflicts, rom) {
+        (true, Some(rom)) => data & rom,
+        _ => data,
+    }
+}
+
+/// Whether a mapper 2, 3 or 7 submapper declares AND-type bus conflicts.
+///
+/// Submapper 0 (unspecified) is treated as conflict-free, as software
+/// written for boards with conflicts doesn't rely on them.
+pub(crate) fn submapper_bus_conflicts(submapper: u8) -> bool {
+    submapper == 2
+}
+
+/// Creates the mapper described by the `rom` header.
+pub fn from_rom<'a>(rom: Rom<'a>) -> Result<Box<dyn Mapper + 'a>, Error> {
+    from_chips(Chips::new(rom))
+}
+
+/// Creates the mapper described by the `chips` header.
+pub fn from_chips<'a>(chips: Chips<'a>) -> Result<Box<dyn Mapper + 'a>, Error> {
+    Ok(match chips.header.mapper {
+        0 => Box::new(NRom::new(chips)),
+        1 => Box::new(Mmc1::new(chips)),
+        2 => Box::new(UxRom::new(chips)),
+        3 => Box::new(CnRom::new(chips)),
+        4 | 119 => Box::new(Mmc3::new(chips)),
+        5 => Box::new(Mmc5::new(chips)),
+        7 => Box::new(AxRom::new(chips)),
+        11 => Box::new(ColorDreams::new(chips)),
+        16 | 159 => Box::new(Bandai::new(chips)),
+        19 => Box::new(Namco163::new(chips)),
+        21 | 22 | 23 | 25 => Box::new(Vrc4::new(chips)),
+        24 | 26 => Box::new(Vrc6::new(chips)),
+        66 => Box::new(GxRom::new(chips)),
+        69 => Box::new(Fme7::new(chips)),
+        85 => Box::new(Vrc7::new(chips)),
+        mapper => {
+            return Err(Error::UnsupportedMapper {
+                mapper,
+                submapper: chips.header.submapper,
+            });
+        }
+    })
+}
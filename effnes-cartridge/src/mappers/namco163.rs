@@ -0,0 +1,292 @@
+use crate::{
+    Banks, Mapper, MemoryReport, Mirroring,
+    audio::{Namco163Audio, namco163::SOUND_RAM_LEN},
+    bank::RamData,
+    chips::Chips,
+};
+
+/// Namco 163 (mapper 19)
+///
+/// Three switchable 8 KiB PRG-ROM banks, twelve 1 KiB banks covering both
+/// the pattern tables and the nametables (each can select CHR-ROM or a
+/// CIRAM page), a 15-bit CPU cycle IRQ counter and the [Namco163Audio]
+/// wavetable sound.
+///
+/// ## Behaviour
+///
+/// - Pattern table bank values $E0-$FF select a CIRAM page, unless disabled
+///   by $E800 bit 6 ($0000-$0FFF) or bit 7 ($1000-$1FFF); nametable bank
+///   values $E0-$FF always do.
+/// - PRG-RAM writes are only allowed while $F800 holds `0100 xxxx`, and
+///   then bits 0-3 protect each 2 KiB page.
+/// - The IRQ counter ($5000 low, $5800 high and enable) counts up on every
+///   CPU cycle until $7FFF, where it raises the IRQ. Writing any of its
+///   registers acknowledges it.
+/// - When the header declares exactly 128 bytes of PRG-RAM, they're the
+///   sound RAM, so the wavetables get battery-backed (as done by NES 2.0
+///   headers); [Mapper::save_ram] then returns them.
+pub struct Namco163<'a> {
+    chips: Chips<'a>,
+    prg: Banks<4>,
+    saved_audio: bool,
+
+    prg_banks: [u8; 3],
+    ppu_banks: [u8; 12],
+    ciram_disable: u8,
+    protect: u8,
+    sound_disable: bool,
+    counter: u16,
+    irq_enabled: bool,
+    pending: bool,
+    audio: Namco163Audio<'a>,
+}
+
+/// Byte fetched from a 1 KiB PPU bank.
+enum Source {
+    Chr(usize),
+    Ciram(usize),
+}
+
+impl<'a> Namco163<'a> {
+    pub fn new(mut chips: Chips<'a>) -> Self {
+        let saved_audio = chips.prg_ram.len() == SOUND_RAM_LEN;
+        let ram = if saved_audio {
+            std::mem::take(&mut chips.prg_ram)
+        } else {
+            RamData::with_init(SOUND_RAM_LEN, chips.ram_init)
+        };
+
+        let prg = Banks::new(0x8000, 0x8000, chips.prg_rom.len());
+        let mut mapper = Self {
+            chips,
+            prg,
+            saved_audio,
+            prg_banks: [0; 3],
+            ppu_banks: [0; 12],
+            ciram_disable: 0,
+            protect: 0,
+            sound_disable: false,
+            counter: 0,
+            irq_enabled: false,
+            pending: false,
+            audio: Namco163Audio::new(ram),
+        };
+        mapper.update();
+        mapper
+    }
+
+    fn update(&mut self) {
+        for (slot, bank) in self.prg_banks.iter().enumerate() {
+            self.prg.set(slot, *bank as usize);
+        }
+        self.prg.set(3, self.prg.last());
+    }
+
+    /// Translates a PPU address ($0000-$2FFF, mirrored up to $3EFF).
+    fn source(&self, addr: u16) -> Source {
+        let addr = addr as usize & 0x3FFF;
+        let slot = if addr >= 0x2000 {
+            8 + ((addr >> 10) & 3)
+        } else {
+            addr >> 10
+        };
+        let bank = self.ppu_banks[slot] as usize;
+        let ciram = bank >= 0xE0 && (slot >= 8 || self.ciram_disable & (0x40 << (slot >> 2)) == 0);
+
+        if ciram {
+            Source::Ciram(((bank & 1) << 10) | (addr & 0x3FF))
+        } else {
+            let chr = self.chips.chr();
+            Source::Chr((bank << 10 | (addr & 0x3FF)) % chr.len().max(1))
+        }
+    }
+
+    fn ppu_fetch(&self, addr: u16, vram: &[u8]) -> u8 {
+        match self.source(addr) {
+            Source::Ciram(offset) => vram[offset % vram.len()],
+            Source::Chr(offset) => self.chips.chr().get(offset).copied().unwrap_or(0),
+        }
+    }
+
+    fn wram_writable(&self, addr: u16) -> bool {
+        self.protect & 0xF0 == 0x40 && self.protect & (1 << ((addr >> 11) & 3)) == 0
+    }
+}
+
+impl Mapper for Namco163<'_> {
+    fn cpu_read(&mut self, addr: u16) -> Option<u8> {
+        match addr {
+            0x4800..=0x4FFF => Some(self.audio.read_data()),
+            _ => self.cpu_peek(addr),
+        }
+    }
+
+    fn cpu_peek(&self, addr: u16) -> Option<u8> {
+        match addr {
+            0x4800..=0x4FFF => Some(self.audio.peek_data()),
+            0x5000..=0x57FF => Some(self.counter as u8),
+            0x5800..=0x5FFF => Some(((self.irq_enabled as u8) << 7) | (self.counter >> 8) as u8),
+            0x6000..=0x7FFF => self.chips.wram_peek(addr),
+            0x8000..=0xFFFF => self.prg.read(&self.chips.prg_rom, addr),
+            _ => None,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, data: u8) {
+        match addr {
+            0x4800..=0x4FFF => self.audio.write_data(data),
+            0x5000..=0x57FF => {
+                self.counter = (self.counter & 0x7F00) | data as u16;
+                self.pending = false;
+            }
+            0x5800..=0x5FFF => {
+                self.counter = (self.counter & 0x00FF) | (((data & 0x7F) as u16) << 8);
+                self.irq_enabled = data & 0x80 != 0;
+                self.pending = false;
+            }
+            0x6000..=0x7FFF if self.wram_writable(addr) => self.chips.wram_write(addr, data),
+            0x8000..=0xDFFF => self.ppu_banks[((addr - 0x8000) >> 11) as usize] = data,
+            0xE000..=0xE7FF => {
+                self.prg_banks[0] = data & 0x3F;
+                self.sound_disable = data & 0x40 != 0;
+                if self.sound_disable {
+                    self.audio.reset();
+                }
+            }
+            0xE800..=0xEFFF => {
+                self.prg_banks[1] = data & 0x3F;
+                self.ciram_disable = data & 0xC0;
+            }
+            0xF000..=0xF7FF => self.prg_banks[2] = data & 0x3F,
+            0xF800..=0xFFFF => {
+                self.protect = data;
+                self.audio.write_address(data);
+            }
+            _ => {}
+        }
+        self.update();
+    }
+
+    fn chr_peek(&self, addr: u16) -> u8 {
+        match self.source(addr) {
+            Source::Chr(offset) => self.chips.chr().get(offset).copied().unwrap_or(0),
+            Source::Ciram(_) => 0,
+        }
+    }
+
+    fn chr_write(&mut self, addr: u16, data: u8) {
+        if let Source::Chr(offset) = self.source(addr)
+            && let Some(chr) = self.chips.chr_mut()
+            && let Some(byte) = chr.get_mut(offset)
+        {
+            *byte = data;
+        }
+    }
+
+    fn prg_rom_offset(&self, addr: u16) -> Option<usize> {
+        self.chips.prg_rom_offset(&self.prg, addr)
+    }
+
+    fn chr_rom_offset(&self, addr: u16) -> Option<usize> {
+        match self.source(addr) {
+            Source::Chr(offset) if offset < self.chips.chr_rom.len() => Some(offset),
+            _ => None,
+        }
+    }
+
+    /// Approximation of the nametable banks; the actual arrangement is
+    /// handled by [Mapper::ppu_read].
+    fn mirroring(&self) -> Mirroring {
+        match [8, 9, 10, 11].map(|slot| self.ppu_banks[slot] & 1) {
+            [0, 0, 0, 0] => Mirroring::SingleScreenLower,
+            [1, 1, 1, 1] => Mirroring::SingleScreenUpper,
+            [0, 0, 1, 1] => Mirroring::Horizontal,
+            _ => Mirroring::Vertical,
+        }
+    }
+
+    fn ppu_read(&mut self, addr: u16, vram: &[u8]) -> u8 {
+        self.ppu_fetch(addr, vram)
+    }
+
+    fn ppu_peek(&self, addr: u16, vram: &[u8]) -> u8 {
+        self.ppu_fetch(addr, vram)
+    }
+
+    fn ppu_write(&mut self, addr: u16, data: u8, vram: &mut [u8]) {
+        match self.source(addr) {
+            Source::Ciram(offset) => {
+                let len = vram.len();
+                vram[offset % len] = data;
+            }
+            Source::Chr(_) => self.chr_write(addr, data),
+        }
+    }
+
+    fn irq(&self) -> bool {
+        self.pending
+    }
+
+    fn cpu_cycle(&mut self) {
+        if self.irq_enabled && self.counter < 0x7FFF {
+            self.counter += 1;
+            if self.counter == 0x7FFF {
+                self.pending = true;
+            }
+        }
+        if !self.sound_disable {
+            self.audio.cycle();
+        }
+    }
+
+    fn audio(&self) -> f32 {
+        if self.sound_disable {
+            0.0
+        } else {
+            self.audio.output()
+        }
+    }
+
+    fn save_ram(&self) -> Option<&[u8]> {
+        if self.saved_audio {
+            self.chips.header.battery.then_some(&**self.audio.ram())
+        } else {
+            self.chips.save_ram()
+        }
+    }
+
+    fn save_ram_mut(&mut self) -> Option<&mut [u8]> {
+        if self.saved_audio {
+            self.chips
+                .header
+                .battery
+                .then_some(&mut **self.audio.ram_mut())
+        } else {
+            self.chips.save_ram_mut()
+        }
+    }
+
+    fn memory_report(&self, report: &mut MemoryReport) {
+        report.add("N163", size_of_val(self));
+        report.add_ram("N163 sound RAM", self.audio.ram());
+        self.chips.memory_report(report);
+    }
+
+    fn cold_reset(&mut self) {
+        self.chips.cold_reset();
+        self.prg_banks = [0; 3];
+        self.ppu_banks = [0; 12];
+        self.warm_reset();
+    }
+
+    fn warm_reset(&mut self) {
+        self.ciram_disable = 0;
+        self.protect = 0;
+        self.sound_disable = false;
+        self.counter = 0;
+        self.irq_enabled = false;
+        self.pending = false;
+        self.audio.reset();
+        self.update();
+    }
+}
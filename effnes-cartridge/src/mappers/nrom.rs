@@ -0,0 +1,76 @@
+use crate::{Banks, Mapper, MemoryReport, Mirroring, chips::Chips};
+
+/// NROM (mapper 0)
+///
+/// 16 KiB or 32 KiB of PRG-ROM at $8000 (16 KiB chips are mirrored at
+/// $C000), 8 KiB of CHR-ROM or CHR-RAM, and optional PRG-RAM at $6000
+/// (Family BASIC). Mirroring is hardwired.
+pub struct NRom<'a> {
+    chips: Chips<'a>,
+    prg: Banks<2>,
+    chr: Banks<1>,
+}
+
+impl<'a> NRom<'a> {
+    pub fn new(chips: Chips<'a>) -> Self {
+        let mut prg = Banks::new(0x8000, 0x8000, chips.prg_rom.len());
+        prg.set(1, 1);
+        let chr = Banks::new(0x0000, 0x2000, chips.chr().len());
+        Self { chips, prg, chr }
+    }
+}
+
+impl Mapper for NRom<'_> {
+    fn cpu_peek(&self, addr: u16) -> Option<u8> {
+        match addr {
+            0x6000..=0x7FFF => self.chips.wram_peek(addr),
+            0x8000..=0xFFFF => self.prg.read(&self.chips.prg_rom, addr),
+            _ => None,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, data: u8) {
+        if let 0x6000..=0x7FFF = addr {
+            self.chips.wram_write(addr, data);
+        }
+    }
+
+    fn chr_peek(&self, addr: u16) -> u8 {
+        self.chr.read(self.chips.chr(), addr).unwrap_or(0)
+    }
+
+    fn chr_write(&mut self, addr: u16, data: u8) {
+        if let Some(chr) = self.chips.chr_mut() {
+            self.chr.write(chr, addr, data);
+        }
+    }
+
+    fn prg_rom_offset(&self, addr: u16) -> Option<usize> {
+        self.chips.prg_rom_offset(&self.prg, addr)
+    }
+
+    fn chr_rom_offset(&self, addr: u16) -> Option<usize> {
+        self.chips.chr_rom_offset(&self.chr, addr)
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.chips.header.mirroring
+    }
+
+    fn save_ram(&self) -> Option<&[u8]> {
+        self.chips.save_ram()
+    }
+
+    fn save_ram_mut(&mut self) -> Option<&mut [u8]> {
+        self.chips.save_ram_mut()
+    }
+
+    fn memory_report(&self, report: &mut MemoryReport) {
+        report.add("NROM", size_of_val(self));
+        self.chips.memory_report(report);
+    }
+
+    fn cold_reset(&mut self) {
+        self.chips.cold_reset();
+    }
+}
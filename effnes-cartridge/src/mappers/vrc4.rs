@@ -0,0 +1,211 @@
+use crate::{Banks, Mapper, MemoryReport, Mirroring, chips::Chips, mappers::vrc_irq::VrcIrq};
+
+/// CPU address lines wired into the VRC2/VRC4 register select pins, as
+/// `(A0 mask, A1 mask)`.
+///
+/// Submapper 0 ORs every wiring the mapper number was used for.
+fn address_lines(mapper: u16, submapper: u8) -> (u16, u16) {
+    match (mapper, submapper) {
+        // VRC4a, VRC4c
+        (21, 1) => (0x02, 0x04),
+        (21, 2) => (0x40, 0x80),
+        (21, _) => (0x42, 0x84),
+        // VRC2a
+        (22, _) => (0x02, 0x01),
+        // VRC4f / VRC2b, VRC4e
+        (23, 1 | 3) => (0x01, 0x02),
+        (23, 2) => (0x04, 0x08),
+        (23, _) => (0x05, 0x0A),
+        // VRC4b / VRC2c, VRC4d
+        (25, 1 | 3) => (0x02, 0x01),
+        (25, 2) => (0x08, 0x04),
+        (_, _) => (0x0A, 0x05),
+    }
+}
+
+/// Konami VRC2 and VRC4 (mappers 21, 22, 23 and 25)
+///
+/// Two switchable 8 KiB PRG-ROM banks plus two fixed ones, eight 1 KiB CHR
+/// banks set by nibbles, and software controlled mirroring.
+///
+/// ## Behaviour
+///
+/// - The register select pins are wired to different CPU address lines on
+///   every board, as selected by the mapper and submapper numbers (see
+///   [address_lines]).
+/// - VRC2 boards (mapper 22, and submapper 3 of mappers 23 and 25) have no
+///   IRQ, no PRG swap mode and 1-bit mirroring; without PRG-RAM, bit 0 of
+///   $6000-$6FFF is a readable latch. VRC2a (mapper 22) ignores the lowest
+///   CHR bank bit.
+/// - VRC4 boards add the PRG swap mode ($9002 bit 1), single-screen
+///   mirroring, 9-bit CHR banks and the [VrcIrq] counter ($F000-$F003).
+pub struct Vrc4<'a> {
+    chips: Chips<'a>,
+    prg: Banks<4>,
+    chr: Banks<8>,
+    lines: (u16, u16),
+    vrc2: bool,
+
+    prg_banks: [u8; 2],
+    swap_mode: bool,
+    chr_banks: [u16; 8],
+    mirroring: u8,
+    latch: u8,
+    irq: VrcIrq,
+}
+
+impl<'a> Vrc4<'a> {
+    pub fn new(chips: Chips<'a>) -> Self {
+        let (mapper, submapper) = (chips.header.mapper, chips.header.submapper);
+        let prg = Banks::new(0x8000, 0x8000, chips.prg_rom.len());
+        let chr = Banks::new(0x0000, 0x2000, chips.chr().len());
+        let mut mapper = Self {
+            chips,
+            prg,
+            chr,
+            lines: address_lines(mapper, submapper),
+            vrc2: mapper == 22 || (matches!(mapper, 23 | 25) && submapper == 3),
+            prg_banks: [0; 2],
+            swap_mode: false,
+            chr_banks: [0; 8],
+            mirroring: 0,
+            latch: 0,
+            irq: VrcIrq::default(),
+        };
+        mapper.update();
+        mapper
+    }
+
+    fn update(&mut self) {
+        let second_last = self.prg.count().saturating_sub(2);
+        let (first, third) = if self.swap_mode {
+            (second_last, self.prg_banks[0] as usize)
+        } else {
+            (self.prg_banks[0] as usize, second_last)
+        };
+        self.prg.set(0, first);
+        self.prg.set(1, self.prg_banks[1] as usize);
+        self.prg.set(2, third);
+        self.prg.set(3, self.prg.last());
+
+        let shift = (self.chips.header.mapper == 22) as u16;
+        for (slot, bank) in self.chr_banks.iter().enumerate() {
+            self.chr.set(slot, (bank >> shift) as usize);
+        }
+    }
+
+    /// Register index (0..=3) selected by `addr`.
+    fn select(&self, addr: u16) -> u8 {
+        (addr & self.lines.0 != 0) as u8 | (((addr & self.lines.1 != 0) as u8) << 1)
+    }
+
+    fn write_register(&mut self, addr: u16, data: u8) {
+        let reg = self.select(addr);
+        match (addr & 0xF000, reg) {
+            (0x8000, _) => self.prg_banks[0] = data & 0x1F,
+            (0x9000, 0 | 1) if self.vrc2 => self.mirroring = data & 0x01,
+            (0x9000, 0) => self.mirroring = data & 0x03,
+            (0x9000, 2) if !self.vrc2 => self.swap_mode = data & 0x02 != 0,
+            (0x9000, _) => {}
+            (0xA000, _) => self.prg_banks[1] = data & 0x1F,
+            (0xB000..=0xE000, reg) => {
+                let bank = &mut self.chr_banks
+                    [(((addr & 0xF000) - 0xB000) >> 11) as usize | (reg >> 1) as usize];
+                if reg & 1 == 0 {
+                    *bank = (*bank & !0x0F) | (data & 0x0F) as u16;
+                } else {
+                    let high = if self.vrc2 { 0x0F } else { 0x1F };
+                    *bank = (*bank & 0x0F) | (((data & high) as u16) << 4);
+                }
+            }
+            _ if self.vrc2 => {}
+            (_, 0) => self.irq.write_latch_low(data),
+            (_, 1) => self.irq.write_latch_high(data),
+            (_, 2) => self.irq.write_control(data),
+            _ => self.irq.acknowledge(),
+        }
+        self.update();
+    }
+}
+
+impl Mapper for Vrc4<'_> {
+    fn cpu_peek(&self, addr: u16) -> Option<u8> {
+        match addr {
+            0x6000..=0x6FFF if self.vrc2 && self.chips.prg_ram.is_empty() => Some(self.latch & 1),
+            0x6000..=0x7FFF => self.chips.wram_peek(addr),
+            0x8000..=0xFFFF => self.prg.read(&self.chips.prg_rom, addr),
+            _ => None,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, data: u8) {
+        match addr {
+            0x6000..=0x6FFF if self.vrc2 && self.chips.prg_ram.is_empty() => self.latch = data,
+            0x6000..=0x7FFF => self.chips.wram_write(addr, data),
+            0x8000..=0xFFFF => self.write_register(addr, data),
+            _ => {}
+        }
+    }
+
+    fn chr_peek(&self, addr: u16) -> u8 {
+        self.chr.read(self.chips.chr(), addr).unwrap_or(0)
+    }
+
+    fn chr_write(&mut self, addr: u16, data: u8) {
+        if let Some(chr) = self.chips.chr_mut() {
+            self.chr.write(chr, addr, data);
+        }
+    }
+
+    fn prg_rom_offset(&self, addr: u16) -> Option<usize> {
+        self.chips.prg_rom_offset(&self.prg, addr)
+    }
+
+    fn chr_rom_offset(&self, addr: u16) -> Option<usize> {
+        self.chips.chr_rom_offset(&self.chr, addr)
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        match self.mirroring {
+            0 => Mirroring::Vertical,
+            1 => Mirroring::Horizontal,
+            2 => Mirroring::SingleScreenLower,
+            _ => Mirroring::SingleScreenUpper,
+        }
+    }
+
+    fn irq(&self) -> bool {
+        self.irq.irq()
+    }
+
+    fn cpu_cycle(&mut self) {
+        self.irq.cycle();
+    }
+
+    fn save_ram(&self) -> Option<&[u8]> {
+        self.chips.save_ram()
+    }
+
+    fn save_ram_mut(&mut self) -> Option<&mut [u8]> {
+        self.chips.save_ram_mut()
+    }
+
+    fn memory_report(&self, report: &mut MemoryReport) {
+        report.add(if self.vrc2 { "VRC2" } else { "VRC4" }, size_of_val(self));
+        self.chips.memory_report(report);
+    }
+
+    fn cold_reset(&mut self) {
+        self.chips.cold_reset();
+        self.prg_banks = [0; 2];
+        self.chr_banks = [0; 8];
+        self.mirroring = 0;
+        self.warm_reset();
+    }
+
+    fn warm_reset(&mut self) {
+        self.swap_mode = false;
+        self.irq = VrcIrq::default();
+        self.update();
+    }
+}
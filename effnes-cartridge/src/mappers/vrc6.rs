@@ -0,0 +1,174 @@
+use crate::{
+    Banks, Mapper, MemoryReport, Mirroring, audio::Vrc6Audio, chips::Chips,
+    mappers::vrc_irq::VrcIrq,
+};
+
+/// Konami VRC6 (mappers 24 and 26)
+///
+/// A 16 KiB and an 8 KiB switchable PRG-ROM bank, eight 1 KiB CHR banks,
+/// the [VrcIrq] counter and the [Vrc6Audio] expansion sound.
+///
+/// ## Behaviour
+///
+/// - Mapper 26 (VRC6b) swaps the A0 and A1 register select lines.
+/// - $8000 selects the 16 KiB bank at $8000, $C000 the 8 KiB bank at $C000,
+///   and $E000-$FFFF is fixed to the last 8 KiB bank.
+/// - $B003 bit 7 enables PRG-RAM, and bits 2-3 select the mirroring. Only
+///   the PPU banking mode 0 (1 KiB CHR banks, CIRAM nametables) is
+///   implemented, which is the one used by every licensed game.
+pub struct Vrc6<'a> {
+    chips: Chips<'a>,
+    prg: Banks<4>,
+    chr: Banks<8>,
+    swapped: bool,
+
+    prg_banks: [u8; 2],
+    chr_banks: [u8; 8],
+    control: u8,
+    irq: VrcIrq,
+    audio: Vrc6Audio,
+}
+
+impl<'a> Vrc6<'a> {
+    pub fn new(chips: Chips<'a>) -> Self {
+        let prg = Banks::new(0x8000, 0x8000, chips.prg_rom.len());
+        let chr = Banks::new(0x0000, 0x2000, chips.chr().len());
+        let mut mapper = Self {
+            swapped: chips.header.mapper == 26,
+            chips,
+            prg,
+            chr,
+            prg_banks: [0; 2],
+            chr_banks: [0; 8],
+            control: 0,
+            irq: VrcIrq::default(),
+            audio: Vrc6Audio::default(),
+        };
+        mapper.update();
+        mapper
+    }
+
+    fn update(&mut self) {
+        self.prg.set_wide(0, 2, self.prg_banks[0] as usize);
+        self.prg.set(2, self.prg_banks[1] as usize);
+        self.prg.set(3, self.prg.last());
+        for (slot, bank) in self.chr_banks.iter().enumerate() {
+            self.chr.set(slot, *bank as usize);
+        }
+    }
+
+    fn ram_enabled(&self) -> bool {
+        self.control & 0x80 != 0
+    }
+
+    fn write_register(&mut self, addr: u16, data: u8) {
+        let reg = if self.swapped {
+            ((addr & 1) << 1 | (addr >> 1) & 1) as u8
+        } else {
+            (addr & 3) as u8
+        };
+
+        match (addr & 0xF000, reg) {
+            (0x8000, _) => self.prg_banks[0] = data & 0x0F,
+            (0x9000, 3) => self.audio.write_control(data),
+            (0x9000, reg) => self.audio.write(0, reg, data),
+            (0xA000, 3) => {}
+            (0xA000, reg) => self.audio.write(1, reg, data),
+            (0xB000, 3) => self.control = data,
+            (0xB000, reg) => self.audio.write(2, reg, data),
+            (0xC000, _) => self.prg_banks[1] = data & 0x1F,
+            (0xD000, reg) => self.chr_banks[reg as usize] = data,
+            (0xE000, reg) => self.chr_banks[4 + reg as usize] = data,
+            (_, 0) => self.irq.write_latch(data),
+            (_, 1) => self.irq.write_control(data),
+            (_, 2) => self.irq.acknowledge(),
+            _ => {}
+        }
+        self.update();
+    }
+}
+
+impl Mapper for Vrc6<'_> {
+    fn cpu_peek(&self, addr: u16) -> Option<u8> {
+        match addr {
+            0x6000..=0x7FFF if self.ram_enabled() => self.chips.wram_peek(addr),
+            0x8000..=0xFFFF => self.prg.read(&self.chips.prg_rom, addr),
+            _ => None,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, data: u8) {
+        match addr {
+            0x6000..=0x7FFF if self.ram_enabled() => self.chips.wram_write(addr, data),
+            0x8000..=0xFFFF => self.write_register(addr, data),
+            _ => {}
+        }
+    }
+
+    fn chr_peek(&self, addr: u16) -> u8 {
+        self.chr.read(self.chips.chr(), addr).unwrap_or(0)
+    }
+
+    fn chr_write(&mut self, addr: u16, data: u8) {
+        if let Some(chr) = self.chips.chr_mut() {
+            self.chr.write(chr, addr, data);
+        }
+    }
+
+    fn prg_rom_offset(&self, addr: u16) -> Option<usize> {
+        self.chips.prg_rom_offset(&self.prg, addr)
+    }
+
+    fn chr_rom_offset(&self, addr: u16) -> Option<usize> {
+        self.chips.chr_rom_offset(&self.chr, addr)
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        match (self.control >> 2) & 3 {
+            0 => Mirroring::Vertical,
+            1 => Mirroring::Horizontal,
+            2 => Mirroring::SingleScreenLower,
+            _ => Mirroring::SingleScreenUpper,
+        }
+    }
+
+    fn irq(&self) -> bool {
+        self.irq.irq()
+    }
+
+    fn cpu_cycle(&mut self) {
+        self.irq.cycle();
+        self.audio.cycle();
+    }
+
+    fn audio(&self) -> f32 {
+        self.audio.output()
+    }
+
+    fn save_ram(&self) -> Option<&[u8]> {
+        self.chips.save_ram()
+    }
+
+    fn save_ram_mut(&mut self) -> Option<&mut [u8]> {
+        self.chips.save_ram_mut()
+    }
+
+    fn memory_report(&self, report: &mut MemoryReport) {
+        report.add("VRC6", size_of_val(self));
+        self.chips.memory_report(report);
+    }
+
+    fn cold_reset(&mut self) {
+        self.chips.cold_reset();
+        self.prg_banks = [0; 2];
+        self.chr_banks = [0; 8];
+        self.control = 0;
+        self.warm_reset();
+    }
+
+    fn warm_reset(&mut self) {
+        self.irq = VrcIrq::default();
+        self.audio = Vrc6Audio::default();
+        self.update();
+    }
+}
@@ -0,0 +1,186 @@
+use crate::{
+    Banks, Mapper, MemoryReport, Mirroring, audio::Opll, chips::Chips, mappers::vrc_irq::VrcIrq,
+};
+
+/// Konami VRC7 (mapper 85)
+///
+/// Three switchable 8 KiB PRG-ROM banks, eight 1 KiB CHR banks, the
+/// [VrcIrq] counter and the [Opll] FM synthesizer.
+///
+/// ## Behaviour
+///
+/// - The second register of every pair is selected by A4 on VRC7a
+///   (submapper 2) and by A3 on VRC7b (submapper 1); submapper 0 decodes
+///   both.
+/// - The OPLL is accessed through $9010 (register select) and $9030 (data),
+///   and is silenced and reset while $E000 bit 6 is set.
+/// - $E000 bit 7 enables PRG-RAM, and bits 0-1 select the mirroring.
+pub struct Vrc7<'a> {
+    chips: Chips<'a>,
+    prg: Banks<4>,
+    chr: Banks<8>,
+    line: u16,
+
+    prg_banks: [u8; 3],
+    chr_banks: [u8; 8],
+    control: u8,
+    irq: VrcIrq,
+    audio: Opll,
+}
+
+impl<'a> Vrc7<'a> {
+    pub fn new(chips: Chips<'a>) -> Self {
+        let prg = Banks::new(0x8000, 0x8000, chips.prg_rom.len());
+        let chr = Banks::new(0x0000, 0x2000, chips.chr().len());
+        let mut mapper = Self {
+            line: match chips.header.submapper {
+                1 => 0x08,
+                2 => 0x10,
+                _ => 0x18,
+            },
+            chips,
+            prg,
+            chr,
+            prg_banks: [0; 3],
+            chr_banks: [0; 8],
+            control: 0,
+            irq: VrcIrq::default(),
+            audio: Opll::default(),
+        };
+        mapper.update();
+        mapper
+    }
+
+    fn update(&mut self) {
+        for (slot, bank) in self.prg_banks.iter().enumerate() {
+            self.prg.set(slot, *bank as usize);
+        }
+        self.prg.set(3, self.prg.last());
+        for (slot, bank) in self.chr_banks.iter().enumerate() {
+            self.chr.set(slot, *bank as usize);
+        }
+    }
+
+    fn ram_enabled(&self) -> bool {
+        self.control & 0x80 != 0
+    }
+
+    fn silenced(&self) -> bool {
+        self.control & 0x40 != 0
+    }
+
+    fn write_register(&mut self, addr: u16, data: u8) {
+        let high = (addr & self.line != 0) as usize;
+        match (addr & 0xF000, high) {
+            (0x9000, _) if addr & 0xF030 == 0x9010 => self.audio.write_address(data),
+            (0x9000, _) if addr & 0xF030 == 0x9030 => self.audio.write_data(data),
+            (0x8000, i) => self.prg_banks[i] = data & 0x3F,
+            (0x9000, 0) => self.prg_banks[2] = data & 0x3F,
+            (0x9000, _) => {}
+            (0xA000..=0xD000, i) => {
+                self.chr_banks[(((addr & 0xF000) - 0xA000) >> 11) as usize | i] = data;
+            }
+            (0xE000, 0) => {
+                self.control = data;
+                if self.silenced() {
+                    self.audio.reset();
+                }
+            }
+            (0xE000, _) => self.irq.write_latch(data),
+            (_, 0) => self.irq.write_control(data),
+            _ => self.irq.acknowledge(),
+        }
+        self.update();
+    }
+}
+
+impl Mapper for Vrc7<'_> {
+    fn cpu_peek(&self, addr: u16) -> Option<u8> {
+        match addr {
+            0x6000..=0x7FFF if self.ram_enabled() => self.chips.wram_peek(addr),
+            0x8000..=0xFFFF => self.prg.read(&self.chips.prg_rom, addr),
+            _ => None,
+        }
+    }
+
+    fn cpu_write(&mut self, addr: u16, data: u8) {
+        match addr {
+            0x6000..=0x7FFF if self.ram_enabled() => self.chips.wram_write(addr, data),
+            0x8000..=0xFFFF => self.write_register(addr, data),
+            _ => {}
+        }
+    }
+
+    fn chr_peek(&self, addr: u16) -> u8 {
+        self.chr.read(self.chips.chr(), addr).unwrap_or(0)
+    }
+
+    fn chr_write(&mut self, addr: u16, data: u8) {
+        if let Some(chr) = self.chips.chr_mut() {
+            self.chr.write(chr, addr, data);
+        }
+    }
+
+    fn prg_rom_offset(&self, addr: u16) -> Option<usize> {
+        self.chips.prg_rom_offset(&self.prg, addr)
+    }
+
+    fn chr_rom_offset(&self, addr: u16) -> Option<usize> {
+        self.chips.chr_rom_offset(&self.chr, addr)
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        match self.control & 3 {
+            0 => Mirroring::Vertical,
+            1 => Mirroring::Horizontal,
+            2 => Mirroring::SingleScreenLower,
+            _ => Mirroring::SingleScreenUpper,
+        }
+    }
+
+    fn irq(&self) -> bool {
+        self.irq.irq()
+    }
+
+    fn cpu_cycle(&mut self) {
+        self.irq.cycle();
+        if !self.silenced() {
+            self.audio.cycle();
+        }
+    }
+
+    fn audio(&self) -> f32 {
+        if self.silenced() {
+            0.0
+        } else {
+            self.audio.output()
+        }
+    }
+
+    fn save_ram(&self) -> Option<&[u8]> {
+        self.chips.save_ram()
+    }
+
+    fn save_ram_mut(&mut self) -> Option<&mut [u8]> {
+        self.chips.save_ram_mut()
+    }
+
+    fn memory_report(&self, report: &mut MemoryReport) {
+        report.add("VRC7", size_of_val(self));
+        self.chips.memory_report(report);
+    }
+
+    fn cold_reset(&mut self) {
+        self.chips.cold_reset();
+        self.prg_banks = [0; 3];
+        self.chr_banks = [0; 8];
+        self.warm_reset();
+    }
+
+    fn warm_reset(&mut self) {
+        self.control = 0;
+        self.irq = VrcIrq::default();
+        self.audio.reset();
+        self.update();
+    }
+}
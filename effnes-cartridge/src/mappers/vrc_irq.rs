@@ -0,0 +1,87 @@
+/// Konami VRC IRQ counter (VRC4, VRC6 and VRC7).
+///
+/// ## Behaviour
+///
+/// - The 8-bit counter counts up from the latch, and raises the IRQ when
+///   clocked at $FF (reloading from the latch).
+/// - Cycle mode clocks the counter on every CPU cycle; scanline mode clocks
+///   it every 113 2/3 CPU cycles, through a prescaler decremented by 3 from
+///   341 on every CPU cycle.
+/// - Writing the control register acknowledges the IRQ and, if the counter
+///   gets enabled, reloads it from the latch.
+/// - Acknowledging the IRQ copies the "enable after acknowledgement" bit
+///   into the enable bit.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct VrcIrq {
+    latch: u8,
+    counter: u8,
+    prescaler: i16,
+    enabled: bool,
+    enable_after_ack: bool,
+    cycle_mode: bool,
+    pending: bool,
+}
+
+impl VrcIrq {
+    /// Writes the whole latch.
+    pub fn write_latch(&mut self, data: u8) {
+        self.latch = data;
+    }
+
+    /// Writes the low nibble of the latch (VRC4).
+    pub fn write_latch_low(&mut self, data: u8) {
+        self.latch = (self.latch & 0xF0) | (data & 0x0F);
+    }
+
+    /// Writes the high nibble of the latch (VRC4).
+    pub fn write_latch_high(&mut self, data: u8) {
+        self.latch = (self.latch & 0x0F) | (data << 4);
+    }
+
+    /// Writes the control register (`---- -MEA`).
+    pub fn write_control(&mut self, data: u8) {
+        self.enable_after_ack = data & 0x01 != 0;
+        self.enabled = data & 0x02 != 0;
+        self.cycle_mode = data & 0x04 != 0;
+        self.pending = false;
+        if self.enabled {
+            self.counter = self.latch;
+            self.prescaler = 341;
+        }
+    }
+
+    pub fn acknowledge(&mut self) {
+        self.pending = false;
+        self.enabled = self.enable_after_ack;
+    }
+
+    pub fn irq(&self) -> bool {
+        self.pending
+    }
+
+    /// CPU cycle clock.
+    pub fn cycle(&mut self) {
+        if !self.enabled {
+            return;
+        }
+
+        if self.cycle_mode {
+            self.clock();
+        } else {
+            self.prescaler -= 3;
+            if self.prescaler <= 0 {
+                self.prescaler += 341;
+                self.clock();
+            }
+        }
+    }
+
+    fn clock(&mut self) {
+        if self.counter == 0xFF {
+            self.counter = self.latch;
+            self.pending = true;
+        } else {
+            self.counter += 1;
+        }
+    }
+}
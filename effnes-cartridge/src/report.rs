@@ -0,0 +1,108 @@
+use crate::bank::RamData;
+use std::{
+    borrow::Cow,
+    fmt::{self, Display},
+};
+
+/// Memory used by a single component.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct MemoryEntry {
+    pub component: Cow<'static, str>,
+    /// Bytes allocated (or embedded) by the emulator.
+    pub resident: usize,
+    /// Bytes borrowed from the frontend (e.g. a memory-mapped ROM file),
+    /// which don't count as resident.
+    pub borrowed: usize,
+}
+
+/// Memory Report
+///
+/// Breakdown of the memory used by every component, as reported by
+/// [crate::Mapper::memory_report].
+///
+/// ```ignore
+/// let report = cartridge.memory_report();
+/// println!("{report}");
+/// assert!(report.resident() < 16 * 1024);
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MemoryReport {
+    entries: Vec<MemoryEntry>,
+}
+
+impl MemoryReport {
+    /// Adds an entry of `resident` allocated bytes.
+    pub fn add(&mut self, component: impl Into<Cow<'static, str>>, resident: usize) {
+        self.entries.push(MemoryEntry {
+            component: component.into(),
+            resident,
+            borrowed: 0,
+        });
+    }
+
+    /// Adds an entry for the contents of a ROM chip.
+    // The `Cow` itself is needed for telling borrowed and owned data apart.
+    #[allow(clippy::ptr_arg)]
+    pub fn add_rom(&mut self, component: impl Into<Cow<'static, str>>, data: &Cow<'_, [u8]>) {
+        let (resident, borrowed) = match data {
+            Cow::Borrowed(data) => (0, data.len()),
+            Cow::Owned(data) => (data.capacity(), 0),
+        };
+        self.entries.push(MemoryEntry {
+            component: component.into(),
+            resident,
+            borrowed,
+        });
+    }
+
+    /// Adds an entry for the contents of a RAM chip.
+    pub fn add_ram(&mut self, component: impl Into<Cow<'static, str>>, data: &RamData<'_>) {
+        let (resident, borrowed) = match data {
+            RamData::Borrowed(data) => (0, data.len()),
+            RamData::Owned(data) => (data.capacity(), 0),
+        };
+        self.entries.push(MemoryEntry {
+            component: component.into(),
+            resident,
+            borrowed,
+        });
+    }
+
+    pub fn entries(&self) -> &[MemoryEntry] {
+        &self.entries
+    }
+
+    /// Total resident bytes.
+    pub fn resident(&self) -> usize {
+        self.entries.iter().map(|e| e.resident).sum()
+    }
+
+    /// Total borrowed bytes.
+    pub fn borrowed(&self) -> usize {
+        self.entries.iter().map(|e| e.borrowed).sum()
+    }
+}
+
+impl Display for MemoryReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{:<24} {:>10} {:>10}",
+            "component", "resident", "borrowed"
+        )?;
+        for entry in &self.entries {
+            writeln!(
+                f,
+                "{:<24} {:>10} {:>10}",
+                entry.component, entry.resident, entry.borrowed
+            )?;
+        }
+        write!(
+            f,
+            "{:<24} {:>10} {:>10}",
+            "total",
+            self.resident(),
+            self.borrowed()
+        )
+    }
+}
@@ -0,0 +1,162 @@
+use crate::{Cartridge, Mapper};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+/// When [SaveFile::poll] writes the save RAM back.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FlushPolicy {
+    /// Never; the frontend calls [SaveFile::flush] when exiting (or
+    /// unloading the cartridge).
+    AtExit,
+    /// Whenever the save RAM changed, at most once per interval.
+    Periodic(Duration),
+}
+
+/// Save File
+///
+/// Persists the battery-backed RAM (or EEPROM) of a cartridge, as reported
+/// by [Mapper::save_ram], into a raw `.sav` file.
+///
+/// ```ignore
+/// let mut save = SaveFile::for_rom(&rom_path, FlushPolicy::Periodic(Duration::from_secs(5)));
+/// save.load(&mut cartridge)?;
+/// while running {
+///     run_frame(&mut cartridge);
+///     save.poll(&cartridge)?;
+/// }
+/// save.flush(&cartridge)?;
+/// ```
+///
+/// ## Behaviour
+///
+/// - The file contents are kept around after every load/flush, so the file
+///   is only written when the save RAM actually changed (see
+///   [SaveFile::is_dirty]).
+/// - Files are written into a temporary file first and then renamed over
+///   the save file, so a crash mid-write never corrupts the previous save.
+/// - Cartridges without save RAM never touch the file system.
+/// - An existing save file is never written before being loaded.
+#[derive(Clone, Debug)]
+pub struct SaveFile {
+    path: PathBuf,
+    policy: FlushPolicy,
+    persisted: Option<Vec<u8>>,
+    last_flush: Instant,
+}
+
+impl SaveFile {
+    pub fn new(path: impl Into<PathBuf>, policy: FlushPolicy) -> Self {
+        Self {
+            path: path.into(),
+            policy,
+            persisted: None,
+            last_flush: Instant::now(),
+        }
+    }
+
+    /// Save file next to a ROM file (`game.nes` saves into `game.sav`).
+    pub fn for_rom(rom: impl AsRef<Path>, policy: FlushPolicy) -> Self {
+        Self::new(rom.as_ref().with_extension("sav"), policy)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn policy(&self) -> FlushPolicy {
+        self.policy
+    }
+
+    /// Loads the save file into the cartridge save RAM.
+    ///
+    /// Returns `false` if there's no save RAM or no save file yet (which
+    /// leaves the save RAM untouched).
+    ///
+    /// ## Errors
+    ///
+    /// I/O errors, and [io::ErrorKind::InvalidData] if the file size doesn't
+    /// match the save RAM size.
+    pub fn load<M: Mapper>(&mut self, cartridge: &mut Cartridge<M>) -> io::Result<bool> {
+        let Some(ram) = cartridge.save_ram_mut() else {
+            return Ok(false);
+        };
+
+        let data = match fs::read(&self.path) {
+            Ok(data) => data,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                self.persisted = Some(ram.to_vec());
+                return Ok(false);
+            }
+            Err(err) => return Err(err),
+        };
+        if data.len() != ram.len() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "save file is {} bytes long, expected {}",
+                    data.len(),
+                    ram.len()
+                ),
+            ));
+        }
+
+        ram.copy_from_slice(&data);
+        self.persisted = Some(data);
+        Ok(true)
+    }
+
+    /// Whether the save RAM changed since the last load/flush.
+    pub fn is_dirty<M: Mapper>(&self, cartridge: &Cartridge<M>) -> bool {
+        match (cartridge.save_ram(), &self.persisted) {
+            (None, _) => false,
+            (Some(ram), Some(persisted)) => ram != persisted.as_slice(),
+            (Some(_), None) => true,
+        }
+    }
+
+    /// Writes the save RAM into the save file, if it's dirty.
+    ///
+    /// Returns whether the file was written.
+    ///
+    /// ## Errors
+    ///
+    /// I/O errors, and [io::ErrorKind::AlreadyExists] if the save file exists
+    /// but was never loaded (it'd be overwritten with a blank save RAM).
+    pub fn flush<M: Mapper>(&mut self, cartridge: &Cartridge<M>) -> io::Result<bool> {
+        self.last_flush = Instant::now();
+        if !self.is_dirty(cartridge) {
+            return Ok(false);
+        }
+        let Some(ram) = cartridge.save_ram() else {
+            return Ok(false);
+        };
+        if self.persisted.is_none() && fs::exists(&self.path)? {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                "save file exists but wasn't loaded",
+            ));
+        }
+
+        let mut temp = self.path.clone().into_os_string();
+        temp.push(".tmp");
+        fs::write(&temp, ram)?;
+        fs::rename(&temp, &self.path)?;
+        self.persisted = Some(ram.to_vec());
+        Ok(true)
+    }
+
+    /// Applies the flush policy; meant to be called once per frame.
+    ///
+    /// Returns whether the file was written.
+    pub fn poll<M: Mapper>(&mut self, cartridge: &Cartridge<M>) -> io::Result<bool> {
+        match self.policy {
+            FlushPolicy::Periodic(interval) if self.last_flush.elapsed() >= interval => {
+                self.flush(cartridge)
+            }
+            _ => Ok(false),
+        }
+    }
+}
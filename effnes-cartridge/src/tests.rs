@@ -0,0 +1,1591 @@
+use crate::mappers::{Fds, Mmc5, fds as fds_mapper};
+use crate::{
+    Banks, Cartridge, Cdl, Chips, CodeDataLogger, Error, FlushPolicy, Mapper, MemoryReport,
+    Mirroring, RamData, SaveFile,
+    cdl::{ChrFlags, PrgFlags},
+    disk, ips,
+    mapper::{CIRAM_LEN, FOUR_SCREEN_VRAM_LEN, nametable_offset},
+};
+use effnes_bus::{
+    InspectBus, MemoryBus,
+    basic::BasicMemory,
+    nes::{NesBus, Unmapped},
+    peripheral::Peripheral,
+    power::RamInit,
+    signal::{Signals, Source},
+};
+use effnes_ca_cpu::vm::VM as CycleAccurateVM;
+use effnes_cpu::debug::DebugCpu;
+use effnes_ines::{
+    Rom,
+    fds::{self, DISK_VERIFICATION, DiskImage},
+};
+use std::{borrow::Cow, time::Duration};
+
+/// 32 KiB of PRG-ROM at $8000, 8 KiB of CHR-RAM, register at $6000 that
+/// selects the mirroring, and an IRQ after every 4th A12 rise.
+struct Dummy {
+    prg: Vec<u8>,
+    chr: [u8; 0x2000],
+    mirroring: Mirroring,
+    rises: u32,
+    cycles: u32,
+}
+
+impl Dummy {
+    fn new(mirroring: Mirroring) -> Self {
+        Self {
+            prg: (0..0x8000).map(|i| i as u8).collect(),
+            chr: [0; 0x2000],
+            mirroring,
+            rises: 0,
+            cycles: 0,
+        }
+    }
+}
+
+impl Mapper for Dummy {
+    fn cpu_peek(&self, addr: u16) -> Option<u8> {
+        (addr >= 0x8000).then(|| self.prg[addr as usize - 0x8000])
+    }
+
+    fn cpu_write(&mut self, addr: u16, data: u8) {
+        if addr == 0x6000 {
+            self.mirroring = if data & 1 == 0 {
+                Mirroring::Vertical
+            } else {
+                Mirroring::Horizontal
+            };
+        }
+    }
+
+    fn chr_peek(&self, addr: u16) -> u8 {
+        self.chr[addr as usize]
+    }
+
+    fn chr_write(&mut self, addr: u16, data: u8) {
+        self.chr[addr as usize] = data;
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        self.mirroring
+    }
+
+    fn irq(&self) -> bool {
+        self.rises != 0 && self.rises.is_multiple_of(4)
+    }
+
+    fn cpu_cycle(&mut self) {
+        self.cycles += 1;
+    }
+
+    fn ppu_a12(&mut self, high: bool) {
+        if high {
+            self.rises += 1;
+        }
+    }
+}
+
+#[test]
+fn nametable_mirroring() {
+    assert_eq!(nametable_offset(Mirroring::Vertical, 0x2000), 0x000);
+    assert_eq!(nametable_offset(Mirroring::Vertical, 0x2400), 0x400);
+    assert_eq!(nametable_offset(Mirroring::Vertical, 0x2800), 0x000);
+    assert_eq!(nametable_offset(Mirroring::Horizontal, 0x2400), 0x000);
+    assert_eq!(nametable_offset(Mirroring::Horizontal, 0x2C05), 0x405);
+    assert_eq!(
+        nametable_offset(Mirroring::SingleScreenUpper, 0x2000),
+        0x400
+    );
+    assert_eq!(nametable_offset(Mirroring::FourScreen, 0x2C00), 0xC00);
+    // $3000-$3EFF mirrors $2000-$2EFF.
+    assert_eq!(nametable_offset(Mirroring::Vertical, 0x3401), 0x401);
+}
+
+#[test]
+fn cpu_side() {
+    let mut cart = Cartridge::new(Dummy::new(Mirroring::Vertical));
+    assert_eq!(cart.read_u8(0x8001), 0x01);
+    assert_eq!(cart.peek_u16(0x8010), 0x1110);
+    // Not driven by the mapper.
+    assert_eq!(cart.read_u8(0x5000), 0x01);
+
+    cart.recv(0x6000, 1);
+    assert_eq!(cart.mapper().mirroring(), Mirroring::Horizontal);
+
+    let mut io = BasicMemory::default_with(0);
+    let mut signals = Signals::default();
+    cart.cycle(&mut io, &mut signals);
+    cart.cycle(&mut io, &mut signals);
+    assert_eq!(cart.mapper().cycles, 2);
+    assert!(!signals.irq());
+}
+
+#[test]
+fn ppu_side() {
+    let mut cart = Cartridge::new(Dummy::new(Mirroring::Vertical));
+    assert_eq!(cart.vram().len(), CIRAM_LEN);
+
+    cart.ppu_write(0x0010, 0xAB);
+    assert_eq!(cart.ppu_read(0x0010), 0xAB);
+
+    cart.ppu_write(0x2005, 0x42);
+    assert_eq!(cart.ppu_peek(0x2805), 0x42);
+    cart.recv(0x6000, 1);
+    assert_eq!(cart.ppu_peek(0x2405), 0x42);
+    assert_eq!(cart.ppu_peek(0x2805), 0x00);
+
+    let four = Cartridge::new(Dummy::new(Mirroring::FourScreen));
+    assert_eq!(four.vram().len(), FOUR_SCREEN_VRAM_LEN);
+}
+
+#[test]
+fn a12_clocking() {
+    let mut cart = Cartridge::new(Box::new(Dummy::new(Mirroring::Vertical)) as Box<dyn Mapper>);
+    for _ in 0..4 {
+        cart.ppu_read(0x0000);
+        cart.ppu_read(0x1000);
+        // No edge while A12 stays high.
+        cart.ppu_read(0x1008);
+    }
+    assert!(cart.irq());
+
+    // The IRQ line follows the mapper on every CPU cycle.
+    let mut io = BasicMemory::default_with(0);
+    let mut signals = Signals::default();
+    cart.cycle(&mut io, &mut signals);
+    assert_eq!(signals.irq_sources(), Source::Mapper);
+
+    cart.ppu_read(0x0000);
+    cart.ppu_read(0x1000);
+    assert!(!cart.irq());
+    cart.cycle(&mut io, &mut signals);
+    assert!(!signals.irq());
+}
+
+#[test]
+fn bank_switching() {
+    // 64 KiB chip, 32 KiB window at $8000 with 8 KiB slots.
+    let chip: Vec<u8> = (0..0x10000).map(|i| (i >> 13) as u8).collect();
+    let mut prg = Banks::<4>::new(0x8000, 0x8000, chip.len());
+    assert_eq!(prg.slot_size(), 0x2000);
+    assert_eq!(prg.count(), 8);
+
+    prg.set(0, 5);
+    prg.set(3, prg.last());
+    assert_eq!(prg.read(&chip, 0x8000), Some(5));
+    assert_eq!(prg.read(&chip, 0xFFFF), Some(7));
+    assert_eq!(prg.offset(0x9FFF), 5 * 0x2000 + 0x1FFF);
+
+    // Out of range banks wrap around the chip size.
+    prg.set(1, 9);
+    assert_eq!(prg.bank(1), 1);
+
+    prg.set_wide(0, 2, 2);
+    assert_eq!(prg.read(&chip, 0x8000), Some(4));
+    assert_eq!(prg.read(&chip, 0xA000), Some(5));
+
+    let empty = Banks::<1>::new(0x6000, 0x2000, 0);
+    assert_eq!(empty.read(&[], 0x6000), None);
+}
+
+#[test]
+fn resident_memory() {
+    static ROM: [u8; 0x4000] = [0; 0x4000];
+    let mut buffer = [0u8; 0x2000];
+
+    let mut report = MemoryReport::default();
+    report.add_rom("PRG-ROM", &Cow::Borrowed(&ROM[..]));
+    report.add_rom("CHR-ROM", &Cow::Owned(vec![0; 0x2000]));
+    report.add_ram("PRG-RAM", &RamData::Borrowed(&mut buffer));
+    assert_eq!(report.resident(), 0x2000);
+    assert_eq!(report.borrowed(), 0x6000);
+
+    let cart = Cartridge::new(Dummy::new(Mirroring::Vertical));
+    let report = cart.memory_report();
+    assert_eq!(report.entries()[0].resident, CIRAM_LEN);
+    assert_eq!(report.entries()[1].resident, size_of::<Dummy>());
+    let total = report.to_string();
+    let total = total.lines().last().unwrap();
+    assert!(total.starts_with("total"));
+    assert!(total.contains(&report.resident().to_string()));
+}
+
+/// Builds a NES 2.0 ROM, with every PRG-ROM byte set to its 16 KiB bank
+/// number and every CHR-ROM byte set to its 8 KiB bank number.
+fn nes20(mapper: u16, submapper: u8, prg_16k: u8, chr_8k: u8, vertical: bool) -> Vec<u8> {
+    let mut data = effnes_ines::header::MAGIC.to_vec();
+    data.extend([
+        prg_16k,
+        chr_8k,
+        ((mapper as u8 & 0x0F) << 4) | vertical as u8,
+        (mapper as u8 & 0xF0) | 0x08,
+        (submapper << 4) | (mapper >> 8) as u8,
+        0,
+        // 8 KiB of PRG-RAM
+        0x07,
+        // 8 KiB of CHR-RAM, if there's no CHR-ROM
+        if chr_8k == 0 { 0x07 } else { 0x00 },
+        0,
+        0,
+        0,
+        0,
+    ]);
+    data.extend((0..prg_16k as usize * 0x4000).map(|i| (i / 0x4000) as u8));
+    data.extend((0..chr_8k as usize * 0x2000).map(|i| (i / 0x2000) as u8));
+    data
+}
+
+fn cartridge(data: &[u8]) -> Cartridge<Box<dyn Mapper + '_>> {
+    Cartridge::from_rom(Rom::parse(data).unwrap()).unwrap()
+}
+
+#[test]
+fn nrom() {
+    let data = nes20(0, 0, 1, 1, true);
+    let mut cart = cartridge(&data);
+    assert_eq!(cart.peek_u8(0xC000), 0);
+    assert_eq!(cart.mapper().mirroring(), Mirroring::Vertical);
+
+    cart.write_u8(0x6123, 0x55);
+    assert_eq!(cart.read_u8(0x6123), 0x55);
+    // CHR-ROM is read-only.
+    cart.ppu_write(0x0000, 0xFF);
+    assert_eq!(cart.ppu_read(0x0000), 0x00);
+
+    // Borrowed ROM data isn't resident.
+    let report = cart.memory_report();
+    assert_eq!(report.borrowed(), 0x4000 + 0x2000);
+}
+
+#[test]
+fn power_on_ram() {
+    let data = nes20(0, 0, 1, 0, true);
+    assert_eq!(cartridge(&data).peek_u8(0x6004), 0x00);
+
+    let rom = Rom::parse(&data).unwrap();
+    let mut cart = Cartridge::from_rom_with(rom, RamInit::Alternating).unwrap();
+    let wram: Vec<u8> = (0x6000..0x6008).map(|addr| cart.peek_u8(addr)).collect();
+    assert_eq!(wram, [0x00, 0x00, 0x00, 0x00, 0xFF, 0xFF, 0xFF, 0xFF]);
+    assert_eq!(cart.ppu_peek(0x1FFF), 0xFF);
+    assert_eq!(cart.ppu_peek(0x2003), 0x00);
+    assert_eq!(cart.ppu_peek(0x2404), 0xFF);
+
+    // Cold resets bring nametable RAM, PRG-RAM and CHR-RAM back to the same
+    // contents.
+    cart.ppu_write(0x2404, 0x12);
+    cart.write_u8(0x6004, 0x12);
+    cart.ppu_write(0x1FFF, 0x12);
+    cart.cold_reset();
+    assert_eq!(cart.ppu_peek(0x2404), 0xFF);
+    assert_eq!(cart.peek_u8(0x6004), 0xFF);
+    assert_eq!(cart.ppu_peek(0x1FFF), 0xFF);
+
+    // But not battery-backed RAM: 8 KiB of PRG-NVRAM and 8 KiB of PRG-RAM
+    // (MMC5 banks 0 and 1).
+    let mut data = nes20(5, 0, 2, 0, false);
+    data[6] |= 0x02;
+    data[10] = 0x77;
+    let rom = Rom::parse(&data).unwrap();
+    let mut cart = Cartridge::from_rom_with(rom, RamInit::Alternating).unwrap();
+    cart.write_u8(0x5102, 0x02);
+    cart.write_u8(0x5103, 0x01);
+    cart.write_u8(0x6004, 0x12);
+    cart.write_u8(0x5113, 0x01);
+    cart.write_u8(0x6004, 0x34);
+    cart.ppu_write(0x0004, 0x56);
+    cart.cold_reset();
+    assert_eq!(cart.ppu_peek(0x0004), 0xFF);
+    assert_eq!(cart.save_ram().map(|ram| ram[4]), Some(0x12));
+    cart.write_u8(0x5113, 0x01);
+    assert_eq!(cart.peek_u8(0x6004), 0xFF);
+
+    // So is extra RAM allocated by mappers.
+    let data = nes20(5, 0, 2, 1, false);
+    let chips = Chips::new(Rom::parse(&data).unwrap()).with_ram_init(RamInit::Ones);
+    let mut mmc5 = Mmc5::new(chips);
+    assert!(mmc5.exram().iter().all(|&byte| byte == 0xFF));
+    mmc5.cold_reset();
+    assert!(mmc5.exram().iter().all(|&byte| byte == 0xFF));
+}
+
+#[test]
+fn cartridge_open_bus() {
+    let data = nes20(0, 0, 2, 1, true);
+    let mut bus = NesBus::new(Unmapped, Unmapped, cartridge(&data));
+
+    // $5000 isn't driven by NROM: the bus keeps the last written value,
+    // even if the write didn't go through the cartridge.
+    bus.write_u8(0x0000, 0x5A);
+    assert_eq!(bus.read_u8(0x5000), 0x5A);
+    assert_eq!(bus.read_u8(0xC000), 0x01);
+    assert_eq!(bus.peek_u8(0x5000), 0x01);
+    assert_eq!(bus.cartridge().peek_u8(0x5000), 0x01);
+}
+
+#[test]
+fn code_data_log() {
+    let mut data = nes20(0, 0, 2, 1, true);
+    let program = [
+        0xAD, 0x00, 0x90, // $8000: LDA $9000
+        0xEA, // $8003: NOP
+        0xB1, 0x10, // $8004: LDA ($10),Y
+        0x6C, 0x00, 0xA1, // $8006: JMP ($A100)
+    ];
+    data[16..16 + program.len()].copy_from_slice(&program);
+    data[16 + 0x2100..16 + 0x2102].copy_from_slice(&[0x10, 0x80]);
+
+    let rom = Rom::parse(&data).unwrap();
+    let cdl = Cdl::for_rom(&rom);
+    let mut bus = CodeDataLogger::new(NesBus::new(Unmapped, Unmapped, cartridge(&data)), cdl);
+    bus.write_u8(0x0010, 0x00);
+    bus.write_u8(0x0011, 0xA0);
+
+    // The reads made by the CPU for each instruction.
+    bus.set_pc(0x8000);
+    bus.read_u8(0x8000);
+    bus.read_u16(0x8001);
+    bus.read_u8(0x9000);
+    bus.set_pc(0x8003);
+    bus.read_u8(0x8003);
+    bus.read_u8(0x8004);
+    bus.set_pc(0x8004);
+    bus.read_u16(0x8004);
+    bus.read_u16(0x0010);
+    bus.read_u8(0xA000);
+    bus.set_pc(0x8006);
+    bus.read_u8(0x8006);
+    bus.read_u16(0x8007);
+    bus.read_u16(0xA100);
+    bus.set_pc(0x8010);
+    bus.log_pcm(0xC000);
+    bus.log_chr(0x0010, ChrFlags::Rendered);
+
+    let cdl = bus.cdl();
+    assert_eq!(cdl.prg(0x0000), PrgFlags::Code);
+    assert_eq!(cdl.prg(0x0004), PrgFlags::Code);
+    assert_eq!(cdl.prg(0x1000), PrgFlags::Data);
+    assert_eq!(
+        cdl.prg(0x2000),
+        PrgFlags::Data | PrgFlags::IndirectData | PrgFlags::bank(0xA000)
+    );
+    assert_eq!(cdl.prg(0x2100).bits(), 0x06);
+    assert_eq!(cdl.prg(0x0010), PrgFlags::Code | PrgFlags::IndirectCode);
+    assert_eq!(cdl.prg(0x4000).bits(), 0x48);
+    assert_eq!(cdl.chr(0x0010), ChrFlags::Rendered);
+    assert_eq!(cdl.cpu_addr(0x2100), Some(0xA100));
+    assert_eq!(cdl.cpu_addr(0x3000), None);
+    assert_eq!(PrgFlags::bank(0xE000).bits(), 0x0C);
+    assert_eq!(PrgFlags::bank(0x6000), PrgFlags::empty());
+
+    // FCEUX layout: PRG flags, then CHR flags.
+    let bytes = cdl.to_bytes();
+    assert_eq!(bytes.len(), 0x8000 + 0x2000);
+    assert_eq!(bytes[0x8010], 0x01);
+    assert_eq!(&Cdl::from_bytes(&bytes, 0x8000, 0x2000).unwrap(), cdl);
+    assert_eq!(
+        Cdl::from_bytes(&bytes, 0x8000, 0),
+        Err(Error::CdlSizeMismatch {
+            expected: 0x8000,
+            actual: 0xA000
+        })
+    );
+
+    let prg = &rom.prg_rom;
+    let listing: Vec<String> = cdl
+        .disassemble(prg, 0x0000..0x0009, 0x8000)
+        .iter()
+        .map(ToString::to_string)
+        .collect();
+    assert_eq!(
+        listing,
+        [
+            "8000  ad 00 90  Lda $9000",
+            "8003  ea        Nop",
+            "8004  b1 10     Lda ($10),Y",
+            "8006  6c 00 a1  Jmp ($a100)",
+        ]
+    );
+    let listing: Vec<String> = cdl
+        .disassemble(prg, 0x2100..0x2103, 0xA100)
+        .iter()
+        .map(ToString::to_string)
+        .collect();
+    assert_eq!(listing, ["a100  .byte $10, $80", "a102  00        Brk"]);
+}
+
+#[test]
+fn code_data_log_cycle_stepped() {
+    let mut data = nes20(0, 0, 2, 1, true);
+    let program = [
+        0xAD, 0x00, 0x90, // $8000: LDA $9000
+        0xA9, 0x01, // $8003: LDA #$01
+        0xAD, 0x01, 0x90, // $8005: LDA $9001
+    ];
+    data[16..16 + program.len()].copy_from_slice(&program);
+
+    let rom = Rom::parse(&data).unwrap();
+    let cdl = Cdl::for_rom(&rom);
+    let mut bus = CodeDataLogger::new(NesBus::new(Unmapped, Unmapped, cartridge(&data)), cdl);
+    let mut cpu = CycleAccurateVM::default();
+    // The opcode is fetched from the byte after the program counter.
+    cpu.set_pc(0x7FFF);
+    assert_eq!(cpu.next_instruction(), Some(0x8000));
+    let mut signals = Signals::default();
+    // LDA, LDA, LDA.
+    for _ in 0..10 {
+        bus.step(&cpu);
+        cpu.cycle(&mut bus, &mut signals);
+    }
+
+    // Operands fetched mid-instruction aren't taken for opcodes.
+    let cdl = bus.cdl();
+    let flags: Vec<PrgFlags> = (0..9).map(|offset| cdl.prg(offset)).collect();
+    assert_eq!(flags[..8], [PrgFlags::Code; 8]);
+    assert_eq!(flags[8], PrgFlags::empty());
+    assert_eq!(cdl.prg(0x1000), PrgFlags::Data);
+    assert_eq!(cdl.prg(0x1001), PrgFlags::Data);
+}
+
+#[test]
+fn uxrom_bus_conflicts() {
+    let data = nes20(2, 1, 8, 0, false);
+    let mut cart = cartridge(&data);
+    assert_eq!(cart.peek_u8(0xC000), 7);
+    cart.write_u8(0x8000, 3);
+    assert_eq!(cart.peek_u8(0x8000), 3);
+    cart.ppu_write(0x0010, 0xAA);
+    assert_eq!(cart.ppu_read(0x0010), 0xAA);
+
+    // Submapper 2: the value is ANDed with the ROM byte (the bank number).
+    let data = nes20(2, 2, 8, 0, false);
+    let mut cart = cartridge(&data);
+    cart.write_u8(0xC000, 3);
+    assert_eq!(cart.peek_u8(0x8000), 3 & 7);
+    cart.write_u8(0x8000, 5);
+    assert_eq!(cart.peek_u8(0x8000), 5 & 3);
+}
+
+#[test]
+fn rom_offsets() {
+    let data = nes20(2, 0, 8, 0, false);
+    let mut cart = cartridge(&data);
+    cart.write_u8(0x8000, 0x03);
+    assert_eq!(cart.mapper().prg_rom_offset(0x8123), Some(0xC123));
+    assert_eq!(cart.mapper().prg_rom_offset(0xC000), Some(0x1C000));
+    assert_eq!(cart.mapper().prg_rom_offset(0x6000), None);
+    // CHR-RAM isn't logged.
+    assert_eq!(cart.mapper().chr_rom_offset(0x0000), None);
+
+    let data = nes20(3, 0, 1, 4, true);
+    let mut cart = cartridge(&data);
+    cart.write_u8(0x8000, 0x02);
+    assert_eq!(cart.mapper().chr_rom_offset(0x0010), Some(0x4010));
+}
+
+#[test]
+fn cnrom() {
+    let data = nes20(3, 2, 2, 4, false);
+    let mut cart = cartridge(&data);
+    cart.write_u8(0xC000, 3);
+    // Bank 1 PRG byte is 1.
+    assert_eq!(cart.ppu_read(0x0000), 1);
+    cart.write_u8(0x8000, 3);
+    assert_eq!(cart.ppu_read(0x1FFF), 0);
+
+    let data = nes20(3, 1, 2, 4, false);
+    let mut cart = cartridge(&data);
+    cart.write_u8(0x8000, 3);
+    assert_eq!(cart.ppu_read(0x1FFF), 3);
+}
+
+#[test]
+fn axrom_single_screen() {
+    let data = nes20(7, 1, 16, 0, false);
+    let mut cart = cartridge(&data);
+    assert_eq!(cart.mapper().mirroring(), Mirroring::SingleScreenLower);
+    cart.ppu_write(0x2000, 0x11);
+    cart.write_u8(0x8000, 0x12);
+    assert_eq!(cart.mapper().mirroring(), Mirroring::SingleScreenUpper);
+    assert_eq!(cart.peek_u8(0x8000), 4);
+    assert_eq!(cart.peek_u8(0xC000), 5);
+    assert_eq!(cart.ppu_read(0x2C00), 0x00);
+    cart.ppu_write(0x2400, 0x22);
+    cart.write_u8(0x8000, 0x00);
+    assert_eq!(cart.ppu_read(0x2800), 0x11);
+    assert_eq!(cart.vram()[0x400], 0x22);
+}
+
+#[test]
+fn gxrom_and_color_dreams() {
+    let data = nes20(66, 0, 8, 4, false);
+    let mut cart = cartridge(&data);
+    // Bus conflicts: the ROM bytes of bank 0 are zero.
+    cart.write_u8(0x8000, 0x13);
+    assert_eq!(cart.peek_u8(0x8000), 0);
+    assert_eq!(cart.ppu_read(0x0000), 0);
+
+    let mut data = nes20(66, 0, 8, 4, false);
+    // Writable value on every PRG byte.
+    data[16..16 + 8 * 0x4000].fill(0xFF);
+    let mut cart = cartridge(&data);
+    cart.write_u8(0x8000, 0x13);
+    assert_eq!(cart.mapper().cpu_peek(0x8000), Some(0xFF));
+    assert_eq!(cart.ppu_read(0x0000), 3);
+
+    let mut data = nes20(11, 0, 8, 4, false);
+    data[16..16 + 8 * 0x4000].fill(0xFF);
+    let mut cart = cartridge(&data);
+    cart.write_u8(0x8000, 0x21);
+    assert_eq!(cart.ppu_read(0x0000), 2);
+}
+
+#[test]
+fn unsupported_mapper() {
+    let data = nes20(255, 1, 1, 1, false);
+    let rom = Rom::parse(&data).unwrap();
+    assert_eq!(
+        Cartridge::from_rom(rom).err(),
+        Some(Error::UnsupportedMapper {
+            mapper: 255,
+            submapper: 1
+        })
+    );
+}
+
+/// Writes `value` into an MMC1 register through the serial port, leaving a
+/// CPU cycle between writes.
+fn mmc1_write(cart: &mut Cartridge<Box<dyn Mapper + '_>>, addr: u16, value: u8) {
+    for bit in 0..5 {
+        cart.write_u8(addr, (value >> bit) & 1);
+        cart.mapper_mut().cpu_cycle();
+        cart.mapper_mut().cpu_cycle();
+    }
+}
+
+#[test]
+fn mmc1_serial_port() {
+    let data = nes20(1, 0, 8, 0, false);
+    let mut cart = cartridge(&data);
+    // Power-on: PRG mode 3, last bank fixed at $C000.
+    assert_eq!(cart.peek_u8(0xC000), 7);
+
+    mmc1_write(&mut cart, 0xE000, 5);
+    assert_eq!(cart.peek_u8(0x8000), 5);
+
+    // Control: vertical mirroring, PRG mode 2 (first bank fixed at $8000).
+    mmc1_write(&mut cart, 0x8000, 0b01010);
+    assert_eq!(cart.mapper().mirroring(), Mirroring::Vertical);
+    assert_eq!(cart.peek_u8(0x8000), 0);
+    assert_eq!(cart.peek_u8(0xC000), 5);
+
+    // A write with bit 7 set resets the shift register and sets PRG mode 3.
+    cart.write_u8(0xE000, 1);
+    cart.mapper_mut().cpu_cycle();
+    cart.mapper_mut().cpu_cycle();
+    cart.write_u8(0x8000, 0x80);
+    cart.mapper_mut().cpu_cycle();
+    cart.mapper_mut().cpu_cycle();
+    mmc1_write(&mut cart, 0xE000, 2);
+    assert_eq!(cart.peek_u8(0x8000), 2);
+    assert_eq!(cart.peek_u8(0xC000), 7);
+}
+
+#[test]
+fn mmc1_consecutive_writes() {
+    let data = nes20(1, 0, 8, 0, false);
+    let mut cart = cartridge(&data);
+
+    // Read-modify-write: the dummy write and the real one hit the port on
+    // consecutive cycles, so only the first one counts.
+    for bit in [1, 1, 0, 0, 0] {
+        cart.write_u8(0xE000, bit);
+        cart.mapper_mut().cpu_cycle();
+        cart.write_u8(0xE000, 0);
+        cart.mapper_mut().cpu_cycle();
+        cart.mapper_mut().cpu_cycle();
+    }
+    assert_eq!(cart.peek_u8(0x8000), 3);
+}
+
+#[test]
+fn mmc1_read_modify_write() {
+    let mut data = nes20(1, 0, 8, 0, false);
+    // $C000 (last bank): INC $8000, five times.
+    let program = 16 + 7 * 0x4000;
+    for i in 0..5 {
+        data[program + i * 3..program + i * 3 + 3].copy_from_slice(&[0xEE, 0x00, 0x80]);
+    }
+    let mut cart = cartridge(&data);
+    let mut io = BasicMemory::default_with(0);
+    let mut cpu = CycleAccurateVM::default();
+    // The opcode is fetched from the byte after the program counter.
+    cpu.set_pc(0xBFFF);
+    let mut signals = Signals::default();
+    for _ in 0..5 * 6 {
+        cpu.cycle(&mut cart, &mut signals);
+        cart.cycle(&mut io, &mut signals);
+    }
+
+    // Every INC writes back the ROM byte (0) and then the incremented one
+    // (1) on the next cycle, which is ignored: the control register ends
+    // up as 0 (one-screen mirroring).
+    assert_eq!(cart.mapper().mirroring(), Mirroring::SingleScreenLower);
+}
+
+#[test]
+fn mmc1_large_boards() {
+    // SUROM: 512 KiB of PRG-ROM, CHR bank bit 4 selects the outer half.
+    let data = nes20(1, 0, 32, 0, false);
+    let mut cart = cartridge(&data);
+    assert_eq!(cart.peek_u8(0xC000), 15);
+    mmc1_write(&mut cart, 0xA000, 0x10);
+    assert_eq!(cart.peek_u8(0x8000), 16);
+    assert_eq!(cart.peek_u8(0xC000), 31);
+
+    // SXROM: 32 KiB of battery-backed PRG-RAM, banked by CHR bits 2-3.
+    let mut data = nes20(1, 0, 32, 0, false);
+    data[6] |= 0x02;
+    data[10] = 0x90;
+    let mut cart = cartridge(&data);
+    cart.write_u8(0x6000, 0x11);
+    mmc1_write(&mut cart, 0xA000, 0x08);
+    assert_eq!(cart.peek_u8(0x6000), 0x00);
+    cart.write_u8(0x6000, 0x22);
+    assert_eq!(
+        cart.save_ram().map(|ram| (ram.len(), ram[0], ram[0x4000])),
+        Some((0x8000, 0x11, 0x22))
+    );
+
+    // PRG-RAM disable (bit 4 of the PRG bank register).
+    mmc1_write(&mut cart, 0xE000, 0x10);
+    assert_eq!(cart.mapper().cpu_peek(0x6000), None);
+}
+
+/// Renders a scanline as the MMC3 sees it: background fetches from $0000
+/// and sprite fetches from $1000 (A12 high after staying low for a while).
+fn mmc3_scanline(cart: &mut Cartridge<Box<dyn Mapper + '_>>) {
+    cart.ppu_read(0x0000);
+    for _ in 0..80 {
+        cart.mapper_mut().cpu_cycle();
+    }
+    // Eight sprite fetches, with nametable fetches in between.
+    for _ in 0..8 {
+        cart.ppu_read(0x2000);
+        cart.ppu_read(0x1000);
+        cart.mapper_mut().cpu_cycle();
+    }
+    for _ in 0..24 {
+        cart.mapper_mut().cpu_cycle();
+    }
+}
+
+#[test]
+fn mmc3_banking() {
+    let data = nes20(4, 0, 8, 16, false);
+    let mut cart = cartridge(&data);
+    // 8 KiB banks: bank n of 16 KiB holds 8 KiB banks 2n and 2n+1.
+    cart.write_u8(0x8000, 6);
+    cart.write_u8(0x8001, 5);
+    assert_eq!(cart.peek_u8(0x8000), 2);
+    assert_eq!(cart.peek_u8(0xC000), 7);
+    assert_eq!(cart.peek_u8(0xE000), 7);
+
+    // PRG mode 1 swaps $8000 and $C000.
+    cart.write_u8(0x8000, 0x46);
+    assert_eq!(cart.peek_u8(0x8000), 7);
+    assert_eq!(cart.peek_u8(0xC000), 2);
+
+    // 1 KiB CHR bank 17 is in 8 KiB bank 2, mapped at $1000 (or $0000 with
+    // the A12 inversion).
+    cart.write_u8(0x8000, 2);
+    cart.write_u8(0x8001, 17);
+    assert_eq!(cart.ppu_peek(0x1000), 2);
+    cart.write_u8(0x8000, 0x80);
+    assert_eq!(cart.ppu_peek(0x0000), 2);
+
+    cart.write_u8(0xA000, 1);
+    assert_eq!(cart.mapper().mirroring(), Mirroring::Horizontal);
+
+    // PRG-RAM protection.
+    cart.write_u8(0x6000, 0x12);
+    assert_eq!(cart.mapper().cpu_peek(0x6000), None);
+    cart.write_u8(0xA001, 0x80);
+    cart.write_u8(0x6000, 0x12);
+    assert_eq!(cart.peek_u8(0x6000), 0x12);
+    cart.write_u8(0xA001, 0xC0);
+    cart.write_u8(0x6000, 0x34);
+    assert_eq!(cart.peek_u8(0x6000), 0x12);
+}
+
+#[test]
+fn mmc3_scanline_irq() {
+    for (submapper, irq_with_zero_latch) in [(0, true), (4, false)] {
+        let data = nes20(4, submapper, 8, 16, false);
+        let mut cart = cartridge(&data);
+        cart.write_u8(0xC000, 3);
+        cart.write_u8(0xC001, 0);
+        cart.write_u8(0xE001, 0);
+
+        // Reload, 2, 1, 0.
+        for _ in 0..3 {
+            mmc3_scanline(&mut cart);
+            assert!(!cart.irq());
+        }
+        mmc3_scanline(&mut cart);
+        assert!(cart.irq());
+
+        cart.write_u8(0xE000, 0);
+        assert!(!cart.irq());
+        cart.write_u8(0xE001, 0);
+
+        // With a zero latch, Sharp MMC3s fire on every scanline while NEC
+        // ones don't fire at all, as the counter never decrements into zero.
+        cart.write_u8(0xC000, 0);
+        for _ in 0..2 {
+            mmc3_scanline(&mut cart);
+            assert_eq!(cart.irq(), irq_with_zero_latch);
+            cart.write_u8(0xE000, 0);
+            cart.write_u8(0xE001, 0);
+        }
+    }
+}
+
+#[test]
+fn mmc3_variants() {
+    // TQROM: CHR bank bit 6 selects CHR-RAM.
+    let data = nes20(119, 0, 8, 8, false);
+    let mut cart = cartridge(&data);
+    cart.write_u8(0x8000, 2);
+    cart.write_u8(0x8001, 0x41);
+    cart.ppu_write(0x1000, 0x99);
+    assert_eq!(cart.ppu_read(0x1000), 0x99);
+    cart.write_u8(0x8001, 0x08);
+    assert_eq!(cart.ppu_read(0x1000), 1);
+
+    // MMC6: 1 KiB of PRG-RAM at $7000, protected by halves.
+    let data = nes20(4, 1, 8, 8, false);
+    let mut cart = cartridge(&data);
+    cart.write_u8(0xA001, 0xF0);
+    cart.write_u8(0x7000, 0x11);
+    assert_eq!(cart.mapper().cpu_peek(0x7000), None);
+
+    cart.write_u8(0x8000, 0x20);
+    cart.write_u8(0xA001, 0xF0);
+    cart.write_u8(0x7000, 0x11);
+    cart.write_u8(0x7200, 0x22);
+    assert_eq!(cart.peek_u8(0x7400), 0x11);
+    assert_eq!(cart.peek_u8(0x7600), 0x22);
+
+    // Only the low half readable: the high half reads as zero.
+    cart.write_u8(0xA001, 0x30);
+    cart.write_u8(0x7000, 0x33);
+    assert_eq!(cart.peek_u8(0x7000), 0x33);
+    assert_eq!(cart.peek_u8(0x7200), 0x00);
+
+    // The high half write-enabled but not readable can't be written either.
+    cart.write_u8(0xA001, 0x70);
+    cart.write_u8(0x7200, 0x44);
+    cart.write_u8(0xA001, 0xF0);
+    assert_eq!(cart.peek_u8(0x7200), 0x22);
+}
+
+/// Fetches a scanline the way the PPU does, for a background at $0000, and
+/// returns the bytes read for the given tile column (NT, AT, PT low, PT
+/// high).
+fn mmc5_scanline(cart: &mut Cartridge<Box<dyn Mapper + '_>>, column: u8) -> [u8; 4] {
+    let mut tile = [0; 4];
+    let fetch = |cart: &mut Cartridge<Box<dyn Mapper + '_>>, x: u8| {
+        let nt = cart.ppu_read(0x2000 + x as u16);
+        let at = cart.ppu_read(0x23C0 + (x / 4) as u16);
+        let lo = cart.ppu_read((nt as u16) << 4);
+        let hi = cart.ppu_read(((nt as u16) << 4) | 8);
+        [nt, at, lo, hi]
+    };
+
+    for x in 2..34 {
+        let bytes = fetch(cart, x);
+        if x == column {
+            tile = bytes;
+        }
+    }
+    for _ in 0..8 {
+        cart.ppu_read(0x2000);
+        cart.ppu_read(0x2000);
+        cart.ppu_read(0x1000);
+        cart.ppu_read(0x1008);
+    }
+    fetch(cart, 0);
+    fetch(cart, 1);
+    // Dummy nametable fetches; the next scanline starts with a third read
+    // of the same address.
+    cart.ppu_read(0x2002);
+    cart.ppu_read(0x2002);
+    tile
+}
+
+#[test]
+fn mmc5_banking() {
+    let data = nes20(5, 0, 16, 16, false);
+    let mut cart = cartridge(&data);
+    // Power-on: PRG mode 3, last bank at $E000.
+    assert_eq!(cart.peek_u8(0xE000), 15);
+
+    // Mode 2: 16 KiB at $8000, 8 KiB at $C000 and $E000.
+    cart.write_u8(0x5100, 2);
+    cart.write_u8(0x5115, 0x80 | 6);
+    cart.write_u8(0x5116, 0x80 | 9);
+    assert_eq!(cart.peek_u8(0x8000), 3);
+    assert_eq!(cart.peek_u8(0xA000), 3);
+    assert_eq!(cart.peek_u8(0xC000), 4);
+
+    // PRG-RAM at $8000, write protected until $5102/$5103 are set.
+    cart.write_u8(0x5115, 0);
+    cart.write_u8(0x8000, 0x42);
+    assert_eq!(cart.peek_u8(0x8000), 0);
+    cart.write_u8(0x5102, 2);
+    cart.write_u8(0x5103, 1);
+    cart.write_u8(0x8000, 0x42);
+    assert_eq!(cart.peek_u8(0x8000), 0x42);
+    assert_eq!(cart.peek_u8(0x6000), 0x42);
+
+    // CHR mode 3 (1 KiB), with 8x8 sprites the last written set is used.
+    cart.write_u8(0x5101, 3);
+    cart.write_u8(0x5120, 8 * 5);
+    assert_eq!(cart.ppu_read(0x0000), 5);
+    cart.write_u8(0x5128, 8 * 6);
+    assert_eq!(cart.ppu_read(0x0000), 6);
+    assert_eq!(cart.ppu_read(0x1000), 6);
+
+    // Multiplier.
+    cart.write_u8(0x5205, 200);
+    cart.write_u8(0x5206, 100);
+    assert_eq!(cart.peek_u16(0x5205), 20000);
+}
+
+#[test]
+fn mmc5_snooped_ppuctrl() {
+    let data = nes20(5, 0, 16, 16, false);
+    let mut bus = NesBus::new(Unmapped, Unmapped, cartridge(&data));
+    // CHR mode 3, with set A written last.
+    bus.write_u8(0x5101, 3);
+    bus.write_u8(0x5128, 8 * 6);
+    bus.write_u8(0x5120, 8 * 5);
+    mmc5_scanline(bus.cartridge_mut(), 0);
+    assert_eq!(mmc5_scanline(bus.cartridge_mut(), 2)[2], 5);
+
+    // 8x16 sprites (through a PPUCTRL mirror): backgrounds use set B.
+    bus.write_u8(0x2008, 0x20);
+    assert_eq!(mmc5_scanline(bus.cartridge_mut(), 2)[2], 6);
+    bus.write_u8(0x2000, 0x00);
+    assert_eq!(mmc5_scanline(bus.cartridge_mut(), 2)[2], 5);
+}
+
+#[test]
+fn mmc5_nametables() {
+    let data = nes20(5, 0, 16, 16, false);
+    let mut cart = cartridge(&data);
+    // CIRAM page 0, CIRAM page 1, ExRAM, fill.
+    cart.write_u8(0x5105, 0b11_10_01_00);
+    cart.write_u8(0x5106, 0x77);
+    cart.write_u8(0x5107, 0x02);
+    cart.ppu_write(0x2000, 0x01);
+    cart.ppu_write(0x2400, 0x02);
+    cart.ppu_write(0x2800, 0x03);
+    assert_eq!(cart.vram()[0x000], 0x01);
+    assert_eq!(cart.vram()[0x400], 0x02);
+    assert_eq!(cart.mapper().ppu_peek(0x2800, cart.vram()), 0x03);
+    assert_eq!(cart.ppu_peek(0x2C00), 0x77);
+    assert_eq!(cart.ppu_peek(0x2FC0), 0xAA);
+
+    // ExRAM as CPU RAM (mode 2).
+    cart.write_u8(0x5104, 2);
+    cart.write_u8(0x5C10, 0x99);
+    assert_eq!(cart.peek_u8(0x5C10), 0x99);
+    assert_eq!(cart.ppu_peek(0x2810), 0x00);
+}
+
+#[test]
+fn mmc5_scanline_irq() {
+    let data = nes20(5, 0, 16, 16, false);
+    let mut cart = cartridge(&data);
+    cart.write_u8(0x5203, 3);
+    cart.write_u8(0x5204, 0x80);
+
+    // Pre-render line: the frame starts with the first fetch of the next
+    // scanline.
+    mmc5_scanline(&mut cart, 0);
+    assert_eq!(cart.peek_u8(0x5204), 0x00);
+    mmc5_scanline(&mut cart, 0);
+    assert_eq!(cart.peek_u8(0x5204), 0x40);
+
+    for _ in 1..3 {
+        mmc5_scanline(&mut cart, 0);
+        assert!(!cart.irq());
+    }
+    mmc5_scanline(&mut cart, 0);
+    assert!(cart.irq());
+    assert_eq!(cart.read_u8(0x5204), 0xC0);
+    assert!(!cart.irq());
+
+    // Rendering stops when the PPU stops reading.
+    for _ in 0..3 {
+        cart.mapper_mut().cpu_cycle();
+    }
+    assert_eq!(cart.peek_u8(0x5204), 0x00);
+}
+
+#[test]
+fn mmc5_exram_modes() {
+    let data = nes20(5, 0, 16, 32, false);
+    let mut cart = cartridge(&data);
+    cart.write_u8(0x5105, 0x00);
+    cart.ppu_write(0x2005, 0x10);
+
+    // Tile 5 uses 4 KiB bank 9 (in 8 KiB bank 4) and palette 3; split tile
+    // at row 2, column 3.
+    cart.write_u8(0x5104, 2);
+    cart.write_u8(0x5C05, 0xC0 | 9);
+    cart.write_u8(0x5C00 + 32 * 2 + 3, 0x20);
+
+    // Extended attributes.
+    cart.write_u8(0x5104, 1);
+    mmc5_scanline(&mut cart, 0);
+    let tile = mmc5_scanline(&mut cart, 5);
+    assert_eq!(tile, [0x10, 0xFF, 4, 4]);
+
+    // Split screen on the left 4 columns, scrolled 16 lines down: tiles
+    // come from ExRAM and the 4 KiB CHR page 3 (in 8 KiB bank 1).
+    cart.write_u8(0x5200, 0x80 | 4);
+    cart.write_u8(0x5201, 16);
+    cart.write_u8(0x5202, 3);
+    let tile = mmc5_scanline(&mut cart, 3);
+    assert_eq!((tile[0], tile[2]), (0x20, 1));
+    let tile = mmc5_scanline(&mut cart, 5);
+    assert_eq!(tile[0], 0x10);
+
+    // ExRAM writes outside of rendering store zeros on modes 0/1.
+    for _ in 0..3 {
+        cart.mapper_mut().cpu_cycle();
+    }
+    cart.write_u8(0x5C05, 0x55);
+    cart.write_u8(0x5104, 2);
+    assert_eq!(cart.peek_u8(0x5C05), 0x00);
+}
+
+#[test]
+fn mmc5_audio() {
+    let data = nes20(5, 0, 16, 16, false);
+    let mut cart = cartridge(&data);
+    assert_eq!(cart.audio(), 0.0);
+
+    // Raw PCM.
+    cart.write_u8(0x5011, 0x80);
+    let pcm = cart.audio();
+    assert!(pcm > 0.0);
+
+    // Pulse 1: constant volume 15, 50% duty.
+    cart.write_u8(0x5015, 0x01);
+    cart.write_u8(0x5000, 0xBF);
+    cart.write_u8(0x5002, 0x10);
+    cart.write_u8(0x5003, 0x08);
+    assert_eq!(cart.peek_u8(0x5015), 0x01);
+    let (mut min, mut max) = (f32::MAX, 0.0f32);
+    for _ in 0..200 {
+        cart.mapper_mut().cpu_cycle();
+        min = min.min(cart.audio());
+        max = max.max(cart.audio());
+    }
+    assert_eq!(min, pcm);
+    assert!(max > pcm);
+
+    // PCM read mode: zero bytes raise the IRQ.
+    cart.write_u8(0x5010, 0x81);
+    cart.write_u8(0x5114, 0x80);
+    cart.read_u8(0x8000);
+    assert!(cart.irq());
+    assert_eq!(cart.read_u8(0x5010) & 0x80, 0x80);
+    assert!(!cart.irq());
+}
+
+#[test]
+fn vrc4_variants() {
+    // VRC4a: A1 and A2 select the registers.
+    let data = nes20(21, 1, 16, 16, false);
+    let mut cart = cartridge(&data);
+    cart.write_u8(0x8000, 4);
+    assert_eq!(cart.peek_u8(0x8000), 2);
+    assert_eq!(cart.peek_u8(0xC000), 15);
+    cart.write_u8(0x9004, 0x02);
+    assert_eq!(cart.peek_u8(0x8000), 15);
+    assert_eq!(cart.peek_u8(0xC000), 2);
+    cart.write_u8(0x9000, 0x03);
+    assert_eq!(cart.mapper().mirroring(), Mirroring::SingleScreenUpper);
+
+    // VRC4b: A1 and A0 select the low and high CHR nibbles.
+    let data = nes20(25, 1, 16, 16, false);
+    let mut cart = cartridge(&data);
+    cart.write_u8(0xB000, 0x08);
+    assert_eq!(cart.ppu_read(0x0000), 1);
+    cart.write_u8(0xB002, 0x01);
+    assert_eq!(cart.ppu_read(0x0000), 3);
+
+    // VRC2a ignores the lowest CHR bank bit.
+    let data = nes20(22, 0, 16, 16, false);
+    let mut cart = cartridge(&data);
+    cart.write_u8(0xE003, 0x01);
+    assert_eq!(cart.ppu_read(0x1C00), 1);
+    cart.write_u8(0x9000, 0x03);
+    assert_eq!(cart.mapper().mirroring(), Mirroring::Horizontal);
+}
+
+#[test]
+fn vrc_irq() {
+    let data = nes20(21, 1, 16, 16, false);
+    let mut cart = cartridge(&data);
+
+    // Cycle mode, counting from $FE.
+    cart.write_u8(0xF000, 0x0E);
+    cart.write_u8(0xF002, 0x0F);
+    cart.write_u8(0xF004, 0x06);
+    cart.mapper_mut().cpu_cycle();
+    assert!(!cart.irq());
+    cart.mapper_mut().cpu_cycle();
+    assert!(cart.irq());
+    cart.write_u8(0xF006, 0x00);
+    assert!(!cart.irq());
+
+    // Scanline mode, counting from $FF: one scanline is 113 2/3 cycles.
+    cart.write_u8(0xF000, 0x0F);
+    cart.write_u8(0xF004, 0x02);
+    for _ in 0..113 {
+        cart.mapper_mut().cpu_cycle();
+    }
+    assert!(!cart.irq());
+    cart.mapper_mut().cpu_cycle();
+    assert!(cart.irq());
+}
+
+#[test]
+fn vrc6() {
+    let data = nes20(24, 0, 16, 16, false);
+    let mut cart = cartridge(&data);
+    cart.write_u8(0x8000, 3);
+    cart.write_u8(0xC000, 5);
+    assert_eq!(cart.peek_u8(0x8000), 3);
+    assert_eq!(cart.peek_u8(0xA000), 3);
+    assert_eq!(cart.peek_u8(0xC000), 2);
+    assert_eq!(cart.peek_u8(0xE000), 15);
+    cart.write_u8(0xE003, 17);
+    assert_eq!(cart.ppu_read(0x1C00), 2);
+
+    cart.write_u8(0xB003, 0x84);
+    assert_eq!(cart.mapper().mirroring(), Mirroring::Horizontal);
+    cart.write_u8(0x6000, 0x55);
+    assert_eq!(cart.peek_u8(0x6000), 0x55);
+
+    // VRC6b swaps A0 and A1: $9002 is the period low byte.
+    let data = nes20(26, 0, 16, 16, false);
+    let mut cart = cartridge(&data);
+    assert_eq!(cart.audio(), 0.0);
+    cart.write_u8(0x9000, 0x8F);
+    cart.write_u8(0x9002, 0x10);
+    cart.write_u8(0x9001, 0x80);
+    let pulse = cart.audio();
+    assert!(pulse > 0.0);
+
+    // Sawtooth ramps up from 0.
+    cart.write_u8(0xB000, 0x3F);
+    cart.write_u8(0xB001, 0x80);
+    let mut max = 0.0f32;
+    for _ in 0..200 {
+        cart.mapper_mut().cpu_cycle();
+        max = max.max(cart.audio());
+    }
+    assert!(max > pulse);
+}
+
+#[test]
+fn vrc7() {
+    let data = nes20(85, 2, 16, 16, false);
+    let mut cart = cartridge(&data);
+    cart.write_u8(0x8000, 3);
+    cart.write_u8(0x8010, 4);
+    cart.write_u8(0x9000, 6);
+    assert_eq!(cart.peek_u8(0x8000), 1);
+    assert_eq!(cart.peek_u8(0xA000), 2);
+    assert_eq!(cart.peek_u8(0xC000), 3);
+    assert_eq!(cart.peek_u8(0xE000), 15);
+    cart.write_u8(0xA010, 9);
+    assert_eq!(cart.ppu_read(0x0400), 1);
+
+    // Instrument 1 at full volume, keyed on.
+    for (reg, data) in [(0x30, 0x10), (0x10, 0x80), (0x20, 0x18)] {
+        cart.write_u8(0x9010, reg);
+        cart.write_u8(0x9030, data);
+    }
+    let mut peak = 0.0f32;
+    for _ in 0..36 * 200 {
+        cart.mapper_mut().cpu_cycle();
+        peak = peak.max(cart.audio().abs());
+    }
+    assert!(peak > 0.0);
+
+    // Silenced by $E000 bit 6.
+    cart.write_u8(0xE000, 0x40);
+    assert_eq!(cart.audio(), 0.0);
+}
+
+fn fme7_command(cart: &mut Cartridge<Box<dyn Mapper + '_>>, command: u8, data: u8) {
+    cart.write_u8(0x8000, command);
+    cart.write_u8(0xA000, data);
+}
+
+#[test]
+fn fme7() {
+    let data = nes20(69, 0, 16, 16, false);
+    let mut cart = cartridge(&data);
+    fme7_command(&mut cart, 0x9, 5);
+    fme7_command(&mut cart, 0x7, 17);
+    assert_eq!(cart.peek_u8(0x8000), 2);
+    assert_eq!(cart.peek_u8(0xE000), 15);
+    assert_eq!(cart.ppu_read(0x1C00), 2);
+
+    // $6000: PRG-ROM, then enabled PRG-RAM.
+    fme7_command(&mut cart, 0x8, 0x03);
+    assert_eq!(cart.peek_u8(0x6000), 1);
+    fme7_command(&mut cart, 0x8, 0xC0);
+    cart.write_u8(0x6000, 0x55);
+    assert_eq!(cart.peek_u8(0x6000), 0x55);
+
+    // The IRQ fires when the counter wraps.
+    fme7_command(&mut cart, 0xE, 2);
+    fme7_command(&mut cart, 0xF, 0);
+    fme7_command(&mut cart, 0xD, 0x81);
+    cart.mapper_mut().cpu_cycle();
+    cart.mapper_mut().cpu_cycle();
+    assert!(!cart.irq());
+    cart.mapper_mut().cpu_cycle();
+    assert!(cart.irq());
+    fme7_command(&mut cart, 0xD, 0x00);
+    assert!(!cart.irq());
+
+    // 5B: tone A at full volume.
+    for (reg, data) in [(0x7, 0x3E), (0x8, 0x0F), (0x0, 0x01)] {
+        cart.write_u8(0xC000, reg);
+        cart.write_u8(0xE000, data);
+    }
+    let (mut min, mut max) = (f32::MAX, 0.0f32);
+    for _ in 0..200 {
+        cart.mapper_mut().cpu_cycle();
+        min = min.min(cart.audio());
+        max = max.max(cart.audio());
+    }
+    assert_eq!(min, 0.0);
+    assert!(max > 0.0);
+}
+
+#[test]
+fn namco163_banking() {
+    let data = nes20(19, 0, 8, 16, false);
+    let mut cart = cartridge(&data);
+    cart.write_u8(0xE000, 3);
+    assert_eq!(cart.peek_u8(0x8000), 1);
+    assert_eq!(cart.peek_u8(0xE000), 7);
+
+    // Pattern tables: CHR-ROM, then a CIRAM page.
+    cart.write_u8(0x8000, 9);
+    assert_eq!(cart.ppu_read(0x0000), 1);
+    cart.write_u8(0x8000, 0xE1);
+    cart.ppu_write(0x0005, 0x77);
+    cart.write_u8(0xC000, 0xE1);
+    assert_eq!(cart.ppu_read(0x2005), 0x77);
+    cart.write_u8(0xE800, 0x40);
+    assert_eq!(cart.ppu_read(0x0005), 12);
+
+    // PRG-RAM is write protected unless enabled through $F800.
+    cart.write_u8(0x6000, 0x55);
+    assert_eq!(cart.peek_u8(0x6000), 0x00);
+    cart.write_u8(0xF800, 0x40);
+    cart.write_u8(0x6000, 0x55);
+    assert_eq!(cart.peek_u8(0x6000), 0x55);
+
+    // IRQ at $7FFF.
+    cart.write_u8(0x5000, 0xFE);
+    cart.write_u8(0x5800, 0xFF);
+    assert!(!cart.irq());
+    cart.mapper_mut().cpu_cycle();
+    assert!(cart.irq());
+    assert_eq!(cart.peek_u8(0x5800), 0xFF);
+    cart.write_u8(0x5000, 0x00);
+    assert!(!cart.irq());
+}
+
+#[test]
+fn namco163_audio() {
+    let mut data = nes20(19, 0, 8, 16, false);
+    // Battery, and 128 bytes of PRG-NVRAM: the sound RAM.
+    data[6] |= 0x02;
+    data[10] = 0x10;
+    let mut cart = cartridge(&data);
+    assert_eq!(cart.save_ram().map(<[u8]>::len), Some(0x80));
+
+    // Square wavetable, played by channel 7 alone.
+    cart.write_u8(0xF800, 0x80);
+    for _ in 0..16 {
+        cart.write_u8(0x4800, 0xF0);
+    }
+    cart.write_u8(0xF800, 0xFA);
+    for data in [0x40, 0x00, 0xE0, 0x00, 0x00, 0x0F] {
+        cart.write_u8(0x4800, data);
+    }
+    assert_eq!(cart.save_ram().unwrap()[0x7F], 0x0F);
+    cart.write_u8(0xF800, 0x7F);
+    assert_eq!(cart.read_u8(0x4800), 0x0F);
+
+    let (mut min, mut max) = (0.0f32, 0.0f32);
+    for _ in 0..15 * 64 {
+        cart.mapper_mut().cpu_cycle();
+        min = min.min(cart.audio());
+        max = max.max(cart.audio());
+    }
+    assert!(min < 0.0 && max > 0.0);
+}
+
+fn i2c_lines(cart: &mut Cartridge<Box<dyn Mapper + '_>>, scl: bool, sda: bool) {
+    cart.write_u8(0x800D, 0x80 | ((scl as u8) << 5) | ((sda as u8) << 6));
+}
+
+fn i2c_sda(cart: &mut Cartridge<Box<dyn Mapper + '_>>) -> bool {
+    cart.read_u8(0x6000) & 0x10 != 0
+}
+
+fn i2c_start(cart: &mut Cartridge<Box<dyn Mapper + '_>>) {
+    i2c_lines(cart, false, true);
+    i2c_lines(cart, true, true);
+    i2c_lines(cart, true, false);
+}
+
+fn i2c_stop(cart: &mut Cartridge<Box<dyn Mapper + '_>>) {
+    i2c_lines(cart, false, false);
+    i2c_lines(cart, true, false);
+    i2c_lines(cart, true, true);
+}
+
+/// Sends a byte MSB first, and returns whether it was acknowledged.
+fn i2c_send(cart: &mut Cartridge<Box<dyn Mapper + '_>>, byte: u8) -> bool {
+    for bit in (0..8).rev() {
+        let sda = byte >> bit & 1 != 0;
+        i2c_lines(cart, false, sda);
+        i2c_lines(cart, true, sda);
+    }
+    i2c_lines(cart, false, true);
+    i2c_lines(cart, true, true);
+    !i2c_sda(cart)
+}
+
+/// Receives a byte MSB first, acknowledging it if `ack`.
+fn i2c_receive(cart: &mut Cartridge<Box<dyn Mapper + '_>>, ack: bool) -> u8 {
+    let mut byte = 0;
+    for _ in 0..8 {
+        i2c_lines(cart, false, true);
+        i2c_lines(cart, true, true);
+        byte = (byte << 1) | i2c_sda(cart) as u8;
+    }
+    i2c_lines(cart, false, !ack);
+    i2c_lines(cart, true, !ack);
+    byte
+}
+
+#[test]
+fn bandai_eeprom() {
+    let mut data = nes20(16, 5, 8, 16, false);
+    // Battery, and 256 bytes of PRG-NVRAM: the 24C02.
+    data[6] |= 0x02;
+    data[10] = 0x20;
+    let mut cart = cartridge(&data);
+    assert_eq!(cart.save_ram().map(<[u8]>::len), Some(0x100));
+
+    cart.write_u8(0x8008, 3);
+    assert_eq!(cart.peek_u8(0x8000), 3);
+    assert_eq!(cart.peek_u8(0xC000), 7);
+
+    // Write two bytes at $10.
+    i2c_start(&mut cart);
+    assert!(i2c_send(&mut cart, 0xA0));
+    assert!(i2c_send(&mut cart, 0x10));
+    assert!(i2c_send(&mut cart, 0x5A));
+    assert!(i2c_send(&mut cart, 0xC3));
+    i2c_stop(&mut cart);
+    assert_eq!(cart.save_ram().unwrap()[0x10..0x12], [0x5A, 0xC3]);
+
+    // Random read: dummy write of the address, then a repeated start.
+    i2c_start(&mut cart);
+    assert!(i2c_send(&mut cart, 0xA0));
+    assert!(i2c_send(&mut cart, 0x10));
+    i2c_start(&mut cart);
+    assert!(i2c_send(&mut cart, 0xA1));
+    assert_eq!(i2c_receive(&mut cart, true), 0x5A);
+    assert_eq!(i2c_receive(&mut cart, false), 0xC3);
+    i2c_stop(&mut cart);
+
+    // Other devices aren't acknowledged.
+    i2c_start(&mut cart);
+    assert!(!i2c_send(&mut cart, 0x50));
+    i2c_stop(&mut cart);
+}
+
+#[test]
+fn bandai_irq() {
+    let data = nes20(16, 5, 8, 16, false);
+    let mut cart = cartridge(&data);
+    cart.write_u8(0x800B, 2);
+    cart.write_u8(0x800C, 0);
+    cart.write_u8(0x800A, 1);
+    cart.mapper_mut().cpu_cycle();
+    assert!(!cart.irq());
+    cart.mapper_mut().cpu_cycle();
+    assert!(cart.irq());
+    cart.write_u8(0x800A, 0);
+    assert!(!cart.irq());
+}
+
+#[test]
+fn save_file() {
+    let path = std::env::temp_dir().join(format!("effnes-save-{}.sav", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+
+    let mut data = nes20(0, 0, 1, 1, false);
+    data[6] |= 0x02;
+    data[10] = 0x70;
+    let mut cart = cartridge(&data);
+    let mut save = SaveFile::new(&path, FlushPolicy::AtExit);
+    assert!(!save.load(&mut cart).unwrap());
+    assert!(!save.is_dirty(&cart));
+    assert!(!save.flush(&cart).unwrap());
+    assert!(!path.exists());
+
+    cart.write_u8(0x6000, 0x55);
+    assert!(save.is_dirty(&cart));
+    assert!(!save.poll(&cart).unwrap());
+    assert!(save.flush(&cart).unwrap());
+    assert!(!save.is_dirty(&cart));
+
+    // Periodic flushes, on a fresh cartridge.
+    let mut cart = cartridge(&data);
+    let mut save = SaveFile::new(&path, FlushPolicy::Periodic(Duration::ZERO));
+    assert!(save.load(&mut cart).unwrap());
+    assert_eq!(cart.peek_u8(0x6000), 0x55);
+    assert!(!save.poll(&cart).unwrap());
+    cart.write_u8(0x6001, 0xAA);
+    assert!(save.poll(&cart).unwrap());
+    assert_eq!(std::fs::read(&path).unwrap()[..2], [0x55, 0xAA]);
+
+    // Size mismatches are rejected.
+    std::fs::write(&path, [0; 16]).unwrap();
+    let err = save.load(&mut cart).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+
+    // Existing saves aren't overwritten without being loaded first.
+    std::fs::write(&path, [0x55; 0x2000]).unwrap();
+    let mut cart = cartridge(&data);
+    let mut save = SaveFile::new(&path, FlushPolicy::Periodic(Duration::ZERO));
+    cart.write_u8(0x6000, 0xAA);
+    let err = save.poll(&cart).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::AlreadyExists);
+    assert_eq!(std::fs::read(&path).unwrap(), [0x55; 0x2000]);
+
+    // Only the battery-backed part of PRG-RAM is saved: MMC5 with 8 KiB of
+    // PRG-NVRAM (bank 0) and 8 KiB of PRG-RAM (bank 1).
+    let mut data = nes20(5, 0, 2, 1, false);
+    data[6] |= 0x02;
+    data[10] = 0x77;
+    let mut cart = cartridge(&data);
+    let mut save = SaveFile::new(&path, FlushPolicy::AtExit);
+    assert!(save.load(&mut cart).unwrap());
+    assert_eq!(cart.save_ram().map(<[u8]>::len), Some(0x2000));
+    assert_eq!(cart.peek_u8(0x6000), 0x55);
+    cart.write_u8(0x5102, 0x02);
+    cart.write_u8(0x5103, 0x01);
+    cart.write_u8(0x5113, 0x01);
+    assert_eq!(cart.peek_u8(0x6000), 0x00);
+    cart.write_u8(0x6000, 0xAA);
+    assert!(!save.is_dirty(&cart));
+    cart.write_u8(0x5113, 0x00);
+    cart.write_u8(0x6000, 0xAA);
+    assert!(save.is_dirty(&cart));
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn ips_patches() {
+    let original = [0u8; 32];
+    let mut modified = original;
+    modified[3] = 1;
+    modified[4] = 2;
+    modified[20] = 3;
+    let patch = ips::create(&original, &modified);
+    assert_eq!(&patch[..5], ips::MAGIC);
+    assert_eq!(patch.len(), 5 + (5 + 2) + (5 + 1) + 3);
+
+    let mut data = original.to_vec();
+    ips::apply(&mut data, &patch).unwrap();
+    assert_eq!(data, modified);
+    assert_eq!(ips::apply(&mut data, &patch[..8]), Err(Error::InvalidPatch));
+}
+
+/// Disk side with the disk info block and a single file, in `.fds` layout.
+fn fds_side() -> Vec<u8> {
+    let mut side = DISK_VERIFICATION.to_vec();
+    side.resize(56, 0);
+    side.extend([2, 1]);
+    side.extend([3, 0, 0]);
+    side.extend(b"FILE0000");
+    side.extend([0x00, 0x60, 3, 0, 0]);
+    side.extend([4, 0xAA, 0xBB, 0xCC]);
+    side.resize(fds::SIDE_LEN, 0);
+    side
+}
+
+fn fds_cartridge(data: &[u8]) -> Cartridge<Fds<'static>> {
+    let image = DiskImage::parse(data).unwrap();
+    let fds = Fds::new(vec![0; fds_mapper::BIOS_LEN].into(), &image).unwrap();
+    Cartridge::new(fds)
+}
+
+/// Runs the drive until the next transferred byte.
+fn fds_next_byte(cart: &mut Cartridge<Fds>) -> u8 {
+    for _ in 0..1_000_000 {
+        cart.mapper_mut().cpu_cycle();
+        if cart.peek_u8(0x4030) & 0x02 != 0 {
+            return cart.read_u8(0x4031);
+        }
+    }
+    panic!("no byte transferred");
+}
+
+#[test]
+fn fds_disk_read_write() {
+    let side = fds_side();
+    assert_eq!(
+        disk::parse_track(&disk::build_track(
+            DiskImage::parse(&side).unwrap().blocks(0)
+        )),
+        side
+    );
+
+    let mut cart = fds_cartridge(&side);
+    assert!(matches!(
+        Fds::new(vec![0; 100].into(), &DiskImage::parse(&side).unwrap()),
+        Err(Error::InvalidBios { len: 100 })
+    ));
+
+    // Read the disk info block, with motor on, read mode and CRC check.
+    cart.write_u8(0x4023, 0x01);
+    cart.write_u8(0x4025, 0x45);
+    assert_eq!(cart.mapper().mirroring(), Mirroring::Vertical);
+    assert_eq!(fds_next_byte(&mut cart), 0x80);
+    let block: Vec<u8> = (0..56).map(|_| fds_next_byte(&mut cart)).collect();
+    assert_eq!(block[..15], DISK_VERIFICATION[..]);
+    fds_next_byte(&mut cart);
+    fds_next_byte(&mut cart);
+    assert_eq!(cart.peek_u8(0x4030) & 0x10, 0x00);
+    assert_eq!(cart.peek_u8(0x4032) & 0x07, 0x00);
+
+    // Write a new file amount block right after it.
+    cart.write_u8(0x4024, 0x00);
+    cart.write_u8(0x4025, 0x41);
+    for data in [0x00, 0x80, 0x02, 0x05] {
+        fds_next_byte(&mut cart);
+        cart.write_u8(0x4024, data);
+    }
+    fds_next_byte(&mut cart);
+    cart.write_u8(0x4025, 0x51);
+    for _ in 0..3 * disk::BYTE_CYCLES {
+        cart.mapper_mut().cpu_cycle();
+    }
+    cart.write_u8(0x4025, 0x00);
+
+    let fds = cart.mapper();
+    assert!(fds.is_modified());
+    assert_eq!(fds.blocks(0)[1], [0x02, 0x05]);
+
+    // The diff brings a fresh disk up to date.
+    let patch = fds.diff();
+    let mut fresh = fds_cartridge(&side);
+    fresh.mapper_mut().apply_diff(&patch).unwrap();
+    assert_eq!(fresh.mapper().disk_data(), fds.disk_data());
+    assert_eq!(
+        fresh
+            .mapper_mut()
+            .apply_diff(&ips::create(&[], &[0; fds::SIDE_LEN + 1])),
+        Err(Error::PatchSizeMismatch {
+            expected: fds::SIDE_LEN,
+            actual: fds::SIDE_LEN + 1,
+        })
+    );
+}
+
+#[test]
+fn fds_sides_and_timer() {
+    let mut data = fds_side();
+    data.extend(fds_side());
+    let mut cart = fds_cartridge(&data);
+    cart.write_u8(0x4023, 0x01);
+
+    assert_eq!(cart.mapper().drive().side(), Some(0));
+    cart.mapper_mut().drive_mut().switch_side(1);
+    assert_eq!(cart.peek_u8(0x4032) & 0x05, 0x05);
+    for _ in 0..disk::SWITCH_CYCLES {
+        cart.mapper_mut().cpu_cycle();
+    }
+    assert_eq!(cart.mapper().drive().side(), Some(1));
+    assert_eq!(cart.peek_u8(0x4032) & 0x05, 0x00);
+
+    // One-shot timer IRQ.
+    cart.write_u8(0x4020, 2);
+    cart.write_u8(0x4021, 0);
+    cart.write_u8(0x4022, 0x02);
+    cart.mapper_mut().cpu_cycle();
+    cart.mapper_mut().cpu_cycle();
+    assert!(!cart.irq());
+    cart.mapper_mut().cpu_cycle();
+    assert!(cart.irq());
+    assert_eq!(cart.read_u8(0x4030) & 0x01, 0x01);
+    assert!(!cart.irq());
+    for _ in 0..10 {
+        cart.mapper_mut().cpu_cycle();
+    }
+    assert!(!cart.irq());
+}
+
+#[test]
+fn fds_audio() {
+    let mut cart = fds_cartridge(&fds_side());
+    cart.write_u8(0x4023, 0x03);
+
+    // Ramp wavetable, fixed gain of 32.
+    cart.write_u8(0x4089, 0x80);
+    for i in 0..64 {
+        cart.write_u8(0x4040 + i, i as u8);
+    }
+    assert_eq!(cart.peek_u8(0x4045), 0x45);
+    cart.write_u8(0x4089, 0x00);
+    cart.write_u8(0x4080, 0xA0);
+    assert_eq!(cart.peek_u8(0x4090), 0x60);
+    cart.write_u8(0x4082, 0xFF);
+    cart.write_u8(0x4083, 0x0F);
+
+    let (mut min, mut max) = (f32::MAX, 0.0f32);
+    for _ in 0..2000 {
+        cart.mapper_mut().cpu_cycle();
+        min = min.min(cart.audio());
+        max = max.max(cart.audio());
+    }
+    assert_eq!(min, 0.0);
+    assert!(max > 0.0);
+
+    // Sound registers are disabled by $4023.
+    cart.write_u8(0x4023, 0x01);
+    assert_eq!(cart.mapper().cpu_peek(0x4090), None);
+}
@@ -85,3 +85,18 @@ impl From<OpCode> for AddressingMode {
         }
     }
 }
+
+impl AddressingMode {
+    /// Number of operand bytes following the opcode.
+    pub fn operand_len(self) -> u16 {
+        match self {
+            Self::Implied => 0,
+            Self::Immediate
+            | Self::ZeroPage
+            | Self::ZeroPageI(_)
+            | Self::IndirectI(_)
+            | Self::Relative => 1,
+            Self::Absolute | Self::AbsoluteI(_) | Self::Indirect => 2,
+        }
+    }
+}
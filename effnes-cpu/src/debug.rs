@@ -4,7 +4,7 @@ use crate::{
     cpu::Cpu,
     opcode::{Mnemonic, OpCode},
 };
-use effnes_bus::InspectBus;
+use effnes_bus::{InspectBus, signal::Signals};
 
 pub struct State {
     pub pc: u16,
@@ -28,6 +28,16 @@ pub trait DebugCpu: Cpu {
     fn set_ac(&mut self, ac: u8);
     fn set_ix(&mut self, ix: u8);
     fn set_iy(&mut self, iy: u8);
+
+    /// Address of the next instruction, if the CPU is between two
+    /// instructions (its next cycle fetches an opcode, or starts servicing
+    /// an interrupt).
+    fn next_instruction(&self) -> Option<u16>;
+
+    /// Whether the next cycle reads the bus only to throw the value away
+    /// (e.g. the unindexed pointer of `(zp,X)`), given the lines it'd sample
+    /// from `signals`.
+    fn dummy_read(&self, signals: &Signals) -> bool;
 }
 
 pub fn debug(vm: &impl DebugCpu, io: &dyn InspectBus) {
@@ -0,0 +1,183 @@
+use crate::{
+    addr::{AddressingMode, IndexRegister},
+    opcode::{Mnemonic, OpCode},
+};
+use std::fmt::{self, Display};
+
+/// Data bytes per [Line::Data].
+pub const DATA_PER_LINE: usize = 8;
+
+/// What a program byte is known to be (e.g. from a code/data log).
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum ByteKind {
+    #[default]
+    Unknown,
+    Code,
+    Data,
+}
+
+/// Decoded instruction.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Instruction {
+    pub addr: u16,
+    pub opcode: OpCode,
+    /// Operand bytes, little endian (0 for implied instructions).
+    pub operand: u16,
+}
+
+impl Instruction {
+    /// Decodes the instruction at the start of `bytes`, placed at `addr`.
+    ///
+    /// Returns `None` if `bytes` ends before the operand does.
+    pub fn decode(bytes: &[u8], addr: u16) -> Option<Self> {
+        let opcode = *bytes.first()?;
+        let len = AddressingMode::from(opcode).operand_len() as usize;
+        let operand = bytes
+            .get(1..=len)?
+            .iter()
+            .rev()
+            .fold(0, |operand, &byte| (operand << 8) | byte as u16);
+        Some(Self {
+            addr,
+            opcode,
+            operand,
+        })
+    }
+
+    pub fn mode(&self) -> AddressingMode {
+        self.opcode.into()
+    }
+
+    pub fn mnemonic(&self) -> Mnemonic {
+        self.opcode.into()
+    }
+
+    /// Size in bytes, opcode included.
+    pub fn size(&self) -> u16 {
+        1 + self.mode().operand_len()
+    }
+
+    /// Destination of a branch.
+    pub fn branch_target(&self) -> Option<u16> {
+        (self.mode() == AddressingMode::Relative).then(|| {
+            self.addr
+                .wrapping_add(2)
+                .wrapping_add_signed(self.operand as u8 as i8 as i16)
+        })
+    }
+}
+
+impl Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use AddressingMode::*;
+        use IndexRegister::{X, Y};
+
+        write!(f, "{}", self.mnemonic())?;
+        let op = self.operand;
+        match self.mode() {
+            Implied => Ok(()),
+            Immediate => write!(f, " #${:02x}", op),
+            Relative => write!(f, " ${:04x}", self.branch_target().unwrap_or_default()),
+            ZeroPage => write!(f, " ${:02x}", op),
+            Absolute => write!(f, " ${:04x}", op),
+            Indirect => write!(f, " (${:04x})", op),
+            ZeroPageI(X) => write!(f, " ${:02x},X", op),
+            ZeroPageI(Y) => write!(f, " ${:02x},Y", op),
+            AbsoluteI(X) => write!(f, " ${:04x},X", op),
+            AbsoluteI(Y) => write!(f, " ${:04x},Y", op),
+            IndirectI(X) => write!(f, " (${:02x},X)", op),
+            IndirectI(Y) => write!(f, " (${:02x}),Y", op),
+        }
+    }
+}
+
+/// Line of a disassembly listing.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Line {
+    Code(Instruction),
+    /// Up to [DATA_PER_LINE] bytes, starting at `addr`.
+    Data {
+        addr: u16,
+        bytes: Vec<u8>,
+    },
+}
+
+impl Line {
+    pub fn addr(&self) -> u16 {
+        match self {
+            Line::Code(instruction) => instruction.addr,
+            Line::Data { addr, .. } => *addr,
+        }
+    }
+}
+
+impl Display for Line {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Line::Code(instruction) => {
+                write!(f, "{:04x}  {:02x}", instruction.addr, instruction.opcode)?;
+                for i in 0..2 {
+                    if i < instruction.mode().operand_len() {
+                        write!(f, " {:02x}", (instruction.operand >> (8 * i)) as u8)?;
+                    } else {
+                        f.write_str("   ")?;
+                    }
+                }
+                write!(f, "  {}", instruction)
+            }
+            Line::Data { addr, bytes } => {
+                write!(f, "{:04x}  .byte ", addr)?;
+                for (i, byte) in bytes.iter().enumerate() {
+                    if i != 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "${:02x}", byte)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Disassembles `data`, placed at `base`, with a linear sweep.
+///
+/// `kind` tells what the byte at each offset into `data` is known to be:
+///
+/// - [ByteKind::Data] bytes are listed as `.byte` lines, and never decoded
+///   as part of an instruction.
+/// - [ByteKind::Code] and [ByteKind::Unknown] bytes are decoded, unless the
+///   instruction would run into data (or past the end of `data`).
+///
+/// With no knowledge at all (every byte [ByteKind::Unknown]) this is a plain
+/// linear sweep.
+pub fn disassemble(data: &[u8], base: u16, kind: impl Fn(usize) -> ByteKind) -> Vec<Line> {
+    let mut lines = Vec::new();
+    let mut offset = 0;
+
+    while offset < data.len() {
+        let addr = base.wrapping_add(offset as u16);
+        if kind(offset) != ByteKind::Data
+            && let Some(instruction) = Instruction::decode(&data[offset..], addr)
+            && (1..instruction.size() as usize).all(|i| kind(offset + i) != ByteKind::Data)
+        {
+            lines.push(Line::Code(instruction));
+            offset += instruction.size() as usize;
+            continue;
+        }
+
+        let mut len = 1;
+        while offset + len < data.len()
+            && len < DATA_PER_LINE
+            && kind(offset + len) == ByteKind::Data
+        {
+            len += 1;
+        }
+        lines.push(Line::Data {
+            addr,
+            bytes: data[offset..offset + len].to_vec(),
+        });
+        offset += len;
+    }
+
+    lines
+}
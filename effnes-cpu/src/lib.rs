@@ -2,4 +2,6 @@ pub mod addr;
 pub mod consts;
 pub mod cpu;
 pub mod debug;
+pub mod disasm;
 pub mod opcode;
+pub mod profile;
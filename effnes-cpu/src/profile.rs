@@ -0,0 +1,408 @@
+use crate::{consts::CpuVector, debug::DebugCpu};
+use effnes_bus::InspectBus;
+use std::{
+    collections::HashMap,
+    fmt::{self, Display, Write},
+};
+
+const JSR: u8 = 0x20;
+
+/// Code that cycles are attributed to.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Routine {
+    /// Code outside of any subroutine or interrupt handler.
+    Top,
+    /// Subroutine entered through a `JSR`.
+    Call(u16),
+    /// NMI handler.
+    Nmi(u16),
+    /// IRQ (or `BRK`) handler.
+    Irq(u16),
+}
+
+impl Routine {
+    /// Entry point (`None` for [Routine::Top]).
+    pub fn addr(self) -> Option<u16> {
+        match self {
+            Routine::Top => None,
+            Routine::Call(addr) | Routine::Nmi(addr) | Routine::Irq(addr) => Some(addr),
+        }
+    }
+}
+
+impl Display for Routine {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Routine::Top => f.write_str("top"),
+            Routine::Call(addr) => write!(f, "${:04x}", addr),
+            Routine::Nmi(addr) => write!(f, "nmi:${:04x}", addr),
+            Routine::Irq(addr) => write!(f, "irq:${:04x}", addr),
+        }
+    }
+}
+
+/// Line of [Profiler::flat].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct FlatEntry {
+    pub routine: Routine,
+    pub calls: u64,
+    /// Cycles spent in the routine itself.
+    pub self_cycles: u64,
+    /// Cycles spent in the routine and everything it called.
+    pub total_cycles: u64,
+}
+
+/// Call tree node: a routine, reached through a given call stack.
+#[derive(Clone, Debug)]
+struct Node {
+    routine: Routine,
+    parent: usize,
+    children: Vec<usize>,
+    calls: u64,
+    cycles: u64,
+}
+
+/// Routine on the call stack.
+#[derive(Copy, Clone, Debug)]
+struct Frame {
+    node: usize,
+    /// Stack pointer right after entering the routine; it's left once the
+    /// stack pointer goes above it again.
+    sp: u8,
+}
+
+/// Instruction seen at the last instruction boundary.
+#[derive(Copy, Clone, Debug)]
+struct Boundary {
+    pc: u16,
+    sp: u8,
+    opcode: u8,
+    cc: usize,
+}
+
+/// Execution Profiler
+///
+/// Attributes the cycles run by a CPU to the call stack they're run under,
+/// building a call tree out of which flat, call tree and collapsed stack
+/// reports are made.
+///
+/// ## Behaviour
+///
+/// - [Profiler::step] is called before running the CPU, and then after
+///   every one of its cycles. Only instruction boundaries (see
+///   [DebugCpu::next_instruction]) are looked at, so it works with both
+///   instruction and cycle stepped CPUs.
+/// - Calls and returns are followed through the stack pointer: a `JSR`
+///   landing on its target with two more bytes on the stack enters a
+///   subroutine, and moving the stack pointer above the one a routine was
+///   entered with (`RTS`, `RTI`, `TXS`, or popping the return address)
+///   leaves it. Pushing a return address and `RTS`ing into it doesn't leave
+///   anything.
+/// - Interrupts are told apart by the CPU landing on the NMI or IRQ handler
+///   with three more bytes on the stack (five if a `JSR` ran right before).
+///   Handlers shared by both vectors are reported as NMI handlers.
+/// - The cycles of an instruction (or interrupt sequence) go to the routine
+///   it started in: a `JSR` is paid by the caller, and an `RTS` by the
+///   callee.
+///
+/// ```ignore
+/// let mut profiler = Profiler::new();
+/// profiler.set_name(0x8123, "update_sprites");
+/// profiler.step(&cpu, &bus);
+///
+/// loop {
+///     cpu.cycle(&mut bus, &mut signals);
+///     profiler.step(&cpu, &bus);
+/// }
+///
+/// print!("{}", profiler.flat_report());
+/// fs::write("game.folded", profiler.collapsed_stacks())?;
+/// ```
+#[derive(Clone, Debug)]
+pub struct Profiler {
+    nodes: Vec<Node>,
+    stack: Vec<Frame>,
+    last: Option<Boundary>,
+    names: HashMap<u16, String>,
+}
+
+impl Default for Profiler {
+    fn default() -> Self {
+        Self {
+            nodes: vec![Node {
+                routine: Routine::Top,
+                parent: 0,
+                children: Vec::new(),
+                calls: 0,
+                cycles: 0,
+            }],
+            stack: Vec::new(),
+            last: None,
+            names: HashMap::new(),
+        }
+    }
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Names the routine at `addr` in the reports (e.g. from a symbol file).
+    pub fn set_name(&mut self, addr: u16, name: impl Into<String>) {
+        self.names.insert(addr, name.into());
+    }
+
+    /// Forgets every cycle and call (names are kept).
+    pub fn reset(&mut self) {
+        let names = std::mem::take(&mut self.names);
+        *self = Self {
+            names,
+            ..Self::default()
+        };
+    }
+
+    /// Cycles run since the first step.
+    pub fn total_cycles(&self) -> u64 {
+        self.nodes.iter().map(|node| node.cycles).sum()
+    }
+
+    /// Current call stack, outermost routine first.
+    pub fn call_stack(&self) -> Vec<Routine> {
+        self.stack
+            .iter()
+            .map(|frame| self.nodes[frame.node].routine)
+            .collect()
+    }
+
+    /// Looks at the CPU after one of its cycles.
+    pub fn step(&mut self, cpu: &impl DebugCpu, io: &impl InspectBus) {
+        if let Some(pc) = cpu.next_instruction() {
+            let state = cpu.state();
+            self.instruction(pc, state.sp, state.cc, io);
+        }
+    }
+
+    /// Accounts for an instruction boundary: the CPU is about to run the
+    /// instruction at `pc`, with the stack pointer at `sp`, after `cc`
+    /// cycles.
+    pub fn instruction(&mut self, pc: u16, sp: u8, cc: usize, io: &impl InspectBus) {
+        if let Some(last) = self.last {
+            let current = self.current();
+            self.nodes[current].cycles += cc.saturating_sub(last.cc) as u64;
+
+            if sp > last.sp {
+                while self.stack.last().is_some_and(|frame| frame.sp < sp) {
+                    self.stack.pop();
+                }
+            } else {
+                let pushed = last.sp - sp;
+                let call = (last.opcode == JSR).then(|| io.peek_u16(last.pc.wrapping_add(1)));
+                let interrupt = if pc == io.peek_u16(CpuVector::Nmi as u16) {
+                    Some(Routine::Nmi(pc))
+                } else if pc == io.peek_u16(CpuVector::Brk as u16) {
+                    Some(Routine::Irq(pc))
+                } else {
+                    None
+                };
+
+                match (call, interrupt, pushed) {
+                    // Interrupt serviced instead of the instruction.
+                    (_, Some(interrupt), 3) => self.enter(interrupt, sp),
+                    // Interrupt serviced right after a JSR.
+                    (Some(target), Some(interrupt), 5) => {
+                        self.enter(Routine::Call(target), last.sp.wrapping_sub(2));
+                        self.enter(interrupt, sp);
+                    }
+                    (Some(target), _, 2) if pc == target => {
+                        self.enter(Routine::Call(target), sp);
+                    }
+                    _ => (),
+                }
+            }
+        }
+
+        self.last = Some(Boundary {
+            pc,
+            sp,
+            opcode: io.peek_u8(pc),
+            cc,
+        });
+    }
+
+    /// Per routine totals, by decreasing self cycles.
+    pub fn flat(&self) -> Vec<FlatEntry> {
+        let totals = self.totals();
+        let mut entries: HashMap<Routine, FlatEntry> = HashMap::new();
+
+        for (i, node) in self.nodes.iter().enumerate() {
+            let entry = entries.entry(node.routine).or_insert(FlatEntry {
+                routine: node.routine,
+                calls: 0,
+                self_cycles: 0,
+                total_cycles: 0,
+            });
+            entry.calls += node.calls;
+            entry.self_cycles += node.cycles;
+            // Recursive calls are already part of the outer call total.
+            if !self.has_ancestor(i, node.routine) {
+                entry.total_cycles += totals[i];
+            }
+        }
+
+        let mut entries: Vec<FlatEntry> = entries.into_values().collect();
+        entries.sort_by_key(|entry| {
+            (
+                std::cmp::Reverse(entry.self_cycles),
+                std::cmp::Reverse(entry.total_cycles),
+                entry.routine.addr(),
+            )
+        });
+        entries
+    }
+
+    /// [Profiler::flat], as a table.
+    pub fn flat_report(&self) -> String {
+        let total = self.total_cycles();
+        let mut out = format!(
+            "{:>12} {:>6} {:>12} {:>6} {:>8}  routine\n",
+            "self", "%", "total", "%", "calls"
+        );
+        for entry in self.flat() {
+            let _ = writeln!(
+                out,
+                "{:>12} {:>6} {:>12} {:>6} {:>8}  {}",
+                entry.self_cycles,
+                percent(entry.self_cycles, total),
+                entry.total_cycles,
+                percent(entry.total_cycles, total),
+                entry.calls,
+                self.name(entry.routine)
+            );
+        }
+        out
+    }
+
+    /// Call tree, with the callees of each routine indented below it, by
+    /// decreasing total cycles.
+    pub fn call_tree_report(&self) -> String {
+        let totals = self.totals();
+        let total = self.total_cycles();
+        let mut out = format!(
+            "{:>12} {:>6} {:>12} {:>8}  routine\n",
+            "total", "%", "self", "calls"
+        );
+
+        let mut pending = vec![(0, 0)];
+        while let Some((i, depth)) = pending.pop() {
+            let node = &self.nodes[i];
+            let _ = writeln!(
+                out,
+                "{:>12} {:>6} {:>12} {:>8}  {:indent$}{}",
+                totals[i],
+                percent(totals[i], total),
+                node.cycles,
+                node.calls,
+                "",
+                self.name(node.routine),
+                indent = depth * 2
+            );
+
+            let mut children = node.children.clone();
+            children.sort_by_key(|&child| totals[child]);
+            pending.extend(children.into_iter().map(|child| (child, depth + 1)));
+        }
+        out
+    }
+
+    /// Collapsed stacks (`top;outer;inner cycles` lines), as taken by
+    /// flame graph tools (e.g. `flamegraph.pl` or `inferno`).
+    pub fn collapsed_stacks(&self) -> String {
+        let mut out = String::new();
+        for (i, node) in self.nodes.iter().enumerate() {
+            if node.cycles == 0 {
+                continue;
+            }
+
+            let mut path = vec![i];
+            let mut j = i;
+            while j != 0 {
+                j = self.nodes[j].parent;
+                path.push(j);
+            }
+            let names: Vec<String> = path
+                .iter()
+                .rev()
+                .map(|&j| self.name(self.nodes[j].routine).replace([';', ' '], "_"))
+                .collect();
+            let _ = writeln!(out, "{} {}", names.join(";"), node.cycles);
+        }
+        out
+    }
+
+    /// Name of `routine` in the reports.
+    pub fn name(&self, routine: Routine) -> String {
+        match routine.addr().and_then(|addr| self.names.get(&addr)) {
+            Some(name) => match routine {
+                Routine::Nmi(_) => format!("nmi:{}", name),
+                Routine::Irq(_) => format!("irq:{}", name),
+                _ => name.clone(),
+            },
+            None => routine.to_string(),
+        }
+    }
+
+    fn current(&self) -> usize {
+        self.stack.last().map_or(0, |frame| frame.node)
+    }
+
+    fn enter(&mut self, routine: Routine, sp: u8) {
+        let parent = self.current();
+        let node = match self.nodes[parent]
+            .children
+            .iter()
+            .copied()
+            .find(|&child| self.nodes[child].routine == routine)
+        {
+            Some(node) => node,
+            None => {
+                self.nodes.push(Node {
+                    routine,
+                    parent,
+                    children: Vec::new(),
+                    calls: 0,
+                    cycles: 0,
+                });
+                let node = self.nodes.len() - 1;
+                self.nodes[parent].children.push(node);
+                node
+            }
+        };
+        self.nodes[node].calls += 1;
+        self.stack.push(Frame { node, sp });
+    }
+
+    /// Cycles of every node, callees included.
+    fn totals(&self) -> Vec<u64> {
+        let mut totals: Vec<u64> = self.nodes.iter().map(|node| node.cycles).collect();
+        // Nodes always come after their parent.
+        for i in (1..self.nodes.len()).rev() {
+            totals[self.nodes[i].parent] += totals[i];
+        }
+        totals
+    }
+
+    fn has_ancestor(&self, mut i: usize, routine: Routine) -> bool {
+        while i != 0 {
+            i = self.nodes[i].parent;
+            if self.nodes[i].routine == routine {
+                return true;
+            }
+        }
+        false
+    }
+}
+
+fn percent(cycles: u64, total: u64) -> String {
+    format!("{:.1}%", cycles as f64 * 100.0 / total.max(1) as f64)
+}
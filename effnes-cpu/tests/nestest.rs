@@ -1,21 +1,23 @@
-use std::fs::File;
-use std::io::{BufRead, BufReader, Read, Seek, SeekFrom};
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader};
 
 use effnes_basic_cpu::vm::VM as BasicVM;
-use effnes_bus::{basic::BasicMemory, peripheral::Peripheral};
+use effnes_bus::{basic::BasicMemory, peripheral::Peripheral, signal::Signals};
 use effnes_ca_cpu::vm::VM as CycleAccurateVM;
 use effnes_cpu::consts::Flags;
 use effnes_cpu::cpu::Cpu;
 use effnes_cpu::debug::{self, DebugCpu, State};
+use effnes_ines::Rom;
 
 mod common;
 
 fn nestest(mut cpu: impl Cpu + DebugCpu + Peripheral) {
     let mut io = BasicMemory::default_with(0);
+    let mut signals = Signals::default();
     {
-        let mut rom = File::open("res/nestest/nestest.nes").unwrap();
-        rom.seek(SeekFrom::Start(16)).unwrap();
-        rom.read_exact(&mut io.memory[0xC000..0xFFFF]).unwrap();
+        let data = fs::read("res/nestest/nestest.nes").unwrap();
+        let rom = Rom::parse(&data).unwrap();
+        io.memory[0xC000..=0xFFFF].copy_from_slice(&rom.prg_rom[..0x4000]);
     }
 
     cpu.cold_reset();
@@ -50,7 +52,7 @@ fn nestest(mut cpu: impl Cpu + DebugCpu + Peripheral) {
         };
 
         while cpu.state().cc < exp.cc {
-            cpu.cycle(&mut io);
+            cpu.cycle(&mut io, &mut signals);
         }
 
         println!("{}", line);
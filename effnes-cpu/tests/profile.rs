@@ -0,0 +1,146 @@
+use effnes_basic_cpu::vm::VM as BasicVM;
+use effnes_bus::{MemoryBus, basic::BasicMemory, peripheral::Peripheral, signal::Signals};
+use effnes_cpu::{
+    debug::DebugCpu,
+    profile::{FlatEntry, Profiler, Routine},
+};
+
+#[test]
+fn profiler_call_stack() {
+    let mut io = BasicMemory::default_with(0);
+    let program: &[(u16, &[u8])] = &[
+        (0x8000, &[0x20, 0x10, 0x80]), // JSR $8010
+        (0x8003, &[0x4C, 0x03, 0x80]), // JMP $8003
+        (0x8010, &[0x20, 0x20, 0x80]), // JSR $8020
+        (0x8013, &[0x60]),             // RTS
+        (0x8020, &[0xEA]),             // NOP
+        (0x8021, &[0x60]),             // RTS
+        (0x9000, &[0x40]),             // RTI
+        (0xFFFA, &[0x00, 0x90]),
+        (0xFFFE, &[0x00, 0x91]),
+    ];
+    for &(addr, bytes) in program {
+        for (i, &byte) in bytes.iter().enumerate() {
+            io.write_u8(addr + i as u16, byte);
+        }
+    }
+
+    let mut cpu = BasicVM::default();
+    cpu.set_pc(0x8000);
+    cpu.set_sp(0xFD);
+    let mut signals = Signals::default();
+    let mut profiler = Profiler::new();
+    profiler.set_name(0x8010, "init");
+    profiler.set_name(0x8020, "wait vblank");
+    profiler.step(&cpu, &io);
+
+    // JSR, JSR, NOP, RTS, RTS, JMP.
+    for _ in 0..6 {
+        cpu.cycle(&mut io, &mut signals);
+        profiler.step(&cpu, &io);
+        if cpu.state().pc == 0x8020 {
+            assert_eq!(
+                profiler.call_stack(),
+                [Routine::Call(0x8010), Routine::Call(0x8020)]
+            );
+        }
+    }
+    assert!(profiler.call_stack().is_empty());
+
+    // NMI, RTI, JMP.
+    signals.set_nmi(true);
+    cpu.cycle(&mut io, &mut signals);
+    profiler.step(&cpu, &io);
+    assert_eq!(profiler.call_stack(), [Routine::Nmi(0x9000)]);
+    signals.set_nmi(false);
+    for _ in 0..2 {
+        cpu.cycle(&mut io, &mut signals);
+        profiler.step(&cpu, &io);
+    }
+    assert!(profiler.call_stack().is_empty());
+
+    assert_eq!(profiler.total_cycles(), 45);
+    assert_eq!(
+        profiler.flat(),
+        [
+            FlatEntry {
+                routine: Routine::Top,
+                calls: 0,
+                self_cycles: 19,
+                total_cycles: 45,
+            },
+            FlatEntry {
+                routine: Routine::Call(0x8010),
+                calls: 1,
+                self_cycles: 12,
+                total_cycles: 20,
+            },
+            FlatEntry {
+                routine: Routine::Call(0x8020),
+                calls: 1,
+                self_cycles: 8,
+                total_cycles: 8,
+            },
+            FlatEntry {
+                routine: Routine::Nmi(0x9000),
+                calls: 1,
+                self_cycles: 6,
+                total_cycles: 6,
+            },
+        ]
+    );
+    assert_eq!(
+        profiler.collapsed_stacks(),
+        "top 19\ntop;init 12\ntop;init;wait_vblank 8\ntop;nmi:$9000 6\n"
+    );
+
+    let tree: Vec<String> = profiler
+        .call_tree_report()
+        .lines()
+        .map(|line| line.trim_end().to_string())
+        .collect();
+    assert_eq!(tree.len(), 5);
+    assert!(tree[1].ends_with("  top"));
+    assert!(tree[2].ends_with("    init"));
+    assert!(tree[3].ends_with("      wait vblank"));
+    assert!(tree[4].ends_with("    nmi:$9000"));
+}
+
+#[test]
+fn profiler_interrupted_call() {
+    let mut io = BasicMemory::default_with(0);
+    let program: &[(u16, &[u8])] = &[
+        (0x8000, &[0x20, 0x10, 0x80]), // JSR $8010
+        (0x8010, &[0x60]),             // RTS
+        (0x9000, &[0x40]),             // RTI
+        (0xFFFA, &[0x00, 0x90]),
+        (0xFFFE, &[0x00, 0x91]),
+    ];
+    for &(addr, bytes) in program {
+        for (i, &byte) in bytes.iter().enumerate() {
+            io.write_u8(addr + i as u16, byte);
+        }
+    }
+
+    let mut cpu = BasicVM::default();
+    cpu.set_pc(0x8000);
+    cpu.set_sp(0xFD);
+    let mut signals = Signals::default();
+    let mut profiler = Profiler::new();
+    profiler.step(&cpu, &io);
+
+    // The NMI is serviced before the JSR runs.
+    signals.set_nmi(true);
+    cpu.cycle(&mut io, &mut signals);
+    profiler.step(&cpu, &io);
+    assert_eq!(profiler.call_stack(), [Routine::Nmi(0x9000)]);
+    signals.set_nmi(false);
+
+    // RTI, JSR.
+    cpu.cycle(&mut io, &mut signals);
+    profiler.step(&cpu, &io);
+    assert!(profiler.call_stack().is_empty());
+    cpu.cycle(&mut io, &mut signals);
+    profiler.step(&cpu, &io);
+    assert_eq!(profiler.call_stack(), [Routine::Call(0x8010)]);
+}
@@ -0,0 +1,22 @@
+use std::{env, fs, path::PathBuf};
+
+/// Copies the game database to embed into `OUT_DIR`: the one at
+/// `EFFNES_NES20DB` if set (e.g. a full copy of `nes20db.xml`), or the one
+/// bundled in `res/` otherwise.
+fn main() {
+    println!("cargo:rerun-if-env-changed=EFFNES_NES20DB");
+    if env::var_os("CARGO_FEATURE_EMBEDDED_DB").is_none() {
+        return;
+    }
+
+    let source = match env::var_os("EFFNES_NES20DB") {
+        Some(path) => PathBuf::from(path),
+        None => PathBuf::from("res/nes20db.xml"),
+    };
+    println!("cargo:rerun-if-changed={}", source.display());
+
+    let out = PathBuf::from(env::var_os("OUT_DIR").unwrap()).join("nes20db.xml");
+    if let Err(err) = fs::copy(&source, &out) {
+        panic!("can't read the game database {}: {}", source.display(), err);
+    }
+}
@@ -0,0 +1,558 @@
+use crate::{
+    Rom,
+    hash::{Crc32, Sha1},
+    header::{ConsoleType, ExpansionDevice, Header, Mirroring, Timing},
+};
+use std::{
+    collections::HashMap,
+    fmt::{self, Display},
+    fs, io,
+    path::Path,
+};
+
+/// Embedded database, in the NES 2.0 XML format (`nes20db.xml`), as picked by
+/// the build script.
+#[cfg(feature = "embedded-db")]
+static EMBEDDED_XML: &str = include_str!(concat!(env!("OUT_DIR"), "/nes20db.xml"));
+
+/// A database entry, describing a known good dump.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Entry {
+    /// Title, taken from the comment inside the entry (if any).
+    pub name: Option<String>,
+    /// CRC-32 of PRG-ROM followed by CHR-ROM.
+    pub crc32: u32,
+    /// SHA-1 of PRG-ROM followed by CHR-ROM.
+    pub sha1: Option<[u8; 20]>,
+
+    pub mapper: u16,
+    pub submapper: u8,
+    pub prg_rom_size: usize,
+    pub chr_rom_size: usize,
+    pub prg_ram_size: usize,
+    pub prg_nvram_size: usize,
+    pub chr_ram_size: usize,
+    pub chr_nvram_size: usize,
+    pub battery: bool,
+    /// Hardwired mirroring (`None` if the entry doesn't specify it).
+    pub mirroring: Option<Mirroring>,
+    pub timing: Timing,
+    pub console: ConsoleType,
+    pub expansion_device: ExpansionDevice,
+}
+
+/// A header field overridden by a database entry, with its previous and
+/// new values.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Correction {
+    Mapper {
+        from: u16,
+        to: u16,
+    },
+    Submapper {
+        from: u8,
+        to: u8,
+    },
+    PrgRamSize {
+        from: usize,
+        to: usize,
+    },
+    PrgNvramSize {
+        from: usize,
+        to: usize,
+    },
+    ChrRamSize {
+        from: usize,
+        to: usize,
+    },
+    ChrNvramSize {
+        from: usize,
+        to: usize,
+    },
+    Battery {
+        from: bool,
+        to: bool,
+    },
+    Mirroring {
+        from: Mirroring,
+        to: Mirroring,
+    },
+    Timing {
+        from: Timing,
+        to: Timing,
+    },
+    Console {
+        from: ConsoleType,
+        to: ConsoleType,
+    },
+    ExpansionDevice {
+        from: ExpansionDevice,
+        to: ExpansionDevice,
+    },
+}
+
+impl Display for Correction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Correction::Mapper { from, to } => write!(f, "mapper: {} -> {}", from, to),
+            Correction::Submapper { from, to } => write!(f, "submapper: {} -> {}", from, to),
+            Correction::PrgRamSize { from, to } => write!(f, "PRG-RAM: {} -> {}", from, to),
+            Correction::PrgNvramSize { from, to } => write!(f, "PRG-NVRAM: {} -> {}", from, to),
+            Correction::ChrRamSize { from, to } => write!(f, "CHR-RAM: {} -> {}", from, to),
+            Correction::ChrNvramSize { from, to } => write!(f, "CHR-NVRAM: {} -> {}", from, to),
+            Correction::Battery { from, to } => write!(f, "battery: {} -> {}", from, to),
+            Correction::Mirroring { from, to } => write!(f, "mirroring: {:?} -> {:?}", from, to),
+            Correction::Timing { from, to } => write!(f, "timing: {:?} -> {:?}", from, to),
+            Correction::Console { from, to } => write!(f, "console: {:?} -> {:?}", from, to),
+            Correction::ExpansionDevice { from, to } => {
+                write!(f, "expansion device: {} -> {}", from.0, to.0)
+            }
+        }
+    }
+}
+
+macro_rules! correct {
+    ($out:ident, $variant:ident, $field:expr, $value:expr) => {
+        if $field != $value {
+            $out.push(Correction::$variant {
+                from: $field,
+                to: $value,
+            });
+            $field = $value;
+        }
+    };
+}
+
+impl Entry {
+    /// Overrides the fields of `header` with the ones of this entry,
+    /// returning what was changed.
+    pub fn apply(&self, header: &mut Header) -> Vec<Correction> {
+        let mut out = Vec::new();
+        correct!(out, Mapper, header.mapper, self.mapper);
+        correct!(out, Submapper, header.submapper, self.submapper);
+        correct!(out, PrgRamSize, header.prg_ram_size, self.prg_ram_size);
+        correct!(
+            out,
+            PrgNvramSize,
+            header.prg_nvram_size,
+            self.prg_nvram_size
+        );
+        correct!(out, ChrRamSize, header.chr_ram_size, self.chr_ram_size);
+        correct!(
+            out,
+            ChrNvramSize,
+            header.chr_nvram_size,
+            self.chr_nvram_size
+        );
+        correct!(out, Battery, header.battery, self.battery);
+        if let Some(mirroring) = self.mirroring {
+            correct!(out, Mirroring, header.mirroring, mirroring);
+        }
+        correct!(out, Timing, header.timing, self.timing);
+        correct!(out, Console, header.console, self.console);
+        correct!(
+            out,
+            ExpansionDevice,
+            header.expansion_device,
+            self.expansion_device
+        );
+        out
+    }
+}
+
+/// Database loading error.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DbError {
+    /// Line (1-based) where the error was found.
+    pub line: usize,
+    pub message: String,
+}
+
+impl Display for DbError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for DbError {}
+
+/// Game database, keyed by the CRC-32 of PRG-ROM + CHR-ROM.
+#[derive(Clone, Debug, Default)]
+pub struct Database {
+    entries: Vec<Entry>,
+    by_crc32: HashMap<u32, Vec<usize>>,
+}
+
+impl Database {
+    /// Returns the database embedded in the crate.
+    ///
+    /// It's parsed on first use. The NES 2.0 database isn't bundled with the
+    /// sources, so it's only complete if the crate was built with
+    /// `EFFNES_NES20DB` pointing to a copy of `nes20db.xml`; see
+    /// [Database::load] for using one at run time instead.
+    #[cfg(feature = "embedded-db")]
+    pub fn embedded() -> &'static Database {
+        static DB: std::sync::OnceLock<Database> = std::sync::OnceLock::new();
+        DB.get_or_init(|| {
+            Database::from_nes20db_xml(EMBEDDED_XML).expect("invalid embedded database")
+        })
+    }
+
+    /// Loads a copy of the NES 2.0 database (`nes20db.xml`), e.g. to use a
+    /// newer one than [Database::embedded].
+    ///
+    /// ## Errors
+    ///
+    /// I/O errors, and [io::ErrorKind::InvalidData] if the file isn't a
+    /// valid database (see [Database::from_nes20db_xml]).
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let xml = fs::read_to_string(path)?;
+        Self::from_nes20db_xml(&xml).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+
+    /// Parses a database in the NES 2.0 XML format.
+    ///
+    /// Only `<game>` elements are read; the name of every entry is taken
+    /// from the (last) comment inside it, and entries without a `<rom>` hash
+    /// are skipped.
+    pub fn from_nes20db_xml(xml: &str) -> Result<Self, DbError> {
+        let mut db = Database::default();
+        let mut game: Option<GameBuilder> = None;
+        let mut comment: Option<String> = None;
+
+        for token in Tokenizer::new(xml) {
+            match token? {
+                Token::Comment(text) => comment = Some(text.trim().to_string()),
+                Token::Open { line, name, attrs } => match (name, &mut game) {
+                    ("game", None) => {
+                        game = Some(GameBuilder::default());
+                        comment = None;
+                    }
+                    ("game", Some(_)) => return Err(err(line, "nested <game> element")),
+                    (_, Some(builder)) => builder.element(line, name, &attrs)?,
+                    _ => {}
+                },
+                Token::Close { line, name } => {
+                    if name == "game" {
+                        let builder = game.take().ok_or_else(|| err(line, "unexpected </game>"))?;
+                        if let Some(entry) = builder.build(comment.take()) {
+                            db.insert(entry);
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(db)
+    }
+
+    pub fn insert(&mut self, entry: Entry) {
+        self.by_crc32
+            .entry(entry.crc32)
+            .or_default()
+            .push(self.entries.len());
+        self.entries.push(entry);
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn entries(&self) -> &[Entry] {
+        &self.entries
+    }
+
+    /// Finds the entries with a CRC-32 of `crc32`.
+    pub fn lookup_crc32(&self, crc32: u32) -> impl Iterator<Item = &Entry> {
+        self.by_crc32
+            .get(&crc32)
+            .into_iter()
+            .flatten()
+            .map(|index| &self.entries[*index])
+    }
+
+    /// Finds the entry matching `prg_rom` + `chr_rom`.
+    ///
+    /// Matches are searched by CRC-32, and then confirmed by SHA-1 (for the
+    /// entries having one).
+    pub fn lookup(&self, prg_rom: &[u8], chr_rom: &[u8]) -> Option<&Entry> {
+        let mut crc = Crc32::default();
+        crc.update(prg_rom);
+        crc.update(chr_rom);
+
+        let mut sha1: Option<[u8; 20]> = None;
+        self.lookup_crc32(crc.finish())
+            .find(|entry| match entry.sha1 {
+                None => true,
+                Some(expected) => {
+                    let digest = *sha1.get_or_insert_with(|| {
+                        let mut sha1 = Sha1::default();
+                        sha1.update(prg_rom);
+                        sha1.update(chr_rom);
+                        sha1.finish()
+                    });
+                    digest == expected
+                }
+            })
+    }
+}
+
+impl Rom<'_> {
+    /// Looks this ROM up in `db`, and overrides the header fields with the
+    /// ones of the matching entry.
+    ///
+    /// Returns the matching entry and what was corrected (which may be
+    /// nothing, if the header was right).
+    pub fn correct_header<'db>(
+        &mut self,
+        db: &'db Database,
+    ) -> Option<(&'db Entry, Vec<Correction>)> {
+        let entry = db.lookup(&self.prg_rom, &self.chr_rom)?;
+        Some((entry, entry.apply(&mut self.header)))
+    }
+}
+
+fn err(line: usize, message: impl Into<String>) -> DbError {
+    DbError {
+        line,
+        message: message.into(),
+    }
+}
+
+#[derive(Default)]
+struct GameBuilder {
+    crc32: Option<u32>,
+    sha1: Option<[u8; 20]>,
+    mapper: u16,
+    submapper: u8,
+    prg_rom_size: usize,
+    chr_rom_size: usize,
+    prg_ram_size: usize,
+    prg_nvram_size: usize,
+    chr_ram_size: usize,
+    chr_nvram_size: usize,
+    battery: bool,
+    mirroring: Option<Mirroring>,
+    timing: u8,
+    console: u8,
+    vs_ppu: u8,
+    vs_hardware: u8,
+    expansion: u8,
+}
+
+impl GameBuilder {
+    fn element(&mut self, line: usize, name: &str, attrs: &[(&str, &str)]) -> Result<(), DbError> {
+        let get = |key: &str| attrs.iter().find(|(k, _)| *k == key).map(|(_, v)| *v);
+        let number = |key: &str| -> Result<usize, DbError> {
+            get(key).map_or(Ok(0), |value| {
+                value
+                    .parse()
+                    .map_err(|_| err(line, format!("invalid {} `{}`", key, value)))
+            })
+        };
+
+        match name {
+            "prgrom" => self.prg_rom_size = number("size")?,
+            "chrrom" => self.chr_rom_size = number("size")?,
+            "prgram" => self.prg_ram_size = number("size")?,
+            "prgnvram" => self.prg_nvram_size = number("size")?,
+            "chrram" => self.chr_ram_size = number("size")?,
+            "chrnvram" => self.chr_nvram_size = number("size")?,
+            "rom" => {
+                let crc32 = get("crc32").ok_or_else(|| err(line, "<rom> without crc32"))?;
+                self.crc32 = Some(
+                    u32::from_str_radix(crc32, 16)
+                        .map_err(|_| err(line, format!("invalid crc32 `{}`", crc32)))?,
+                );
+                if let Some(sha1) = get("sha1") {
+                    self.sha1 = Some(
+                        parse_sha1(sha1)
+                            .ok_or_else(|| err(line, format!("invalid sha1 `{}`", sha1)))?,
+                    );
+                }
+            }
+            "pcb" => {
+                self.mapper = number("mapper")? as u16;
+                self.submapper = number("submapper")? as u8;
+                self.battery = number("battery")? != 0;
+                self.mirroring = match get("mirroring") {
+                    Some("H") => Some(Mirroring::Horizontal),
+                    Some("V") => Some(Mirroring::Vertical),
+                    Some("4") => Some(Mirroring::FourScreen),
+                    _ => None,
+                };
+            }
+            "console" => {
+                self.console = number("type")? as u8;
+                self.timing = number("region")? as u8;
+            }
+            "vs" => {
+                self.vs_ppu = number("ppu")? as u8;
+                self.vs_hardware = number("hardware")? as u8;
+            }
+            "expansion" => self.expansion = number("type")? as u8,
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn build(self, name: Option<String>) -> Option<Entry> {
+        Some(Entry {
+            name,
+            crc32: self.crc32?,
+            sha1: self.sha1,
+            mapper: self.mapper,
+            submapper: self.submapper,
+            prg_rom_size: self.prg_rom_size,
+            chr_rom_size: self.chr_rom_size,
+            prg_ram_size: self.prg_ram_size,
+            prg_nvram_size: self.prg_nvram_size,
+            chr_ram_size: self.chr_ram_size,
+            chr_nvram_size: self.chr_nvram_size,
+            battery: self.battery,
+            mirroring: self.mirroring,
+            timing: match self.timing {
+                0 => Timing::Ntsc,
+                1 => Timing::Pal,
+                2 => Timing::MultiRegion,
+                _ => Timing::Dendy,
+            },
+            console: match self.console {
+                0 => ConsoleType::Nes,
+                1 => ConsoleType::VsSystem {
+                    ppu: self.vs_ppu,
+                    hardware: self.vs_hardware,
+                },
+                2 => ConsoleType::Playchoice10,
+                kind => ConsoleType::Extended(kind),
+            },
+            expansion_device: ExpansionDevice(self.expansion),
+        })
+    }
+}
+
+fn parse_sha1(hex: &str) -> Option<[u8; 20]> {
+    if hex.len() != 40 {
+        return None;
+    }
+
+    let mut out = [0u8; 20];
+    for (byte, chunk) in out.iter_mut().zip(hex.as_bytes().chunks_exact(2)) {
+        *byte = u8::from_str_radix(std::str::from_utf8(chunk).ok()?, 16).ok()?;
+    }
+    Some(out)
+}
+
+enum Token<'a> {
+    Comment(&'a str),
+    Open {
+        line: usize,
+        name: &'a str,
+        attrs: Vec<(&'a str, &'a str)>,
+    },
+    Close {
+        line: usize,
+        name: &'a str,
+    },
+}
+
+/// Minimal XML tokenizer, covering the subset used by the NES 2.0 database
+/// (elements, attributes, comments and prolog; no entities nor CDATA).
+struct Tokenizer<'a> {
+    xml: &'a str,
+    pos: usize,
+    line: usize,
+}
+
+impl<'a> Tokenizer<'a> {
+    fn new(xml: &'a str) -> Self {
+        Self {
+            xml,
+            pos: 0,
+            line: 1,
+        }
+    }
+
+    fn advance(&mut self, len: usize) -> &'a str {
+        let text = &self.xml[self.pos..self.pos + len];
+        self.line += text.bytes().filter(|b| *b == b'\n').count();
+        self.pos += len;
+        text
+    }
+
+    fn tag(&self, line: usize, text: &'a str) -> Result<Token<'a>, DbError> {
+        if let Some(name) = text.strip_prefix('/') {
+            return Ok(Token::Close {
+                line,
+                name: name.trim(),
+            });
+        }
+
+        // Self-closing elements never contain anything, so the parser doesn't
+        // need a matching close token.
+        let text = text.trim_end_matches('/').trim_end();
+        let name_end = text.find(char::is_whitespace).unwrap_or(text.len());
+        let name = &text[..name_end];
+
+        let mut attrs = Vec::new();
+        let mut rest = text[name_end..].trim_start();
+        while !rest.is_empty() {
+            let eq = rest
+                .find('=')
+                .ok_or_else(|| err(line, format!("malformed attribute in <{}>", name)))?;
+            let key = rest[..eq].trim();
+            let value = rest[eq + 1..].trim_start();
+            let quote = value
+                .chars()
+                .next()
+                .filter(|c| *c == '"' || *c == '\'')
+                .ok_or_else(|| err(line, format!("unquoted attribute `{}`", key)))?;
+            let end = value[1..]
+                .find(quote)
+                .ok_or_else(|| err(line, format!("unterminated attribute `{}`", key)))?;
+            attrs.push((key, &value[1..end + 1]));
+            rest = value[end + 2..].trim_start();
+        }
+
+        Ok(Token::Open { line, name, attrs })
+    }
+}
+
+impl<'a> Iterator for Tokenizer<'a> {
+    type Item = Result<Token<'a>, DbError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let start = self.xml[self.pos..].find('<')?;
+            self.advance(start);
+            let line = self.line;
+            let rest = &self.xml[self.pos..];
+
+            if rest.starts_with("<!--") {
+                let Some(end) = rest.find("-->") else {
+                    self.pos = self.xml.len();
+                    return Some(Err(err(line, "unterminated comment")));
+                };
+                let text = self.advance(end + 3);
+                return Some(Ok(Token::Comment(&text[4..end])));
+            }
+
+            let Some(end) = rest.find('>') else {
+                self.pos = self.xml.len();
+                return Some(Err(err(line, "unterminated tag")));
+            };
+            let text = self.advance(end + 1);
+            let inner = &text[1..end];
+
+            // Prolog / doctype
+            if inner.starts_with('?') || inner.starts_with('!') {
+                continue;
+            }
+            return Some(self.tag(line, inner.trim()));
+        }
+    }
+}
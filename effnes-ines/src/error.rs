@@ -0,0 +1,102 @@
+use std::fmt::{self, Display};
+
+/// A section of a ROM file.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Section {
+    Header,
+    Trainer,
+    PrgRom,
+    ChrRom,
+    MiscRom,
+    /// UNIF chunk, by ID.
+    Chunk([u8; 4]),
+    /// FDS disk side, by index.
+    DiskSide(u8),
+}
+
+impl Display for Section {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Section::Header => "header",
+            Section::Trainer => "trainer",
+            Section::PrgRom => "PRG-ROM",
+            Section::ChrRom => "CHR-ROM",
+            Section::MiscRom => "miscellaneous ROM",
+            Section::Chunk(id) => {
+                return write!(f, "`{}` chunk", String::from_utf8_lossy(id));
+            }
+            Section::DiskSide(side) => return write!(f, "disk side {}", side),
+        })
+    }
+}
+
+/// ROM parsing error.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// The file doesn't start with `NES<EOF>` (or `UNIF`, for UNIF files,
+    /// or `FDS<EOF>` / a disk info block, for FDS images).
+    InvalidMagic([u8; 4]),
+
+    /// The file ends before the end of `section`.
+    Truncated {
+        section: Section,
+        /// Offset of the section start.
+        offset: usize,
+        /// Size of the section, as declared by the header.
+        expected: usize,
+        /// Bytes available from `offset` on.
+        available: usize,
+    },
+
+    /// The (exponent-multiplier notation) size of `section` can't be
+    /// represented on this platform.
+    SizeOverflow { section: Section },
+
+    /// The header declares no PRG-ROM.
+    EmptyPrgRom,
+
+    /// The NES 2.0 header declares miscellaneous ROMs, but there's no data
+    /// after the CHR-ROM.
+    MissingMiscRom,
+
+    /// A required UNIF chunk (`MAPR` or `PRG0`) is missing.
+    MissingChunk([u8; 4]),
+
+    /// The UNIF board name can't be mapped into a mapper number.
+    UnknownBoard(String),
+
+    /// An FDS disk side doesn't start with the disk info block.
+    InvalidDiskSide(u8),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::InvalidMagic(magic) => write!(f, "invalid magic {:02x?}", magic),
+            Error::Truncated {
+                section,
+                offset,
+                expected,
+                available,
+            } => write!(
+                f,
+                "truncated {} (${:x} bytes at offset ${:x}, but only ${:x} available)",
+                section, expected, offset, available
+            ),
+            Error::SizeOverflow { section } => write!(f, "{} size overflow", section),
+            Error::EmptyPrgRom => f.write_str("the header declares no PRG-ROM"),
+            Error::MissingMiscRom => {
+                f.write_str("the header declares miscellaneous ROMs, but there's no data")
+            }
+            Error::MissingChunk(id) => {
+                write!(f, "missing `{}` chunk", String::from_utf8_lossy(id))
+            }
+            Error::UnknownBoard(name) => write!(f, "unknown UNIF board `{}`", name),
+            Error::InvalidDiskSide(side) => {
+                write!(f, "disk side {} doesn't start with a disk info block", side)
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {}
@@ -0,0 +1,189 @@
+use crate::error::{Error, Section};
+use std::borrow::Cow;
+
+/// fwNES header magic number.
+pub const MAGIC: [u8; 4] = *b"FDS\x1A";
+
+/// Size of the (optional) fwNES header.
+pub const HEADER_LEN: usize = 16;
+
+/// Size of a disk side on `.fds` images: blocks without CRCs or gaps.
+pub const SIDE_LEN: usize = 65500;
+
+/// Size of a disk side on QD images: blocks followed by their CRCs.
+pub const QD_SIDE_LEN: usize = 0x10000;
+
+/// Start of the disk info block, present on every side.
+pub const DISK_VERIFICATION: &[u8; 15] = b"\x01*NINTENDO-HVC*";
+
+/// Disk image layout.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum DiskFormat {
+    /// fwNES `.fds` images (with or without header).
+    Fds,
+    /// Raw QuickDisk dumps, with a CRC after every block.
+    Qd,
+}
+
+impl DiskFormat {
+    pub fn side_len(self) -> usize {
+        match self {
+            DiskFormat::Fds => SIDE_LEN,
+            DiskFormat::Qd => QD_SIDE_LEN,
+        }
+    }
+
+    /// Bytes after every block (its CRC on QD images).
+    fn crc_len(self) -> usize {
+        match self {
+            DiskFormat::Fds => 0,
+            DiskFormat::Qd => 2,
+        }
+    }
+}
+
+/// Famicom Disk System image.
+///
+/// Side data is borrowed from the input, just like [crate::Rom] contents.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DiskImage<'a> {
+    pub format: DiskFormat,
+    /// Whether the image had a fwNES header.
+    pub header: bool,
+    pub sides: Vec<Cow<'a, [u8]>>,
+}
+
+impl<'a> DiskImage<'a> {
+    /// Parses a `.fds` image (with or without fwNES header) or a QD image.
+    ///
+    /// Headerless images are told apart by their size. Trailing data after
+    /// the sides declared by a fwNES header is ignored.
+    pub fn parse(data: &'a [u8]) -> Result<Self, Error> {
+        let (format, header, sides, offset) = if data.starts_with(&MAGIC) {
+            if data.len() < HEADER_LEN {
+                return Err(Error::Truncated {
+                    section: Section::Header,
+                    offset: 0,
+                    expected: HEADER_LEN,
+                    available: data.len(),
+                });
+            }
+            (DiskFormat::Fds, true, data[4] as usize, HEADER_LEN)
+        } else if !data.is_empty() && data.len().is_multiple_of(SIDE_LEN) {
+            (DiskFormat::Fds, false, data.len() / SIDE_LEN, 0)
+        } else if !data.is_empty() && data.len().is_multiple_of(QD_SIDE_LEN) {
+            (DiskFormat::Qd, false, data.len() / QD_SIDE_LEN, 0)
+        } else if data.starts_with(DISK_VERIFICATION) {
+            // Headerless and truncated.
+            (DiskFormat::Fds, false, data.len().div_ceil(SIDE_LEN), 0)
+        } else {
+            let mut magic = [0; 4];
+            let len = data.len().min(4);
+            magic[..len].copy_from_slice(&data[..len]);
+            return Err(Error::InvalidMagic(magic));
+        };
+
+        let side_len = format.side_len();
+        let sides = (0..sides)
+            .map(|side| {
+                let start = offset + side * side_len;
+                let available = data.len().saturating_sub(start);
+                if available < side_len {
+                    return Err(Error::Truncated {
+                        section: Section::DiskSide(side as u8),
+                        offset: start,
+                        expected: side_len,
+                        available,
+                    });
+                }
+                let data = &data[start..start + side_len];
+                if !data.starts_with(DISK_VERIFICATION) {
+                    return Err(Error::InvalidDiskSide(side as u8));
+                }
+                Ok(Cow::Borrowed(data))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if sides.is_empty() {
+            return Err(Error::InvalidDiskSide(0));
+        }
+        Ok(Self {
+            format,
+            header,
+            sides,
+        })
+    }
+
+    /// Blocks of `side`.
+    ///
+    /// ## Panics
+    ///
+    /// If `side` is out of range.
+    pub fn blocks(&self, side: usize) -> Blocks<'_> {
+        Blocks::new(&self.sides[side], self.format)
+    }
+
+    /// Copies the borrowed data, detaching it from the input.
+    pub fn into_owned(self) -> DiskImage<'static> {
+        DiskImage {
+            format: self.format,
+            header: self.header,
+            sides: self
+                .sides
+                .into_iter()
+                .map(|side| Cow::Owned(side.into_owned()))
+                .collect(),
+        }
+    }
+}
+
+/// Size of a block, from its type (and the file size declared by the
+/// previous file header block, for file data blocks).
+pub fn block_len(kind: u8, file_size: usize) -> Option<usize> {
+    match kind {
+        // Disk info
+        1 => Some(56),
+        // File amount
+        2 => Some(2),
+        // File header
+        3 => Some(16),
+        // File data
+        4 => Some(1 + file_size),
+        _ => None,
+    }
+}
+
+/// Iterator over the blocks of a disk side (without their CRCs), up to the
+/// first invalid block type or the end of the side.
+pub struct Blocks<'a> {
+    data: &'a [u8],
+    format: DiskFormat,
+    offset: usize,
+    file_size: usize,
+}
+
+impl<'a> Blocks<'a> {
+    pub fn new(data: &'a [u8], format: DiskFormat) -> Self {
+        Self {
+            data,
+            format,
+            offset: 0,
+            file_size: 0,
+        }
+    }
+}
+
+impl<'a> Iterator for Blocks<'a> {
+    type Item = &'a [u8];
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let kind = *self.data.get(self.offset)?;
+        let len = block_len(kind, self.file_size)?;
+        let block = self.data.get(self.offset..self.offset + len)?;
+        if kind == 3 {
+            self.file_size = block[13] as usize | (block[14] as usize) << 8;
+        }
+        self.offset += len + self.format.crc_len();
+        Some(block)
+    }
+}
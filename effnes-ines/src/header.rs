@@ -0,0 +1,343 @@
+use crate::error::{Error, Section};
+
+/// iNES magic number (`NES<EOF>`).
+pub const MAGIC: [u8; 4] = *b"NES\x1A";
+
+/// Size of the iNES header.
+pub const HEADER_LEN: usize = 16;
+
+/// Size of the (optional) trainer.
+pub const TRAINER_LEN: usize = 512;
+
+/// iNES header revision.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Format {
+    /// Headers with garbage on bytes 7..=15 (e.g. `DiskDude!`). Only the
+    /// low nibble of the mapper number is trusted.
+    Archaic,
+    INes,
+    Nes20,
+    /// Not an iNES header, but a description built from a UNIF file.
+    Unif,
+}
+
+/// Nametable arrangement, as hardwired on the board.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Mirroring {
+    /// Vertical arrangement (`$2000 == $2400`).
+    Horizontal,
+    /// Horizontal arrangement (`$2000 == $2800`).
+    Vertical,
+    /// The cartridge provides the extra nametable RAM.
+    FourScreen,
+    /// Every nametable maps into the first page of CIRAM (UNIF only).
+    SingleScreenLower,
+    /// Every nametable maps into the second page of CIRAM (UNIF only).
+    SingleScreenUpper,
+}
+
+/// Console the ROM runs on.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ConsoleType {
+    /// Nintendo Entertainment System / Family Computer.
+    Nes,
+    /// Nintendo Vs. System, with its PPU and hardware types (bytes 13 low and
+    /// high nibbles on NES 2.0 headers, zero otherwise).
+    VsSystem { ppu: u8, hardware: u8 },
+    /// Nintendo Playchoice 10.
+    Playchoice10,
+    /// Extended console type (byte 13, low nibble).
+    Extended(u8),
+}
+
+/// CPU/PPU timing.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Timing {
+    /// RP2C02 ("NTSC NES")
+    Ntsc,
+    /// RP2C07 ("Licensed PAL NES")
+    Pal,
+    /// Runs on both.
+    MultiRegion,
+    /// UA6538 ("Dendy")
+    Dendy,
+}
+
+/// Default expansion device (NES 2.0 byte 15).
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ExpansionDevice(pub u8);
+
+impl ExpansionDevice {
+    pub const UNSPECIFIED: Self = Self(0x00);
+    pub const STANDARD_CONTROLLERS: Self = Self(0x01);
+    pub const FOUR_SCORE: Self = Self(0x02);
+    pub const FAMICOM_FOUR_PLAYERS_ADAPTER: Self = Self(0x03);
+    pub const ZAPPER: Self = Self(0x08);
+}
+
+/// Parsed iNES / NES 2.0 header.
+///
+/// Sizes are in bytes. iNES headers can't represent most of the RAM fields,
+/// so those are inferred (see [Header::parse]).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Header {
+    pub format: Format,
+    pub mapper: u16,
+    pub submapper: u8,
+
+    pub prg_rom_size: usize,
+    pub chr_rom_size: usize,
+    /// Volatile PRG-RAM.
+    pub prg_ram_size: usize,
+    /// Non volatile (battery-backed) PRG-RAM / EEPROM.
+    pub prg_nvram_size: usize,
+    /// Volatile CHR-RAM.
+    pub chr_ram_size: usize,
+    /// Non volatile (battery-backed) CHR-RAM.
+    pub chr_nvram_size: usize,
+
+    pub battery: bool,
+    pub trainer: bool,
+    pub mirroring: Mirroring,
+    pub console: ConsoleType,
+    pub timing: Timing,
+    /// Number of miscellaneous ROMs following the CHR-ROM.
+    pub misc_roms: u8,
+    pub expansion_device: ExpansionDevice,
+}
+
+/// Decodes a NES 2.0 `64 << shift` RAM size nibble.
+fn ram_size(shift: u8) -> usize {
+    match shift & 0x0F {
+        0 => 0,
+        shift => 64 << shift,
+    }
+}
+
+/// Decodes a NES 2.0 ROM size, given its LSB (header byte 4 or 5), its MSB
+/// nibble (header byte 9) and its unit size.
+fn rom_size(lsb: u8, msb: u8, unit: usize, section: Section) -> Result<usize, Error> {
+    if msb == 0x0F {
+        // Exponent-multiplier notation: EEEEEEMM
+        let exponent = (lsb >> 2) as u32;
+        let multiplier = ((lsb & 0b11) as usize) * 2 + 1;
+        1usize
+            .checked_shl(exponent)
+            .filter(|size| size.leading_zeros() >= 2)
+            .map(|size| size * multiplier)
+            .ok_or(Error::SizeOverflow { section })
+    } else {
+        Ok((((msb as usize) << 8) | lsb as usize) * unit)
+    }
+}
+
+impl Header {
+    /// Parses the 16-byte header at the start of `data`.
+    ///
+    /// ## Format detection
+    ///
+    /// - NES 2.0: `flags 7 & 0x0C == 0x08`.
+    /// - iNES: `flags 7 & 0x0C == 0x00` and bytes 12..=15 are zero.
+    /// - Archaic iNES: anything else.
+    ///
+    /// ## Inferred fields (iNES / Archaic)
+    ///
+    /// - PRG-RAM: byte 8 (in 8 KiB units, with zero meaning 8 KiB). It's
+    ///   reported as non volatile if the battery bit is set.
+    /// - CHR-RAM: 8 KiB if there's no CHR-ROM.
+    /// - Timing: byte 9 bit 0 (PAL if set).
+    pub fn parse(data: &[u8]) -> Result<Self, Error> {
+        if data.len() < HEADER_LEN {
+            return Err(Error::Truncated {
+                section: Section::Header,
+                offset: 0,
+                expected: HEADER_LEN,
+                available: data.len(),
+            });
+        }
+
+        if data[0..4] != MAGIC {
+            return Err(Error::InvalidMagic([data[0], data[1], data[2], data[3]]));
+        }
+
+        let flags6 = data[6];
+        let flags7 = data[7];
+        let format = match flags7 & 0x0C {
+            0x08 => Format::Nes20,
+            0x00 if data[12..16].iter().all(|b| *b == 0) => Format::INes,
+            _ => Format::Archaic,
+        };
+
+        let mirroring = if flags6 & 0b1000 != 0 {
+            Mirroring::FourScreen
+        } else if flags6 & 0b0001 != 0 {
+            Mirroring::Vertical
+        } else {
+            Mirroring::Horizontal
+        };
+        let battery = flags6 & 0b0010 != 0;
+        let trainer = flags6 & 0b0100 != 0;
+
+        let mut header = Header {
+            format,
+            mapper: (flags6 >> 4) as u16,
+            submapper: 0,
+            prg_rom_size: data[4] as usize * 0x4000,
+            chr_rom_size: data[5] as usize * 0x2000,
+            prg_ram_size: 0,
+            prg_nvram_size: 0,
+            chr_ram_size: 0,
+            chr_nvram_size: 0,
+            battery,
+            trainer,
+            mirroring,
+            console: ConsoleType::Nes,
+            timing: Timing::Ntsc,
+            misc_roms: 0,
+            expansion_device: ExpansionDevice::UNSPECIFIED,
+        };
+
+        match format {
+            Format::Unif => unreachable!(),
+            Format::Archaic => header.infer_ram(8 * 1024),
+            Format::INes => {
+                header.mapper |= (flags7 & 0xF0) as u16;
+                header.console = match flags7 & 0b11 {
+                    0b01 => ConsoleType::VsSystem {
+                        ppu: 0,
+                        hardware: 0,
+                    },
+                    0b10 => ConsoleType::Playchoice10,
+                    _ => ConsoleType::Nes,
+                };
+                if data[9] & 1 != 0 {
+                    header.timing = Timing::Pal;
+                }
+                header.infer_ram(data[8].max(1) as usize * 0x2000);
+            }
+            Format::Nes20 => {
+                header.mapper |= (flags7 & 0xF0) as u16 | (((data[8] & 0x0F) as u16) << 8);
+                header.submapper = data[8] >> 4;
+                header.prg_rom_size = rom_size(data[4], data[9] & 0x0F, 0x4000, Section::PrgRom)?;
+                header.chr_rom_size = rom_size(data[5], data[9] >> 4, 0x2000, Section::ChrRom)?;
+                header.prg_ram_size = ram_size(data[10]);
+                header.prg_nvram_size = ram_size(data[10] >> 4);
+                header.chr_ram_size = ram_size(data[11]);
+                header.chr_nvram_size = ram_size(data[11] >> 4);
+                header.timing = match data[12] & 0b11 {
+                    0 => Timing::Ntsc,
+                    1 => Timing::Pal,
+                    2 => Timing::MultiRegion,
+                    _ => Timing::Dendy,
+                };
+                header.console = match flags7 & 0b11 {
+                    0 => ConsoleType::Nes,
+                    1 => ConsoleType::VsSystem {
+                        ppu: data[13] & 0x0F,
+                        hardware: data[13] >> 4,
+                    },
+                    2 => ConsoleType::Playchoice10,
+                    _ => ConsoleType::Extended(data[13] & 0x0F),
+                };
+                header.misc_roms = data[14] & 0b11;
+                header.expansion_device = ExpansionDevice(data[15] & 0x3F);
+            }
+        }
+
+        Ok(header)
+    }
+
+    fn infer_ram(&mut self, prg_ram: usize) {
+        if self.battery {
+            self.prg_nvram_size = prg_ram;
+        } else {
+            self.prg_ram_size = prg_ram;
+        }
+        if self.chr_rom_size == 0 {
+            self.chr_ram_size = 0x2000;
+        }
+    }
+
+    /// Encodes this header as a NES 2.0 header.
+    ///
+    /// RAM sizes are rounded up to the next representable size, and ROM
+    /// sizes that aren't a multiple of their unit size use the
+    /// exponent-multiplier notation (rounding them up if needed).
+    pub fn to_nes20_bytes(&self) -> [u8; HEADER_LEN] {
+        let mut out = [0u8; HEADER_LEN];
+        out[0..4].copy_from_slice(&MAGIC);
+
+        let (prg_lsb, prg_msb) = encode_rom_size(self.prg_rom_size, 0x4000);
+        let (chr_lsb, chr_msb) = encode_rom_size(self.chr_rom_size, 0x2000);
+        out[4] = prg_lsb;
+        out[5] = chr_lsb;
+        out[9] = prg_msb | (chr_msb << 4);
+
+        out[6] = ((self.mapper as u8) << 4)
+            | ((self.mirroring == Mirroring::FourScreen) as u8) << 3
+            | (self.trainer as u8) << 2
+            | (self.battery as u8) << 1
+            | (self.mirroring == Mirroring::Vertical) as u8;
+
+        let (console, extra) = match self.console {
+            ConsoleType::Nes => (0, 0),
+            ConsoleType::VsSystem { ppu, hardware } => (1, (ppu & 0x0F) | (hardware << 4)),
+            ConsoleType::Playchoice10 => (2, 0),
+            ConsoleType::Extended(kind) => (3, kind & 0x0F),
+        };
+        out[7] = (self.mapper as u8 & 0xF0) | 0x08 | console;
+        out[8] = ((self.mapper >> 8) as u8 & 0x0F) | (self.submapper << 4);
+        out[10] = encode_ram_size(self.prg_ram_size) | (encode_ram_size(self.prg_nvram_size) << 4);
+        out[11] = encode_ram_size(self.chr_ram_size) | (encode_ram_size(self.chr_nvram_size) << 4);
+        out[12] = match self.timing {
+            Timing::Ntsc => 0,
+            Timing::Pal => 1,
+            Timing::MultiRegion => 2,
+            Timing::Dendy => 3,
+        };
+        out[13] = extra;
+        out[14] = self.misc_roms & 0b11;
+        out[15] = self.expansion_device.0 & 0x3F;
+        out
+    }
+}
+
+fn encode_ram_size(size: usize) -> u8 {
+    if size == 0 {
+        return 0;
+    }
+
+    let mut shift = 1;
+    while (64usize << shift) < size && shift < 15 {
+        shift += 1;
+    }
+    shift
+}
+
+fn encode_rom_size(size: usize, unit: usize) -> (u8, u8) {
+    let units = size / unit;
+    if size.is_multiple_of(unit) && units < 0xF00 {
+        return (units as u8, (units >> 8) as u8);
+    }
+
+    // Smallest 2^E * (2 * MM + 1) >= size
+    let mut best: Option<(usize, u8)> = None;
+    for multiplier in 0..4u8 {
+        let factor = multiplier as usize * 2 + 1;
+        for exponent in 0..64u8 {
+            let Some(value) = 1usize
+                .checked_shl(exponent as u32)
+                .and_then(|v| v.checked_mul(factor))
+            else {
+                break;
+            };
+            if value >= size {
+                if best.is_none_or(|(current, _)| value < current) {
+                    best = Some((value, exponent << 2 | multiplier));
+                }
+                break;
+            }
+        }
+    }
+    (best.map_or(0xFF, |(_, lsb)| lsb), 0x0F)
+}
@@ -0,0 +1,14 @@
+pub mod db;
+pub mod error;
+pub mod fds;
+pub mod hash;
+pub mod header;
+pub mod rom;
+pub mod unif;
+
+pub use error::Error;
+pub use header::Header;
+pub use rom::Rom;
+
+#[cfg(test)]
+mod tests;
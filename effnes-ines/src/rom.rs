@@ -0,0 +1,88 @@
+use crate::{
+    error::{Error, Section},
+    header::{HEADER_LEN, Header, TRAINER_LEN},
+};
+use std::borrow::Cow;
+
+/// Cartridge description: a parsed header plus the ROM contents.
+///
+/// The ROM data is borrowed from the input whenever possible, so a ROM
+/// backed by a memory-mapped file (or `'static` data) is never copied.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Rom<'a> {
+    pub header: Header,
+    pub trainer: Option<Cow<'a, [u8]>>,
+    pub prg_rom: Cow<'a, [u8]>,
+    pub chr_rom: Cow<'a, [u8]>,
+    /// Miscellaneous ROM data (NES 2.0 only), everything after the CHR-ROM.
+    pub misc_rom: Cow<'a, [u8]>,
+}
+
+fn section(data: &[u8], section: Section, offset: usize, len: usize) -> Result<&[u8], Error> {
+    let available = data.len().saturating_sub(offset);
+    if available < len {
+        return Err(Error::Truncated {
+            section,
+            offset,
+            expected: len,
+            available,
+        });
+    }
+    Ok(&data[offset..offset + len])
+}
+
+impl<'a> Rom<'a> {
+    /// Parses an iNES / NES 2.0 file.
+    ///
+    /// Trailing data is ignored, except on NES 2.0 files declaring
+    /// miscellaneous ROMs.
+    pub fn parse(data: &'a [u8]) -> Result<Self, Error> {
+        let header = Header::parse(data)?;
+        if header.prg_rom_size == 0 {
+            return Err(Error::EmptyPrgRom);
+        }
+
+        let mut offset = HEADER_LEN;
+        let trainer = if header.trainer {
+            let trainer = section(data, Section::Trainer, offset, TRAINER_LEN)?;
+            offset += TRAINER_LEN;
+            Some(Cow::Borrowed(trainer))
+        } else {
+            None
+        };
+
+        let prg_rom = section(data, Section::PrgRom, offset, header.prg_rom_size)?;
+        offset += header.prg_rom_size;
+        let chr_rom = section(data, Section::ChrRom, offset, header.chr_rom_size)?;
+        offset += header.chr_rom_size;
+
+        let misc_rom: &[u8] = if header.misc_roms > 0 {
+            let misc = &data[offset..];
+            if misc.is_empty() {
+                return Err(Error::MissingMiscRom);
+            }
+            misc
+        } else {
+            &[]
+        };
+
+        Ok(Self {
+            header,
+            trainer,
+            prg_rom: Cow::Borrowed(prg_rom),
+            chr_rom: Cow::Borrowed(chr_rom),
+            misc_rom: Cow::Borrowed(misc_rom),
+        })
+    }
+
+    /// Copies the borrowed data, detaching it from the input.
+    pub fn into_owned(self) -> Rom<'static> {
+        Rom {
+            header: self.header,
+            trainer: self.trainer.map(|t| Cow::Owned(t.into_owned())),
+            prg_rom: Cow::Owned(self.prg_rom.into_owned()),
+            chr_rom: Cow::Owned(self.chr_rom.into_owned()),
+            misc_rom: Cow::Owned(self.misc_rom.into_owned()),
+        }
+    }
+}
@@ -0,0 +1,445 @@
+use crate::{
+    Error, Header, Rom,
+    db::{Correction, Database},
+    error::Section,
+    fds::{self, DISK_VERIFICATION, DiskFormat, DiskImage},
+    hash::{Crc32, Sha1},
+    header::{ConsoleType, ExpansionDevice, Format, MAGIC, Mirroring, Timing},
+    unif::{board_mapper, board_prg_ram},
+};
+
+fn ines(prg: u8, chr: u8, flags6: u8, flags7: u8) -> Vec<u8> {
+    let mut data = MAGIC.to_vec();
+    data.extend([prg, chr, flags6, flags7, 0, 0, 0, 0, 0, 0, 0, 0]);
+    data
+}
+
+fn with_data(mut header: Vec<u8>, len: usize) -> Vec<u8> {
+    header.extend((0..len).map(|i| i as u8));
+    header
+}
+
+#[test]
+fn ines_header() {
+    let data = with_data(ines(2, 1, 0x13, 0x40), 0x8000 + 0x2000);
+    let rom = Rom::parse(&data).unwrap();
+
+    let h = &rom.header;
+    assert_eq!(h.format, Format::INes);
+    assert_eq!(h.mapper, 0x41);
+    assert_eq!(h.prg_rom_size, 0x8000);
+    assert_eq!(h.chr_rom_size, 0x2000);
+    assert_eq!(h.prg_nvram_size, 0x2000);
+    assert_eq!(h.prg_ram_size, 0);
+    assert_eq!(h.chr_ram_size, 0);
+    assert_eq!(h.mirroring, Mirroring::Vertical);
+    assert!(h.battery);
+    assert_eq!(h.timing, Timing::Ntsc);
+    assert_eq!(rom.prg_rom.len(), 0x8000);
+    assert_eq!(rom.chr_rom[0], 0x00);
+    assert_eq!(rom.prg_rom[1], 0x01);
+}
+
+#[test]
+fn archaic_header() {
+    let mut data = ines(1, 0, 0x18, 0x40);
+    data[8..16].copy_from_slice(b"iskDude!");
+    let data = with_data(data, 0x4000);
+
+    let h = Header::parse(&data).unwrap();
+    assert_eq!(h.format, Format::Archaic);
+    assert_eq!(h.mapper, 1);
+    assert_eq!(h.mirroring, Mirroring::FourScreen);
+    assert_eq!(h.chr_ram_size, 0x2000);
+}
+
+#[test]
+fn nes20_header() {
+    let mut data = ines(0x02, 0x00, 0x02, 0x09);
+    data[8] = 0x31; // submapper 3, mapper 0x1xx
+    data[9] = 0x01; // PRG-ROM MSB
+    data[10] = 0x70; // 8 KiB PRG-NVRAM
+    data[11] = 0x07; // 8 KiB CHR-RAM
+    data[12] = 0x03;
+    data[13] = 0x2C;
+    data[14] = 0x01;
+    data[15] = 0x08;
+    let h = Header::parse(&data).unwrap();
+
+    assert_eq!(h.format, Format::Nes20);
+    assert_eq!(h.mapper, 0x100);
+    assert_eq!(h.submapper, 3);
+    assert_eq!(h.prg_rom_size, 0x102 * 0x4000);
+    assert_eq!(h.prg_ram_size, 0);
+    assert_eq!(h.prg_nvram_size, 0x2000);
+    assert_eq!(h.chr_ram_size, 0x2000);
+    assert_eq!(h.timing, Timing::Dendy);
+    assert_eq!(
+        h.console,
+        ConsoleType::VsSystem {
+            ppu: 0x0C,
+            hardware: 0x02
+        }
+    );
+    assert_eq!(h.misc_roms, 1);
+    assert_eq!(h.expansion_device, ExpansionDevice::ZAPPER);
+
+    assert_eq!(Header::parse(&h.to_nes20_bytes()).unwrap(), h);
+}
+
+#[test]
+fn nes20_exponent_sizes() {
+    // 2^6 * 3 and 2^10 * 1 (EEEEEEMM)
+    let mut data = ines((6 << 2) | 1, 10 << 2, 0, 0x08);
+    data[9] = 0xFF;
+    let h = Header::parse(&data).unwrap();
+    assert_eq!(h.prg_rom_size, (1 << 6) * 3);
+    assert_eq!(h.chr_rom_size, 1 << 10);
+    assert_eq!(Header::parse(&h.to_nes20_bytes()).unwrap(), h);
+
+    data[4] = 0xFF;
+    assert!(matches!(
+        Header::parse(&data),
+        Err(Error::SizeOverflow {
+            section: Section::PrgRom
+        })
+    ));
+}
+
+#[test]
+fn trainer_and_misc_rom() {
+    let mut header = ines(1, 1, 0x04, 0x08);
+    header[14] = 1;
+    let data = with_data(header, 512 + 0x4000 + 0x2000 + 100);
+    let rom = Rom::parse(&data).unwrap();
+    assert_eq!(rom.trainer.as_deref().map(<[u8]>::len), Some(512));
+    assert_eq!(rom.prg_rom[0], 0x00);
+    assert_eq!(rom.misc_rom.len(), 100);
+
+    let owned = rom.clone().into_owned();
+    assert_eq!(owned, rom);
+
+    let data = with_data(header_with_misc(), 0x4000);
+    assert_eq!(Rom::parse(&data), Err(Error::MissingMiscRom));
+}
+
+fn header_with_misc() -> Vec<u8> {
+    let mut header = ines(1, 0, 0, 0x08);
+    header[14] = 1;
+    header
+}
+
+#[test]
+fn malformed_files() {
+    assert!(matches!(
+        Header::parse(b"NES\x1A"),
+        Err(Error::Truncated {
+            section: Section::Header,
+            available: 4,
+            ..
+        })
+    ));
+
+    assert_eq!(
+        Header::parse(&[0; 16]),
+        Err(Error::InvalidMagic([0, 0, 0, 0]))
+    );
+
+    let data = with_data(ines(2, 1, 0, 0), 0x4000);
+    assert_eq!(
+        Rom::parse(&data),
+        Err(Error::Truncated {
+            section: Section::PrgRom,
+            offset: 16,
+            expected: 0x8000,
+            available: 0x4000,
+        })
+    );
+
+    let data = with_data(ines(1, 1, 0, 0), 0x4000 + 10);
+    assert!(matches!(
+        Rom::parse(&data),
+        Err(Error::Truncated {
+            section: Section::ChrRom,
+            available: 10,
+            ..
+        })
+    ));
+
+    assert_eq!(Rom::parse(&ines(0, 1, 0, 0)), Err(Error::EmptyPrgRom));
+}
+
+#[test]
+fn hash_vectors() {
+    assert_eq!(Crc32::checksum(b"123456789"), 0xCBF4_3926);
+    assert_eq!(
+        Sha1::digest(b"abc"),
+        [
+            0xA9, 0x99, 0x3E, 0x36, 0x47, 0x06, 0x81, 0x6A, 0xBA, 0x3E, 0x25, 0x71, 0x78, 0x50,
+            0xC2, 0x6C, 0x9C, 0xD0, 0xD8, 0x9D
+        ]
+    );
+
+    let data: Vec<u8> = (0..1000).map(|i| (i * 7) as u8).collect();
+    let mut sha1 = Sha1::default();
+    data.chunks(33).for_each(|chunk| sha1.update(chunk));
+    assert_eq!(sha1.finish(), Sha1::digest(&data));
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+#[test]
+fn database_corrections() {
+    // Mapper 0 header, on a battery-backed MMC1 game
+    let data = with_data(ines(2, 1, 0x00, 0x00), 0x8000 + 0x2000);
+    let mut rom = Rom::parse(&data).unwrap();
+    let body = &data[16..];
+
+    let xml = format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+        <nes20db>
+          <game>
+            <!-- Test Game (USA) -->
+            <prgrom size="32768"/>
+            <chrrom size="8192"/>
+            <rom size="40960" crc32="{:08X}" sha1="{}"/>
+            <prgnvram size="8192"/>
+            <pcb mapper="1" submapper="5" mirroring="V" battery="1"/>
+            <console type="0" region="1"/>
+          </game>
+          <game>
+            <!-- Hash collision -->
+            <rom size="40960" crc32="{:08X}" sha1="{}"/>
+            <pcb mapper="2"/>
+          </game>
+        </nes20db>"#,
+        Crc32::checksum(body),
+        hex(&Sha1::digest(body)),
+        Crc32::checksum(body),
+        hex(&[0; 20]),
+    );
+    let db = Database::from_nes20db_xml(&xml).unwrap();
+    assert_eq!(db.len(), 2);
+
+    let (entry, corrections) = rom.correct_header(&db).unwrap();
+    assert_eq!(entry.name.as_deref(), Some("Test Game (USA)"));
+    assert_eq!(
+        corrections,
+        [
+            Correction::Mapper { from: 0, to: 1 },
+            Correction::Submapper { from: 0, to: 5 },
+            Correction::PrgRamSize {
+                from: 0x2000,
+                to: 0
+            },
+            Correction::PrgNvramSize {
+                from: 0,
+                to: 0x2000
+            },
+            Correction::Battery {
+                from: false,
+                to: true
+            },
+            Correction::Mirroring {
+                from: Mirroring::Horizontal,
+                to: Mirroring::Vertical
+            },
+            Correction::Timing {
+                from: Timing::Ntsc,
+                to: Timing::Pal
+            },
+        ]
+    );
+    assert_eq!(rom.header.mapper, 1);
+    assert_eq!(rom.correct_header(&db).unwrap().1, []);
+
+    assert!(db.lookup(&rom.prg_rom[1..], &rom.chr_rom).is_none());
+}
+
+#[test]
+fn database_errors() {
+    let err = Database::from_nes20db_xml("<game>\n<rom crc32=\"xyz\"/></game>").unwrap_err();
+    assert_eq!(err.line, 2);
+    assert!(Database::from_nes20db_xml("<game><game>").is_err());
+    assert!(Database::from_nes20db_xml("<!-- unterminated").is_err());
+    #[cfg(feature = "embedded-db")]
+    assert!(Database::embedded().entries().iter().all(|e| e.crc32 != 0));
+
+    let path = std::env::temp_dir().join(format!("effnes-nes20db-{}.xml", std::process::id()));
+    std::fs::write(
+        &path,
+        "<nes20db><game><rom crc32=\"1234ABCD\"/></game></nes20db>",
+    )
+    .unwrap();
+    assert_eq!(
+        Database::load(&path)
+            .unwrap()
+            .lookup_crc32(0x1234ABCD)
+            .count(),
+        1
+    );
+    std::fs::write(&path, "<game><game>").unwrap();
+    let err = Database::load(&path).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    std::fs::remove_file(&path).unwrap();
+    assert!(Database::load(&path).is_err());
+}
+
+fn unif(chunks: &[(&[u8; 4], &[u8])]) -> Vec<u8> {
+    let mut data = b"UNIF".to_vec();
+    data.extend(7u32.to_le_bytes());
+    data.resize(32, 0);
+    for (id, body) in chunks {
+        data.extend(*id);
+        data.extend((body.len() as u32).to_le_bytes());
+        data.extend(*body);
+    }
+    data
+}
+
+#[test]
+fn unif_rom() {
+    let prg0 = [0xAA; 0x4000];
+    let prg1 = [0xBB; 0x4000];
+    let chr0 = [0xCC; 0x2000];
+    let data = unif(&[
+        (b"MAPR", b"NES-SNROM\0"),
+        (b"NAME", b"Test\0"),
+        (b"PRG1", &prg1),
+        (b"PRG0", &prg0),
+        (b"CHR0", &chr0),
+        (b"MIRR", &[3]),
+        (b"BATR", &[1]),
+        (b"TVCI", &[1]),
+    ]);
+    let rom = Rom::parse_any(&data).unwrap();
+
+    let h = &rom.header;
+    assert_eq!(h.format, Format::Unif);
+    assert_eq!((h.mapper, h.submapper), (1, 0));
+    assert_eq!(h.prg_rom_size, 0x8000);
+    assert_eq!(h.chr_rom_size, 0x2000);
+    assert_eq!((h.prg_ram_size, h.prg_nvram_size), (0, 0x2000));
+    assert_eq!(h.chr_ram_size, 0);
+    assert_eq!(h.mirroring, Mirroring::SingleScreenUpper);
+    assert_eq!(h.timing, Timing::Pal);
+    assert!(h.battery);
+
+    // PRG0 comes first, regardless of the chunk order
+    assert_eq!(rom.prg_rom[0], 0xAA);
+    assert_eq!(rom.prg_rom[0x4000], 0xBB);
+    assert!(matches!(rom.chr_rom, std::borrow::Cow::Borrowed(_)));
+}
+
+#[test]
+fn unif_errors() {
+    assert_eq!(board_mapper("UNL-TQROM"), Some((119, 0)));
+    assert_eq!(board_mapper("hvc-unrom"), Some((2, 2)));
+    assert_eq!(board_mapper("NES-AOROM"), Some((7, 1)));
+    assert_eq!(board_mapper("NES-AMROM"), Some((7, 2)));
+    assert_eq!(board_mapper("NES-XYZ"), None);
+    assert_eq!(board_prg_ram("NES-SOROM", true), Some((0x2000, 0x2000)));
+    assert_eq!(board_prg_ram("NES-SXROM", false), Some((0x8000, 0)));
+    assert_eq!(board_prg_ram("NES-NROM-256", true), Some((0, 0)));
+
+    let prg0 = [0; 0x4000];
+    assert_eq!(
+        Rom::parse_unif(&unif(&[(b"PRG0", &prg0)])),
+        Err(Error::MissingChunk(*b"MAPR"))
+    );
+    assert_eq!(
+        Rom::parse_unif(&unif(&[(b"MAPR", b"NES-NROM-256\0")])),
+        Err(Error::MissingChunk(*b"PRG0"))
+    );
+    assert_eq!(
+        Rom::parse_unif(&unif(&[(b"MAPR", b"FOO\0"), (b"PRG0", &prg0)])),
+        Err(Error::UnknownBoard("FOO".into()))
+    );
+
+    let mut data = unif(&[(b"MAPR", b"NES-NROM-256\0"), (b"PRG0", &prg0)]);
+    data.truncate(data.len() - 1);
+    assert!(matches!(
+        Rom::parse_unif(&data),
+        Err(Error::Truncated {
+            section: Section::Chunk(id),
+            expected: 0x4000,
+            available: 0x3FFF,
+            ..
+        }) if &id == b"PRG0"
+    ));
+}
+
+/// A disk side with a single 3 byte file, in fwNES or QD layout.
+fn disk_side(format: DiskFormat) -> Vec<u8> {
+    let crc: &[u8] = if format == DiskFormat::Qd {
+        &[0, 0]
+    } else {
+        &[]
+    };
+    let mut side = DISK_VERIFICATION.to_vec();
+    side.resize(56, 0);
+    side.extend(crc);
+    side.extend([2, 1]);
+    side.extend(crc);
+    side.extend([3, 0, 0]);
+    side.extend(b"FILE0000");
+    side.extend([0x00, 0x60, 3, 0, 0]);
+    side.extend(crc);
+    side.extend([4, 0xAA, 0xBB, 0xCC]);
+    side.extend(crc);
+    side.resize(format.side_len(), 0);
+    side
+}
+
+#[test]
+fn fds_image() {
+    let side = disk_side(DiskFormat::Fds);
+    let mut data = fds::MAGIC.to_vec();
+    data.extend([2, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+    data.extend(&side);
+    data.extend(&side);
+    let image = DiskImage::parse(&data).unwrap();
+    assert_eq!(image.format, DiskFormat::Fds);
+    assert!(image.header);
+    assert_eq!(image.sides.len(), 2);
+    let blocks: Vec<_> = image.blocks(1).collect();
+    assert_eq!(blocks.len(), 4);
+    assert_eq!(blocks[3], [4, 0xAA, 0xBB, 0xCC]);
+
+    // Headerless images.
+    let image = DiskImage::parse(&side).unwrap();
+    assert!(!image.header);
+    assert_eq!(image.sides.len(), 1);
+
+    let side = disk_side(DiskFormat::Qd);
+    let image = DiskImage::parse(&side).unwrap();
+    assert_eq!(image.format, DiskFormat::Qd);
+    assert_eq!(image.blocks(0).last(), Some(&[4, 0xAA, 0xBB, 0xCC][..]));
+}
+
+#[test]
+fn fds_errors() {
+    let mut data = fds::MAGIC.to_vec();
+    data.extend([2, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+    data.extend(disk_side(DiskFormat::Fds));
+    assert_eq!(
+        DiskImage::parse(&data),
+        Err(Error::Truncated {
+            section: Section::DiskSide(1),
+            offset: fds::HEADER_LEN + fds::SIDE_LEN,
+            expected: fds::SIDE_LEN,
+            available: 0,
+        })
+    );
+
+    assert_eq!(
+        DiskImage::parse(&[0; fds::SIDE_LEN]),
+        Err(Error::InvalidDiskSide(0))
+    );
+    assert_eq!(
+        DiskImage::parse(b"NES\x1A"),
+        Err(Error::InvalidMagic(*b"NES\x1A"))
+    );
+}
@@ -0,0 +1,314 @@
+use crate::{
+    Rom,
+    error::{Error, Section},
+    header::{ConsoleType, ExpansionDevice, Format, Header, Mirroring, Timing},
+};
+use std::borrow::Cow;
+
+/// UNIF magic number.
+pub const MAGIC: [u8; 4] = *b"UNIF";
+
+/// Size of the UNIF header (magic, revision and padding).
+pub const HEADER_LEN: usize = 32;
+
+/// Board name prefixes that don't change the board wiring.
+const PREFIXES: [&str; 6] = ["NES-", "HVC-", "UNL-", "BTL-", "BMC-", "IREM-"];
+
+/// Known boards, as `(name, mapper, submapper, prg_ram, battery)`, where
+/// `prg_ram` is the PRG-RAM size and `battery` how much of it is kept by a
+/// battery when the board has one.
+///
+/// Names are matched after removing one of [PREFIXES].
+static BOARDS: &[(&str, u16, u8, usize, usize)] = &[
+    // Discrete logic
+    ("NROM", 0, 0, 0, 0),
+    ("NROM-128", 0, 0, 0, 0),
+    ("NROM-256", 0, 0, 0, 0),
+    ("RROM", 0, 0, 0, 0),
+    ("RROM-128", 0, 0, 0, 0),
+    ("UNROM", 2, 2, 0, 0),
+    ("UOROM", 2, 2, 0, 0),
+    ("CNROM", 3, 2, 0, 0),
+    ("ANROM", 7, 1, 0, 0),
+    ("AN1ROM", 7, 1, 0, 0),
+    ("AMROM", 7, 2, 0, 0),
+    ("AOROM", 7, 1, 0, 0),
+    ("BNROM", 34, 2, 0, 0),
+    ("NINA-001", 34, 1, 0x2000, 0),
+    ("CPROM", 13, 0, 0, 0),
+    ("GNROM", 66, 0, 0, 0),
+    ("MHROM", 66, 0, 0, 0),
+    // MMC1
+    ("SAROM", 1, 0, 0x2000, 0x2000),
+    ("SBROM", 1, 0, 0, 0),
+    ("SCROM", 1, 0, 0, 0),
+    ("SC1ROM", 1, 0, 0, 0),
+    ("SEROM", 1, 5, 0, 0),
+    ("SFROM", 1, 0, 0, 0),
+    ("SGROM", 1, 0, 0, 0),
+    ("SHROM", 1, 5, 0, 0),
+    ("SH1ROM", 1, 5, 0, 0),
+    ("SJROM", 1, 0, 0x2000, 0x2000),
+    ("SKROM", 1, 0, 0x2000, 0x2000),
+    ("SLROM", 1, 0, 0, 0),
+    ("SL1ROM", 1, 0, 0, 0),
+    ("SL2ROM", 1, 0, 0, 0),
+    ("SL3ROM", 1, 0, 0, 0),
+    ("SLRROM", 1, 0, 0, 0),
+    ("SNROM", 1, 0, 0x2000, 0x2000),
+    ("SOROM", 1, 0, 0x4000, 0x2000),
+    ("SUROM", 1, 0, 0x2000, 0x2000),
+    ("SXROM", 1, 0, 0x8000, 0x8000),
+    // MMC2 / MMC4
+    ("PNROM", 9, 0, 0, 0),
+    ("PEEOROM", 9, 0, 0, 0),
+    ("FJROM", 10, 0, 0x2000, 0x2000),
+    ("FKROM", 10, 0, 0x2000, 0x2000),
+    // MMC3 / MMC6
+    ("TBROM", 4, 0, 0, 0),
+    ("TEROM", 4, 0, 0, 0),
+    ("TFROM", 4, 0, 0, 0),
+    ("TGROM", 4, 0, 0, 0),
+    ("TKROM", 4, 0, 0x2000, 0x2000),
+    ("TLROM", 4, 0, 0, 0),
+    ("TL1ROM", 4, 0, 0, 0),
+    ("TL2ROM", 4, 0, 0, 0),
+    ("TNROM", 4, 0, 0x2000, 0x2000),
+    ("TR1ROM", 4, 0, 0, 0),
+    ("TSROM", 4, 0, 0x2000, 0),
+    ("TVROM", 4, 0, 0, 0),
+    ("B4", 4, 0, 0, 0),
+    ("HKROM", 4, 1, 0x400, 0x400),
+    ("TKSROM", 118, 0, 0x2000, 0x2000),
+    ("TLSROM", 118, 0, 0, 0),
+    ("TQROM", 119, 0, 0, 0),
+    // MMC5
+    ("EKROM", 5, 0, 0x2000, 0x2000),
+    ("ELROM", 5, 0, 0, 0),
+    ("ETROM", 5, 0, 0x4000, 0x2000),
+    ("EWROM", 5, 0, 0x8000, 0x8000),
+    // Sunsoft
+    ("BTR", 69, 0, 0x2000, 0x2000),
+    ("JLROM", 69, 0, 0, 0),
+    ("JSROM", 69, 0, 0x2000, 0),
+];
+
+/// Finds the mapper and submapper numbers of a UNIF board.
+///
+/// ```ignore
+/// assert_eq!(board_mapper("NES-SNROM"), Some((1, 0)));
+/// ```
+pub fn board_mapper(name: &str) -> Option<(u16, u8)> {
+    board(name).map(|(_, mapper, submapper, _, _)| (*mapper, *submapper))
+}
+
+/// Finds the PRG-RAM of a UNIF board, as `(volatile, battery-backed)` sizes.
+///
+/// ```ignore
+/// assert_eq!(board_prg_ram("NES-SOROM", true), Some((0x2000, 0x2000)));
+/// ```
+pub fn board_prg_ram(name: &str, battery: bool) -> Option<(usize, usize)> {
+    board(name).map(|(_, _, _, prg_ram, backed)| {
+        let nvram = if battery { *backed } else { 0 };
+        (prg_ram - nvram, nvram)
+    })
+}
+
+fn board(name: &str) -> Option<&'static (&'static str, u16, u8, usize, usize)> {
+    let name = name.trim().to_ascii_uppercase();
+    let base = PREFIXES
+        .iter()
+        .find_map(|prefix| name.strip_prefix(prefix))
+        .unwrap_or(&name);
+
+    BOARDS.iter().find(|(board, ..)| *board == base)
+}
+
+struct Chunk<'a> {
+    id: [u8; 4],
+    data: &'a [u8],
+}
+
+fn chunks(data: &[u8]) -> impl Iterator<Item = Result<Chunk<'_>, Error>> {
+    let mut offset = HEADER_LEN;
+    std::iter::from_fn(move || {
+        if offset >= data.len() {
+            return None;
+        }
+
+        let Some(head) = data.get(offset..offset + 8) else {
+            let error = Error::Truncated {
+                section: Section::Chunk([0; 4]),
+                offset,
+                expected: 8,
+                available: data.len() - offset,
+            };
+            offset = data.len();
+            return Some(Err(error));
+        };
+
+        let id = [head[0], head[1], head[2], head[3]];
+        let len = u32::from_le_bytes([head[4], head[5], head[6], head[7]]) as usize;
+        let start = offset + 8;
+        let available = data.len() - start;
+        if available < len {
+            offset = data.len();
+            return Some(Err(Error::Truncated {
+                section: Section::Chunk(id),
+                offset: start,
+                expected: len,
+                available,
+            }));
+        }
+
+        offset = start + len;
+        Some(Ok(Chunk {
+            id,
+            data: &data[start..start + len],
+        }))
+    })
+}
+
+/// Concatenates the numbered chunks (e.g. `PRG0` to `PRGF`), borrowing the
+/// data if there's only one.
+fn concat<'a>(banks: &[Option<&'a [u8]>; 16]) -> Cow<'a, [u8]> {
+    let mut present = banks.iter().flatten();
+    match (present.next(), present.next()) {
+        (None, _) => Cow::Borrowed(&[]),
+        (Some(only), None) => Cow::Borrowed(only),
+        _ => Cow::Owned(
+            banks
+                .iter()
+                .flatten()
+                .flat_map(|b| b.iter())
+                .copied()
+                .collect(),
+        ),
+    }
+}
+
+impl<'a> Rom<'a> {
+    /// Parses a UNIF file.
+    ///
+    /// ## Behaviour
+    ///
+    /// - The mapper and submapper numbers come from the `MAPR` board name
+    ///   (see [board_mapper]).
+    /// - `PRG0..=PRGF` and `CHR0..=CHRF` are concatenated in order.
+    /// - `MIRR` sets the mirroring (mapper controlled boards are reported as
+    ///   horizontal), `BATR` the battery and `TVCI` the timing.
+    /// - As UNIF doesn't declare RAM sizes, the PRG-RAM is the one of the
+    ///   board (partly non volatile if there's a battery), and 8 KiB of
+    ///   CHR-RAM are assumed if there's no CHR-ROM, or if there's a `VROR`
+    ///   chunk.
+    pub fn parse_unif(data: &'a [u8]) -> Result<Self, Error> {
+        if data.len() < HEADER_LEN {
+            return Err(Error::Truncated {
+                section: Section::Header,
+                offset: 0,
+                expected: HEADER_LEN,
+                available: data.len(),
+            });
+        }
+
+        if data[0..4] != MAGIC {
+            return Err(Error::InvalidMagic([data[0], data[1], data[2], data[3]]));
+        }
+
+        let mut board: Option<&[u8]> = None;
+        let mut prg: [Option<&[u8]>; 16] = [None; 16];
+        let mut chr: [Option<&[u8]>; 16] = [None; 16];
+        let mut mirroring = Mirroring::Horizontal;
+        let mut battery = false;
+        let mut chr_ram = false;
+        let mut timing = Timing::Ntsc;
+
+        for chunk in chunks(data) {
+            let chunk = chunk?;
+            let bank = (chunk.id[3] as char).to_digit(16).map(|bank| bank as usize);
+            match (&chunk.id[0..3], bank) {
+                (b"PRG", Some(bank)) => prg[bank] = Some(chunk.data),
+                (b"CHR", Some(bank)) => chr[bank] = Some(chunk.data),
+                _ => match &chunk.id {
+                    b"MAPR" => board = Some(chunk.data),
+                    b"BATR" => battery = chunk.data.first().is_none_or(|b| *b != 0),
+                    b"VROR" => chr_ram = true,
+                    b"MIRR" => {
+                        mirroring = match chunk.data.first() {
+                            Some(1) => Mirroring::Vertical,
+                            Some(2) => Mirroring::SingleScreenLower,
+                            Some(3) => Mirroring::SingleScreenUpper,
+                            Some(4) => Mirroring::FourScreen,
+                            _ => Mirroring::Horizontal,
+                        }
+                    }
+                    b"TVCI" => {
+                        timing = match chunk.data.first() {
+                            Some(1) => Timing::Pal,
+                            Some(2) => Timing::MultiRegion,
+                            _ => Timing::Ntsc,
+                        }
+                    }
+                    _ => {}
+                },
+            }
+        }
+
+        let board = board.ok_or(Error::MissingChunk(*b"MAPR"))?;
+        let board = String::from_utf8_lossy(board.split(|b| *b == 0).next().unwrap_or(&[]))
+            .trim()
+            .to_string();
+        let (mapper, submapper) =
+            board_mapper(&board).ok_or_else(|| Error::UnknownBoard(board.clone()))?;
+
+        if prg[0].is_none() {
+            return Err(Error::MissingChunk(*b"PRG0"));
+        }
+        let prg_rom = concat(&prg);
+        let chr_rom = concat(&chr);
+        if prg_rom.is_empty() {
+            return Err(Error::EmptyPrgRom);
+        }
+
+        let (prg_ram, prg_nvram) = board_prg_ram(&board, battery).unwrap_or_default();
+        let header = Header {
+            format: Format::Unif,
+            mapper,
+            submapper,
+            prg_rom_size: prg_rom.len(),
+            chr_rom_size: chr_rom.len(),
+            prg_ram_size: prg_ram,
+            prg_nvram_size: prg_nvram,
+            chr_ram_size: if chr_rom.is_empty() || chr_ram {
+                0x2000
+            } else {
+                0
+            },
+            chr_nvram_size: 0,
+            battery,
+            trainer: false,
+            mirroring,
+            console: ConsoleType::Nes,
+            timing,
+            misc_roms: 0,
+            expansion_device: ExpansionDevice::UNSPECIFIED,
+        };
+
+        Ok(Self {
+            header,
+            trainer: None,
+            prg_rom,
+            chr_rom,
+            misc_rom: Cow::Borrowed(&[]),
+        })
+    }
+
+    /// Parses an iNES / NES 2.0 or UNIF file, depending on its magic number.
+    pub fn parse_any(data: &'a [u8]) -> Result<Self, Error> {
+        if data.starts_with(&MAGIC) {
+            Self::parse_unif(data)
+        } else {
+            Self::parse(data)
+        }
+    }
+}
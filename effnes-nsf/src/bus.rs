@@ -0,0 +1,374 @@
+use crate::nsf::{Expansion, Nsf};
+use effnes_apu::apu::{Apu, Region};
+use effnes_bus::{InspectBus, MemoryBus};
+use effnes_cartridge::{
+    RamData,
+    audio::{FdsAudio, Namco163Audio, Opll, Sunsoft5bAudio, Vrc6Audio, namco163::SOUND_RAM_LEN},
+    mappers::mmc5::{EXRAM_LEN, Mmc5Audio},
+};
+use std::borrow::Cow;
+
+/// Address of the idle loop (`JMP IDLE_LOOP`) that INIT and PLAY return
+/// into. Nothing is mapped there on NSF players.
+pub const IDLE_LOOP: u16 = 0x4100;
+
+/// Code of the idle loop.
+const IDLE_CODE: [u8; 3] = [0x4C, IDLE_LOOP as u8, (IDLE_LOOP >> 8) as u8];
+
+/// Size of the console internal RAM.
+pub const RAM_LEN: usize = 0x800;
+
+/// Size of the cartridge RAM at $6000-$7FFF.
+pub const WRAM_LEN: usize = 0x2000;
+
+/// Size of the FDS RAM, which spans $6000-$FFFF.
+pub const FDS_RAM_LEN: usize = 0xA000;
+
+/// Size of a bank switching window.
+const BANK_LEN: usize = 0x1000;
+
+/// Expansion audio chips, only allocated if the tune uses them.
+#[derive(Default)]
+struct Chips {
+    vrc6: Option<Vrc6Audio>,
+    vrc7: Option<Opll>,
+    fds: Option<FdsAudio>,
+    mmc5: Option<Mmc5Audio>,
+    mmc5_exram: Vec<u8>,
+    mmc5_multiplicands: [u8; 2],
+    namco163: Option<Namco163Audio<'static>>,
+    sunsoft5b: Option<Sunsoft5bAudio>,
+}
+
+impl Chips {
+    fn new(expansion: Expansion) -> Self {
+        let mmc5 = expansion.contains(Expansion::Mmc5);
+        Self {
+            vrc6: expansion.contains(Expansion::Vrc6).then(Vrc6Audio::default),
+            vrc7: expansion.contains(Expansion::Vrc7).then(Opll::default),
+            fds: expansion.contains(Expansion::Fds).then(FdsAudio::default),
+            mmc5: mmc5.then(Mmc5Audio::default),
+            mmc5_exram: if mmc5 { vec![0; EXRAM_LEN] } else { Vec::new() },
+            mmc5_multiplicands: [0; 2],
+            namco163: expansion
+                .contains(Expansion::Namco163)
+                .then(|| Namco163Audio::new(RamData::owned(SOUND_RAM_LEN, 0))),
+            sunsoft5b: expansion
+                .contains(Expansion::Sunsoft5b)
+                .then(Sunsoft5bAudio::default),
+        }
+    }
+}
+
+/// NSF Player Bus
+///
+/// The CPU memory map of an NSF player:
+///
+/// - $0000-$1FFF: internal RAM (mirrored).
+/// - $4000-$4017: APU.
+/// - $4100-$4102: idle loop (see [IDLE_LOOP]).
+/// - $5FF8-$5FFF: bank switching registers (4 KiB banks at $8000-$FFFF),
+///   plus $5FF6-$5FF7 on FDS tunes ($6000-$7FFF).
+/// - $6000-$7FFF: RAM.
+/// - $8000-$FFFF: program data (RAM on FDS tunes).
+/// - The registers of the expansion chips declared by the tune.
+///
+/// ## Behaviour
+///
+/// - FDS tunes run from RAM: bank switching copies the selected bank into
+///   the RAM window, as the FDS has no ROM to switch.
+/// - Unmapped addresses read as 0.
+/// - DMC samples are read without stalling the CPU.
+pub struct NsfBus<'a> {
+    rom: Cow<'a, [u8]>,
+    /// Bytes between the start of the first bank and the load address.
+    padding: usize,
+    initial_banks: Option<[u8; 8]>,
+    load_address: u16,
+    fds: bool,
+    /// Current banks at $8000-$FFFF.
+    banks: [u8; 8],
+
+    ram: [u8; RAM_LEN],
+    wram: Vec<u8>,
+
+    apu: Apu,
+    expansion: Expansion,
+    chips: Chips,
+
+    returned: bool,
+}
+
+impl<'a> NsfBus<'a> {
+    /// Creates the bus for `nsf` (borrowing its program data), in a
+    /// `region` console.
+    pub fn new(nsf: &Nsf<'a>, region: Region) -> Self {
+        let fds = nsf.expansion.contains(Expansion::Fds);
+        let padding = match nsf.banks {
+            Some(_) => nsf.load_address as usize & (BANK_LEN - 1),
+            None => nsf.load_address.saturating_sub(0x8000) as usize,
+        };
+
+        let mut bus = Self {
+            rom: nsf.data.clone(),
+            padding,
+            initial_banks: nsf.banks,
+            load_address: nsf.load_address,
+            fds,
+            banks: [0, 1, 2, 3, 4, 5, 6, 7],
+            ram: [0; RAM_LEN],
+            wram: vec![0; if fds { FDS_RAM_LEN } else { WRAM_LEN }],
+            apu: Apu::new(region),
+            expansion: nsf.expansion,
+            chips: Chips::new(nsf.expansion),
+            returned: true,
+        };
+        bus.reset();
+        bus
+    }
+
+    /// Restores the power-on state expected by INIT: cleared RAM, silenced
+    /// APU and expansion chips, and initial banks.
+    pub fn reset(&mut self) {
+        self.ram.fill(0);
+        self.wram.fill(0);
+        self.chips = Chips::new(self.expansion);
+
+        self.apu.reset();
+        for addr in 0x4000..=0x4013 {
+            self.apu.write(addr, 0);
+        }
+        self.apu.write(0x4015, 0x00);
+        self.apu.write(0x4015, 0x0F);
+        self.apu.write(0x4017, 0x40);
+        if let Some(fds) = &mut self.chips.fds {
+            fds.write(0x4089, 0x80);
+            fds.write(0x408A, 0xE8);
+        }
+
+        match self.initial_banks {
+            Some(banks) => {
+                if self.fds {
+                    self.switch_bank(0x5FF6, banks[6]);
+                    self.switch_bank(0x5FF7, banks[7]);
+                }
+                for (addr, bank) in (0x5FF8..=0x5FFF).zip(banks) {
+                    self.switch_bank(addr, bank);
+                }
+            }
+            None if self.fds => {
+                let start = self.load_address as usize - 0x6000;
+                let len = self.rom.len().min(FDS_RAM_LEN - start);
+                self.wram[start..start + len].copy_from_slice(&self.rom[..len]);
+            }
+            None => self.banks = [0, 1, 2, 3, 4, 5, 6, 7],
+        }
+        self.returned = true;
+    }
+
+    fn rom_byte(&self, bank: u8, offset: usize) -> u8 {
+        (bank as usize * BANK_LEN + offset)
+            .checked_sub(self.padding)
+            .and_then(|index| self.rom.get(index))
+            .copied()
+            .unwrap_or(0)
+    }
+
+    /// Handles a write into $5FF6-$5FFF.
+    fn switch_bank(&mut self, addr: u16, bank: u8) {
+        if self.fds {
+            let start = (addr - 0x5FF6) as usize * BANK_LEN;
+            for offset in 0..BANK_LEN {
+                self.wram[start + offset] = self.rom_byte(bank, offset);
+            }
+        } else if addr >= 0x5FF8 {
+            self.banks[(addr - 0x5FF8) as usize] = bank;
+        }
+    }
+
+    pub fn apu(&self) -> &Apu {
+        &self.apu
+    }
+
+    /// Whether the CPU reached the idle loop, since the last
+    /// [NsfBus::call_started].
+    pub fn returned(&self) -> bool {
+        self.returned
+    }
+
+    /// Marks the start of an INIT/PLAY call.
+    pub fn call_started(&mut self) {
+        self.returned = false;
+    }
+
+    /// CPU cycle clock. Returns the mixed audio output.
+    pub fn cycle(&mut self) -> f32 {
+        if let Some(addr) = self.apu.dmc_request() {
+            let data = self.peek_u8(addr);
+            self.apu.dmc_fill(data);
+        }
+        self.apu.cycle();
+
+        let chips = &mut self.chips;
+        let mut expansion = 0.0;
+        if let Some(vrc6) = &mut chips.vrc6 {
+            vrc6.cycle();
+            expansion += vrc6.output();
+        }
+        if let Some(vrc7) = &mut chips.vrc7 {
+            vrc7.cycle();
+            expansion += vrc7.output();
+        }
+        if let Some(fds) = &mut chips.fds {
+            fds.cycle();
+            expansion += fds.output();
+        }
+        if let Some(mmc5) = &mut chips.mmc5 {
+            mmc5.cycle();
+            expansion += mmc5.output();
+        }
+        if let Some(namco163) = &mut chips.namco163 {
+            namco163.cycle();
+            expansion += namco163.output();
+        }
+        if let Some(sunsoft5b) = &mut chips.sunsoft5b {
+            sunsoft5b.cycle();
+            expansion += sunsoft5b.output();
+        }
+        self.apu.levels().mix_with_expansion(expansion)
+    }
+}
+
+impl MemoryBus for NsfBus<'_> {
+    fn read_u8(&mut self, addr: u16) -> u8 {
+        match addr {
+            0x4015 => return self.apu.read_status(),
+            IDLE_LOOP => self.returned = true,
+            0x4800..=0x4FFF => {
+                if let Some(namco163) = &mut self.chips.namco163 {
+                    return namco163.read_data();
+                }
+            }
+            0x5010 => {
+                if let Some(mmc5) = &mut self.chips.mmc5 {
+                    let status = mmc5.pcm_status();
+                    mmc5.acknowledge();
+                    return status;
+                }
+            }
+            _ => {}
+        }
+        self.peek_u8(addr)
+    }
+
+    fn read_u16(&mut self, addr: u16) -> u16 {
+        u16::from_le_bytes([self.read_u8(addr), self.read_u8(addr.wrapping_add(1))])
+    }
+
+    fn write_u8(&mut self, addr: u16, data: u8) {
+        let chips = &mut self.chips;
+        match addr {
+            0x0000..=0x1FFF => self.ram[addr as usize & (RAM_LEN - 1)] = data,
+            0x4000..=0x4013 | 0x4015 | 0x4017 => self.apu.write(addr, data),
+            0x4040..=0x408A => {
+                if let Some(fds) = &mut chips.fds {
+                    fds.write(addr, data);
+                }
+            }
+            0x4800..=0x4FFF => {
+                if let Some(namco163) = &mut chips.namco163 {
+                    namco163.write_data(data);
+                }
+            }
+            0x5000..=0x5015 => {
+                if let Some(mmc5) = &mut chips.mmc5 {
+                    mmc5.write(addr, data);
+                }
+            }
+            0x5205 | 0x5206 if chips.mmc5.is_some() => {
+                chips.mmc5_multiplicands[(addr - 0x5205) as usize] = data;
+            }
+            0x5C00..=0x5FF5 if chips.mmc5.is_some() => {
+                chips.mmc5_exram[(addr - 0x5C00) as usize] = data;
+            }
+            0x5FF6..=0x5FFF => self.switch_bank(addr, data),
+            0x6000..=0x7FFF => self.wram[(addr - 0x6000) as usize] = data,
+            0x8000..=0xFFFF => {
+                if self.fds {
+                    self.wram[(addr - 0x6000) as usize] = data;
+                }
+                match addr {
+                    0x9000..=0x9003 | 0xA000..=0xA002 | 0xB000..=0xB002 => {
+                        if let Some(vrc6) = &mut chips.vrc6 {
+                            match (addr >> 12, addr as u8 & 0x03) {
+                                (0x9, 3) => vrc6.write_control(data),
+                                (channel, reg) => vrc6.write((channel - 0x9) as u8, reg, data),
+                            }
+                        }
+                    }
+                    0x9010 | 0x9030 => {
+                        if let Some(vrc7) = &mut chips.vrc7 {
+                            if addr == 0x9010 {
+                                vrc7.write_address(data);
+                            } else {
+                                vrc7.write_data(data);
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+                if let Some(sunsoft5b) = &mut chips.sunsoft5b {
+                    match addr {
+                        0xC000..=0xDFFF => sunsoft5b.write_address(data),
+                        0xE000..=0xFFFF => sunsoft5b.write_data(data),
+                        _ => {}
+                    }
+                }
+                if let (Some(namco163), 0xF800..=0xFFFF) = (&mut chips.namco163, addr) {
+                    namco163.write_address(data);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+impl InspectBus for NsfBus<'_> {
+    fn peek_u8(&self, addr: u16) -> u8 {
+        let chips = &self.chips;
+        match addr {
+            0x0000..=0x1FFF => self.ram[addr as usize & (RAM_LEN - 1)],
+            0x4015 => self.apu.peek_status(),
+            0x4040..=0x4092 => chips
+                .fds
+                .as_ref()
+                .and_then(|fds| fds.peek(addr))
+                .map_or(0, |data| data | 0x40),
+            IDLE_LOOP..=0x4102 => IDLE_CODE[(addr - IDLE_LOOP) as usize],
+            0x4800..=0x4FFF => chips.namco163.as_ref().map_or(0, |n163| n163.peek_data()),
+            0x5010 => chips.mmc5.as_ref().map_or(0, |mmc5| mmc5.pcm_status()),
+            0x5015 => chips.mmc5.as_ref().map_or(0, |mmc5| mmc5.status()),
+            0x5205 | 0x5206 if chips.mmc5.is_some() => {
+                let [a, b] = chips.mmc5_multiplicands;
+                let product = a as u16 * b as u16;
+                if addr == 0x5205 {
+                    product as u8
+                } else {
+                    (product >> 8) as u8
+                }
+            }
+            0x5C00..=0x5FF5 if chips.mmc5.is_some() => chips.mmc5_exram[(addr - 0x5C00) as usize],
+            0x6000..=0x7FFF => self.wram[(addr - 0x6000) as usize],
+            0x8000..=0xFFFF if self.fds => self.wram[(addr - 0x6000) as usize],
+            0x8000..=0xFFFF => {
+                let window = (addr as usize - 0x8000) / BANK_LEN;
+                self.rom_byte(self.banks[window], addr as usize & (BANK_LEN - 1))
+            }
+            _ => 0,
+        }
+    }
+
+    fn peek_u16(&self, addr: u16) -> u16 {
+        u16::from_le_bytes([self.peek_u8(addr), self.peek_u8(addr.wrapping_add(1))])
+    }
+}
@@ -0,0 +1,82 @@
+use std::fmt::{self, Display};
+
+/// A section of an NSF/NSFe file.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Section {
+    Header,
+    /// NSFe chunk, by ID.
+    Chunk([u8; 4]),
+}
+
+impl Display for Section {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Section::Header => f.write_str("header"),
+            Section::Chunk(id) => write!(f, "`{}` chunk", String::from_utf8_lossy(id)),
+        }
+    }
+}
+
+/// NSF/NSFe parsing error.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// The file doesn't start with `NESM<EOF>` or `NSFE`.
+    InvalidMagic([u8; 4]),
+
+    /// The file ends before the end of `section`.
+    Truncated {
+        section: Section,
+        /// Offset of the section start.
+        offset: usize,
+        /// Size of the section, as declared by the file.
+        expected: usize,
+        /// Bytes available from `offset` on.
+        available: usize,
+    },
+
+    /// A required NSFe chunk (`INFO` or `DATA`) is missing.
+    MissingChunk([u8; 4]),
+
+    /// An NSFe chunk that players must understand (its ID starts with an
+    /// uppercase letter) is unknown.
+    UnknownChunk([u8; 4]),
+
+    /// The load address is below $8000 (or below $6000, for FDS tunes),
+    /// which would overlap the registers or the RAM.
+    InvalidLoadAddress(u16),
+
+    /// The file declares no songs.
+    NoSongs,
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::InvalidMagic(magic) => write!(f, "invalid magic {:02x?}", magic),
+            Error::Truncated {
+                section,
+                offset,
+                expected,
+                available,
+            } => write!(
+                f,
+                "truncated {} (${:x} bytes at offset ${:x}, but only ${:x} available)",
+                section, expected, offset, available
+            ),
+            Error::MissingChunk(id) => {
+                write!(f, "missing `{}` chunk", String::from_utf8_lossy(id))
+            }
+            Error::UnknownChunk(id) => {
+                write!(
+                    f,
+                    "unknown required `{}` chunk",
+                    String::from_utf8_lossy(id)
+                )
+            }
+            Error::InvalidLoadAddress(addr) => write!(f, "invalid load address ${:04x}", addr),
+            Error::NoSongs => f.write_str("no songs"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
@@ -0,0 +1,11 @@
+pub mod bus;
+pub mod error;
+pub mod nsf;
+pub mod player;
+
+pub use error::Error;
+pub use nsf::{Expansion, Nsf, Track};
+pub use player::Player;
+
+#[cfg(test)]
+mod tests;
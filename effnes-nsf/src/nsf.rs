@@ -0,0 +1,430 @@
+use crate::error::{Error, Section};
+use bitflags::bitflags;
+use std::{borrow::Cow, time::Duration};
+
+/// NSF magic number.
+pub const MAGIC: [u8; 5] = *b"NESM\x1A";
+
+/// NSFe magic number.
+pub const NSFE_MAGIC: [u8; 4] = *b"NSFE";
+
+/// Size of the NSF header.
+pub const HEADER_LEN: usize = 0x80;
+
+/// PLAY period used when the file declares none, in microseconds (NTSC).
+pub const DEFAULT_NTSC_SPEED: u16 = 16639;
+
+/// PLAY period used when the file declares none, in microseconds (PAL).
+pub const DEFAULT_PAL_SPEED: u16 = 19997;
+
+/// File format.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Format {
+    Nsf,
+    Nsfe,
+}
+
+/// Console timings supported by a tune.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Timing {
+    Ntsc,
+    Pal,
+    /// Both; the tune checks the X register on INIT.
+    Dual,
+}
+
+impl Timing {
+    fn from_flags(flags: u8) -> Self {
+        match flags & 0x03 {
+            0 => Timing::Ntsc,
+            1 => Timing::Pal,
+            _ => Timing::Dual,
+        }
+    }
+}
+
+bitflags! {
+    /// Expansion audio chips used by a tune.
+    #[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+    pub struct Expansion: u8 {
+        const Vrc6      = 0b0000_0001;
+        const Vrc7      = 0b0000_0010;
+        const Fds       = 0b0000_0100;
+        const Mmc5      = 0b0000_1000;
+        const Namco163  = 0b0001_0000;
+        const Sunsoft5b = 0b0010_0000;
+    }
+}
+
+/// Per track metadata (only available on NSFe files, or NSF2 files with
+/// metadata chunks).
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Track {
+    pub name: Option<String>,
+    /// Play time, before the fade out.
+    pub duration: Option<Duration>,
+    /// Fade out time.
+    pub fade: Option<Duration>,
+}
+
+/// NSF/NSFe Music File
+///
+/// ## Behaviour
+///
+/// - Song numbers are zero based (the NSF header stores them one based).
+/// - Text fields are decoded lossily, as rips use many encodings.
+/// - NSFe metadata chunks appended to NSF2 files are parsed as well.
+///
+/// ```ignore
+/// let data = std::fs::read("smb3.nsfe")?;
+/// let nsf = Nsf::parse(&data)?;
+/// for (song, track) in nsf.tracks.iter().enumerate() {
+///     println!("{song}: {:?} ({:?})", track.name, track.duration);
+/// }
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Nsf<'a> {
+    pub format: Format,
+    /// NSF version (1 or 2; 0 for NSFe files).
+    pub version: u8,
+    pub songs: u8,
+    pub starting_song: u8,
+    pub load_address: u16,
+    pub init_address: u16,
+    pub play_address: u16,
+    pub title: String,
+    pub artist: String,
+    pub copyright: String,
+    pub ripper: Option<String>,
+    /// PLAY period on NTSC consoles, in microseconds.
+    pub ntsc_speed: u16,
+    /// PLAY period on PAL consoles, in microseconds.
+    pub pal_speed: u16,
+    /// Initial 4 KiB bank numbers for $8000-$FFFF, if the tune is
+    /// bankswitched.
+    pub banks: Option<[u8; 8]>,
+    pub timing: Timing,
+    pub expansion: Expansion,
+    /// One entry for every song.
+    pub tracks: Vec<Track>,
+    /// Suggested song order.
+    pub playlist: Option<Vec<u8>>,
+    /// Program data, to be loaded at [Nsf::load_address].
+    pub data: Cow<'a, [u8]>,
+}
+
+/// Decodes a NUL terminated (or padded) string.
+fn text(data: &[u8]) -> String {
+    let end = data.iter().position(|b| *b == 0).unwrap_or(data.len());
+    String::from_utf8_lossy(&data[..end]).into_owned()
+}
+
+fn u16_at(data: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_le_bytes([
+        *data.get(offset)?,
+        *data.get(offset + 1)?,
+    ]))
+}
+
+/// Reads a list of millisecond times (negative values stand for unknown).
+fn times(data: &[u8]) -> impl Iterator<Item = Option<Duration>> + '_ {
+    data.chunks_exact(4).map(|ms| {
+        let ms = i32::from_le_bytes([ms[0], ms[1], ms[2], ms[3]]);
+        u64::try_from(ms).ok().map(Duration::from_millis)
+    })
+}
+
+struct Chunk<'a> {
+    id: [u8; 4],
+    data: &'a [u8],
+}
+
+/// Iterates over the NSFe chunks (`length`, `id`, `data`) starting at
+/// `offset`, up to the `NEND` chunk.
+fn chunks(data: &[u8], mut offset: usize) -> impl Iterator<Item = Result<Chunk<'_>, Error>> {
+    std::iter::from_fn(move || {
+        if offset >= data.len() {
+            return None;
+        }
+
+        let Some(head) = data.get(offset..offset + 8) else {
+            let error = Error::Truncated {
+                section: Section::Chunk([0; 4]),
+                offset,
+                expected: 8,
+                available: data.len() - offset,
+            };
+            offset = data.len();
+            return Some(Err(error));
+        };
+
+        let len = u32::from_le_bytes([head[0], head[1], head[2], head[3]]) as usize;
+        let id = [head[4], head[5], head[6], head[7]];
+        let start = offset + 8;
+        let available = data.len() - start;
+        if available < len {
+            offset = data.len();
+            return Some(Err(Error::Truncated {
+                section: Section::Chunk(id),
+                offset: start,
+                expected: len,
+                available,
+            }));
+        }
+
+        offset = if &id == b"NEND" {
+            data.len()
+        } else {
+            start + len
+        };
+        Some(Ok(Chunk {
+            id,
+            data: &data[start..start + len],
+        }))
+    })
+}
+
+impl<'a> Nsf<'a> {
+    /// Parses an NSF or NSFe file.
+    ///
+    /// ## Errors
+    ///
+    /// - [Error::InvalidMagic] if the file isn't an NSF/NSFe file.
+    /// - [Error::Truncated] if the header or a chunk is cut short.
+    /// - [Error::MissingChunk] / [Error::UnknownChunk] on malformed NSFe
+    ///   files.
+    /// - [Error::InvalidLoadAddress] and [Error::NoSongs] on unplayable
+    ///   files.
+    pub fn parse(data: &'a [u8]) -> Result<Self, Error> {
+        let nsf = if data.starts_with(&MAGIC) {
+            Self::parse_nsf(data)?
+        } else if data.starts_with(&NSFE_MAGIC) {
+            Self::parse_nsfe(data)?
+        } else {
+            let mut magic = [0; 4];
+            for (dst, src) in magic.iter_mut().zip(data) {
+                *dst = *src;
+            }
+            return Err(Error::InvalidMagic(magic));
+        };
+
+        if nsf.songs == 0 {
+            return Err(Error::NoSongs);
+        }
+        let lowest = if nsf.expansion.contains(Expansion::Fds) {
+            0x6000
+        } else {
+            0x8000
+        };
+        if nsf.load_address < lowest {
+            return Err(Error::InvalidLoadAddress(nsf.load_address));
+        }
+        Ok(nsf)
+    }
+
+    fn empty(format: Format) -> Self {
+        Self {
+            format,
+            version: 0,
+            songs: 1,
+            starting_song: 0,
+            load_address: 0x8000,
+            init_address: 0x8000,
+            play_address: 0x8000,
+            title: String::new(),
+            artist: String::new(),
+            copyright: String::new(),
+            ripper: None,
+            ntsc_speed: DEFAULT_NTSC_SPEED,
+            pal_speed: DEFAULT_PAL_SPEED,
+            banks: None,
+            timing: Timing::Ntsc,
+            expansion: Expansion::empty(),
+            tracks: Vec::new(),
+            playlist: None,
+            data: Cow::Borrowed(&[]),
+        }
+    }
+
+    fn parse_nsf(data: &'a [u8]) -> Result<Self, Error> {
+        let Some(header) = data.get(..HEADER_LEN) else {
+            return Err(Error::Truncated {
+                section: Section::Header,
+                offset: 0,
+                expected: HEADER_LEN,
+                available: data.len(),
+            });
+        };
+
+        let mut nsf = Self::empty(Format::Nsf);
+        nsf.version = header[0x05];
+        nsf.songs = header[0x06];
+        nsf.starting_song = header[0x07].saturating_sub(1);
+        nsf.load_address = u16::from_le_bytes([header[0x08], header[0x09]]);
+        nsf.init_address = u16::from_le_bytes([header[0x0A], header[0x0B]]);
+        nsf.play_address = u16::from_le_bytes([header[0x0C], header[0x0D]]);
+        nsf.title = text(&header[0x0E..0x2E]);
+        nsf.artist = text(&header[0x2E..0x4E]);
+        nsf.copyright = text(&header[0x4E..0x6E]);
+        nsf.ntsc_speed = u16::from_le_bytes([header[0x6E], header[0x6F]]);
+        nsf.pal_speed = u16::from_le_bytes([header[0x78], header[0x79]]);
+        nsf.timing = Timing::from_flags(header[0x7A]);
+        nsf.expansion = Expansion::from_bits_truncate(header[0x7B]);
+
+        let banks: [u8; 8] = header[0x70..0x78].try_into().unwrap();
+        nsf.banks = banks.iter().any(|bank| *bank != 0).then_some(banks);
+
+        // NSF2: a non zero program length means metadata chunks follow the
+        // program data.
+        let len = u32::from_le_bytes([header[0x7D], header[0x7E], header[0x7F], 0]) as usize;
+        let end = if nsf.version >= 2 && len != 0 {
+            HEADER_LEN + len
+        } else {
+            data.len()
+        };
+        let Some(program) = data.get(HEADER_LEN..end) else {
+            return Err(Error::Truncated {
+                section: Section::Header,
+                offset: HEADER_LEN,
+                expected: len,
+                available: data.len() - HEADER_LEN,
+            });
+        };
+        nsf.data = Cow::Borrowed(program);
+        nsf.tracks = vec![Track::default(); nsf.songs as usize];
+
+        for chunk in chunks(data, end) {
+            nsf.apply_chunk(chunk?)?;
+        }
+        Ok(nsf)
+    }
+
+    fn parse_nsfe(data: &'a [u8]) -> Result<Self, Error> {
+        let mut nsf = Self::empty(Format::Nsfe);
+        let mut info = false;
+        let mut program = false;
+
+        for chunk in chunks(data, NSFE_MAGIC.len()) {
+            let chunk = chunk?;
+            match &chunk.id {
+                b"INFO" => {
+                    let Some(fields) = chunk.data.get(..8) else {
+                        return Err(Error::Truncated {
+                            section: Section::Chunk(chunk.id),
+                            offset: 0,
+                            expected: 8,
+                            available: chunk.data.len(),
+                        });
+                    };
+                    nsf.load_address = u16::from_le_bytes([fields[0], fields[1]]);
+                    nsf.init_address = u16::from_le_bytes([fields[2], fields[3]]);
+                    nsf.play_address = u16::from_le_bytes([fields[4], fields[5]]);
+                    nsf.timing = Timing::from_flags(fields[6]);
+                    nsf.expansion = Expansion::from_bits_truncate(fields[7]);
+                    nsf.songs = chunk.data.get(8).copied().unwrap_or(1);
+                    nsf.starting_song = chunk.data.get(9).copied().unwrap_or(0);
+                    nsf.tracks.resize(nsf.songs as usize, Track::default());
+                    info = true;
+                }
+                b"DATA" => {
+                    nsf.data = Cow::Borrowed(chunk.data);
+                    program = true;
+                }
+                _ => nsf.apply_chunk(chunk)?,
+            }
+        }
+
+        if !info {
+            return Err(Error::MissingChunk(*b"INFO"));
+        }
+        if !program {
+            return Err(Error::MissingChunk(*b"DATA"));
+        }
+        Ok(nsf)
+    }
+
+    /// Applies an NSFe chunk (other than `INFO` and `DATA`).
+    fn apply_chunk(&mut self, chunk: Chunk<'_>) -> Result<(), Error> {
+        match &chunk.id {
+            b"BANK" => {
+                let mut banks = [0; 8];
+                for (dst, src) in banks.iter_mut().zip(chunk.data) {
+                    *dst = *src;
+                }
+                self.banks = Some(banks);
+            }
+            b"RATE" => {
+                if let Some(speed) = u16_at(chunk.data, 0) {
+                    self.ntsc_speed = speed;
+                }
+                if let Some(speed) = u16_at(chunk.data, 2) {
+                    self.pal_speed = speed;
+                }
+            }
+            b"auth" => {
+                let mut fields = chunk.data.split(|b| *b == 0).map(text);
+                self.title = fields.next().unwrap_or_default();
+                self.artist = fields.next().unwrap_or_default();
+                self.copyright = fields.next().unwrap_or_default();
+                self.ripper = fields.next().filter(|ripper| !ripper.is_empty());
+            }
+            b"tlbl" => {
+                let names = chunk.data.split(|b| *b == 0).map(text);
+                for (track, name) in self.tracks.iter_mut().zip(names) {
+                    track.name = Some(name);
+                }
+            }
+            b"time" => {
+                for (track, time) in self.tracks.iter_mut().zip(times(chunk.data)) {
+                    track.duration = time;
+                }
+            }
+            b"fade" => {
+                for (track, time) in self.tracks.iter_mut().zip(times(chunk.data)) {
+                    track.fade = time;
+                }
+            }
+            b"plst" => self.playlist = Some(chunk.data.to_vec()),
+            // Already described by the header, or meant for editors.
+            b"NSF2" | b"NEND" | b"INFO" | b"DATA" => {}
+            id if id[0].is_ascii_uppercase() => return Err(Error::UnknownChunk(*id)),
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Whether the tune uses the bank switching registers ($5FF8-$5FFF).
+    pub fn is_bankswitched(&self) -> bool {
+        self.banks.is_some()
+    }
+
+    /// Metadata of `song`, if it exists.
+    pub fn track(&self, song: u8) -> Option<&Track> {
+        self.tracks.get(song as usize)
+    }
+
+    /// Copies the program data, if it's borrowed.
+    pub fn into_owned(self) -> Nsf<'static> {
+        Nsf {
+            format: self.format,
+            version: self.version,
+            songs: self.songs,
+            starting_song: self.starting_song,
+            load_address: self.load_address,
+            init_address: self.init_address,
+            play_address: self.play_address,
+            title: self.title,
+            artist: self.artist,
+            copyright: self.copyright,
+            ripper: self.ripper,
+            ntsc_speed: self.ntsc_speed,
+            pal_speed: self.pal_speed,
+            banks: self.banks,
+            timing: self.timing,
+            expansion: self.expansion,
+            tracks: self.tracks,
+            playlist: self.playlist,
+            data: Cow::Owned(self.data.into_owned()),
+        }
+    }
+}
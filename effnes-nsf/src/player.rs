@@ -0,0 +1,257 @@
+use crate::{
+    bus::{IDLE_LOOP, NsfBus},
+    nsf::{DEFAULT_NTSC_SPEED, DEFAULT_PAL_SPEED, Nsf, Timing, Track},
+};
+use effnes_apu::{
+    apu::Region,
+    resampler::{CPU_CLOCK_NTSC, CPU_CLOCK_PAL, Resampler},
+    sink::{AudioSink, Sample},
+};
+use effnes_bus::{MemoryBus, peripheral::Peripheral, signal::Signals};
+use effnes_cpu::{consts::Flags, debug::DebugCpu};
+use std::time::Duration;
+
+/// Stack pointer at the start of every INIT/PLAY call.
+const STACK_TOP: u8 = 0xFD;
+
+/// NSF Player
+///
+/// Drives a CPU (any of the effnes VMs) through the INIT and PLAY routines
+/// of an [Nsf] tune, and renders its audio.
+///
+/// ## Behaviour
+///
+/// - [Player::select] calls INIT with the song number in A and the region
+///   in X (0 for NTSC, 1 for PAL).
+/// - PLAY is called once per play period ([Nsf::ntsc_speed] or
+///   [Nsf::pal_speed] microseconds), as long as the previous call (INIT
+///   included) already returned. Returning routines land on an idle loop
+///   (see [IDLE_LOOP]), which keeps the CPU busy until the next call.
+/// - Once the track duration is reached (NSFe only), the output fades out
+///   for the track fade time.
+/// - IRQs are not delivered to the CPU.
+///
+/// ```ignore
+/// let nsf = Nsf::parse(&data)?;
+/// let mut player = Player::new(nsf, VM::default(), 48_000.0);
+/// player.select(2);
+///
+/// let mut samples: Vec<i16> = Vec::new();
+/// while !player.finished() {
+///     player.run_frame(&mut samples);
+/// }
+/// ```
+pub struct Player<'a, C> {
+    nsf: Nsf<'a>,
+    cpu: C,
+    bus: NsfBus<'a>,
+    /// CPU lines, left released (see [Player]).
+    signals: Signals,
+    resampler: Resampler,
+    sample_rate: f64,
+    region: Region,
+    song: u8,
+
+    /// CPU cycles between PLAY calls.
+    period: f64,
+    /// CPU cycles run since the song was selected.
+    cycles: u64,
+    /// CPU cycles run past the end of the last frame.
+    overrun: f64,
+}
+
+impl<'a, C: Peripheral + DebugCpu> Player<'a, C> {
+    /// Creates a player, with the first region supported by `nsf`, and
+    /// selects its starting song.
+    ///
+    /// ## Panics
+    ///
+    /// If `sample_rate` isn't lower than the CPU clock rate.
+    pub fn new(nsf: Nsf<'a>, cpu: C, sample_rate: f64) -> Self {
+        let region = match nsf.timing {
+            Timing::Pal => Region::Pal,
+            Timing::Ntsc | Timing::Dual => Region::Ntsc,
+        };
+        let mut player = Self {
+            bus: NsfBus::new(&nsf, region),
+            cpu,
+            signals: Signals::default(),
+            resampler: Resampler::new(CPU_CLOCK_NTSC, sample_rate),
+            sample_rate,
+            region,
+            song: nsf.starting_song.min(nsf.songs - 1),
+            period: 0.0,
+            cycles: 0,
+            overrun: 0.0,
+            nsf,
+        };
+        player.set_region(region);
+        player
+    }
+
+    /// Changes the console region, restarting the current song.
+    ///
+    /// Tunes that only support the other region are still played, at the
+    /// requested rate.
+    pub fn with_region(mut self, region: Region) -> Self {
+        self.set_region(region);
+        self
+    }
+
+    fn set_region(&mut self, region: Region) {
+        let (clock_rate, speed, default) = match region {
+            Region::Ntsc => (CPU_CLOCK_NTSC, self.nsf.ntsc_speed, DEFAULT_NTSC_SPEED),
+            Region::Pal => (CPU_CLOCK_PAL, self.nsf.pal_speed, DEFAULT_PAL_SPEED),
+        };
+        let speed = if speed == 0 { default } else { speed };
+
+        self.region = region;
+        self.period = speed as f64 * clock_rate / 1_000_000.0;
+        self.bus = NsfBus::new(&self.nsf, region);
+        self.resampler = Resampler::new(clock_rate, self.sample_rate);
+        self.select(self.song);
+    }
+
+    pub fn nsf(&self) -> &Nsf<'a> {
+        &self.nsf
+    }
+
+    pub fn cpu(&self) -> &C {
+        &self.cpu
+    }
+
+    pub fn bus(&self) -> &NsfBus<'a> {
+        &self.bus
+    }
+
+    pub fn region(&self) -> Region {
+        self.region
+    }
+
+    /// Current song (zero based).
+    pub fn song(&self) -> u8 {
+        self.song
+    }
+
+    /// Metadata of the current song.
+    pub fn track(&self) -> &Track {
+        &self.nsf.tracks[self.song as usize]
+    }
+
+    /// Starts playing `song` (zero based) from the beginning.
+    ///
+    /// ## Panics
+    ///
+    /// If `song` isn't lower than [Nsf::songs].
+    pub fn select(&mut self, song: u8) {
+        assert!(song < self.nsf.songs, "song {} out of range", song);
+        self.song = song;
+        self.cycles = 0;
+        self.overrun = 0.0;
+
+        self.bus.reset();
+        self.resampler.reset();
+        self.cpu.set_flags(Flags::IntDis | Flags::Reserved);
+        self.cpu.set_ac(song);
+        self.cpu.set_ix((self.region == Region::Pal) as u8);
+        self.cpu.set_iy(0);
+        self.call(self.nsf.init_address);
+    }
+
+    /// Calls the routine at `addr`, returning into the idle loop.
+    fn call(&mut self, addr: u16) {
+        let ret = IDLE_LOOP.wrapping_sub(1);
+        self.bus
+            .write_u8(0x100 | STACK_TOP as u16, (ret >> 8) as u8);
+        self.bus
+            .write_u8(0x100 | STACK_TOP.wrapping_sub(1) as u16, ret as u8);
+        self.cpu.set_sp(STACK_TOP.wrapping_sub(2));
+        self.cpu.set_pc(addr);
+        self.bus.call_started();
+    }
+
+    /// Time played since the song was selected.
+    pub fn position(&self) -> Duration {
+        let clock_rate = match self.region {
+            Region::Ntsc => CPU_CLOCK_NTSC,
+            Region::Pal => CPU_CLOCK_PAL,
+        };
+        Duration::from_secs_f64(self.cycles as f64 / clock_rate)
+    }
+
+    /// Whether the song (plus its fade out) is over. Always `false` for
+    /// songs without a known duration.
+    pub fn finished(&self) -> bool {
+        let track = self.track();
+        track
+            .duration
+            .is_some_and(|duration| self.position() >= duration + track.fade.unwrap_or_default())
+    }
+
+    /// Output gain, following the fade out.
+    fn gain(&self) -> f32 {
+        let track = self.track();
+        let Some(duration) = track.duration else {
+            return 1.0;
+        };
+        let position = self.position();
+        if position < duration {
+            return 1.0;
+        }
+        match track.fade {
+            Some(fade) if !fade.is_zero() => {
+                (1.0 - (position - duration).as_secs_f32() / fade.as_secs_f32()).max(0.0)
+            }
+            _ => 0.0,
+        }
+    }
+
+    /// Runs the CPU for one instruction (or cycle, on cycle accurate VMs),
+    /// clocking the rest of the system as many cycles as it took.
+    fn step(&mut self, gain: f32) -> u32 {
+        let start = self.cpu.state().cc;
+        self.cpu.cycle(&mut self.bus, &mut self.signals);
+        let cycles = if self.cpu.is_cycle_accurate() {
+            1
+        } else {
+            (self.cpu.state().cc - start).max(1) as u32
+        };
+
+        for _ in 0..cycles {
+            let amplitude = self.bus.cycle();
+            self.resampler.push(amplitude * gain);
+        }
+        self.cycles += cycles as u64;
+        cycles
+    }
+
+    /// Runs a play period (calling PLAY, if the previous call returned),
+    /// and pushes the rendered audio into `sink`.
+    ///
+    /// Returns the number of frames pushed.
+    pub fn run_frame<S: Sample>(&mut self, sink: &mut impl AudioSink<S>) -> usize {
+        if self.bus.returned() {
+            self.call(self.nsf.play_address);
+        }
+
+        let gain = self.gain();
+        let mut elapsed = self.overrun;
+        while elapsed < self.period {
+            elapsed += self.step(gain) as f64;
+        }
+        self.overrun = elapsed - self.period;
+        self.resampler.end_frame(sink)
+    }
+
+    /// Renders (at least) `duration` of audio into `sink`.
+    ///
+    /// Returns the number of frames pushed.
+    pub fn render<S: Sample>(&mut self, sink: &mut impl AudioSink<S>, duration: Duration) -> usize {
+        let end = self.position() + duration;
+        let mut frames = 0;
+        while self.position() < end {
+            frames += self.run_frame(sink);
+        }
+        frames
+    }
+}
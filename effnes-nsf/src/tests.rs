@@ -0,0 +1,258 @@
+use crate::{
+    Error, Expansion, Nsf, Player,
+    error::Section,
+    nsf::{Format, HEADER_LEN, MAGIC, NSFE_MAGIC, Timing},
+};
+use effnes_apu::apu::Region;
+use effnes_basic_cpu::vm::VM;
+use effnes_bus::InspectBus;
+use std::time::Duration;
+
+const SAMPLE_RATE: f64 = 44_100.0;
+
+/// INIT ($8000): stores A and X at $0200/$0201 and starts a square wave.
+/// PLAY ($8020): increments $0202.
+const PROGRAM: &[u8] = &[
+    0x8D, 0x00, 0x02, // STA $0200
+    0x8E, 0x01, 0x02, // STX $0201
+    0xA9, 0xBF, 0x8D, 0x00, 0x40, // LDA #$BF; STA $4000
+    0xA9, 0xFD, 0x8D, 0x02, 0x40, // LDA #$FD; STA $4002
+    0xA9, 0x00, 0x8D, 0x03, 0x40, // LDA #$00; STA $4003
+    0x60, // RTS
+    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, //
+    0xEE, 0x02, 0x02, // INC $0202
+    0x60, // RTS
+];
+
+fn nsf_file(load: u16, banks: [u8; 8], expansion: u8, program: &[u8]) -> Vec<u8> {
+    let mut file = vec![0; HEADER_LEN];
+    file[..5].copy_from_slice(&MAGIC);
+    file[0x05] = 1;
+    file[0x06] = 3;
+    file[0x07] = 2;
+    file[0x08..0x0A].copy_from_slice(&load.to_le_bytes());
+    file[0x0A..0x0C].copy_from_slice(&0x8000u16.to_le_bytes());
+    file[0x0C..0x0E].copy_from_slice(&0x8020u16.to_le_bytes());
+    file[0x0E..0x13].copy_from_slice(b"Title");
+    file[0x2E..0x34].copy_from_slice(b"Artist");
+    file[0x4E..0x52].copy_from_slice(b"2026");
+    file[0x6E..0x70].copy_from_slice(&16639u16.to_le_bytes());
+    file[0x70..0x78].copy_from_slice(&banks);
+    file[0x78..0x7A].copy_from_slice(&19997u16.to_le_bytes());
+    file[0x7A] = 0x02;
+    file[0x7B] = expansion;
+    file.extend_from_slice(program);
+    file
+}
+
+fn chunk(file: &mut Vec<u8>, id: &[u8; 4], data: &[u8]) {
+    file.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    file.extend_from_slice(id);
+    file.extend_from_slice(data);
+}
+
+fn nsfe_info() -> Vec<u8> {
+    let mut info = Vec::new();
+    info.extend_from_slice(&0x8000u16.to_le_bytes());
+    info.extend_from_slice(&0x8000u16.to_le_bytes());
+    info.extend_from_slice(&0x8020u16.to_le_bytes());
+    info.extend_from_slice(&[0x01, 0x00, 2, 1]);
+    info
+}
+
+fn ms(values: &[i32]) -> Vec<u8> {
+    values.iter().flat_map(|v| v.to_le_bytes()).collect()
+}
+
+#[test]
+fn nsf_header() {
+    let file = nsf_file(0x8000, [0; 8], 0x21, PROGRAM);
+    let nsf = Nsf::parse(&file).unwrap();
+
+    assert_eq!(nsf.format, Format::Nsf);
+    assert_eq!(nsf.songs, 3);
+    assert_eq!(nsf.starting_song, 1);
+    assert_eq!(
+        (nsf.load_address, nsf.init_address, nsf.play_address),
+        (0x8000, 0x8000, 0x8020)
+    );
+    assert_eq!(
+        (&*nsf.title, &*nsf.artist, &*nsf.copyright),
+        ("Title", "Artist", "2026")
+    );
+    assert_eq!(nsf.timing, Timing::Dual);
+    assert_eq!(nsf.expansion, Expansion::Vrc6 | Expansion::Sunsoft5b);
+    assert!(!nsf.is_bankswitched());
+    assert_eq!(nsf.tracks.len(), 3);
+    assert_eq!(&*nsf.data, PROGRAM);
+
+    assert_eq!(Nsf::parse(b"NESM"), Err(Error::InvalidMagic(*b"NESM")));
+    assert_eq!(
+        Nsf::parse(&file[..0x40]),
+        Err(Error::Truncated {
+            section: Section::Header,
+            offset: 0,
+            expected: HEADER_LEN,
+            available: 0x40,
+        })
+    );
+    let low = nsf_file(0x6000, [0; 8], 0, PROGRAM);
+    assert_eq!(Nsf::parse(&low), Err(Error::InvalidLoadAddress(0x6000)));
+    let fds = nsf_file(0x6000, [0; 8], 0x04, PROGRAM);
+    assert!(Nsf::parse(&fds).is_ok());
+}
+
+#[test]
+fn nsfe_metadata() {
+    let mut file = NSFE_MAGIC.to_vec();
+    chunk(&mut file, b"INFO", &nsfe_info());
+    chunk(&mut file, b"DATA", PROGRAM);
+    chunk(&mut file, b"auth", b"Title\0Artist\0\0Ripper\0");
+    chunk(&mut file, b"tlbl", b"Intro\0Loop\0");
+    chunk(&mut file, b"time", &ms(&[100, -1]));
+    chunk(&mut file, b"fade", &ms(&[50]));
+    chunk(&mut file, b"plst", &[1, 0]);
+    chunk(&mut file, b"xtra", &[0xFF]);
+    chunk(&mut file, b"NEND", &[]);
+    file.extend_from_slice(b"trailing garbage");
+
+    let nsf = Nsf::parse(&file).unwrap();
+    assert_eq!(nsf.format, Format::Nsfe);
+    assert_eq!((nsf.songs, nsf.starting_song), (2, 1));
+    assert_eq!(nsf.timing, Timing::Pal);
+    assert_eq!((&*nsf.title, &*nsf.artist), ("Title", "Artist"));
+    assert_eq!(nsf.copyright, "");
+    assert_eq!(nsf.ripper.as_deref(), Some("Ripper"));
+    assert_eq!(nsf.track(0).unwrap().name.as_deref(), Some("Intro"));
+    assert_eq!(
+        nsf.track(0).unwrap().duration,
+        Some(Duration::from_millis(100))
+    );
+    assert_eq!(nsf.track(0).unwrap().fade, Some(Duration::from_millis(50)));
+    assert_eq!(nsf.track(1).unwrap().name.as_deref(), Some("Loop"));
+    assert_eq!(nsf.track(1).unwrap().duration, None);
+    assert_eq!(nsf.playlist.as_deref(), Some(&[1, 0][..]));
+    assert_eq!(&*nsf.data, PROGRAM);
+
+    let mut unknown = NSFE_MAGIC.to_vec();
+    chunk(&mut unknown, b"INFO", &nsfe_info());
+    chunk(&mut unknown, b"XTRA", &[]);
+    assert_eq!(Nsf::parse(&unknown), Err(Error::UnknownChunk(*b"XTRA")));
+
+    let mut missing = NSFE_MAGIC.to_vec();
+    chunk(&mut missing, b"INFO", &nsfe_info());
+    assert_eq!(Nsf::parse(&missing), Err(Error::MissingChunk(*b"DATA")));
+
+    // The player stops after the duration plus the fade out.
+    let mut player = Player::new(nsf, VM::default(), SAMPLE_RATE);
+    assert_eq!(player.region(), Region::Pal);
+    player.select(0);
+    let mut samples: Vec<f32> = Vec::new();
+    while !player.finished() {
+        player.run_frame(&mut samples);
+    }
+    let position = player.position().as_millis();
+    assert!((150..180).contains(&position), "{position}");
+}
+
+#[test]
+fn player_calls_init_and_play() {
+    let file = nsf_file(0x8000, [0; 8], 0, PROGRAM);
+    let nsf = Nsf::parse(&file).unwrap();
+    let mut player = Player::new(nsf, VM::default(), SAMPLE_RATE);
+    assert_eq!(player.song(), 1);
+    assert_eq!(player.region(), Region::Ntsc);
+
+    let mut samples: Vec<i16> = Vec::new();
+    for _ in 0..10 {
+        player.run_frame(&mut samples);
+    }
+    let bus = player.bus();
+    // INIT returns during the first frame, and PLAY runs on every other.
+    assert_eq!(
+        (
+            bus.peek_u8(0x0200),
+            bus.peek_u8(0x0201),
+            bus.peek_u8(0x0202)
+        ),
+        (1, 0, 9)
+    );
+    assert_eq!(bus.apu().peek_status() & 0x01, 0x01);
+    assert!(samples.len() > 7000);
+    assert!(samples.iter().any(|s| *s > 1000));
+    assert!(samples.iter().any(|s| *s < -1000));
+
+    // Selecting a song resets the RAM and calls INIT again, on PAL timing.
+    let mut player = player.with_region(Region::Pal);
+    player.select(2);
+    player.run_frame(&mut samples);
+    let bus = player.bus();
+    assert_eq!(
+        (
+            bus.peek_u8(0x0200),
+            bus.peek_u8(0x0201),
+            bus.peek_u8(0x0202)
+        ),
+        (2, 1, 0)
+    );
+    let position = player.position().as_micros();
+    assert!((19997..20100).contains(&position), "{position}");
+}
+
+#[test]
+fn player_bankswitching() {
+    // INIT: switches bank 2 into $9000, and copies $9000 into $0203.
+    let mut program = vec![
+        0xA9, 0x02, 0x8D, 0xF9, 0x5F, // LDA #$02; STA $5FF9
+        0xAD, 0x00, 0x90, // LDA $9000
+        0x8D, 0x03, 0x02, // STA $0203
+        0xAD, 0x00, 0x80, // LDA $8000
+        0x8D, 0x04, 0x02, // STA $0204
+        0x60, // RTS
+    ];
+    program.resize(0x1000 - 0x10 + 0x2000, 0);
+    program[0x1000 - 0x10] = 0x11;
+    program[0x2000 - 0x10] = 0x22;
+
+    // Loaded at $8010: the program starts 16 bytes into bank 0.
+    let mut file = nsf_file(0x8010, [0, 1, 2, 0, 0, 0, 0, 0], 0, &program);
+    file[0x0A..0x0C].copy_from_slice(&0x8010u16.to_le_bytes());
+    let nsf = Nsf::parse(&file).unwrap();
+    assert!(nsf.is_bankswitched());
+
+    let mut player = Player::new(nsf, VM::default(), SAMPLE_RATE);
+    assert_eq!(player.bus().peek_u8(0x9000), 0x11);
+    let mut samples: Vec<i16> = Vec::new();
+    player.run_frame(&mut samples);
+
+    let bus = player.bus();
+    assert_eq!(bus.peek_u8(0x0203), 0x22);
+    assert_eq!(bus.peek_u8(0x0204), 0x00);
+    assert_eq!(bus.peek_u8(0x8010), 0xA9);
+    assert_eq!(bus.peek_u8(0xA000), 0x22);
+}
+
+#[test]
+fn player_expansion_audio() {
+    // INIT: starts a VRC6 pulse at full volume, leaving the APU silent.
+    let program = [
+        0xA9, 0x7F, 0x8D, 0x00, 0x90, // LDA #$7F; STA $9000
+        0xA9, 0x00, 0x8D, 0x01, 0x90, // LDA #$00; STA $9001
+        0xA9, 0x81, 0x8D, 0x02, 0x90, // LDA #$81; STA $9002
+        0x60, // RTS
+    ];
+    let render = |expansion: u8| {
+        let file = nsf_file(0x8000, [0; 8], expansion, &program);
+        let nsf = Nsf::parse(&file).unwrap();
+        let mut player = Player::new(nsf, VM::default(), SAMPLE_RATE);
+        let mut samples: Vec<f32> = Vec::new();
+        player.render(&mut samples, Duration::from_millis(100));
+        samples
+            .iter()
+            .skip(samples.len() / 2)
+            .fold(0.0f32, |max, s| max.max(s.abs()))
+    };
+
+    assert!(render(Expansion::Vrc6.bits()) > 0.05);
+    assert!(render(0) < 0.001);
+}